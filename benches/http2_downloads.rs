@@ -0,0 +1,65 @@
+//! Compares installing a toolchain over HTTP/1.1 and over HTTP/2.
+//!
+//! Each round installs the `minimal` profile of the stable toolchain into a
+//! fresh temporary `RUSTUP_HOME` twice, once per protocol, so that both see
+//! the same CDN conditions. This needs network access to the dist server
+//! (`RUSTUP_DIST_SERVER` is honoured), and downloads the toolchain twice per
+//! round:
+//!
+//! ```text
+//! cargo bench --bench http2_downloads -- [ROUNDS]
+//! ```
+//!
+//! The speedup depends mostly on the latency to the server: multiplexing saves
+//! a connection and TLS handshake per component, which matters more the
+//! further away the server is and the more components are installed.
+
+use std::env;
+use std::time::{Duration, Instant};
+
+use rustup::dist::Profile;
+use rustup::installer::{self, DownloadConfig, RustupConfig};
+
+fn main() {
+    let rounds = env::args()
+        .skip(1)
+        .find(|arg| !arg.starts_with('-'))
+        .map(|arg| arg.parse().expect("ROUNDS must be a number"))
+        .unwrap_or(3);
+
+    let mut totals = [Duration::ZERO; 2];
+    for round in 1..=rounds {
+        for (total, use_http2) in totals.iter_mut().zip([false, true]) {
+            let elapsed = install(use_http2);
+            println!("round {round}: http2={use_http2}: {elapsed:.2?}");
+            *total += elapsed;
+        }
+    }
+
+    let [http1, http2] = totals.map(|total| total / rounds);
+    println!(
+        "mean over {rounds} rounds: HTTP/1.1 {http1:.2?}, HTTP/2 {http2:.2?} ({:.2}x)",
+        http1.as_secs_f64() / http2.as_secs_f64()
+    );
+}
+
+/// Installs the minimal stable toolchain into a temporary home, returning how long it took.
+fn install(use_http2: bool) -> Duration {
+    let home = tempfile::tempdir().unwrap();
+    let download = DownloadConfig {
+        use_http2,
+        ..DownloadConfig::default()
+    };
+
+    let config = RustupConfig::new()
+        .no_prompt(true)
+        .no_modify_path(true)
+        .profile(Profile::Minimal)
+        .download(download)
+        .rustup_home(home.path().join("rustup"))
+        .cargo_home(home.path().join("cargo"));
+
+    let start = Instant::now();
+    installer::install_rust_with_config(config).unwrap();
+    start.elapsed()
+}
@@ -24,14 +24,12 @@ use rs_tracing::{
 use tracing_subscriber::{EnvFilter, Registry, reload::Handle};
 
 use rustup::cli::errors::CliError;
-#[cfg(windows)]
-use rustup::cli::self_update;
-use rustup::cli::{common, log, proxy_mode, rustup_mode, setup_mode};
+use rustup::cli::{common, log, proxy_mode, rustup_mode, self_update, setup_mode};
 use rustup::env_var::RUST_RECURSION_COUNT_MAX;
 use rustup::errors::RustupError;
-use rustup::is_proxyable_tools;
 use rustup::process::Process;
 use rustup::utils;
+use rustup::{is_proxyable_tool_fast, is_proxyable_tools};
 
 fn main() -> Result<ExitCode> {
     #[cfg(windows)]
@@ -114,8 +112,17 @@ async fn run_rustup_inner(
             }
         }
         Some(n) => {
-            is_proxyable_tools(n)?;
-            proxy_mode::main(n, current_dir, process)
+            let proxied = match is_proxyable_tool_fast(n) {
+                true => None,
+                false => self_update::registered_proxy(n, process)?,
+            };
+            if proxied.is_none()
+                && !is_proxyable_tool_fast(n)
+                && !self_update::dup_tools(process)?.iter().any(|t| t == n)
+            {
+                is_proxyable_tools(n)?;
+            }
+            proxy_mode::main(n, proxied, current_dir, process)
                 .await
                 .map(utils::ExitCode::from)
         }
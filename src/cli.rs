@@ -2,6 +2,7 @@
 #[macro_use]
 pub mod log;
 pub mod common;
+pub(crate) mod completions;
 pub mod errors;
 mod help;
 mod job;
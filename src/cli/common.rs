@@ -3,7 +3,6 @@
 use std::fmt::Display;
 use std::fs;
 use std::io::{BufRead, Write};
-use std::path::Path;
 use std::sync::LazyLock;
 use std::{cmp, env};
 
@@ -19,6 +18,7 @@ use crate::{
     dist::{DistOptions, TargetTriple, ToolchainDesc},
     errors::RustupError,
     install::{InstallMethod, UpdateStatus},
+    installer,
     process::Process,
     toolchain::{LocalToolchainName, Toolchain, ToolchainName},
     utils::{self, ExitCode},
@@ -215,7 +215,7 @@ pub(crate) async fn update_all_channels(cfg: &Cfg<'_>, force_update: bool) -> Re
     let profile = cfg.get_profile()?;
     let mut toolchains = Vec::new();
     for (desc, distributable) in cfg.list_channels()? {
-        let options = DistOptions::new(&[], &[], &desc, profile, force_update, cfg)?
+        let options = DistOptions::new(&[], &[], &desc, profile.clone(), force_update, cfg)?
             .for_update(&distributable, false);
         let result = InstallMethod::Dist(options).install().await;
 
@@ -254,39 +254,72 @@ pub(crate) async fn update_all_channels(cfg: &Cfg<'_>, force_update: bool) -> Re
 ///
 /// `items` represents the list of items, with the name and a boolean
 /// to represent whether the item is currently installed.
+/// Which targets or components to list, as chosen with `--installed` and
+/// `--not-installed`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum InstalledFilter {
+    All,
+    Installed,
+    NotInstalled,
+}
+
+impl InstalledFilter {
+    pub(super) fn new(installed: bool, not_installed: bool) -> Self {
+        match (installed, not_installed) {
+            (true, _) => Self::Installed,
+            (false, true) => Self::NotInstalled,
+            (false, false) => Self::All,
+        }
+    }
+
+    pub(super) fn matches(self, installed: bool) -> bool {
+        match self {
+            Self::All => true,
+            Self::Installed => installed,
+            Self::NotInstalled => !installed,
+        }
+    }
+}
+
 pub(super) fn list_items(
     items: impl Iterator<Item = (impl Display, bool)>,
-    installed_only: bool,
+    filter: InstalledFilter,
     quiet: bool,
     process: &Process,
 ) -> Result<ExitCode> {
     let t = process.stdout();
     let mut t = t.lock();
     let bold = Style::new().bold();
-    for (name, installed) in items {
-        if installed && !installed_only && !quiet {
+    for (name, installed) in items.filter(|&(_, installed)| filter.matches(installed)) {
+        if installed && filter == InstalledFilter::All && !quiet {
             writeln!(t, "{bold}{name}{bold:#} {CONTEXT}(installed){CONTEXT:#}")?;
-        } else if installed || !installed_only {
+        } else {
             writeln!(t, "{name}")?;
         }
     }
     Ok(ExitCode::SUCCESS)
 }
 
-pub(crate) async fn list_toolchains(cfg: &Cfg<'_>, verbose: bool, quiet: bool) -> Result<ExitCode> {
+pub(crate) async fn list_toolchains(
+    cfg: &Cfg<'_>,
+    verbose: bool,
+    quiet: bool,
+    json: bool,
+) -> Result<ExitCode> {
     let toolchains = cfg.list_toolchains()?;
-    if toolchains.is_empty() {
+    if json {
+        let active_toolchain_name = active_toolchain_name(cfg).await;
+        let toolchains = installer::list_toolchains_for(cfg, active_toolchain_name.as_ref())?;
+        writeln!(
+            cfg.process.stdout().lock(),
+            "{}",
+            serde_json::to_string(&toolchains)?
+        )?;
+    } else if toolchains.is_empty() {
         writeln!(cfg.process.stdout().lock(), "no installed toolchains")?;
     } else {
         let default_toolchain_name = cfg.get_default()?;
-        let active_toolchain_name: Option<ToolchainName> =
-            if let Ok(Some((LocalToolchainName::Named(toolchain), _source))) =
-                cfg.maybe_ensure_active_toolchain(None).await
-            {
-                Some(toolchain)
-            } else {
-                None
-            };
+        let active_toolchain_name = active_toolchain_name(cfg).await;
 
         for toolchain in toolchains {
             let is_default_toolchain = default_toolchain_name.as_ref() == Some(&toolchain);
@@ -304,6 +337,13 @@ pub(crate) async fn list_toolchains(cfg: &Cfg<'_>, verbose: bool, quiet: bool) -
         }
     }
 
+    async fn active_toolchain_name(cfg: &Cfg<'_>) -> Option<ToolchainName> {
+        match cfg.maybe_ensure_active_toolchain(None).await {
+            Ok(Some((LocalToolchainName::Named(toolchain), _source))) => Some(toolchain),
+            _ => None,
+        }
+    }
+
     fn print_toolchain(
         cfg: &Cfg<'_>,
         toolchain: &str,
@@ -324,7 +364,10 @@ pub(crate) async fn list_toolchains(cfg: &Cfg<'_>, verbose: bool, quiet: bool) -
             (false, false) => "",
         };
 
-        let toolchain_path = cfg.toolchains_dir.join(toolchain);
+        let toolchain_path = match ToolchainName::try_from(toolchain) {
+            Ok(name) => cfg.toolchain_path(&name.into()),
+            Err(_) => cfg.toolchains_dir.join(toolchain),
+        };
         let toolchain_meta = fs::symlink_metadata(&toolchain_path)?;
         let toolchain_path = if verbose {
             if toolchain_meta.is_dir() {
@@ -346,24 +389,29 @@ pub(crate) async fn list_toolchains(cfg: &Cfg<'_>, verbose: bool, quiet: bool) -
     Ok(ExitCode::SUCCESS)
 }
 
-pub(crate) fn list_overrides(cfg: &Cfg<'_>) -> Result<ExitCode> {
-    let overrides = cfg.settings_file.with(|s| Ok(s.overrides.clone()))?;
+pub(crate) fn list_overrides(cfg: &Cfg<'_>, json: bool) -> Result<ExitCode> {
+    let overrides = installer::list_overrides_for(cfg)?;
 
-    if overrides.is_empty() {
+    if json {
+        writeln!(
+            cfg.process.stdout().lock(),
+            "{}",
+            serde_json::to_string(&overrides)?
+        )?;
+    } else if overrides.is_empty() {
         writeln!(cfg.process.stdout().lock(), "no overrides")?;
     } else {
         let mut any_not_exist = false;
-        for (k, v) in overrides {
-            let dir_exists = Path::new(&k).is_dir();
-            if !dir_exists {
+        for o in overrides {
+            if !o.exists {
                 any_not_exist = true;
             }
             writeln!(
                 cfg.process.stdout().lock(),
                 "{:<40}\t{:<20}",
-                utils::format_path_for_display(&k)
-                    + if dir_exists { "" } else { " (not a directory)" },
-                v
+                utils::format_path_for_display(&o.dir.to_string_lossy())
+                    + if o.exists { "" } else { " (not a directory)" },
+                o.toolchain
             )?
         }
         if any_not_exist {
@@ -449,6 +497,11 @@ fn show_backtrace(process: &Process) -> bool {
         return true;
     }
 
+    // The arguments belong to the program embedding rustup
+    if process.is_library() {
+        return false;
+    }
+
     for arg in process.args() {
         if arg == "-v" || arg == "--verbose" {
             return true;
@@ -0,0 +1,177 @@
+//! The completion scripts written by `rustup completions --dynamic`.
+//!
+//! These are the static scripts generated by `clap_complete`, with a wrapper
+//! that asks `rustup completions <SHELL> --candidates <KIND>` for the toolchain names,
+//! targets, components and profiles whenever one of them is being completed,
+//! so that the script does not go stale when toolchains are installed.
+
+use std::io::Write;
+use std::path::PathBuf;
+
+use anyhow::{Result, bail};
+use clap::Command;
+use clap_complete::Shell;
+
+use crate::process::Process;
+use crate::utils;
+
+/// Writes the dynamic completion script for the `rustup` command `cmd` in
+/// `shell` to `writer`.
+pub(crate) fn write_dynamic_script(
+    shell: Shell,
+    cmd: &mut Command,
+    writer: &mut dyn Write,
+) -> Result<()> {
+    let mut script = Vec::new();
+    clap_complete::generate(shell, cmd, "rustup", &mut script);
+    let script = String::from_utf8(script)?;
+
+    match shell {
+        Shell::Bash => write!(writer, "{script}{BASH}")?,
+        Shell::Zsh => {
+            // Register the wrapper instead of the static completions
+            let script = match script.rfind(ZSH_STATIC_TRAILER) {
+                Some(at) => &script[..at],
+                None => &script,
+            };
+            write!(writer, "{script}{ZSH}")?
+        }
+        Shell::Fish => write!(writer, "{script}{FISH}")?,
+        _ => bail!("rustup does not currently support dynamic completions for {shell}"),
+    }
+    Ok(())
+}
+
+/// The completion script for `shell` installed at the location suggested by
+/// `rustup completions --help`, if there is one.
+///
+/// Only Bash, Fish and Zsh read their completions from a file of their own;
+/// the PowerShell and Elvish scripts are sourced from the shell's profile.
+pub(crate) fn installed_script(shell: Shell, process: &Process) -> Option<PathBuf> {
+    let home = process.home_dir()?;
+    let xdg_dir = |var, default| match process.var_os(var) {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => home.join(default),
+    };
+    let path = match shell {
+        Shell::Bash => xdg_dir("XDG_DATA_HOME", ".local/share")
+            .join("bash-completion")
+            .join("completions")
+            .join("rustup"),
+        Shell::Fish => xdg_dir("XDG_CONFIG_HOME", ".config")
+            .join("fish")
+            .join("completions")
+            .join("rustup.fish"),
+        Shell::Zsh => home.join(".zfunc").join("_rustup"),
+        _ => return None,
+    };
+    utils::is_file(&path).then_some(path)
+}
+
+const BASH: &str = r#"
+_rustup_dynamic() {
+    local cur="${COMP_WORDS[COMP_CWORD]}" prev="${COMP_WORDS[COMP_CWORD-1]}"
+    local cmd="${COMP_WORDS[1]}" sub="${COMP_WORDS[2]}"
+    local kind="" prefix=""
+    case "$prev" in
+        --toolchain) kind=toolchain ;;
+        --target|-t) kind=target ;;
+        --component|-c) kind=component ;;
+        --profile) kind=profile ;;
+    esac
+    if [[ -z "$kind" && "$cur" != -* ]]; then
+        if [[ $COMP_CWORD -eq 1 && "$cur" == +* ]]; then
+            kind=toolchain prefix=+
+        elif [[ $COMP_CWORD -eq 2 ]]; then
+            case "$cmd" in
+                default|run|update|up|upgrade|uninstall) kind=toolchain ;;
+            esac
+        elif [[ $COMP_CWORD -ge 3 ]]; then
+            case "$cmd $sub" in
+                "update "*|"up "*|"upgrade "*|"uninstall "*) kind=toolchain ;;
+                "toolchain install"|"toolchain add"|"toolchain update") kind=toolchain ;;
+                "toolchain uninstall"|"toolchain remove"|"toolchain rm") kind=toolchain ;;
+                "target add"|"target install"|"target remove"|"target rm") kind=target ;;
+                "target uninstall") kind=target ;;
+                "component add"|"component remove"|"component rm") kind=component ;;
+                "component uninstall") kind=component ;;
+                "override set"|"override add") [[ $COMP_CWORD -eq 3 ]] && kind=toolchain ;;
+                "set profile") [[ $COMP_CWORD -eq 3 ]] && kind=profile ;;
+            esac
+        fi
+    fi
+
+    if [[ -n "$kind" ]]; then
+        local IFS=$'\n'
+        COMPREPLY=($(compgen -P "$prefix" -W "$(rustup completions bash --candidates "$kind" 2>/dev/null)" -- "${cur#"$prefix"}"))
+        return 0
+    fi
+    _rustup "$@"
+}
+
+if [[ "${BASH_VERSINFO[0]}" -eq 4 && "${BASH_VERSINFO[1]}" -ge 4 || "${BASH_VERSINFO[0]}" -gt 4 ]]; then
+    complete -F _rustup_dynamic -o nosort -o bashdefault -o default rustup
+else
+    complete -F _rustup_dynamic -o bashdefault -o default rustup
+fi
+"#;
+
+const ZSH_STATIC_TRAILER: &str = "if [ \"$funcstack[1]\" = \"_rustup\" ]; then";
+
+const ZSH: &str = r#"_rustup_dynamic() {
+    local cur=$words[CURRENT] prev=$words[CURRENT-1] cmd=$words[2] sub=$words[3]
+    local kind prefix
+    case $prev in
+        --toolchain) kind=toolchain ;;
+        --target|-t) kind=target ;;
+        --component|-c) kind=component ;;
+        --profile) kind=profile ;;
+    esac
+    if [[ -z $kind && $cur != -* ]]; then
+        if (( CURRENT == 2 )) && [[ $cur == +* ]]; then
+            kind=toolchain prefix=+
+        elif (( CURRENT == 3 )); then
+            case $cmd in
+                default|run|update|up|upgrade|uninstall) kind=toolchain ;;
+            esac
+        elif (( CURRENT >= 4 )); then
+            case "$cmd $sub" in
+                "update "*|"up "*|"upgrade "*|"uninstall "*) kind=toolchain ;;
+                "toolchain "(install|add|update|uninstall|remove|rm)) kind=toolchain ;;
+                "target "(add|install|remove|rm|uninstall)) kind=target ;;
+                "component "(add|remove|rm|uninstall)) kind=component ;;
+                "override "(set|add)) (( CURRENT == 4 )) && kind=toolchain ;;
+                "set profile") (( CURRENT == 4 )) && kind=profile ;;
+            esac
+        fi
+    fi
+
+    if [[ -n $kind ]]; then
+        local -a candidates
+        candidates=(${(f)"$(rustup completions zsh --candidates $kind 2>/dev/null)"})
+        compadd -P "$prefix" -- $candidates
+        return
+    fi
+    _rustup "$@"
+}
+
+compdef _rustup_dynamic rustup
+if [ "$funcstack[1]" = "_rustup" ]; then
+    _rustup_dynamic "$@"
+fi
+"#;
+
+const FISH: &str = r#"
+function __fish_rustup_candidates
+    rustup completions fish --candidates $argv[1] 2>/dev/null
+end
+
+complete -c rustup -l toolchain -x -a "(__fish_rustup_candidates toolchain)"
+complete -c rustup -n "__fish_rustup_needs_command; and string match -q -- '+*' (commandline -ct)" -f -a "(__fish_rustup_candidates toolchain | string replace -r '^' '+')"
+complete -c rustup -n "__fish_rustup_using_subcommand default; or __fish_rustup_using_subcommand run; or __fish_rustup_using_subcommand update; or __fish_rustup_using_subcommand uninstall" -f -a "(__fish_rustup_candidates toolchain)"
+complete -c rustup -n "__fish_rustup_using_subcommand toolchain; and __fish_seen_subcommand_from install add update uninstall remove rm" -f -a "(__fish_rustup_candidates toolchain)"
+complete -c rustup -n "__fish_rustup_using_subcommand override; and __fish_seen_subcommand_from set add" -f -a "(__fish_rustup_candidates toolchain)"
+complete -c rustup -n "__fish_rustup_using_subcommand target; and __fish_seen_subcommand_from add install remove rm uninstall" -f -a "(__fish_rustup_candidates target)"
+complete -c rustup -n "__fish_rustup_using_subcommand component; and __fish_seen_subcommand_from add remove rm uninstall" -f -a "(__fish_rustup_candidates component)"
+complete -c rustup -n "__fish_rustup_using_subcommand set; and __fish_seen_subcommand_from profile" -f -a "(__fish_rustup_candidates profile)"
+"#;
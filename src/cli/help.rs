@@ -161,6 +161,44 @@ pub(crate) fn toolchain_link_help() -> String {
     )
 }
 
+pub(crate) fn toolchain_pin_help() -> String {
+    format!(
+        r"{HEADER}Discussion:{HEADER:#}
+  Writes the channel of an installed toolchain to the
+  `rust-toolchain.toml` file of a directory, the current one unless
+  `--path` is given, so that everyone working in the directory uses
+  the same toolchain. The installed components beyond the minimal
+  profile, and the installed targets other than the host, are written
+  too unless `--no-components` is given.
+
+  Any other settings in an existing `rust-toolchain.toml` are kept. As
+  overrides take precedence over toolchain files, the override for the
+  directory, if there is one, is removed.
+
+  To pin the current directory to the installed nightly:
+
+    {LITERAL}$ rustup toolchain pin nightly{LITERAL:#}"
+    )
+}
+
+pub(crate) fn toolchain_update_file_help() -> String {
+    format!(
+        r"{HEADER}Discussion:{HEADER:#}
+  Reads the `rust-toolchain.toml` file of a directory, the current one
+  unless `--path` is given, and if its channel is pinned to a date, such
+  as `nightly-2024-01-15`, moves it to the newest release of the
+  channel.
+
+  Nightlies that lack any of the components or targets the file asks
+  for, including those of its profile, are skipped. The other settings
+  of the file are kept.
+
+  To see whether there is a newer nightly without changing the file:
+
+    {LITERAL}$ rustup toolchain update-file --dry-run{LITERAL:#}"
+    )
+}
+
 pub(crate) fn override_help() -> String {
     format!(
         r"{HEADER}Discussion:{HEADER:#}
@@ -216,6 +254,20 @@ pub(crate) fn run_help() -> String {
     )
 }
 
+pub(crate) fn mirror_help() -> String {
+    format!(
+        r"{HEADER}Discussion:{HEADER:#}
+  Mirrors are tried in the order they were added, each falling back
+  to the next when a file fails to download from it, such as on an
+  HTTP error or a timeout. When none of them has the file, it is
+  downloaded from the release server itself, which is the one set by
+  `RUSTUP_DIST_SERVER` if any, unless the latest `rustup mirror add`
+  was passed `--no-fallback`.
+
+  The mirrors are kept in `settings.toml`, as the `mirrors` array."
+    )
+}
+
 pub(crate) fn check_help() -> String {
     format!(
         r"{HEADER}Exit status:{HEADER:#}
@@ -236,6 +288,25 @@ pub(crate) fn doc_help() -> String {
     )
 }
 
+pub(crate) fn completions_location_help() -> String {
+    format!(
+        r"{HEADER}Discussion:{HEADER:#}
+  Prints where the completion scripts of rustup are installed, looking
+  in the locations suggested by `rustup completions --help`:
+
+  - Bash: `$XDG_DATA_HOME/bash-completion/completions/rustup`
+  - Fish: `$XDG_CONFIG_HOME/fish/completions/rustup.fish`
+  - Zsh: `~/.zfunc/_rustup`
+
+  with `XDG_DATA_HOME` defaulting to `~/.local/share` and
+  `XDG_CONFIG_HOME` to `~/.config`. PowerShell and Elvish completions
+  are sourced from the shell's profile, so they have no location.
+
+  With `--shell`, only the path for that shell is printed, and it is an
+  error if no script is installed there."
+    )
+}
+
 pub(crate) fn completions_help() -> String {
     format!(
         r"{HEADER}Discussion:{HEADER:#}
@@ -347,6 +418,15 @@ pub(crate) fn completions_help() -> String {
 
     {LITERAL}PS C:\> rustup completions powershell >> ${{env:USERPROFILE}}\Documents\WindowsPowerShell\Microsoft.PowerShell_profile.ps1{LITERAL:#}
 
+  {SUBHEADER}Dynamic completions:{SUBHEADER:#}
+
+  With `--dynamic`, the Bash, Fish and Zsh scripts ask rustup for the
+  installed toolchains, and the targets and components of the active
+  toolchain, each time one of them is completed, so they do not need to
+  be regenerated after installing a toolchain:
+
+    {LITERAL}$ rustup completions bash --dynamic > ~/.local/share/bash-completion/completions/rustup{LITERAL:#}
+
   {SUBHEADER}Cargo:{SUBHEADER:#}
 
   Rustup can also generate a completion script for `cargo`. The script output
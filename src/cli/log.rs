@@ -3,7 +3,12 @@ use std::fmt;
 use anstyle::{AnsiColor, Color, Style};
 #[cfg(feature = "otel")]
 use opentelemetry_sdk::trace::Tracer;
-use tracing::{Event, Subscriber, level_filters::LevelFilter};
+use serde_json::{Map, Value};
+use tracing::{
+    Event, Subscriber,
+    field::{Field, Visit},
+    level_filters::LevelFilter,
+};
 use tracing_subscriber::{
     EnvFilter, Layer, Registry,
     fmt::{
@@ -42,11 +47,14 @@ pub fn tracing_subscriber(
 /// When the `RUSTUP_LOG` environment variable is present, a standard [`tracing_subscriber`]
 /// formatter will be used according to the filtering directives set in its value.
 /// Otherwise, this logger will use [`EventFormatter`] to mimic "classic" Rustup `stderr` output.
+///
+/// With `rustup --json`, each log line is instead written as a JSON object by [`JsonFormatter`].
 fn console_logger<S>(process: &Process) -> (impl Layer<S> + use<S>, reload::Handle<EnvFilter, S>)
 where
     S: Subscriber + for<'span> LookupSpan<'span>,
 {
     let maybe_rustup_log_directives = process.var("RUSTUP_LOG");
+    let json = json_output_requested(process);
     let process = process.clone();
     let logger = tracing_subscriber::fmt::layer()
         .with_writer(process.stderr())
@@ -57,7 +65,16 @@ where
                 .with_default_directive(LevelFilter::INFO.into())
                 .parse_lossy(directives),
         );
-        (logger.compact().with_filter(env_filter).boxed(), handle)
+        if json {
+            let logger = logger.with_ansi(false).event_format(JsonFormatter);
+            (logger.with_filter(env_filter).boxed(), handle)
+        } else {
+            (logger.compact().with_filter(env_filter).boxed(), handle)
+        }
+    } else if json {
+        let (env_filter, handle) = reload::Layer::new(EnvFilter::new("rustup=INFO"));
+        let logger = logger.with_ansi(false).event_format(JsonFormatter);
+        (logger.with_filter(env_filter).boxed(), handle)
     } else {
         // Receive log lines from Rustup only.
         let (env_filter, handle) = reload::Layer::new(EnvFilter::new("rustup=INFO"));
@@ -96,6 +113,68 @@ where
     }
 }
 
+/// Whether `rustup` was invoked with `--json` ahead of its subcommand.
+///
+/// The logger is set up before the command line is parsed, so the flag is looked
+/// for among the leading options only, leaving those of e.g. `rustup run` alone.
+fn json_output_requested(process: &Process) -> bool {
+    if process.name().as_deref() != Some("rustup") {
+        return false;
+    }
+    process
+        .args()
+        .skip(1)
+        .take_while(|arg| arg.starts_with('-') || arg.starts_with('+'))
+        .any(|arg| arg == "--json")
+}
+
+/// Writes each event as a single-line JSON object holding its level and fields.
+struct JsonFormatter;
+
+impl<S, N> FormatEvent<S, N> for JsonFormatter
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        _ctx: &FmtContext<'_, S, N>,
+        mut writer: format::Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        let mut fields = JsonFields(Map::new());
+        let level = NotificationLevel::from(*event.metadata().level());
+        fields.0.insert("level".into(), level.to_string().into());
+        event.record(&mut fields);
+        writeln!(writer, "{}", Value::Object(fields.0))
+    }
+}
+
+struct JsonFields(Map<String, Value>);
+
+impl Visit for JsonFields {
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0
+            .insert(field.name().into(), format!("{value:?}").into());
+    }
+}
+
 impl NotificationLevel {
     fn fg_color(&self) -> Option<Color> {
         match self {
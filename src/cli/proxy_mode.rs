@@ -11,7 +11,12 @@ use crate::{
 };
 
 #[tracing::instrument(level = "trace", skip(process))]
-pub async fn main(arg0: &str, current_dir: PathBuf, process: &Process) -> Result<ExitStatus> {
+pub async fn main(
+    arg0: &str,
+    proxied: Option<PathBuf>,
+    current_dir: PathBuf,
+    process: &Process,
+) -> Result<ExitStatus> {
     self_update::cleanup_self_updater(process)?;
 
     let _setup = job::setup();
@@ -43,7 +48,10 @@ pub async fn main(arg0: &str, current_dir: PathBuf, process: &Process) -> Result
         })
         .await?;
 
-    let mut cmd = toolchain.command(arg0)?;
+    let mut cmd = match &proxied {
+        Some(path) => toolchain.proxied_command(path),
+        None => toolchain.command(arg0)?,
+    };
     cmd.env("RUSTUP_TOOLCHAIN_SOURCE", source.to_string());
     run_command_for_dir(cmd, arg0, &cmd_args)
 }
@@ -3,6 +3,7 @@ use std::{
     env::consts::EXE_SUFFIX,
     fmt,
     io::{self, Write},
+    mem,
     path::{Path, PathBuf},
     process::ExitStatus,
     str::FromStr,
@@ -12,8 +13,11 @@ use std::{
 
 use anstream::ColorChoice;
 use anstyle::Style;
-use anyhow::{Context, Error, Result, anyhow};
-use clap::{Args, CommandFactory, Parser, Subcommand, ValueEnum, builder::PossibleValue};
+use anyhow::{Context, Error, Result, anyhow, bail};
+use clap::{
+    Args, CommandFactory, Parser, Subcommand, ValueEnum,
+    builder::{PossibleValue, PossibleValuesParser},
+};
 use clap_cargo::style::{CONTEXT, ERROR, GOOD, HEADER, TRANSIENT, WARN};
 use clap_complete::Shell;
 use futures_util::stream::StreamExt;
@@ -22,18 +26,21 @@ use itertools::Itertools;
 use tokio::sync::Semaphore;
 use tracing::{error, info, warn};
 use tracing_subscriber::{EnvFilter, Registry, reload::Handle};
+use url::Url;
 
 use crate::{
     cli::{
-        common::{self, PackageUpdate, update_console_filter},
+        common::{self, InstalledFilter, PackageUpdate, update_console_filter},
+        completions,
         errors::CliError,
         help::{
-            check_help, completions_help, default_help, doc_help, install_help,
-            maybe_resolvable_toolchain_arg_help, official_toolchain_arg_help, override_help,
-            override_unset_help, resolvable_local_toolchain_arg_help,
-            resolvable_toolchain_arg_help, run_help, rustup_help, show_active_toolchain_help,
-            show_help, toolchain_help, toolchain_install_help, toolchain_link_help, topic_arg_help,
-            update_help,
+            check_help, completions_help, completions_location_help, default_help, doc_help,
+            install_help, maybe_resolvable_toolchain_arg_help, mirror_help,
+            official_toolchain_arg_help, override_help, override_unset_help,
+            resolvable_local_toolchain_arg_help, resolvable_toolchain_arg_help, run_help,
+            rustup_help, show_active_toolchain_help, show_help, toolchain_help,
+            toolchain_install_help, toolchain_link_help, toolchain_pin_help,
+            toolchain_update_file_help, topic_arg_help, update_help,
         },
         self_update::{self, SelfUpdateMode, check_rustup_update},
         topical_doc,
@@ -41,19 +48,27 @@ use crate::{
     command, component_for_bin,
     config::{ActiveSource, Cfg},
     dist::{
-        AutoInstallMode, DistOptions, PartialToolchainDesc, Profile, TargetTriple,
+        AutoInstallMode, Channel, ComponentPolicy, DistOptions, PartialToolchainDesc, Profile,
+        TargetTriple, ToolchainDesc,
         download::DownloadCfg,
         manifest::{Component, ComponentStatus},
+        manifestation::DEFAULT_CONCURRENT_DOWNLOADS,
     },
+    download,
     errors::RustupError,
     install::{InstallMethod, UpdateStatus},
+    installer::{
+        self, ActiveToolchainInfo, CompletionKind, ComponentInfo, ComponentKind, Diagnostic,
+        DiagnosticStatus, DownloadConfig, TargetAddResult, ToolchainUpdateStatus,
+    },
     process::{ColorableTerminal, Process},
+    settings::{MirrorSelection, ToolchainFileSearchLimit},
     toolchain::{
         CustomToolchainName, DistributableToolchain, LocalToolchainName,
         MaybeResolvableToolchainName, ResolvableLocalToolchainName, ResolvableToolchainName,
-        Toolchain, ToolchainName,
+        Toolchain, ToolchainName, parse_toolchain_file,
     },
-    utils::{self, ExitCode},
+    utils::{self, ExitCode, units::Size},
 };
 
 const TOOLCHAIN_OVERRIDE_ERROR: &str = "To override the toolchain using the 'rustup +toolchain' syntax, \
@@ -93,6 +108,10 @@ struct Rustup {
     #[arg(short, long, conflicts_with = "verbose")]
     quiet: bool,
 
+    /// Print log messages and errors as JSON objects, one per line
+    #[arg(long)]
+    json: bool,
+
     /// Release channel (e.g. +stable) or custom toolchain to set override
     #[arg(
         name = "+toolchain",
@@ -189,6 +208,10 @@ enum RustupSubcmd {
         /// Install toolchains that require an emulator. See https://github.com/rust-lang/rustup/wiki/Non-host-toolchains
         #[arg(long)]
         force_non_host: bool,
+
+        /// Only report whether newer releases of the toolchains, `stable` by default, are available
+        #[arg(long, conflicts_with_all = ["force", "force_non_host"])]
+        notify_only: bool,
     },
 
     /// Check for updates to Rust toolchains and rustup
@@ -267,6 +290,12 @@ enum RustupSubcmd {
         toolchain: Option<PartialToolchainDesc>,
     },
 
+    /// Run binaries installed by `cargo install` through rustup
+    Proxy {
+        #[command(subcommand)]
+        subcmd: ProxySubcmd,
+    },
+
     /// Modify the rustup installation
     Self_ {
         #[command(subcommand)]
@@ -279,6 +308,13 @@ enum RustupSubcmd {
         subcmd: SetSubcmd,
     },
 
+    /// Manage mirrors of the release server to download toolchains from
+    #[command(after_help = mirror_help())]
+    Mirror {
+        #[command(subcommand)]
+        subcmd: MirrorSubcmd,
+    },
+
     /// Generate tab-completion scripts for your shell
     #[command(after_help = completions_help(), arg_required_else_help = true)]
     Completions {
@@ -286,6 +322,15 @@ enum RustupSubcmd {
 
         #[arg(default_value = "rustup")]
         command: CompletionCommand,
+
+        /// Complete toolchain names, targets, components and profiles from the
+        /// installation at the time of completion
+        #[arg(long, conflicts_with = "command")]
+        dynamic: bool,
+
+        /// Print the values an argument of KIND can take, one per line
+        #[arg(long, value_name = "KIND", hide = true, conflicts_with_all = ["command", "dynamic"])]
+        candidates: Option<CompletionKind>,
     },
 }
 
@@ -305,13 +350,28 @@ enum ShowSubcmd {
         /// Enable verbose output with rustc information
         #[arg(short, long)]
         verbose: bool,
+
+        /// Print the toolchain, the reason it is active and the rustc version as JSON
+        #[arg(long, conflicts_with = "verbose")]
+        json: bool,
     },
 
     /// Display the computed value of RUSTUP_HOME
     Home,
 
-    /// Show the default profile used for the `rustup install` command
+    /// Display the computed value of CARGO_HOME
+    CargoHome,
+
+    /// Show the profile the active toolchain was installed with, or else the
+    /// default profile used for the `rustup install` command
     Profile,
+
+    /// Show the path of the `rust-analyzer` binary of the active toolchain
+    RustAnalyzerPath {
+        /// Find the toolchain active for this directory instead of the current one
+        #[arg(long)]
+        dir: Option<PathBuf>,
+    },
 }
 
 #[derive(Debug, Subcommand)]
@@ -330,6 +390,10 @@ enum ToolchainSubcmd {
         /// Force the output to be a single column
         #[arg(short, long, conflicts_with = "verbose")]
         quiet: bool,
+
+        /// Print the toolchains as a JSON array of objects
+        #[arg(long, conflicts_with_all = ["verbose", "quiet"])]
+        json: bool,
     },
 
     /// Install or update the given toolchains, or by default the active toolchain
@@ -355,6 +419,52 @@ enum ToolchainSubcmd {
         /// Path to the directory
         path: PathBuf,
     },
+
+    /// Uninstall dated nightly toolchains older than a number of days
+    Gc {
+        /// Remove the nightlies dated more than DAYS days ago
+        #[arg(long, value_name = "DAYS")]
+        older_than: u64,
+
+        /// Keep the toolchains named by `rust-toolchain.toml` files under PATH
+        #[arg(long = "search-path", value_name = "PATH")]
+        search_paths: Vec<PathBuf>,
+
+        /// List the toolchains that would be removed without removing them
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Pin a directory to an installed toolchain with a `rust-toolchain.toml` file
+    #[command(after_help = toolchain_pin_help())]
+    Pin {
+        #[arg(help = resolvable_toolchain_arg_help())]
+        toolchain: ResolvableToolchainName,
+
+        /// Path to the directory
+        #[arg(long)]
+        path: Option<PathBuf>,
+
+        /// Only pin the channel, leaving out the installed components and targets
+        #[arg(long)]
+        no_components: bool,
+    },
+
+    /// Update the date of the channel pinned by a `rust-toolchain.toml` file to the newest release
+    #[command(after_help = toolchain_update_file_help())]
+    UpdateFile {
+        /// Path to the directory
+        #[arg(long)]
+        path: Option<PathBuf>,
+
+        /// Report the newest release without updating the file
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Install the toolchain the file is updated to
+        #[arg(long, conflicts_with = "dry_run")]
+        install: bool,
+    },
 }
 
 #[derive(Debug, Default, Args)]
@@ -391,27 +501,104 @@ struct UpdateOpts {
     #[arg(long)]
     no_update: bool,
 
+    /// Do nothing for a toolchain that is already installed with all the requested components
+    /// and targets, without checking for updates of it or of rustup
+    #[arg(long, requires = "toolchain")]
+    if_not_installed: bool,
+
     /// Force an update, even if some components are missing
     #[arg(long)]
     force: bool,
 
+    /// Whether to fail (strict) or to warn and carry on (lenient) when a requested component,
+    /// including one of the profile, is unavailable [default: strict, or lenient with --force]
+    #[arg(long, value_enum, value_name = "POLICY", conflicts_with = "force")]
+    component_policy: Option<ComponentPolicy>,
+
     /// Allow rustup to downgrade the toolchain to satisfy your component choice
     #[arg(long)]
     allow_downgrade: bool,
 
+    /// Rehash the installed files against their packages, failing the installation on a mismatch
+    #[arg(long)]
+    verify: bool,
+
+    /// Install the toolchain that the `rust-toolchain.toml` at the root of the current Cargo
+    /// workspace asks for, along with its components and targets
+    #[arg(long, conflicts_with_all = ["toolchain", "print_manifest"])]
+    workspace: bool,
+
+    /// Print the manifest of the given toolchains as TOML, without installing anything
+    #[arg(long, requires = "toolchain")]
+    print_manifest: bool,
+
+    /// Print the manifest as JSON instead
+    #[arg(long, requires = "print_manifest")]
+    json: bool,
+
+    /// Print the number of bytes the installation would download, without installing anything
+    #[arg(long, requires = "toolchain", conflicts_with = "print_manifest")]
+    print_download_size: bool,
+
     /// Install toolchains that require an emulator. See https://github.com/rust-lang/rustup/wiki/Non-host-toolchains
     #[arg(long)]
     force_non_host: bool,
+
+    /// After installing a nightly, remove the older nightlies beyond the N most recent ones
+    #[arg(
+        long,
+        value_name = "N",
+        requires = "toolchain",
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..),
+    )]
+    keep_max_nightly: Option<usize>,
+
+    /// Retry the installation of each toolchain up to N times when a download fails,
+    /// keeping what was already downloaded
+    #[arg(
+        long = "retry",
+        value_name = "N",
+        default_value_t = 0,
+        requires = "toolchain"
+    )]
+    retry_count: u32,
+
+    /// Give up on the download of a component after SECONDS, retrying it like any other failed download
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        requires = "toolchain",
+        value_parser = clap::builder::RangedU64ValueParser::<u64>::new().range(1..),
+    )]
+    timeout: Option<u64>,
+}
+
+impl UpdateOpts {
+    fn component_policy(&self) -> ComponentPolicy {
+        match (self.component_policy, self.force) {
+            (Some(policy), _) => policy,
+            (None, true) => ComponentPolicy::Lenient,
+            (None, false) => ComponentPolicy::Strict,
+        }
+    }
 }
 
 #[derive(Debug, Default, Args)]
 struct UninstallOpts {
     #[arg(
         help = resolvable_toolchain_arg_help(),
-        required = true,
+        required_unless_present = "all_but_default",
         num_args = 1..,
     )]
     toolchain: Vec<ResolvableToolchainName>,
+
+    /// Remove every toolchain except the default one and those selected by an override
+    #[arg(long, conflicts_with = "toolchain")]
+    all_but_default: bool,
+
+    /// Only list the toolchains `--all-but-default` would remove
+    #[arg(long, requires = "all_but_default")]
+    dry_run: bool,
 }
 
 #[derive(Debug, Subcommand)]
@@ -429,6 +616,10 @@ enum TargetSubcmd {
         #[arg(long)]
         installed: bool,
 
+        /// List only the targets that are not installed
+        #[arg(long, conflicts_with = "installed")]
+        not_installed: bool,
+
         /// Force the output to be a single column
         #[arg(long, short)]
         quiet: bool,
@@ -438,11 +629,24 @@ enum TargetSubcmd {
     #[command(alias = "install")]
     Add {
         /// List of targets to install; "all" installs all available targets
-        #[arg(required = true, num_args = 1..)]
+        #[arg(required_unless_present_any = ["bulk", "all"], num_args = 1..)]
         target: Vec<String>,
 
         #[arg(long, help = official_toolchain_arg_help())]
         toolchain: Option<PartialToolchainDesc>,
+
+        /// Also install the targets listed in FILE, one per line, in a single
+        /// download session; unavailable ones are reported instead of stopping the others
+        #[arg(long, value_name = "FILE")]
+        bulk: Option<PathBuf>,
+
+        /// Install every available target in a single download session
+        #[arg(long, conflicts_with_all = ["target", "bulk"])]
+        all: bool,
+
+        /// Leave TRIPLE out of the targets installed by `--all`
+        #[arg(long, value_name = "TRIPLE", requires = "all")]
+        exclude: Vec<String>,
     },
 
     /// Remove a target from a Rust toolchain
@@ -469,9 +673,17 @@ enum ComponentSubcmd {
         #[arg(long)]
         installed: bool,
 
+        /// List only the components that are not installed
+        #[arg(long, conflicts_with = "installed")]
+        not_installed: bool,
+
         /// Force the output to be a single column
         #[arg(long, short)]
         quiet: bool,
+
+        /// Print the components as a JSON array of objects
+        #[arg(long, conflicts_with = "quiet")]
+        json: bool,
     },
 
     /// Add a component to a Rust toolchain
@@ -484,6 +696,14 @@ enum ComponentSubcmd {
 
         #[arg(long)]
         target: Option<String>,
+
+        /// Skip the components that are unavailable for the target with a warning, instead of failing
+        #[arg(long)]
+        allow_missing: bool,
+
+        /// Add the components for every target installed for the toolchain
+        #[arg(long, conflicts_with = "target")]
+        all_targets: bool,
     },
 
     /// Remove a component from a Rust toolchain
@@ -508,7 +728,11 @@ enum ComponentSubcmd {
 )]
 enum OverrideSubcmd {
     /// List directory toolchain overrides
-    List,
+    List {
+        /// Print the overrides as a JSON array of objects
+        #[arg(long)]
+        json: bool,
+    },
 
     /// Set the override toolchain for a directory
     #[command(alias = "add")]
@@ -521,6 +745,13 @@ enum OverrideSubcmd {
         path: Option<PathBuf>,
     },
 
+    /// Remove the overrides for directories that no longer exist
+    Clean {
+        /// List the overrides that would be removed without removing them
+        #[arg(long)]
+        dry_run: bool,
+    },
+
     /// Remove the override toolchain for a directory
     #[command(aliases = ["remove", "rm", "delete", "del"], after_help = override_unset_help())]
     Unset {
@@ -534,6 +765,27 @@ enum OverrideSubcmd {
     },
 }
 
+#[derive(Debug, Subcommand)]
+#[command(arg_required_else_help = true, subcommand_required = true)]
+enum ProxySubcmd {
+    /// Replace a binary in the Cargo bin directory with a rustup proxy, so it runs
+    /// in the environment of the active toolchain
+    Add {
+        /// The name of the binary, such as `sccache`
+        binary: String,
+
+        /// Replace the binary even if it was not installed by `cargo install`
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Put a binary replaced by `rustup proxy add` back in place
+    Remove {
+        /// The name of the binary, such as `sccache`
+        binary: String,
+    },
+}
+
 #[derive(Debug, Subcommand)]
 #[command(
     name = "self",
@@ -558,6 +810,50 @@ enum SelfSubcmd {
 
     /// Upgrade the internal data format
     UpgradeData,
+
+    /// Check the installation for common problems
+    Diagnose,
+
+    /// Verify that the rustup binary and its proxies are intact
+    Check,
+
+    /// Register rustup as the system Rust provider by putting its proxies on the system `PATH`
+    InstallHook,
+
+    /// Undo the changes made by `rustup self install-hook`
+    UninstallHook,
+
+    /// Print the path of the installed completion scripts
+    #[command(after_help = completions_location_help())]
+    CompletionsLocation {
+        /// Only print the path of the script for SHELL
+        #[arg(long)]
+        shell: Option<Shell>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+#[command(arg_required_else_help = true, subcommand_required = true)]
+enum MirrorSubcmd {
+    /// Add a mirror, to be tried after those already added
+    Add {
+        /// The root URL of the mirror, such as `https://mirror.example.com`
+        url: Url,
+
+        /// Don't fall back to the release server when no mirror has a file
+        #[arg(long)]
+        no_fallback: bool,
+    },
+
+    /// Remove a mirror
+    #[command(aliases = ["rm"])]
+    Remove {
+        /// The root URL of the mirror
+        url: Url,
+    },
+
+    /// List the mirrors in the order they are tried
+    List,
 }
 
 #[derive(Debug, Subcommand)]
@@ -583,6 +879,18 @@ enum SetSubcmd {
         #[arg(value_enum, default_value_t)]
         auto_install_mode: AutoInstallMode,
     },
+
+    /// How far up the directory tree to look for overrides and toolchain files
+    ToolchainFileSearchLimit {
+        #[arg(value_enum, default_value_t)]
+        limit: ToolchainFileSearchLimit,
+    },
+
+    /// Which mirror of the release server to download from first
+    MirrorSelection {
+        #[arg(value_enum, default_value_t)]
+        selection: MirrorSelection,
+    },
 }
 
 #[tracing::instrument(level = "trace", fields(args = format!("{:?}", process.args_os().collect::<Vec<_>>())), skip(process, console_filter))]
@@ -638,20 +946,25 @@ pub async fn main(
         RustupSubcmd::Uninstall { opts } => toolchain_remove(cfg, opts).await,
         RustupSubcmd::Show { verbose, subcmd } => handle_epipe(match subcmd {
             None => show(cfg, verbose).await,
-            Some(ShowSubcmd::ActiveToolchain { verbose }) => {
-                show_active_toolchain(cfg, verbose).await
+            Some(ShowSubcmd::ActiveToolchain { verbose, json }) => {
+                show_active_toolchain(cfg, verbose, json).await
             }
             Some(ShowSubcmd::Home) => show_rustup_home(cfg),
-            Some(ShowSubcmd::Profile) => {
-                writeln!(process.stdout().lock(), "{}", cfg.get_profile()?)?;
-                Ok(ExitCode::SUCCESS)
-            }
+            Some(ShowSubcmd::CargoHome) => show_cargo_home(cfg),
+            Some(ShowSubcmd::Profile) => show_profile(cfg),
+            Some(ShowSubcmd::RustAnalyzerPath { dir }) => show_rust_analyzer_path(cfg, dir),
         }),
+        RustupSubcmd::Update {
+            toolchain,
+            notify_only: true,
+            ..
+        } => update_notify(cfg, toolchain).await,
         RustupSubcmd::Update {
             toolchain,
             no_self_update,
             force,
             force_non_host,
+            notify_only: false,
         } => {
             update(
                 cfg,
@@ -668,13 +981,30 @@ pub async fn main(
         }
         RustupSubcmd::Toolchain { subcmd } => match subcmd {
             ToolchainSubcmd::Install { opts } => update(cfg, opts, true).await,
-            ToolchainSubcmd::List { verbose, quiet } => {
-                handle_epipe(common::list_toolchains(cfg, verbose, quiet).await)
-            }
+            ToolchainSubcmd::List {
+                verbose,
+                quiet,
+                json,
+            } => handle_epipe(common::list_toolchains(cfg, verbose, quiet, json).await),
             ToolchainSubcmd::Link { toolchain, path } => {
                 toolchain_link(cfg, &toolchain, &path).await
             }
             ToolchainSubcmd::Uninstall { opts } => toolchain_remove(cfg, opts).await,
+            ToolchainSubcmd::Gc {
+                older_than,
+                search_paths,
+                dry_run,
+            } => toolchain_gc(cfg, older_than, &search_paths, dry_run),
+            ToolchainSubcmd::Pin {
+                toolchain,
+                path,
+                no_components,
+            } => toolchain_pin(cfg, &toolchain, path.as_deref(), no_components),
+            ToolchainSubcmd::UpdateFile {
+                path,
+                dry_run,
+                install,
+            } => toolchain_update_file(cfg, path.as_deref(), dry_run, install).await,
         },
         RustupSubcmd::Check { opts } => check_updates(cfg, opts).await,
         RustupSubcmd::Default {
@@ -685,9 +1015,30 @@ pub async fn main(
             TargetSubcmd::List {
                 toolchain,
                 installed,
+                not_installed,
                 quiet,
-            } => handle_epipe(target_list(cfg, toolchain, installed, quiet).await),
-            TargetSubcmd::Add { target, toolchain } => target_add(cfg, target, toolchain).await,
+            } => {
+                let filter = InstalledFilter::new(installed, not_installed);
+                handle_epipe(target_list(cfg, toolchain, filter, quiet).await)
+            }
+            TargetSubcmd::Add {
+                toolchain,
+                all: true,
+                exclude,
+                ..
+            } => target_add_all(cfg, &exclude, toolchain).await,
+            TargetSubcmd::Add {
+                target,
+                toolchain,
+                bulk: Some(bulk),
+                ..
+            } => target_add_bulk(cfg, target, &bulk, toolchain).await,
+            TargetSubcmd::Add {
+                target,
+                toolchain,
+                bulk: None,
+                ..
+            } => target_add(cfg, target, toolchain).await,
             TargetSubcmd::Remove { target, toolchain } => {
                 target_remove(cfg, target, toolchain).await
             }
@@ -696,13 +1047,30 @@ pub async fn main(
             ComponentSubcmd::List {
                 toolchain,
                 installed,
+                not_installed,
                 quiet,
-            } => handle_epipe(component_list(cfg, toolchain, installed, quiet).await),
+                json,
+            } => {
+                let filter = InstalledFilter::new(installed, not_installed);
+                handle_epipe(component_list(cfg, toolchain, filter, quiet, json).await)
+            }
             ComponentSubcmd::Add {
                 component,
                 toolchain,
                 target,
-            } => component_add(cfg, component, toolchain, target).await,
+                allow_missing,
+                all_targets,
+            } => {
+                component_add(
+                    cfg,
+                    component,
+                    toolchain,
+                    target,
+                    allow_missing,
+                    all_targets,
+                )
+                .await
+            }
             ComponentSubcmd::Remove {
                 component,
                 toolchain,
@@ -710,10 +1078,11 @@ pub async fn main(
             } => component_remove(cfg, component, toolchain, target).await,
         },
         RustupSubcmd::Override { subcmd } => match subcmd {
-            OverrideSubcmd::List => handle_epipe(common::list_overrides(cfg)),
+            OverrideSubcmd::List { json } => handle_epipe(common::list_overrides(cfg, json)),
             OverrideSubcmd::Set { toolchain, path } => {
                 override_add(cfg, toolchain, path.as_deref()).await
             }
+            OverrideSubcmd::Clean { dry_run } => override_clean(cfg, dry_run),
             OverrideSubcmd::Unset { path, nonexistent } => {
                 override_remove(cfg, path.as_deref(), nonexistent)
             }
@@ -734,6 +1103,14 @@ pub async fn main(
         } => doc(cfg, path, toolchain, topic.as_deref(), &page).await,
         #[cfg(not(windows))]
         RustupSubcmd::Man { command, toolchain } => man(cfg, &command, toolchain).await,
+        RustupSubcmd::Proxy { subcmd } => match subcmd {
+            ProxySubcmd::Add { binary, force } => {
+                self_update::register_proxy(cfg, &binary, force).map(|_| ExitCode::SUCCESS)
+            }
+            ProxySubcmd::Remove { binary } => {
+                self_update::unregister_proxy(cfg, &binary).map(|_| ExitCode::SUCCESS)
+            }
+        },
         RustupSubcmd::Self_ { subcmd } => match subcmd {
             SelfSubcmd::Update => self_update::update(cfg).await,
             SelfSubcmd::Uninstall {
@@ -741,6 +1118,15 @@ pub async fn main(
                 no_modify_path,
             } => self_update::uninstall(no_prompt, no_modify_path, process),
             SelfSubcmd::UpgradeData => cfg.upgrade_data().map(|_| ExitCode::SUCCESS),
+            SelfSubcmd::Diagnose => self_diagnose(cfg).await,
+            SelfSubcmd::Check => self_check(cfg).await,
+            SelfSubcmd::InstallHook => {
+                self_update::install_path_hook(process).map(|_| ExitCode::SUCCESS)
+            }
+            SelfSubcmd::UninstallHook => {
+                self_update::uninstall_path_hook(process).map(|_| ExitCode::SUCCESS)
+            }
+            SelfSubcmd::CompletionsLocation { shell } => completions_location(process, shell),
         },
         RustupSubcmd::Set { subcmd } => match subcmd {
             SetSubcmd::DefaultHost { host_triple } => cfg
@@ -755,8 +1141,43 @@ pub async fn main(
             SetSubcmd::AutoInstall { auto_install_mode } => cfg
                 .set_auto_install(auto_install_mode)
                 .map(|_| ExitCode::SUCCESS),
+            SetSubcmd::ToolchainFileSearchLimit { limit } => cfg
+                .set_toolchain_file_search_limit(limit)
+                .map(|_| ExitCode::SUCCESS),
+            SetSubcmd::MirrorSelection { selection } => cfg
+                .set_mirror_selection(selection)
+                .map(|_| ExitCode::SUCCESS),
         },
-        RustupSubcmd::Completions { shell, command } => {
+        RustupSubcmd::Mirror { subcmd } => match subcmd {
+            MirrorSubcmd::Add { url, no_fallback } => cfg
+                .add_mirror(&url, !no_fallback)
+                .map(|_| ExitCode::SUCCESS),
+            MirrorSubcmd::Remove { url } => cfg.remove_mirror(&url).map(|_| ExitCode::SUCCESS),
+            MirrorSubcmd::List => handle_epipe(mirror_list(cfg)),
+        },
+        RustupSubcmd::Completions {
+            candidates: Some(kind),
+            ..
+        } => {
+            let mut stdout = process.stdout().lock();
+            for candidate in installer::completion_candidates_for(cfg, kind)? {
+                writeln!(stdout, "{candidate}")?;
+            }
+            Ok(ExitCode::SUCCESS)
+        }
+        RustupSubcmd::Completions {
+            shell,
+            dynamic: true,
+            ..
+        } => {
+            completions::write_dynamic_script(
+                shell,
+                &mut Rustup::command(),
+                &mut process.stdout().lock(),
+            )?;
+            Ok(ExitCode::SUCCESS)
+        }
+        RustupSubcmd::Completions { shell, command, .. } => {
             output_completion_script(shell, command, process)
         }
     }
@@ -837,7 +1258,7 @@ async fn check_updates(cfg: &Cfg<'_>, opts: CheckOpts) -> Result<ExitCode> {
         let multi_progress_bars =
             MultiProgress::with_draw_target(cfg.process.progress_draw_target());
         let semaphore = Arc::new(Semaphore::new(concurrent_downloads));
-        let channels = tokio_stream::iter(channels.into_iter()).map(|(name, distributable)| {
+        let channels = tokio_stream::iter(channels).map(|(name, distributable)| {
             let msg = format!("{bold}{name} - {bold:#}");
             let status = "Checking...";
             let template = format!(
@@ -935,9 +1356,45 @@ async fn check_updates(cfg: &Cfg<'_>, opts: CheckOpts) -> Result<ExitCode> {
     })
 }
 
+async fn update_notify(cfg: &Cfg<'_>, names: Vec<PartialToolchainDesc>) -> Result<ExitCode> {
+    let names = match names.is_empty() {
+        true => vec![PartialToolchainDesc::from_str("stable")?],
+        false => names,
+    };
+
+    let stdout = cfg.process.stdout();
+    let mut update_available = false;
+    for name in names {
+        let desc = name.resolve(&cfg.get_default_host_triple()?)?;
+        let distributable = match DistributableToolchain::new(cfg, desc.clone()) {
+            Ok(d) => d,
+            Err(RustupError::ToolchainNotInstalled { .. }) => {
+                writeln!(stdout.lock(), "{desc} - not installed")?;
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        };
+        match installer::toolchain_update_status(&distributable).await? {
+            ToolchainUpdateStatus::UpdateAvailable { from, to } => {
+                update_available = true;
+                writeln!(stdout.lock(), "{desc} - update available: {from} -> {to}")?;
+            }
+            ToolchainUpdateStatus::Current => writeln!(stdout.lock(), "{desc} - up to date")?,
+            ToolchainUpdateStatus::NotInstalled => {
+                writeln!(stdout.lock(), "{desc} - not installed")?
+            }
+        }
+    }
+
+    if update_available {
+        info!("run `rustup update` to install the new releases");
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
 async fn update(
     cfg: &mut Cfg<'_>,
-    opts: UpdateOpts,
+    mut opts: UpdateOpts,
     ensure_active_toolchain: bool,
 ) -> Result<ExitCode> {
     let mut exit_code = ExitCode::SUCCESS;
@@ -946,16 +1403,42 @@ async fn update(
     let self_update_mode = SelfUpdateMode::from_cfg(cfg)?;
     let should_self_update = !opts.no_self_update;
     let force_non_host = opts.force_non_host;
-    cfg.profile_override = opts.profile;
+    cfg.profile_override = opts.profile.clone();
+    if opts.verify {
+        cfg.verify_after_install = true;
+    }
+    if let Some(timeout) = opts.timeout {
+        let concurrent_downloads = cfg.process.concurrent_downloads();
+        let download = cfg.download_config.get_or_insert_with(|| DownloadConfig {
+            max_parallel_downloads: concurrent_downloads.unwrap_or(DEFAULT_CONCURRENT_DOWNLOADS),
+            ..DownloadConfig::default()
+        });
+        download.timeout = Some(Duration::from_secs(timeout));
+    }
 
     let cfg = &cfg;
     if cfg.get_profile()? == Profile::Complete {
         warn!("{}", common::WARN_COMPLETE_PROFILE);
     }
 
+    if opts.print_manifest {
+        return print_manifests(cfg, &opts.toolchain, opts.json).await;
+    }
+    if opts.print_download_size {
+        return print_download_size(cfg, &opts).await;
+    }
+
     let dl_cfg = DownloadCfg::new(cfg);
-    let names = opts.toolchain;
-    if !names.is_empty() {
+    let names = mem::take(&mut opts.toolchain);
+    if opts.workspace {
+        let (status, desc) = cfg
+            .install_workspace_toolchain(&cfg.current_dir, force_non_host)
+            .await?;
+        writeln!(cfg.process.stdout().lock())?;
+        common::show_channel_update(cfg, PackageUpdate::Toolchain(desc), Ok(status))?;
+        exit_code &= self_update_mode.update(should_self_update, &dl_cfg).await?;
+    } else if !names.is_empty() {
+        let mut all_installed = true;
         for name in names {
             // This needs another pass to fix it all up
             if name.has_triple() {
@@ -969,32 +1452,29 @@ async fn update(
                 )?;
             }
             let desc = name.resolve(&cfg.get_default_host_triple()?)?;
-
-            let components = opts.component.iter().map(|s| &**s).collect::<Vec<_>>();
-            let targets = opts.target.iter().map(|s| &**s).collect::<Vec<_>>();
-            let dist_opts = DistOptions::new(
-                &components,
-                &targets,
-                &desc,
-                cfg.get_profile()?,
-                opts.force,
-                cfg,
-            )?;
-
-            let status = match DistributableToolchain::new(cfg, desc.clone()) {
-                Ok(d) => {
-                    if !opts.no_update {
-                        InstallMethod::Dist(dist_opts.for_update(&d, opts.allow_downgrade))
-                            .install()
-                            .await?
-                    } else {
-                        UpdateStatus::Unchanged
+            if opts.if_not_installed && is_installed_with(cfg, &desc, &opts)? {
+                info!("toolchain '{desc}' is already installed");
+                continue;
+            }
+            all_installed = false;
+
+            let mut attempt = 0;
+            let status = loop {
+                match install_or_update(cfg, &desc, &opts).await {
+                    Ok(status) => break status,
+                    Err(e) if attempt < opts.retry_count && is_retriable(&e) => {
+                        attempt += 1;
+                        let delay = Duration::from_secs(1 << (attempt - 1).min(5));
+                        warn!("{e:#}");
+                        info!(
+                            "retrying the installation of '{desc}' in {}s (attempt {attempt} of {})",
+                            delay.as_secs(),
+                            opts.retry_count,
+                        );
+                        tokio::time::sleep(delay).await;
                     }
+                    Err(e) => return Err(e),
                 }
-                Err(RustupError::ToolchainNotInstalled { .. }) => {
-                    DistributableToolchain::install(dist_opts).await?.0
-                }
-                Err(e) => Err(e)?,
             };
 
             writeln!(cfg.process.stdout().lock())?;
@@ -1003,11 +1483,18 @@ async fn update(
                 PackageUpdate::Toolchain(desc.clone()),
                 Ok(status.clone()),
             )?;
+            if let Some(keep) = opts.keep_max_nightly
+                && desc.channel == Channel::Nightly
+            {
+                installer::prune_nightlies_for(cfg, &desc, keep)?;
+            }
             if cfg.get_default()?.is_none() && matches!(status, UpdateStatus::Installed) {
                 cfg.set_default(Some(&desc.into()))?;
             }
         }
-        exit_code &= self_update_mode.update(should_self_update, &dl_cfg).await?;
+        if !all_installed {
+            exit_code &= self_update_mode.update(should_self_update, &dl_cfg).await?;
+        }
     } else if ensure_active_toolchain {
         let (toolchain, source) = cfg.ensure_active_toolchain(force_non_host, true).await?;
         info!("the active toolchain `{toolchain}` has been installed");
@@ -1018,13 +1505,143 @@ async fn update(
         exit_code &= self_update_mode.update(should_self_update, &dl_cfg).await?;
 
         info!("cleaning up downloads & tmp directories");
-        utils::delete_dir_contents_following_links(&cfg.download_dir);
+        if !cfg.offline {
+            utils::delete_dir_contents_following_links(&cfg.download_dir);
+        }
         dl_cfg.tmp_cx.clean();
     }
 
     Ok(exit_code)
 }
 
+/// Whether the installation failed for want of a component that could not be
+/// fetched, so that trying again may get further.
+fn is_retriable(err: &Error) -> bool {
+    download::is_download_failure(err)
+        || err.chain().any(|e| {
+            matches!(
+                e.downcast_ref(),
+                Some(RustupError::ComponentDownloadTimedOut { .. })
+            )
+        })
+}
+
+/// Whether `desc` is installed with all the components and targets of `opts`,
+/// judging by the files on disk.
+fn is_installed_with(cfg: &Cfg<'_>, desc: &ToolchainDesc, opts: &UpdateOpts) -> Result<bool> {
+    let distributable = match DistributableToolchain::new(cfg, desc.clone()) {
+        Ok(d) => d,
+        Err(RustupError::ToolchainNotInstalled { .. }) => return Ok(false),
+        Err(e) => return Err(e.into()),
+    };
+    let components = opts.component.iter().map(|s| &**s).collect::<Vec<_>>();
+    let targets = opts.target.iter().map(|s| &**s).collect::<Vec<_>>();
+    distributable.components_exist(&components, &targets)
+}
+
+/// Installs `desc`, or updates it as `opts` ask when it is already installed.
+///
+/// Archives that were fully downloaded are kept in the download directory,
+/// so calling this again after a failure only fetches what is missing.
+async fn install_or_update(
+    cfg: &Cfg<'_>,
+    desc: &ToolchainDesc,
+    opts: &UpdateOpts,
+) -> Result<UpdateStatus> {
+    let components = opts.component.iter().map(|s| &**s).collect::<Vec<_>>();
+    let targets = opts.target.iter().map(|s| &**s).collect::<Vec<_>>();
+    let dist_opts = DistOptions::new(
+        &components,
+        &targets,
+        desc,
+        cfg.get_profile()?,
+        opts.component_policy().skips_unavailable(),
+        cfg,
+    )?;
+
+    Ok(match DistributableToolchain::new(cfg, desc.clone()) {
+        Ok(d) => {
+            if !opts.no_update {
+                InstallMethod::Dist(dist_opts.for_update(&d, opts.allow_downgrade))
+                    .install()
+                    .await?
+            } else {
+                UpdateStatus::Unchanged
+            }
+        }
+        Err(RustupError::ToolchainNotInstalled { .. }) => {
+            DistributableToolchain::install(dist_opts).await?.0
+        }
+        Err(e) => Err(e)?,
+    })
+}
+
+async fn print_download_size(cfg: &Cfg<'_>, opts: &UpdateOpts) -> Result<ExitCode> {
+    let components = opts.component.iter().map(|s| &**s).collect::<Vec<_>>();
+    let targets = opts.target.iter().map(|s| &**s).collect::<Vec<_>>();
+    let host = cfg.get_default_host_triple()?;
+    let mut total = Some(0);
+    for name in &opts.toolchain {
+        let desc = name.clone().resolve(&host)?;
+        let resolved = installer::estimate_download_size_for(
+            cfg,
+            &desc,
+            cfg.get_profile()?,
+            &components,
+            &targets,
+        )
+        .await?;
+        for component in &resolved.missing_components {
+            warn!("component '{component}' is unavailable for download for '{desc}'");
+        }
+        let known = resolved
+            .downloads
+            .iter()
+            .filter_map(|d| d.size)
+            .sum::<u64>();
+        let known = Size::new(known as usize).to_string();
+        let unknown = resolved
+            .downloads
+            .iter()
+            .filter(|d| d.size.is_none())
+            .map(|d| d.component.as_str())
+            .collect::<Vec<_>>();
+        match unknown.is_empty() {
+            true => info!("{} to download for '{desc}'", known.trim_start()),
+            false => info!(
+                "{} to download for '{desc}', plus {} of unknown size",
+                known.trim_start(),
+                unknown.join(", ")
+            ),
+        }
+        total = total.zip(resolved.total_download_size).map(|(a, b)| a + b);
+    }
+    match total {
+        Some(total) => writeln!(cfg.process.stdout().lock(), "{total}")?,
+        None => writeln!(cfg.process.stdout().lock(), "unknown")?,
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+async fn print_manifests(
+    cfg: &Cfg<'_>,
+    names: &[PartialToolchainDesc],
+    json: bool,
+) -> Result<ExitCode> {
+    let host = cfg.get_default_host_triple()?;
+    for name in names {
+        let desc = name.clone().resolve(&host)?;
+        let manifest = installer::fetch_manifest_for(cfg, &desc).await?;
+        let mut stdout = cfg.process.stdout().lock();
+        if json {
+            writeln!(stdout, "{}", serde_json::to_string_pretty(&manifest)?)?;
+        } else {
+            write!(stdout, "{}", manifest.stringify()?)?;
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
 async fn run(
     cfg: &Cfg<'_>,
     toolchain: ResolvableLocalToolchainName,
@@ -1215,11 +1832,18 @@ async fn show(cfg: &Cfg<'_>, verbose: bool) -> Result<ExitCode> {
 }
 
 #[tracing::instrument(level = "trace", skip_all)]
-async fn show_active_toolchain(cfg: &Cfg<'_>, verbose: bool) -> Result<ExitCode> {
+async fn show_active_toolchain(cfg: &Cfg<'_>, verbose: bool, json: bool) -> Result<ExitCode> {
     match cfg.maybe_ensure_active_toolchain(None).await? {
         Some((toolchain_name, source)) => {
             let toolchain = Toolchain::with_source(cfg, toolchain_name.clone(), &source)?;
-            if verbose {
+            if json {
+                let info = ActiveToolchainInfo::new(&toolchain, &source);
+                writeln!(
+                    cfg.process.stdout().lock(),
+                    "{}",
+                    serde_json::to_string(&info)?
+                )?;
+            } else if verbose {
                 writeln!(
                     cfg.process.stdout().lock(),
                     "{}\nactive because: {}\ncompiler: {}\npath: {}",
@@ -1245,16 +1869,110 @@ async fn show_active_toolchain(cfg: &Cfg<'_>, verbose: bool) -> Result<ExitCode>
     Ok(ExitCode::SUCCESS)
 }
 
+fn completions_location(process: &Process, shell: Option<Shell>) -> Result<ExitCode> {
+    let mut stdout = process.stdout().lock();
+    if let Some(shell) = shell {
+        let Some(path) = completions::installed_script(shell, process) else {
+            bail!("no completion script is installed for {shell}");
+        };
+        writeln!(stdout, "{}", path.display())?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let mut found = false;
+    for &shell in Shell::value_variants() {
+        if let Some(path) = completions::installed_script(shell, process) {
+            writeln!(stdout, "{shell}: {}", path.display())?;
+            found = true;
+        }
+    }
+    if !found {
+        info!("no completion scripts are installed; see `rustup completions --help`");
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+async fn self_diagnose(cfg: &Cfg<'_>) -> Result<ExitCode> {
+    let report = self_update::diagnose(cfg).await;
+    print_diagnostics(cfg, &report.checks)?;
+    Ok(if report.is_ok() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
+
+async fn self_check(cfg: &Cfg<'_>) -> Result<ExitCode> {
+    let report = self_update::check_integrity(cfg).await;
+    print_diagnostics(cfg, &report.checks)?;
+    Ok(if report.is_ok() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
+
+fn print_diagnostics(cfg: &Cfg<'_>, checks: &[Diagnostic]) -> Result<()> {
+    let t = cfg.process.stdout();
+    let mut t = t.lock();
+    for check in checks {
+        let (style, status) = match check.status {
+            DiagnosticStatus::Ok => (GOOD, "ok"),
+            DiagnosticStatus::Warning => (WARN, "warning"),
+            DiagnosticStatus::Error => (ERROR, "error"),
+        };
+        writeln!(t, "{style}{status}{style:#}: {}", check.message)?;
+        if let Some(fix) = &check.fix {
+            writeln!(t, "  {CONTEXT}help:{CONTEXT:#} {fix}")?;
+        }
+    }
+    Ok(())
+}
+
 #[tracing::instrument(level = "trace", skip_all)]
 fn show_rustup_home(cfg: &Cfg<'_>) -> Result<ExitCode> {
     writeln!(cfg.process.stdout().lock(), "{}", cfg.rustup_dir.display())?;
     Ok(ExitCode::SUCCESS)
 }
 
+#[tracing::instrument(level = "trace", skip_all)]
+fn show_cargo_home(cfg: &Cfg<'_>) -> Result<ExitCode> {
+    let cargo_home = cfg.process.cargo_home()?;
+    writeln!(cfg.process.stdout().lock(), "{}", cargo_home.display())?;
+    Ok(ExitCode::SUCCESS)
+}
+
+fn show_profile(cfg: &Cfg<'_>) -> Result<ExitCode> {
+    let profile = match cfg.active_toolchain()? {
+        Some((name, _)) => match Toolchain::new(cfg, name) {
+            Ok(toolchain) => installer::toolchain_profile(&toolchain)?,
+            Err(RustupError::ToolchainNotInstalled { .. }) => cfg.get_profile()?,
+            Err(e) => return Err(e.into()),
+        },
+        None => cfg.get_profile()?,
+    };
+    writeln!(cfg.process.stdout().lock(), "{profile}")?;
+    Ok(ExitCode::SUCCESS)
+}
+
+fn show_rust_analyzer_path(cfg: &mut Cfg<'_>, dir: Option<PathBuf>) -> Result<ExitCode> {
+    if let Some(dir) = dir {
+        cfg.current_dir = cfg.current_dir.join(dir);
+    }
+
+    let Some(path) = installer::find_tool_path_for(cfg, "rust-analyzer")? else {
+        return Err(anyhow!(
+            "'rust-analyzer' is not installed for the active toolchain.\nhelp: run `rustup component add rust-analyzer` to install it"
+        ));
+    };
+    writeln!(cfg.process.stdout().lock(), "{}", path.display())?;
+    Ok(ExitCode::SUCCESS)
+}
+
 async fn target_list(
     cfg: &Cfg<'_>,
     toolchain: Option<PartialToolchainDesc>,
-    installed_only: bool,
+    filter: InstalledFilter,
     quiet: bool,
 ) -> Result<ExitCode> {
     let toolchain = toolchain.map(|desc| (desc, ActiveSource::CommandLine));
@@ -1271,7 +1989,7 @@ async fn target_list(
                     None
                 }
             }),
-            installed_only,
+            filter,
             quiet,
             cfg.process,
         )
@@ -1279,7 +1997,7 @@ async fn target_list(
         let toolchain = cfg.toolchain_from_partial(toolchain).await?.0;
         common::list_items(
             toolchain.installed_targets()?.iter().map(|s| (s, true)),
-            installed_only,
+            filter,
             quiet,
             cfg.process,
         )
@@ -1339,6 +2057,63 @@ async fn target_add(
     Ok(ExitCode::SUCCESS)
 }
 
+async fn target_add_bulk(
+    cfg: &Cfg<'_>,
+    mut targets: Vec<String>,
+    bulk: &Path,
+    toolchain: Option<PartialToolchainDesc>,
+) -> Result<ExitCode> {
+    let distributable = DistributableToolchain::from_partial(
+        toolchain.map(|desc| (desc, ActiveSource::CommandLine)),
+        cfg,
+    )
+    .await?;
+
+    targets.extend(installer::read_target_list(bulk)?);
+    let names = targets.iter().map(String::as_str).collect::<Vec<_>>();
+    let results = installer::add_targets_to(&distributable, &names).await?;
+
+    let mut exit_code = ExitCode::SUCCESS;
+    for (target, result) in targets.iter().zip(results) {
+        match result {
+            TargetAddResult::NotAvailable => {
+                warn!(
+                    "target '{target}' is not available for {}",
+                    distributable.desc()
+                );
+                exit_code = ExitCode::FAILURE;
+            }
+            TargetAddResult::AlreadyInstalled => {
+                info!("target '{target}' is already installed");
+            }
+            TargetAddResult::Added => {}
+        }
+    }
+    Ok(exit_code)
+}
+
+async fn target_add_all(
+    cfg: &Cfg<'_>,
+    exclude: &[String],
+    toolchain: Option<PartialToolchainDesc>,
+) -> Result<ExitCode> {
+    let distributable = DistributableToolchain::from_partial(
+        toolchain.map(|desc| (desc, ActiveSource::CommandLine)),
+        cfg,
+    )
+    .await?;
+
+    let exclude = exclude.iter().map(String::as_str).collect::<Vec<_>>();
+    let targets = installer::missing_targets(&distributable, &exclude)?;
+    if targets.is_empty() {
+        info!("all available targets are already installed");
+        return Ok(ExitCode::SUCCESS);
+    }
+    let names = targets.iter().map(String::as_str).collect::<Vec<_>>();
+    installer::add_targets_to(&distributable, &names).await?;
+    Ok(ExitCode::SUCCESS)
+}
+
 async fn target_remove(
     cfg: &Cfg<'_>,
     targets: Vec<String>,
@@ -1382,19 +2157,54 @@ async fn target_remove(
 async fn component_list(
     cfg: &Cfg<'_>,
     toolchain: Option<PartialToolchainDesc>,
-    installed_only: bool,
+    filter: InstalledFilter,
     quiet: bool,
+    json: bool,
 ) -> Result<ExitCode> {
     let toolchain = toolchain.map(|desc| (desc, ActiveSource::CommandLine));
 
     // downcasting required because the toolchain files can name any toolchain
-    if let Ok(distributable) = DistributableToolchain::from_partial(toolchain.clone(), cfg).await {
+    let distributable = DistributableToolchain::from_partial(toolchain.clone(), cfg).await;
+    if json {
+        let components = match distributable {
+            Ok(distributable) => distributable
+                .components()?
+                .into_iter()
+                .map(ComponentInfo::from_status)
+                .collect::<Vec<_>>(),
+            Err(_) => {
+                let toolchain = cfg.toolchain_from_partial(toolchain).await?.0;
+                toolchain
+                    .installed_components()?
+                    .iter()
+                    .map(|c| ComponentInfo {
+                        name: c.name().to_owned(),
+                        installed: true,
+                        available: true,
+                        kind: ComponentKind::of(c.name()),
+                    })
+                    .collect()
+            }
+        };
+        let components = components
+            .into_iter()
+            .filter(|c| filter.matches(c.installed))
+            .collect::<Vec<_>>();
+        writeln!(
+            cfg.process.stdout().lock(),
+            "{}",
+            serde_json::to_string(&components)?
+        )?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if let Ok(distributable) = distributable {
         common::list_items(
             distributable
                 .components()?
                 .into_iter()
                 .filter_map(|c| c.available.then_some((c.name, c.installed))),
-            installed_only,
+            filter,
             quiet,
             cfg.process,
         )
@@ -1405,7 +2215,7 @@ async fn component_list(
                 .installed_components()?
                 .iter()
                 .map(|s| (s.name(), true)),
-            installed_only,
+            filter,
             quiet,
             cfg.process,
         )
@@ -1417,6 +2227,8 @@ async fn component_add(
     components: Vec<String>,
     toolchain: Option<PartialToolchainDesc>,
     target: Option<String>,
+    allow_missing: bool,
+    all_targets: bool,
 ) -> Result<ExitCode> {
     let distributable = DistributableToolchain::from_partial(
         toolchain.map(|desc| (desc, ActiveSource::CommandLine)),
@@ -1424,10 +2236,37 @@ async fn component_add(
     )
     .await?;
 
+    if all_targets {
+        let mut batch = Vec::new();
+        for component in &components {
+            for new_component in distributable.component_for_targets(component, None)? {
+                match allow_missing {
+                    true => {
+                        distributable
+                            .add_component_if_available(new_component)
+                            .await?
+                    }
+                    false => batch.push(new_component),
+                }
+            }
+        }
+        if !batch.is_empty() {
+            distributable.add_components(batch).await?;
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+
     let target = get_target(target, &distributable);
     for component in &components {
         let new_component = Component::try_new(component, &distributable, target.as_ref())?;
-        distributable.add_component(new_component).await?;
+        match allow_missing {
+            true => {
+                distributable
+                    .add_component_if_available(new_component)
+                    .await?
+            }
+            false => distributable.add_component(new_component).await?,
+        }
     }
 
     Ok(ExitCode::SUCCESS)
@@ -1487,7 +2326,103 @@ async fn toolchain_link(cfg: &Cfg<'_>, dest: &CustomToolchainName, src: &Path) -
     Ok(ExitCode::SUCCESS)
 }
 
+fn toolchain_pin(
+    cfg: &Cfg<'_>,
+    toolchain: &ResolvableToolchainName,
+    path: Option<&Path>,
+    no_components: bool,
+) -> Result<ExitCode> {
+    let dir = cfg.current_dir.join(path.unwrap_or(Path::new(".")));
+    let file = installer::pin_toolchain_for(cfg, &dir, &toolchain.to_string(), !no_components)?;
+    info!("pinned '{}' to toolchain '{toolchain}'", file.display());
+    Ok(ExitCode::SUCCESS)
+}
+
+async fn toolchain_update_file(
+    cfg: &Cfg<'_>,
+    path: Option<&Path>,
+    dry_run: bool,
+    install: bool,
+) -> Result<ExitCode> {
+    let dir = cfg.current_dir.join(path.unwrap_or(Path::new(".")));
+    let result = installer::update_toolchain_file_for(cfg, &dir, dry_run).await?;
+    let file = result.path.display();
+    let (channel, old_date) = (&result.channel, &result.old_date);
+    let Some(new_date) = &result.new_date else {
+        info!("'{file}' is up to date with {channel}-{old_date}");
+        return Ok(ExitCode::SUCCESS);
+    };
+    if !result.updated {
+        info!("would update '{file}' from {channel}-{old_date} to {channel}-{new_date}");
+        return Ok(ExitCode::SUCCESS);
+    }
+    info!("updated '{file}' from {channel}-{old_date} to {channel}-{new_date}");
+
+    if install {
+        let spec = parse_toolchain_file(&result.path)?;
+        let desc = format!("{channel}-{new_date}")
+            .parse::<PartialToolchainDesc>()?
+            .resolve(&cfg.get_default_host_triple()?)?;
+        cfg.ensure_installed(
+            &desc,
+            spec.components,
+            spec.targets,
+            spec.profile,
+            false,
+            true,
+        )
+        .await?;
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn toolchain_gc(
+    cfg: &Cfg<'_>,
+    older_than: u64,
+    search_paths: &[PathBuf],
+    dry_run: bool,
+) -> Result<ExitCode> {
+    let older_than = Duration::from_secs(older_than.saturating_mul(24 * 60 * 60));
+    let search_paths = search_paths
+        .iter()
+        .map(|path| cfg.current_dir.join(path))
+        .collect::<Vec<_>>();
+    let search_paths = search_paths
+        .iter()
+        .map(PathBuf::as_path)
+        .collect::<Vec<_>>();
+    let report = installer::gc_old_nightlies_for(cfg, older_than, &search_paths, dry_run)?;
+
+    if report.removed.is_empty() {
+        info!("no nightly toolchain is old enough to be removed");
+        return Ok(ExitCode::SUCCESS);
+    }
+    let freed = Size::new(report.freed_bytes as usize).to_string();
+    if dry_run {
+        for name in &report.removed {
+            info!("would uninstall toolchain '{name}'");
+        }
+        info!("{} would be freed", freed.trim_start());
+    } else {
+        info!("freed {}", freed.trim_start());
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
 async fn toolchain_remove(cfg: &mut Cfg<'_>, opts: UninstallOpts) -> Result<ExitCode> {
+    if opts.all_but_default {
+        let removed = installer::remove_all_toolchains_except_for(cfg, &[], opts.dry_run)?;
+        if removed.is_empty() {
+            info!("no toolchains to remove");
+        }
+        if opts.dry_run {
+            for name in removed {
+                info!("would remove toolchain '{name}'");
+            }
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+
     let default_toolchain = cfg.get_default().ok().flatten();
     let active_toolchain = cfg
         .maybe_ensure_active_toolchain(Some(false))
@@ -1549,17 +2484,31 @@ async fn override_add(
     Ok(ExitCode::SUCCESS)
 }
 
+fn mirror_list(cfg: &Cfg<'_>) -> Result<ExitCode> {
+    let mut stdout = cfg.process.stdout().lock();
+    for mirror in cfg.mirrors()? {
+        writeln!(stdout, "{mirror}")?;
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn override_clean(cfg: &Cfg<'_>, dry_run: bool) -> Result<ExitCode> {
+    let removed = installer::clean_stale_overrides_for(cfg, dry_run)?;
+    if removed.is_empty() {
+        info!("no overrides for nonexistent directories");
+    }
+    for dir in removed {
+        match dry_run {
+            true => info!("would remove the override for '{}'", dir.display()),
+            false => info!("override toolchain for '{}' removed", dir.display()),
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
 fn override_remove(cfg: &Cfg<'_>, path: Option<&Path>, nonexistent: bool) -> Result<ExitCode> {
     let paths = if nonexistent {
-        let list: Vec<_> = cfg.settings_file.with(|s| {
-            Ok(s.overrides
-                .iter()
-                .filter_map(|(k, _)| {
-                    let path = Path::new(k);
-                    (!path.is_dir()).then(|| path.to_owned())
-                })
-                .collect())
-        })?;
+        let list = cfg.settings_file.with(|s| Ok(s.stale_overrides()))?;
         if list.is_empty() {
             info!("no nonexistent paths detected");
         }
@@ -1571,7 +2520,7 @@ fn override_remove(cfg: &Cfg<'_>, path: Option<&Path>, nonexistent: bool) -> Res
     };
 
     for p in &paths {
-        if cfg.settings_file.with_mut(|s| Ok(s.remove_override(p)))? {
+        if cfg.with_settings_mut(|s| Ok(s.remove_override(p)))? {
             info!("override toolchain for '{}' removed", p.display());
         } else {
             info!("no override toolchain for '{}'", p.display());
@@ -1866,6 +2815,26 @@ impl fmt::Display for CompletionCommand {
     }
 }
 
+/// The `rustup` command for generating completions, offering `toolchains` as the
+/// values of toolchain arguments.
+pub(crate) fn completion_command(toolchains: &[String]) -> clap::Command {
+    fn offer(cmd: clap::Command, toolchains: &[String]) -> clap::Command {
+        cmd.mut_args(|arg| match arg.get_id().as_str() {
+            "toolchain" => arg.value_parser(PossibleValuesParser::new(toolchains)),
+            _ => arg,
+        })
+        .mut_subcommands(|cmd| offer(cmd, toolchains))
+    }
+
+    let plus_toolchains = toolchains
+        .iter()
+        .map(|t| format!("+{t}"))
+        .collect::<Vec<_>>();
+    offer(Rustup::command(), toolchains).mut_arg("+toolchain", |arg| {
+        arg.value_parser(PossibleValuesParser::new(plus_toolchains))
+    })
+}
+
 fn output_completion_script(
     shell: Shell,
     command: CompletionCommand,
@@ -41,7 +41,7 @@ use std::str::FromStr;
 use std::{fmt, fs};
 
 use anstyle::Style;
-use anyhow::{Context, Result, anyhow};
+use anyhow::{Context, Result, anyhow, bail};
 use cfg_if::cfg_if;
 use clap::ValueEnum;
 use clap::builder::PossibleValue;
@@ -50,6 +50,7 @@ use itertools::Itertools;
 use same_file::Handle;
 use serde::{Deserialize, Serialize};
 use tracing::{error, info, trace, warn};
+use url::Url;
 
 use crate::{
     DUP_TOOLS, TOOLS,
@@ -60,13 +61,15 @@ use crate::{
     },
     config::Cfg,
     dist::{
-        DistOptions, PartialToolchainDesc, Profile, TargetTriple, ToolchainDesc,
+        ComponentPolicy, DistOptions, PartialToolchainDesc, Profile, TargetTriple, ToolchainDesc,
         download::DownloadCfg,
     },
     download::download_file,
     errors::RustupError,
     install::{InstallMethod, UpdateStatus},
+    installer::{DownloadConfig, NetworkConfig},
     process::Process,
+    settings::SettingsFile,
     toolchain::{
         DistributableToolchain, MaybeOfficialToolchainName, ResolvableToolchainName, Toolchain,
         ToolchainName,
@@ -74,13 +77,22 @@ use crate::{
     utils::{self, ExitCode},
 };
 
+mod check;
+pub(crate) use check::check_integrity;
+
+mod diagnose;
+pub(crate) use diagnose::diagnose;
+
 #[cfg(unix)]
 mod shell;
 
 #[cfg(unix)]
 mod unix;
 #[cfg(unix)]
-use unix::{delete_rustup_and_cargo_home, do_add_to_path, do_remove_from_path};
+use unix::{
+    delete_rustup_and_cargo_home, do_add_path_hook, do_add_to_path, do_remove_from_path,
+    do_remove_path_hook,
+};
 #[cfg(unix)]
 pub(crate) use unix::{run_update, self_replace};
 
@@ -94,7 +106,10 @@ pub use windows::complete_windows_uninstall;
 #[cfg(all(windows, feature = "test"))]
 pub use windows::{RegistryGuard, RegistryValueId, USER_PATH, get_path};
 #[cfg(windows)]
-use windows::{delete_rustup_and_cargo_home, do_add_to_path, do_remove_from_path};
+use windows::{
+    delete_rustup_and_cargo_home, do_add_path_hook, do_add_to_path, do_remove_from_path,
+    do_remove_path_hook,
+};
 #[cfg(windows)]
 pub(crate) use windows::{run_update, self_replace};
 
@@ -106,6 +121,19 @@ pub(crate) struct InstallOpts<'a> {
     pub no_update_toolchain: bool,
     pub components: &'a [&'a str],
     pub targets: &'a [&'a str],
+    pub dist_server: Option<Url>,
+    pub network: Option<NetworkConfig>,
+    pub download: Option<DownloadConfig>,
+    /// Only install from the local caches, as with `RUSTUP_OFFLINE`.
+    pub offline: bool,
+    /// Let an existing default toolchain be downgraded to a release that has
+    /// all of `components`, as with `rustup toolchain install --allow-downgrade`.
+    pub allow_downgrade: bool,
+    /// Rehash the installed files, as with `rustup toolchain install --verify`.
+    pub verify_after_install: bool,
+    /// What to do about requested components that are unavailable, as with
+    /// `rustup toolchain install --component-policy`.
+    pub component_policy: ComponentPolicy,
 }
 
 impl InstallOpts<'_> {
@@ -118,9 +146,38 @@ impl InstallOpts<'_> {
             no_update_toolchain,
             components,
             targets,
+            dist_server,
+            network,
+            download,
+            offline,
+            allow_downgrade: _,
+            verify_after_install,
+            component_policy: _,
         } = self;
 
-        cfg.set_profile(profile)?;
+        match profile {
+            // Only used for this install, as other versions of rustup would
+            // not be able to read it back from the settings file
+            Profile::Custom(_) => cfg.profile_override = Some(profile),
+            _ => cfg.set_profile(profile)?,
+        }
+
+        if let Some(dist_server) = &dist_server {
+            cfg.set_dist_server(Some(dist_server.as_str()))?;
+        }
+
+        if network.is_some() {
+            cfg.network = network;
+        }
+        if download.is_some() {
+            cfg.download_config = download;
+        }
+        if offline {
+            cfg.offline = true;
+        }
+        if verify_after_install {
+            cfg.verify_after_install = true;
+        }
 
         if let Some(default_host_triple) = &default_host_triple {
             // Set host triple now as it will affect resolution of toolchain_str
@@ -268,6 +325,13 @@ impl SelfUpdateMode {
             // disable automatic self updates.
             return Ok(SelfUpdateMode::Disable);
         }
+        if cfg
+            .process
+            .var("RUSTUP_NO_SELF_UPDATE")
+            .is_ok_and(|v| v != "0")
+        {
+            return Ok(SelfUpdateMode::Disable);
+        }
 
         cfg.settings_file.with(|s| {
             Ok(match s.auto_self_update {
@@ -541,6 +605,25 @@ Your `PATH` environment variable will not be touched.
 
 static DEFAULT_UPDATE_ROOT: &str = "https://static.rust-lang.org/rustup";
 
+/// The target of the rustup builds that self-updates download.
+fn release_triple(process: &Process) -> TargetTriple {
+    // Get build triple
+    let triple = TargetTriple::from_build();
+
+    // For windows x86 builds seem slow when used with windows defender.
+    // The website defaulted to i686-windows-gnu builds for a long time.
+    // This ensures that we update to a version that's appropriate for users
+    // and also works around if the website messed up the detection.
+    // If someone really wants to use another version, they still can enforce
+    // that using the environment variable RUSTUP_OVERRIDE_HOST_TRIPLE.
+    #[cfg(windows)]
+    let triple = TargetTriple::from_host(process).unwrap_or(triple);
+
+    #[cfg(not(windows))]
+    let _ = process;
+    triple
+}
+
 fn update_root(process: &Process) -> String {
     process
         .var("RUSTUP_UPDATE_ROOT")
@@ -753,7 +836,7 @@ fn check_existence_of_settings_file(process: &Process) -> Result<()> {
 fn pre_install_msg(no_modify_path: bool, process: &Process) -> Result<String> {
     let cargo_home = process.cargo_home()?;
     let cargo_home_bin = cargo_home.join("bin");
-    let rustup_home = home::rustup_home()?;
+    let rustup_home = process.rustup_home()?;
 
     if !no_modify_path {
         // Brittle code warning: some duplication in unix::do_add_to_path
@@ -903,7 +986,7 @@ fn install_proxies_with_opts(process: &Process, force_hard_links: bool) -> Resul
         utils::symlink_or_hardlink_file
     };
 
-    for tool in DUP_TOOLS {
+    for tool in dup_tools(process)? {
         let tool_path = bin_path.join(format!("{tool}{EXE_SUFFIX}"));
         if let Ok(handle) = Handle::from_path(&tool_path) {
             // Like above, don't clobber anything that's already linked to
@@ -953,6 +1036,186 @@ fn install_proxies_with_opts(process: &Process, force_hard_links: bool) -> Resul
     Ok(())
 }
 
+/// The tools installed like [`DUP_TOOLS`], which are the compiled-in ones
+/// followed by those added to the `dup_tools` array of `settings.toml`.
+pub fn dup_tools(process: &Process) -> Result<Vec<String>> {
+    let mut tools: Vec<_> = DUP_TOOLS.iter().map(|t| t.to_string()).collect();
+    // Don't create the settings file when installing rustup itself, which
+    // also needs no `RUSTUP_HOME` yet
+    let path = process.rustup_home().map(|home| home.join("settings.toml"));
+    if let Ok(path) = path
+        && utils::is_file(&path)
+    {
+        let extra = SettingsFile::new(path).with(|s| Ok(s.dup_tools.clone()))?;
+        for tool in extra {
+            if !tools.contains(&tool) {
+                tools.push(tool);
+            }
+        }
+    }
+    Ok(tools)
+}
+
+/// Adds `binary` to the `dup_tools` of the settings and installs its proxy,
+/// unless something other than rustup is already installed under that name.
+pub(crate) fn add_dup_tool(cfg: &Cfg<'_>, binary: &str) -> Result<()> {
+    if crate::is_proxyable_tool_fast(binary) || binary == "rustup" {
+        bail!("'{binary}' is already proxied by rustup");
+    }
+    if binary.is_empty() || binary.contains(['/', '\\']) {
+        bail!("invalid binary name: '{binary}'");
+    }
+
+    cfg.with_settings_mut(|s| {
+        if !s.dup_tools.iter().any(|t| t == binary) {
+            s.dup_tools.push(binary.to_owned());
+        }
+        Ok(())
+    })?;
+    install_proxies(cfg.process)
+}
+
+/// Undoes [`add_dup_tool`], removing the proxy of `binary` if rustup
+/// installed one.
+pub(crate) fn remove_dup_tool(cfg: &Cfg<'_>, binary: &str) -> Result<()> {
+    if DUP_TOOLS.contains(&binary) {
+        bail!("'{binary}' is always proxied by rustup and cannot be removed");
+    }
+    let removed = cfg.with_settings_mut(|s| {
+        let len = s.dup_tools.len();
+        s.dup_tools.retain(|t| t != binary);
+        Ok(s.dup_tools.len() != len)
+    })?;
+    if !removed {
+        bail!("'{binary}' is not in the `dup_tools` of the settings");
+    }
+
+    let bin_path = cfg.process.cargo_home()?.join("bin");
+    let tool_path = bin_path.join(format!("{binary}{EXE_SUFFIX}"));
+    let rustup = Handle::from_path(bin_path.join(format!("rustup{EXE_SUFFIX}")))?;
+    if Handle::from_path(&tool_path).is_ok_and(|tool| tool == rustup) {
+        utils::remove_file("proxy", &tool_path)?;
+    }
+    Ok(())
+}
+
+/// Where the binaries replaced by proxies registered with `rustup proxy add`
+/// are kept, relative to `RUSTUP_HOME`.
+const PROXIED_DIR: &str = "proxied";
+
+/// Replaces `binary` in `CARGO_HOME/bin` with a proxy, so that it runs in the
+/// environment of the active toolchain like the tools rustup proxies itself.
+///
+/// Unless `force` is set, only binaries installed by `cargo install` are
+/// replaced, as anything else there was probably put there on purpose.
+pub(crate) fn register_proxy(cfg: &Cfg<'_>, binary: &str, force: bool) -> Result<()> {
+    if crate::is_proxyable_tool_fast(binary) || binary == "rustup" {
+        bail!("'{binary}' is already proxied by rustup");
+    }
+    if binary.is_empty() || binary.contains(['/', '\\']) {
+        bail!("invalid binary name: '{binary}'");
+    }
+
+    let _lock = cfg.lock_home()?;
+    let file_name = format!("{binary}{EXE_SUFFIX}");
+    let bin_path = cfg.process.cargo_home()?.join("bin");
+    let tool_path = bin_path.join(&file_name);
+    let proxied_path = cfg.rustup_dir.join(PROXIED_DIR).join(&file_name);
+    let rustup_path = bin_path.join(format!("rustup{EXE_SUFFIX}"));
+
+    let Ok(tool) = Handle::from_path(&tool_path) else {
+        bail!("'{binary}' is not installed in '{}'", bin_path.display());
+    };
+    if tool == Handle::from_path(&rustup_path)? {
+        if utils::is_file(&proxied_path) {
+            info!("'{binary}' is already proxied by rustup");
+            return Ok(());
+        }
+        bail!("'{}' is a rustup proxy of nothing", tool_path.display());
+    }
+    drop(tool);
+
+    if !force && !installed_by_cargo(cfg.process, &file_name)? {
+        bail!(
+            "'{}' was not installed by `cargo install`; use `--force` to proxy it anyway",
+            tool_path.display()
+        );
+    }
+
+    utils::ensure_dir_exists("proxied binaries", &cfg.rustup_dir.join(PROXIED_DIR))?;
+    let permit_copy_rename = cfg.process.permit_copy_rename();
+    utils::rename(
+        "proxied binary",
+        &tool_path,
+        &proxied_path,
+        permit_copy_rename,
+    )?;
+    utils::symlink_or_hardlink_file(&rustup_path, &tool_path)?;
+    info!("'{binary}' now runs through rustup");
+    Ok(())
+}
+
+/// Undoes [`register_proxy`], putting the proxied binary back in place.
+pub(crate) fn unregister_proxy(cfg: &Cfg<'_>, binary: &str) -> Result<()> {
+    let _lock = cfg.lock_home()?;
+    let file_name = format!("{binary}{EXE_SUFFIX}");
+    let proxied_path = cfg.rustup_dir.join(PROXIED_DIR).join(&file_name);
+    if !utils::is_file(&proxied_path) {
+        bail!("'{binary}' is not a registered proxy");
+    }
+
+    let bin_path = cfg.process.cargo_home()?.join("bin");
+    let tool_path = bin_path.join(&file_name);
+    let rustup = Handle::from_path(bin_path.join(format!("rustup{EXE_SUFFIX}")))?;
+    match Handle::from_path(&tool_path) {
+        // Reinstalled since, so the proxied binary is stale
+        Ok(tool) if tool != rustup => {
+            return utils::remove_file("proxied binary", &proxied_path);
+        }
+        Ok(_) => utils::remove_file("proxy", &tool_path)?,
+        Err(_) => {}
+    }
+    let permit_copy_rename = cfg.process.permit_copy_rename();
+    utils::rename(
+        "proxied binary",
+        &proxied_path,
+        &tool_path,
+        permit_copy_rename,
+    )
+}
+
+/// The binary run by the proxy `binary` registered with [`register_proxy`].
+pub fn registered_proxy(binary: &str, process: &Process) -> Result<Option<PathBuf>> {
+    let path = process
+        .rustup_home()?
+        .join(PROXIED_DIR)
+        .join(format!("{binary}{EXE_SUFFIX}"));
+    Ok(utils::is_file(&path).then_some(path))
+}
+
+/// Whether `file_name` in `CARGO_HOME/bin` was installed by `cargo install`,
+/// going by the metadata Cargo keeps of the packages it installed.
+fn installed_by_cargo(process: &Process, file_name: &str) -> Result<bool> {
+    let path = process.cargo_home()?.join(".crates.toml");
+    let Ok(contents) = fs::read_to_string(&path) else {
+        return Ok(false);
+    };
+    let metadata =
+        toml::from_str::<toml::Table>(&contents).with_context(|| RustupError::ParsingFile {
+            name: "cargo install metadata",
+            path,
+        })?;
+    // `[v1]` maps each package to the binaries it installed
+    Ok(metadata
+        .get("v1")
+        .and_then(|packages| packages.as_table())
+        .into_iter()
+        .flat_map(|packages| packages.values())
+        .filter_map(|bins| bins.as_array())
+        .flatten()
+        .any(|bin| bin.as_str() == Some(file_name)))
+}
+
 fn check_proxy_sanity(process: &Process, components: &[&str], desc: &ToolchainDesc) -> Result<()> {
     let bin_path = process.cargo_home()?.join("bin");
 
@@ -998,9 +1261,18 @@ async fn maybe_install_rust(opts: InstallOpts<'_>, cfg: &mut Cfg<'_>) -> Result<
     }
 
     let (components, targets) = (opts.components, opts.targets);
+    let allow_downgrade = opts.allow_downgrade;
+    let skip_unavailable = opts.component_policy.skips_unavailable();
     let toolchain = opts.install(cfg)?;
     if let Some(desc) = &toolchain {
-        let options = DistOptions::new(components, targets, desc, cfg.get_profile()?, true, cfg)?;
+        let options = DistOptions::new(
+            components,
+            targets,
+            desc,
+            cfg.get_profile()?,
+            skip_unavailable,
+            cfg,
+        )?;
         let status = if Toolchain::exists(cfg, &desc.into())? {
             warn!("Updating existing toolchain, profile choice will be ignored");
             // If we have a partial install we might not be able to read content here. We could:
@@ -1009,7 +1281,7 @@ async fn maybe_install_rust(opts: InstallOpts<'_>, cfg: &mut Cfg<'_>) -> Result<
             // - delete the partial install and start over
             // For now, we error.
             let toolchain = DistributableToolchain::new(cfg, desc.clone())?;
-            InstallMethod::Dist(options.for_update(&toolchain, false))
+            InstallMethod::Dist(options.for_update(&toolchain, allow_downgrade))
                 .install()
                 .await?
         } else {
@@ -1025,6 +1297,17 @@ async fn maybe_install_rust(opts: InstallOpts<'_>, cfg: &mut Cfg<'_>) -> Result<
     Ok(())
 }
 
+/// Registers `CARGO_HOME/bin` as the system Rust provider, by putting it at the
+/// front of the system `PATH`.
+pub(crate) fn install_path_hook(process: &Process) -> Result<()> {
+    do_add_path_hook(process)
+}
+
+/// Undoes [`install_path_hook`].
+pub(crate) fn uninstall_path_hook(process: &Process) -> Result<()> {
+    do_remove_path_hook(process)
+}
+
 pub(crate) fn uninstall(
     no_prompt: bool,
     no_modify_path: bool,
@@ -1059,10 +1342,13 @@ pub(crate) fn uninstall(
         }
     }
 
+    // Read before RUSTUP_HOME, and the settings with it, are gone
+    let dup_tools = dup_tools(process)?;
+
     info!("removing rustup home");
 
     // Delete RUSTUP_HOME
-    let rustup_dir = home::rustup_home()?;
+    let rustup_dir = process.rustup_home()?;
     if rustup_dir.exists() {
         utils::remove_dir("rustup_home", &rustup_dir)?;
     }
@@ -1099,7 +1385,8 @@ pub(crate) fn uninstall(
     // until this process exits (on windows).
     let tools = TOOLS
         .iter()
-        .chain(DUP_TOOLS.iter())
+        .map(|t| t.to_string())
+        .chain(dup_tools)
         .map(|t| format!("{t}{EXE_SUFFIX}"));
     let tools: Vec<_> = tools.chain(vec![format!("rustup{EXE_SUFFIX}")]).collect();
     let bin_dir = cargo_home.join("bin");
@@ -1277,17 +1564,7 @@ pub(crate) async fn prepare_update(dl_cfg: &DownloadCfg<'_>) -> Result<Option<Pa
         utils::remove_file("setup", &setup_path)?;
     }
 
-    // Get build triple
-    let triple = TargetTriple::from_build();
-
-    // For windows x86 builds seem slow when used with windows defender.
-    // The website defaulted to i686-windows-gnu builds for a long time.
-    // This ensures that we update to a version that's appropriate for users
-    // and also works around if the website messed up the detection.
-    // If someone really wants to use another version, they still can enforce
-    // that using the environment variable RUSTUP_OVERRIDE_HOST_TRIPLE.
-    #[cfg(windows)]
-    let triple = TargetTriple::from_host(dl_cfg.process).unwrap_or(triple);
+    let triple = release_triple(dl_cfg.process);
 
     // Get update root.
     let update_root = update_root(dl_cfg.process);
@@ -1318,7 +1595,15 @@ pub(crate) async fn prepare_update(dl_cfg: &DownloadCfg<'_>) -> Result<Option<Pa
 
     // Download new version
     info!("downloading self-update (new version: {available_version})");
-    download_file(&download_url, &setup_path, None, None, dl_cfg.process).await?;
+    download_file(
+        &download_url,
+        &setup_path,
+        None,
+        None,
+        dl_cfg.network,
+        dl_cfg.process,
+    )
+    .await?;
 
     // Mark as executable
     utils::make_executable(&setup_path)?;
@@ -1326,7 +1611,7 @@ pub(crate) async fn prepare_update(dl_cfg: &DownloadCfg<'_>) -> Result<Option<Pa
     Ok(Some(setup_path))
 }
 
-async fn get_available_rustup_version(dl_cfg: &DownloadCfg<'_>) -> Result<String> {
+pub(crate) async fn get_available_rustup_version(dl_cfg: &DownloadCfg<'_>) -> Result<String> {
     let update_root = update_root(dl_cfg.process);
     let tempdir = tempfile::Builder::new()
         .prefix("rustup-update")
@@ -1337,7 +1622,15 @@ async fn get_available_rustup_version(dl_cfg: &DownloadCfg<'_>) -> Result<String
     let release_file_url = format!("{update_root}/release-stable.toml");
     let release_file_url = utils::parse_url(&release_file_url)?;
     let release_file = tempdir.path().join("release-stable.toml");
-    download_file(&release_file_url, &release_file, None, None, dl_cfg.process).await?;
+    download_file(
+        &release_file_url,
+        &release_file,
+        None,
+        None,
+        dl_cfg.network,
+        dl_cfg.process,
+    )
+    .await?;
     let release_toml_str = utils::read_file("rustup release", &release_file)?;
     let release_toml = toml::from_str::<RustupManifest>(&release_toml_str)
         .context("unable to parse rustup release file")?;
@@ -1430,7 +1723,7 @@ mod tests {
 
     use crate::cli::self_update::InstallOpts;
     use crate::config::Cfg;
-    use crate::dist::{PartialToolchainDesc, Profile};
+    use crate::dist::{ComponentPolicy, PartialToolchainDesc, Profile};
     use crate::test::{Env, test_dir, with_rustup_home};
     use crate::{for_host, process::TestProcess};
 
@@ -1451,6 +1744,13 @@ mod tests {
                 components: &[],
                 targets: &[],
                 no_update_toolchain: false,
+                dist_server: None,
+                network: None,
+                download: None,
+                offline: false,
+                allow_downgrade: false,
+                verify_after_install: false,
+                component_policy: ComponentPolicy::Lenient,
             };
 
             assert_eq!(
@@ -0,0 +1,237 @@
+//! The checks run by `rustup self check` and [`installer::check_self_integrity`].
+//!
+//! [`installer::check_self_integrity`]: crate::installer::check_self_integrity
+
+use std::env::consts::EXE_SUFFIX;
+use std::fs;
+use std::path::Path;
+
+use same_file::Handle;
+
+use super::{release_triple, update_root};
+use crate::{
+    TOOLS,
+    config::Cfg,
+    dist::{
+        component::Components,
+        download::{DownloadCfg, file_hash},
+        prefix::InstallPrefix,
+    },
+    download::download_file,
+    installer::{Diagnostic, SelfCheckReport},
+    utils,
+};
+
+pub(crate) async fn check_integrity(cfg: &Cfg<'_>) -> SelfCheckReport {
+    let rustup = match cfg.process.cargo_home() {
+        Ok(cargo_home) => cargo_home.join(format!("bin/rustup{EXE_SUFFIX}")),
+        Err(_) => {
+            return SelfCheckReport {
+                checks: vec![Diagnostic::error(
+                    "rustup-installed",
+                    "the cargo home directory could not be determined",
+                    "set CARGO_HOME to the directory rustup was installed into",
+                )],
+            };
+        }
+    };
+    if !rustup.is_file() {
+        return SelfCheckReport {
+            checks: vec![Diagnostic::error(
+                "rustup-installed",
+                format!("rustup is not installed at '{}'", rustup.display()),
+                "install rustup with rustup-init, or set CARGO_HOME to where it was installed",
+            )],
+        };
+    }
+
+    SelfCheckReport {
+        checks: vec![
+            binary_matches_release(cfg, &rustup).await,
+            proxies_linked(&rustup),
+            binary_writable(&rustup),
+            toolchain_metadata_supported(cfg),
+        ],
+    }
+}
+
+async fn binary_matches_release(cfg: &Cfg<'_>, rustup: &Path) -> Diagnostic {
+    let check = "rustup-binary-hash";
+    let version = env!("CARGO_PKG_VERSION");
+    if cfg.offline {
+        return Diagnostic::warning(
+            check,
+            format!(
+                "the release of rustup {version} was not fetched because RUSTUP_OFFLINE is set"
+            ),
+            "unset RUSTUP_OFFLINE to check it",
+        );
+    }
+
+    let triple = release_triple(cfg.process);
+    let url = format!(
+        "{}/archive/{version}/{triple}/rustup-init{EXE_SUFFIX}.sha256",
+        update_root(cfg.process)
+    );
+    let expected = async {
+        let url = utils::parse_url(&url)?;
+        let dir = tempfile::Builder::new().prefix("rustup-check").tempdir()?;
+        let file = dir.path().join("rustup-init.sha256");
+        download_file(
+            &url,
+            &file,
+            None,
+            None,
+            DownloadCfg::new(cfg).network,
+            cfg.process,
+        )
+        .await?;
+        let hash = utils::read_file("rustup hash", &file)?;
+        Ok::<_, anyhow::Error>(
+            hash.split_whitespace()
+                .next()
+                .unwrap_or_default()
+                .to_owned(),
+        )
+    };
+    let expected = match expected.await {
+        Ok(hash) => hash,
+        Err(e) => {
+            return Diagnostic::warning(
+                check,
+                format!("the hash of rustup {version} could not be fetched from '{url}': {e:#}"),
+                "check your network connection and proxy settings, or RUSTUP_UPDATE_ROOT",
+            );
+        }
+    };
+
+    match file_hash(rustup) {
+        Ok(actual) if actual == expected => Diagnostic::ok(
+            check,
+            format!(
+                "'{}' is the release build of rustup {version}",
+                rustup.display()
+            ),
+        ),
+        Ok(_) => Diagnostic::error(
+            check,
+            format!(
+                "'{}' differs from the release build of rustup {version}",
+                rustup.display()
+            ),
+            "reinstall rustup with rustup-init, unless it was built or patched on purpose",
+        ),
+        Err(e) => Diagnostic::error(
+            check,
+            format!("'{}' could not be read: {e:#}", rustup.display()),
+            "fix the permissions of the file, or reinstall rustup with rustup-init",
+        ),
+    }
+}
+
+fn proxies_linked(rustup: &Path) -> Diagnostic {
+    let check = "proxies-linked";
+    let bin = rustup.parent().unwrap_or(rustup);
+    let handle = match Handle::from_path(rustup) {
+        Ok(handle) => handle,
+        Err(e) => {
+            return Diagnostic::error(
+                check,
+                format!("'{}' could not be opened: {e}", rustup.display()),
+                "fix the permissions of the file, or reinstall rustup with rustup-init",
+            );
+        }
+    };
+
+    let broken = TOOLS
+        .iter()
+        .filter(|tool| {
+            let proxy = bin.join(format!("{tool}{EXE_SUFFIX}"));
+            Handle::from_path(proxy).map_or(true, |proxy| proxy != handle)
+        })
+        .copied()
+        .collect::<Vec<_>>();
+    if broken.is_empty() {
+        return Diagnostic::ok(
+            check,
+            format!("the proxies in '{}' run rustup", bin.display()),
+        );
+    }
+    Diagnostic::error(
+        check,
+        format!(
+            "these proxies are missing from '{}' or do not run rustup: {}",
+            bin.display(),
+            broken.join(", ")
+        ),
+        "run `rustup self update` to link them again",
+    )
+}
+
+fn binary_writable(rustup: &Path) -> Diagnostic {
+    let check = "rustup-binary-writable";
+    let bin = rustup.parent().unwrap_or(rustup);
+    let readonly = fs::metadata(rustup).map(|m| m.permissions().readonly());
+    match (tempfile::tempfile_in(bin), readonly) {
+        (Ok(_), Ok(false)) => Diagnostic::ok(
+            check,
+            format!(
+                "'{}' can be replaced by `rustup self update`",
+                rustup.display()
+            ),
+        ),
+        (Err(e), _) | (_, Err(e)) => Diagnostic::error(
+            check,
+            format!("'{}' is not writable: {e}", bin.display()),
+            "fix the permissions of the directory, or update rustup with the tool that installed it",
+        ),
+        (Ok(_), Ok(true)) => Diagnostic::error(
+            check,
+            format!("'{}' is read-only", rustup.display()),
+            "fix the permissions of the file, or update rustup with the tool that installed it",
+        ),
+    }
+}
+
+fn toolchain_metadata_supported(cfg: &Cfg<'_>) -> Diagnostic {
+    let check = "toolchain-metadata-version";
+    let toolchains = match cfg.list_toolchains() {
+        Ok(toolchains) => toolchains,
+        Err(e) => {
+            return Diagnostic::error(
+                check,
+                format!("the installed toolchains could not be listed: {e:#}"),
+                format!("check that '{}' is readable", cfg.toolchains_dir.display()),
+            );
+        }
+    };
+
+    let unsupported = toolchains
+        .iter()
+        .filter(|name| {
+            let path = cfg.toolchain_path(&(*name).into());
+            Components::open(InstallPrefix::from(path)).is_err()
+        })
+        .map(|name| name.to_string())
+        .collect::<Vec<_>>();
+    if unsupported.is_empty() {
+        return Diagnostic::ok(
+            check,
+            format!(
+                "the metadata of {} installed toolchain(s) is supported",
+                toolchains.len()
+            ),
+        );
+    }
+    let first = &unsupported[0];
+    Diagnostic::error(
+        check,
+        format!(
+            "these toolchains were installed in a format this rustup does not support: {}",
+            unsupported.join(", ")
+        ),
+        format!(
+            "reinstall them, e.g. `rustup toolchain uninstall {first} && rustup toolchain install {first}`"
+        ),
+    )
+}
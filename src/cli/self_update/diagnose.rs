@@ -0,0 +1,217 @@
+//! The checks run by `rustup self diagnose` and [`installer::diagnose`].
+//!
+//! [`installer::diagnose`]: crate::installer::diagnose
+
+use std::env::{self, consts::EXE_SUFFIX};
+use std::path::{Path, PathBuf};
+
+use itertools::Itertools;
+use same_file::is_same_file;
+use url::Url;
+
+use crate::{
+    config::Cfg,
+    dist::{Profile, download::DownloadCfg},
+    download::content_length,
+    installer::{Diagnostic, DiagnosticReport},
+    toolchain::Toolchain,
+};
+
+pub(crate) async fn diagnose(cfg: &Cfg<'_>) -> DiagnosticReport {
+    DiagnosticReport {
+        checks: vec![
+            rustup_on_path(cfg),
+            cargo_bin_on_path(cfg),
+            other_rust(cfg),
+            toolchains_intact(cfg),
+            rustup_home_writable(cfg),
+            dist_server_reachable(cfg).await,
+        ],
+    }
+}
+
+/// The directories in `PATH`, in order.
+fn path_dirs(cfg: &Cfg<'_>) -> Vec<PathBuf> {
+    cfg.process
+        .var_os("PATH")
+        .map(|paths| env::split_paths(&paths).collect())
+        .unwrap_or_default()
+}
+
+fn rustup_on_path(cfg: &Cfg<'_>) -> Diagnostic {
+    let check = "rustup-on-path";
+    let rustup = format!("rustup{EXE_SUFFIX}");
+    match path_dirs(cfg)
+        .iter()
+        .map(|dir| dir.join(&rustup))
+        .find(|p| p.is_file())
+    {
+        Some(path) => Diagnostic::ok(check, format!("rustup is on PATH at '{}'", path.display())),
+        None => Diagnostic::warning(
+            check,
+            "rustup is not on PATH",
+            "add the directory rustup is installed in, usually `$CARGO_HOME/bin`, to PATH",
+        ),
+    }
+}
+
+fn cargo_bin_on_path(cfg: &Cfg<'_>) -> Diagnostic {
+    let check = "cargo-bin-on-path";
+    let Ok(cargo_home) = cfg.process.cargo_home() else {
+        return Diagnostic::error(
+            check,
+            "the cargo home directory could not be determined",
+            "set CARGO_HOME to the directory rustup was installed into",
+        );
+    };
+    let bin = cargo_home.join("bin");
+    let on_path = path_dirs(cfg)
+        .iter()
+        .any(|dir| *dir == bin || is_same_file(dir, &bin).unwrap_or(false));
+    if on_path {
+        return Diagnostic::ok(check, format!("'{}' is on PATH", bin.display()));
+    }
+
+    let fix = if cfg!(windows) {
+        "restart your terminal, or add it to the `Path` environment variable of your user"
+    } else {
+        "restart your shell, or run `. \"$CARGO_HOME/env\"` in it"
+    };
+    Diagnostic::warning(
+        check,
+        format!(
+            "'{}' is not on PATH, so the rustup proxies for cargo and rustc will not be found",
+            bin.display()
+        ),
+        fix,
+    )
+}
+
+fn other_rust(cfg: &Cfg<'_>) -> Diagnostic {
+    let check = "conflicting-installation";
+    // A `rustc` or `cargo` that is not a rustup proxy belongs to another installation
+    let rustup = cfg
+        .process
+        .cargo_home()
+        .map(|home| home.join(format!("bin/rustup{EXE_SUFFIX}")));
+    let is_proxy = |dir: &Path, binary: &Path| {
+        let local_rustup = dir.join(format!("rustup{EXE_SUFFIX}"));
+        [Some(local_rustup), rustup.as_ref().ok().cloned()]
+            .into_iter()
+            .flatten()
+            .any(|rustup| is_same_file(binary, rustup).unwrap_or(false))
+    };
+    let other = path_dirs(cfg).into_iter().find(|dir| {
+        ["rustc", "cargo"].iter().any(|tool| {
+            let binary = dir.join(format!("{tool}{EXE_SUFFIX}"));
+            binary.is_file() && !is_proxy(dir, &binary)
+        })
+    });
+    match other {
+        None => Diagnostic::ok(check, "no other installation of Rust is on PATH"),
+        Some(dir) => Diagnostic::warning(
+            check,
+            format!(
+                "another installation of Rust is on PATH at '{}'",
+                dir.display()
+            ),
+            "uninstall it, or make sure it comes after `$CARGO_HOME/bin` in PATH",
+        ),
+    }
+}
+
+fn toolchains_intact(cfg: &Cfg<'_>) -> Diagnostic {
+    let check = "toolchains-intact";
+    let toolchains = match cfg.list_toolchains() {
+        Ok(toolchains) => toolchains,
+        Err(e) => {
+            return Diagnostic::error(
+                check,
+                format!("the installed toolchains could not be listed: {e:#}"),
+                format!("check that '{}' is readable", cfg.toolchains_dir.display()),
+            );
+        }
+    };
+
+    let mut broken = Vec::new();
+    for name in &toolchains {
+        let intact = Toolchain::new(cfg, name.into())
+            .map_err(anyhow::Error::from)
+            .and_then(|toolchain| toolchain.installed_components())
+            .and_then(|components| {
+                components.iter().try_fold(true, |intact, component| {
+                    let (missing, corrupted) = component.verify()?;
+                    Ok(intact && missing.is_empty() && corrupted.is_empty())
+                })
+            });
+        if !intact.unwrap_or(false) {
+            broken.push(name);
+        }
+    }
+
+    if broken.is_empty() {
+        return Diagnostic::ok(
+            check,
+            format!("{} installed toolchain(s) are intact", toolchains.len()),
+        );
+    }
+    let first = broken[0];
+    // Reinstall with the profile the toolchain was installed with, where it is known
+    let profile = Toolchain::new(cfg, first.into())
+        .ok()
+        .and_then(|toolchain| toolchain.install_metadata().ok().flatten())
+        .filter(|metadata| !matches!(metadata.profile, Profile::Custom(_)))
+        .map(|metadata| format!(" --profile {}", metadata.profile))
+        .unwrap_or_default();
+    Diagnostic::error(
+        check,
+        format!(
+            "files of these toolchains are missing or corrupted: {}",
+            broken.iter().join(", ")
+        ),
+        format!(
+            "reinstall them, e.g. `rustup toolchain uninstall {first} && rustup toolchain install {first}{profile}`"
+        ),
+    )
+}
+
+fn rustup_home_writable(cfg: &Cfg<'_>) -> Diagnostic {
+    let check = "rustup-home-writable";
+    match tempfile::tempfile_in(&cfg.rustup_dir) {
+        Ok(_) => Diagnostic::ok(check, format!("'{}' is writable", cfg.rustup_dir.display())),
+        Err(e) => Diagnostic::error(
+            check,
+            format!("'{}' is not writable: {e}", cfg.rustup_dir.display()),
+            "fix the permissions of the directory, or point RUSTUP_HOME to a writable one",
+        ),
+    }
+}
+
+async fn dist_server_reachable(cfg: &Cfg<'_>) -> Diagnostic {
+    let check = "dist-server-reachable";
+    if cfg.offline {
+        return Diagnostic::warning(
+            check,
+            "the dist server was not contacted because RUSTUP_OFFLINE is set",
+            "unset RUSTUP_OFFLINE to check it",
+        );
+    }
+
+    let server = &cfg.dist_root_server;
+    let url = format!("{}/channel-rust-stable.toml.sha256", cfg.dist_root_url);
+    let dl_cfg = DownloadCfg::new(cfg);
+    let reached = match Url::parse(&url) {
+        Ok(url) => content_length(&url, dl_cfg.network, dl_cfg.download_config, cfg.process)
+            .await
+            .map(|_| ()),
+        Err(e) => Err(e.into()),
+    };
+    match reached {
+        Ok(()) => Diagnostic::ok(check, format!("the dist server '{server}' is reachable")),
+        Err(e) => Diagnostic::error(
+            check,
+            format!("the dist server '{server}' could not be reached: {e:#}"),
+            "check your network connection and proxy settings, or RUSTUP_DIST_SERVER",
+        ),
+    }
+}
@@ -123,6 +123,62 @@ pub(crate) fn do_write_env_files(process: &Process) -> Result<()> {
     Ok(())
 }
 
+/// The file that makes the system shells pick up the Cargo bin directory:
+/// an entry in `/etc/paths.d` on macOS, and a profile script elsewhere.
+fn path_hook_file(process: &Process) -> PathBuf {
+    // test runner should set this, nothing else
+    if let Ok(path) = process.var("RUSTUP_OVERRIDE_PATH_HOOK") {
+        return PathBuf::from(path);
+    }
+    if cfg!(target_os = "macos") {
+        PathBuf::from("/etc/paths.d/rustup")
+    } else {
+        PathBuf::from("/etc/profile.d/rustup.sh")
+    }
+}
+
+pub(crate) fn do_add_path_hook(process: &Process) -> Result<()> {
+    let cargo_home = process.cargo_home()?;
+    if process
+        .home_dir()
+        .is_some_and(|home| cargo_home.starts_with(home))
+    {
+        warn!(
+            "the Cargo home '{}' is inside your home directory, so it may not be readable by other users",
+            cargo_home.display()
+        );
+    }
+
+    let hook = if cfg!(target_os = "macos") {
+        format!("{}\n", cargo_home.join("bin").display())
+    } else {
+        // The env script keeps the Cargo bin directory at the front of `PATH`
+        do_write_env_files(process)?;
+        format!(". \"{}\"\n", cargo_home.join("env").display())
+    };
+
+    let hook_file = path_hook_file(process);
+    utils::write_file("path hook", &hook_file, &hook).with_context(|| {
+        format!(
+            "could not write '{}': you may need to run this command as root",
+            hook_file.display()
+        )
+    })
+}
+
+pub(crate) fn do_remove_path_hook(process: &Process) -> Result<()> {
+    let hook_file = path_hook_file(process);
+    if !hook_file.exists() {
+        return Ok(());
+    }
+    utils::remove_file("path hook", &hook_file).with_context(|| {
+        format!(
+            "could not remove '{}': you may need to run this command as root",
+            hook_file.display()
+        )
+    })
+}
+
 /// Tell the upgrader to replace the rustup bins, then delete
 /// itself.
 pub(crate) fn run_update(setup_path: &Path) -> Result<utils::ExitCode> {
@@ -278,6 +278,7 @@ pub(crate) async fn try_install_msvc(
         &visual_studio,
         None,
         None,
+        dl_cfg.network,
         dl_cfg.process,
     )
     .await?;
@@ -576,6 +577,16 @@ pub(crate) fn do_remove_from_path(process: &Process) -> Result<()> {
     do_remove_from_programs()
 }
 
+/// Puts the Cargo bin directory at the front of the user `PATH`, without
+/// registering rustup in the list of installed programs.
+pub(crate) fn do_add_path_hook(process: &Process) -> Result<()> {
+    _apply_new_path(_with_path_cargo_home_bin(_add_to_path, process)?)
+}
+
+pub(crate) fn do_remove_path_hook(process: &Process) -> Result<()> {
+    _apply_new_path(_with_path_cargo_home_bin(_remove_from_path, process)?)
+}
+
 const RUSTUP_UNINSTALL_ENTRY: &str = r"Software\Microsoft\Windows\CurrentVersion\Uninstall\Rustup";
 
 fn rustup_uninstall_reg_key() -> Result<Key> {
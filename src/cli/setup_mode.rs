@@ -11,7 +11,7 @@ use crate::{
         self_update::{self, InstallOpts},
     },
     config::Cfg,
-    dist::Profile,
+    dist::{ComponentPolicy, Profile},
     process::Process,
     toolchain::MaybeOfficialToolchainName,
     utils,
@@ -128,6 +128,13 @@ pub async fn main(
         no_update_toolchain: no_update_default_toolchain,
         components: &component.iter().map(|s| &**s).collect::<Vec<_>>(),
         targets: &target.iter().map(|s| &**s).collect::<Vec<_>>(),
+        dist_server: None,
+        network: None,
+        download: None,
+        offline: false,
+        allow_downgrade: false,
+        verify_after_install: false,
+        component_policy: ComponentPolicy::Lenient,
     };
 
     let mut cfg = Cfg::from_env(current_dir, quiet, process)?;
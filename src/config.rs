@@ -1,12 +1,19 @@
 use std::fmt::{self, Debug, Display};
+use std::fs::{self, TryLockError};
 use std::io;
+use std::num::NonZero;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::sync::{Arc, Mutex, Weak};
+use std::thread;
+use std::time::{Duration, Instant};
 
 use anyhow::{Context, Result, anyhow, bail};
 use serde::Deserialize;
 use thiserror::Error as ThisError;
+use tokio::runtime::{Handle, RuntimeFlavor};
 use tracing::{debug, info, trace, warn};
+use url::Url;
 
 use crate::{
     cli::{common, self_update::SelfUpdateMode},
@@ -14,11 +21,15 @@ use crate::{
         self, AutoInstallMode, DistOptions, PartialToolchainDesc, Profile, TargetTriple,
         ToolchainDesc,
     },
+    download::mirror_selector::{self, MirrorSelector},
     errors::RustupError,
     fallback_settings::FallbackSettings,
     install::{InstallMethod, UpdateStatus},
+    installer::{DownloadConfig, NetworkConfig, ProgressHandler},
     process::Process,
-    settings::{MetadataVersion, Settings, SettingsFile},
+    settings::{
+        MetadataVersion, MirrorSelection, Settings, SettingsFile, ToolchainFileSearchLimit,
+    },
     toolchain::{
         CustomToolchainName, DistributableToolchain, LocalToolchainName, PathBasedToolchainName,
         ResolvableLocalToolchainName, ResolvableToolchainName, Toolchain, ToolchainName,
@@ -26,6 +37,9 @@ use crate::{
     utils,
 };
 
+/// The extension of [`Cfg::toolchain_link_file`].
+const TOOLCHAIN_LINK_EXTENSION: &str = "link";
+
 #[derive(Debug, ThisError)]
 enum OverrideFileConfigError {
     #[error(
@@ -39,23 +53,48 @@ enum OverrideFileConfigError {
 }
 
 #[derive(Debug, Default, Deserialize, PartialEq, Eq)]
-struct OverrideFile {
-    toolchain: ToolchainSection,
+pub(crate) struct OverrideFile {
+    pub(crate) toolchain: ToolchainSection,
 }
 
 impl OverrideFile {
     fn is_empty(&self) -> bool {
         self.toolchain.is_empty()
     }
+
+    /// Fails if the file at `path` asks for a newer rustup than this one.
+    pub(crate) fn check_min_rustup_version(&self, path: &Path) -> Result<()> {
+        let Some(required) = &self.toolchain.min_rustup_version else {
+            return Ok(());
+        };
+        let min = semver::Version::parse(required).with_context(|| {
+            format!(
+                "invalid min-rustup-version '{required}' in '{}'",
+                path.display()
+            )
+        })?;
+        let current = semver::Version::parse(env!("CARGO_PKG_VERSION"))?;
+        if current < min {
+            return Err(RustupError::RustupTooOld {
+                path: path.to_owned(),
+                required: required.clone(),
+                current: current.to_string(),
+            }
+            .into());
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug, Default, Deserialize, PartialEq, Eq)]
-struct ToolchainSection {
-    channel: Option<String>,
-    path: Option<PathBuf>,
-    components: Option<Vec<String>>,
-    targets: Option<Vec<String>>,
-    profile: Option<String>,
+pub(crate) struct ToolchainSection {
+    pub(crate) channel: Option<String>,
+    pub(crate) path: Option<PathBuf>,
+    pub(crate) components: Option<Vec<String>>,
+    pub(crate) targets: Option<Vec<String>>,
+    pub(crate) profile: Option<String>,
+    #[serde(rename = "min-rustup-version")]
+    pub(crate) min_rustup_version: Option<String>,
 }
 
 impl ToolchainSection {
@@ -96,6 +135,7 @@ pub(crate) enum ActiveSource {
     CommandLine,
     OverrideDb(PathBuf),
     ToolchainFile(PathBuf),
+    EnvFile(PathBuf),
 }
 
 impl ActiveSource {
@@ -108,6 +148,9 @@ impl ActiveSource {
             Self::CommandLine => String::from("overridden by +toolchain on the command line"),
             Self::OverrideDb(path) => format!("directory override for '{}'", path.display()),
             Self::ToolchainFile(path) => format!("overridden by '{}'", path.display()),
+            Self::EnvFile(path) => {
+                format!("overridden by RUSTUP_TOOLCHAIN in '{}'", path.display())
+            }
         }
     }
 }
@@ -120,6 +163,7 @@ impl Display for ActiveSource {
             Self::CommandLine => "cli",
             Self::OverrideDb(_) => "path-override",
             Self::ToolchainFile(_) => "toolchain-file",
+            Self::EnvFile(_) => "env-file",
         })
     }
 }
@@ -228,6 +272,9 @@ impl From<LocalToolchainName> for OverrideCfg {
     }
 }
 
+/// How often [`Cfg::lock_home`] checks whether the lock was released.
+const LOCK_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
 #[cfg(unix)]
 pub(crate) const UNIX_FALLBACK_SETTINGS: &str = "/etc/rustup/settings.toml";
 
@@ -243,9 +290,109 @@ pub(crate) struct Cfg<'a> {
     env_override: Option<LocalToolchainName>,
     pub(crate) dist_root_server: String,
     pub dist_root_url: String,
+    /// The release servers to try, in order, when a download from
+    /// `dist_root_server` fails: the remaining mirrors, then the release server.
+    pub(crate) dist_fallback_servers: Vec<String>,
+    /// Picks the mirror to download from first, for the `latency` `mirror_selection`.
+    pub(crate) mirror_selector: Option<MirrorSelector>,
     pub quiet: bool,
     pub current_dir: PathBuf,
     pub process: &'a Process,
+    pub progress_handler: Option<ProgressHandler>,
+    pub network: Option<NetworkConfig>,
+    pub download_config: Option<DownloadConfig>,
+    /// Whether dist files are only read from the local caches, set by `RUSTUP_OFFLINE`.
+    pub offline: bool,
+    /// Whether installed files are rehashed against their packages, which is
+    /// the default when `CI` is set.
+    pub verify_after_install: bool,
+    /// How long to wait for other rustup processes to release `RUSTUP_HOME`,
+    /// set by `RUSTUP_LOCK_TIMEOUT`.
+    pub lock_timeout: Duration,
+    /// The keyring to verify the signatures of dist manifests against, set by
+    /// `RUSTUP_SIGNATURE_KEYRING` when `RUSTUP_VERIFY_SIGNATURES` is set.
+    pub(crate) signature_keyring: Option<PathBuf>,
+    home_lock: Mutex<Weak<fs::File>>,
+}
+
+/// An exclusive lock on `RUSTUP_HOME`, released once every [`Cfg::lock_home`]
+/// guard sharing it is dropped.
+pub(crate) struct HomeLock {
+    _file: Option<Arc<fs::File>>,
+}
+
+/// A pending [`Cfg::lock_home`], checked every [`LOCK_POLL_INTERVAL`].
+struct HomeLockAttempt {
+    file: Option<Arc<fs::File>>,
+    start: Instant,
+    waiting: bool,
+}
+
+impl HomeLockAttempt {
+    fn new() -> Self {
+        Self {
+            file: None,
+            start: Instant::now(),
+            waiting: false,
+        }
+    }
+
+    /// Takes the lock if it is free, or shares the one this process holds.
+    ///
+    /// Returns `None` while another process holds the lock, giving up once it
+    /// was waited on for longer than [`Cfg::lock_timeout`].
+    fn poll(&mut self, cfg: &Cfg<'_>) -> Result<Option<HomeLock>> {
+        let mut held = cfg.home_lock.lock().unwrap();
+        if let Some(file) = held.upgrade() {
+            return Ok(Some(HomeLock { _file: Some(file) }));
+        }
+
+        let path = cfg.rustup_dir.join("lock");
+        let file = match &self.file {
+            Some(file) => file.clone(),
+            None => {
+                let file = fs::OpenOptions::new()
+                    .create(true)
+                    .truncate(false)
+                    .write(true)
+                    .open(&path)
+                    .with_context(|| RustupError::WritingFile {
+                        name: "lock",
+                        path: path.clone(),
+                    })?;
+                self.file.insert(Arc::new(file)).clone()
+            }
+        };
+
+        match file.try_lock() {
+            Ok(()) => {
+                *held = Arc::downgrade(&file);
+                return Ok(Some(HomeLock { _file: Some(file) }));
+            }
+            Err(TryLockError::WouldBlock) => {}
+            // Some file systems, e.g. certain network mounts, cannot lock at all.
+            Err(TryLockError::Error(e)) if e.kind() == io::ErrorKind::Unsupported => {
+                debug!("cannot lock '{}': {e}", path.display());
+                return Ok(Some(HomeLock { _file: None }));
+            }
+            Err(TryLockError::Error(e)) => {
+                return Err(e).with_context(|| format!("could not lock '{}'", path.display()));
+            }
+        }
+
+        if self.start.elapsed() >= cfg.lock_timeout {
+            return Err(RustupError::LockTimeout {
+                path,
+                timeout: cfg.lock_timeout.as_secs(),
+            }
+            .into());
+        }
+        if !self.waiting {
+            info!("waiting for another rustup process to finish using RUSTUP_HOME");
+            self.waiting = true;
+        }
+        Ok(None)
+    }
 }
 
 impl<'a> Cfg<'a> {
@@ -299,9 +446,23 @@ impl<'a> Cfg<'a> {
             None => None,
         };
 
-        let dist_root_server = dist_root_server(process)?;
+        let mut dist_servers = settings_file.with(|s| dist_servers(s, process))?;
+        let dist_root_server = dist_servers.remove(0);
+        let mirror_selector =
+            settings_file.with(|s| Ok(latency_mirror_selector(s, &rustup_dir)))?;
         let dist_root = dist_root_server.clone() + "/dist";
 
+        let signature_keyring = match process
+            .var_os("RUSTUP_VERIFY_SIGNATURES")
+            .is_some_and(|it| it != "0")
+        {
+            true => match process.var_os("RUSTUP_SIGNATURE_KEYRING") {
+                Some(keyring) => Some(current_dir.join(keyring)),
+                None => return Err(RustupError::SignatureKeyringMissing.into()),
+            },
+            false => None,
+        };
+
         let cfg = Self {
             profile_override: None,
             rustup_dir,
@@ -314,9 +475,26 @@ impl<'a> Cfg<'a> {
             env_override,
             dist_root_server,
             dist_root_url: dist_root,
+            dist_fallback_servers: dist_servers,
+            mirror_selector,
             quiet,
             current_dir,
             process,
+            progress_handler: None,
+            network: None,
+            download_config: None,
+            offline: process.var_os("RUSTUP_OFFLINE").is_some_and(|it| it != "0"),
+            verify_after_install: process.var_os("CI").is_some(),
+            lock_timeout: Duration::from_secs(match process.var("RUSTUP_LOCK_TIMEOUT") {
+                Ok(s) => NonZero::from_str(&s)
+                    .context(
+                        "invalid value in RUSTUP_LOCK_TIMEOUT -- must be a natural number greater than zero",
+                    )?
+                    .get(),
+                Err(_) => 60,
+            }),
+            signature_keyring,
+            home_lock: Mutex::new(Weak::new()),
         };
 
         // Run some basic checks against the constructed configuration
@@ -327,11 +505,71 @@ impl<'a> Cfg<'a> {
                 .context("Unable parse configuration")?,
         )?;
 
+        // Report settings that cannot work, without getting in the way of
+        // the command being run
+        let installed = cfg.list_toolchains()?;
+        let host = cfg.get_default_host_triple()?;
+        match cfg
+            .settings_file
+            .with(|s| Ok(s.validate(&installed, &host)))?
+        {
+            Ok(warnings) => warnings.iter().for_each(|w| info!("settings: {w}")),
+            Err(e) => warn!("invalid settings: {e}"),
+        }
+
         Ok(cfg)
     }
 
+    /// Locks `RUSTUP_HOME` against changes by other rustup processes.
+    ///
+    /// Nested calls share the lock, so that it can be taken around any change.
+    /// Gives up after [`Self::lock_timeout`] if another process holds the lock.
+    /// Async code should call [`Self::lock_home_async`] instead.
+    pub(crate) fn lock_home(&self) -> Result<HomeLock> {
+        let mut attempt = HomeLockAttempt::new();
+        if let Some(lock) = attempt.poll(self)? {
+            return Ok(lock);
+        }
+        let mut wait = || loop {
+            thread::sleep(LOCK_POLL_INTERVAL);
+            if let Some(lock) = attempt.poll(self)? {
+                return Ok(lock);
+            }
+        };
+        // Don't stall the other tasks of a runtime this is called from.
+        match Handle::try_current() {
+            Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => {
+                tokio::task::block_in_place(wait)
+            }
+            _ => wait(),
+        }
+    }
+
+    /// Like [`Self::lock_home`], but waits for the lock without blocking the
+    /// runtime's thread.
+    pub(crate) async fn lock_home_async(&self) -> Result<HomeLock> {
+        let mut attempt = HomeLockAttempt::new();
+        loop {
+            if let Some(lock) = attempt.poll(self)? {
+                return Ok(lock);
+            }
+            tokio::time::sleep(LOCK_POLL_INTERVAL).await;
+        }
+    }
+
+    /// Changes the settings file while holding the [`Self::lock_home`] lock,
+    /// starting from its latest contents on disk.
+    pub(crate) fn with_settings_mut<T, F: FnOnce(&mut Settings) -> Result<T>>(
+        &self,
+        f: F,
+    ) -> Result<T> {
+        let _lock = self.lock_home()?;
+        self.settings_file.invalidate();
+        self.settings_file.with_mut(f)
+    }
+
     pub(crate) fn set_default(&self, toolchain: Option<&ToolchainName>) -> Result<()> {
-        self.settings_file.with_mut(|s| {
+        self.with_settings_mut(|s| {
             s.default_toolchain = toolchain.map(|t| t.to_string());
             Ok(())
         })?;
@@ -346,8 +584,8 @@ impl<'a> Cfg<'a> {
 
     pub(crate) fn set_profile(&mut self, profile: Profile) -> Result<()> {
         self.profile_override = None;
-        self.settings_file.with_mut(|s| {
-            s.profile = Some(profile);
+        self.with_settings_mut(|s| {
+            s.profile = Some(profile.clone());
             Ok(())
         })?;
         info!("profile set to {}", profile.as_str());
@@ -355,7 +593,7 @@ impl<'a> Cfg<'a> {
     }
 
     pub(crate) fn set_auto_self_update(&mut self, mode: SelfUpdateMode) -> Result<()> {
-        self.settings_file.with_mut(|s| {
+        self.with_settings_mut(|s| {
             s.auto_self_update = Some(mode);
             Ok(())
         })?;
@@ -363,8 +601,83 @@ impl<'a> Cfg<'a> {
         Ok(())
     }
 
+    /// Persists `server` as the release server, without the `/dist` suffix.
+    ///
+    /// `RUSTUP_DIST_SERVER` and `RUSTUP_DIST_ROOT` still take precedence.
+    pub(crate) fn set_dist_server(&mut self, server: Option<&str>) -> Result<()> {
+        let server = server.map(server_root);
+        self.with_settings_mut(|s| {
+            s.dist_server = server;
+            Ok(())
+        })?;
+        self.reload_dist_servers()?;
+        info!("dist server set to {}", self.dist_root_server);
+        Ok(())
+    }
+
+    /// Adds `mirror` to the end of the mirrors of the release server, and sets
+    /// whether to `fallback` to the release server when all of them fail.
+    pub(crate) fn add_mirror(&mut self, mirror: &Url, fallback: bool) -> Result<()> {
+        let mirror = server_root(mirror.as_str());
+        self.with_settings_mut(|s| {
+            if s.mirrors.contains(&mirror) {
+                info!("mirror {mirror} is already added");
+            } else {
+                info!("mirror {mirror} added");
+                s.mirrors.push(mirror);
+            }
+            s.mirror_fallback = (!fallback).then_some(false);
+            Ok(())
+        })?;
+        self.reload_dist_servers()
+    }
+
+    pub(crate) fn remove_mirror(&mut self, mirror: &Url) -> Result<()> {
+        let mirror = server_root(mirror.as_str());
+        self.with_settings_mut(|s| {
+            let len = s.mirrors.len();
+            s.mirrors.retain(|m| *m != mirror);
+            match s.mirrors.len() == len {
+                true => info!("no mirror {mirror} to remove"),
+                false => info!("mirror {mirror} removed"),
+            }
+            if s.mirrors.is_empty() {
+                s.mirror_fallback = None;
+            }
+            Ok(())
+        })?;
+        self.reload_dist_servers()
+    }
+
+    /// Replaces the mirrors of the release server with `mirrors`.
+    pub(crate) fn set_mirrors(&mut self, mirrors: &[Url]) -> Result<()> {
+        self.with_settings_mut(|s| {
+            s.mirrors = mirrors.iter().map(|m| server_root(m.as_str())).collect();
+            if s.mirrors.is_empty() {
+                s.mirror_fallback = None;
+            }
+            Ok(())
+        })?;
+        self.reload_dist_servers()
+    }
+
+    pub(crate) fn mirrors(&self) -> Result<Vec<String>> {
+        self.settings_file.with(|s| Ok(s.mirrors.clone()))
+    }
+
+    fn reload_dist_servers(&mut self) -> Result<()> {
+        let mut servers = self.settings_file.with(|s| dist_servers(s, self.process))?;
+        self.dist_root_server = servers.remove(0);
+        self.dist_root_url = self.dist_root_server.clone() + "/dist";
+        self.dist_fallback_servers = servers;
+        self.mirror_selector = self
+            .settings_file
+            .with(|s| Ok(latency_mirror_selector(s, &self.rustup_dir)))?;
+        Ok(())
+    }
+
     pub(crate) fn set_auto_install(&mut self, mode: AutoInstallMode) -> Result<()> {
-        self.settings_file.with_mut(|s| {
+        self.with_settings_mut(|s| {
             s.auto_install = Some(mode);
             Ok(())
         })?;
@@ -372,6 +685,28 @@ impl<'a> Cfg<'a> {
         Ok(())
     }
 
+    pub(crate) fn set_mirror_selection(&mut self, selection: MirrorSelection) -> Result<()> {
+        self.with_settings_mut(|s| {
+            s.mirror_selection = Some(selection);
+            Ok(())
+        })?;
+        self.reload_dist_servers()?;
+        info!("mirror selection set to {selection}");
+        Ok(())
+    }
+
+    pub(crate) fn set_toolchain_file_search_limit(
+        &mut self,
+        limit: ToolchainFileSearchLimit,
+    ) -> Result<()> {
+        self.with_settings_mut(|s| {
+            s.toolchain_file_search_limit = Some(limit);
+            Ok(())
+        })?;
+        info!("setting toolchain file search limit to {limit}");
+        Ok(())
+    }
+
     pub(crate) fn should_auto_install(&self) -> Result<bool> {
         if let Ok(mode) = self.process.var("RUSTUP_AUTO_INSTALL") {
             Ok(mode != "0")
@@ -389,11 +724,11 @@ impl<'a> Cfg<'a> {
     // a user upgrades from a version of Rustup without profiles to a version of
     // Rustup with profiles.
     pub(crate) fn get_profile(&self) -> Result<Profile> {
-        if let Some(p) = self.profile_override {
-            return Ok(p);
+        if let Some(p) = &self.profile_override {
+            return Ok(p.clone());
         }
         self.settings_file
-            .with(|s| Ok(s.profile.unwrap_or_default()))
+            .with(|s| Ok(s.profile.clone().unwrap_or_default()))
     }
 
     pub(crate) fn ensure_toolchains_dir(&self) -> Result<(), anyhow::Error> {
@@ -459,7 +794,7 @@ impl<'a> Cfg<'a> {
                     utils::remove_file("update hash", &file.path())?;
                 }
 
-                self.settings_file.with_mut(|s| {
+                self.with_settings_mut(|s| {
                     s.version = MetadataVersion::default();
                     Ok(())
                 })
@@ -540,12 +875,21 @@ impl<'a> Cfg<'a> {
                 // error when a nested rustup invocation occurs
                 Some((name.clone().into(), ActiveSource::Environment))
             }
+            // Then a `RUSTUP_TOOLCHAIN` from a `.env` file, if enabled
+            else if let Some((name, env_file)) = self.settings_file.with(|s| {
+                    self.find_env_file_toolchain(&self.current_dir, s)
+                })? {
+                Some((name.into(), ActiveSource::EnvFile(env_file)))
+            }
             // Then walk up the directory tree from 'path' looking for either the
             // directory in the override database, or a `rust-toolchain{.toml}` file,
             // in that order.
-            else if let Some((override_cfg, active_source)) = self.settings_file.with(|s| {
+            else if let Some((override_cfg, active_source)) = {
+                self.remove_stale_overrides_above(&self.current_dir)?;
+                self.settings_file.with(|s| {
                     self.find_override_from_dir_walk(&self.current_dir, s)
-                })? {
+                })?
+            } {
                 Some((override_cfg, active_source))
             }
             // Otherwise, there is no override.
@@ -556,14 +900,33 @@ impl<'a> Cfg<'a> {
         Ok(override_config)
     }
 
+    /// Removes the overrides for directories on the walk up from `dir` that
+    /// no longer exist, e.g. a project directory deleted from under the shell.
+    fn remove_stale_overrides_above(&self, dir: &Path) -> Result<()> {
+        let stale = self.settings_file.with(|s| {
+            Ok(self
+                .search_dirs(dir, s)
+                .into_iter()
+                .filter(|d| !d.is_dir() && s.dir_override(d).is_some())
+                .map(Path::to_owned)
+                .collect::<Vec<_>>())
+        })?;
+        for dir in stale {
+            warn!(
+                "removing the override for '{}', which no longer exists",
+                dir.display()
+            );
+            self.with_settings_mut(|s| Ok(s.remove_override(&dir)))?;
+        }
+        Ok(())
+    }
+
     fn find_override_from_dir_walk(
         &self,
         dir: &Path,
         settings: &Settings,
     ) -> Result<Option<(OverrideCfg, ActiveSource)>> {
-        let mut dir = Some(dir);
-
-        while let Some(d) = dir {
+        for d in self.search_dirs(dir, settings) {
             // First check the override database
             if let Some(name) = settings.dir_override(d) {
                 let source = ActiveSource::OverrideDb(d.to_owned());
@@ -580,50 +943,8 @@ impl<'a> Cfg<'a> {
             }
 
             // Then look for 'rust-toolchain' or 'rust-toolchain.toml'
-            let path_rust_toolchain = d.join("rust-toolchain");
-            let path_rust_toolchain_toml = d.join("rust-toolchain.toml");
-
-            let (toolchain_file, contents, parse_mode) = match (
-                utils::read_file("toolchain file", &path_rust_toolchain),
-                utils::read_file("toolchain file", &path_rust_toolchain_toml),
-            ) {
-                (contents, Err(_)) => {
-                    // no `rust-toolchain.toml` exists
-                    (path_rust_toolchain, contents, ParseMode::Both)
-                }
-                (Err(_), Ok(contents)) => {
-                    // only `rust-toolchain.toml` exists
-                    (path_rust_toolchain_toml, Ok(contents), ParseMode::OnlyToml)
-                }
-                (Ok(contents), Ok(_)) => {
-                    // both `rust-toolchain` and `rust-toolchain.toml` exist
-
-                    warn!(
-                        "both {} and {} exist; using contents of {0}",
-                        path_rust_toolchain
-                            .canonicalize()
-                            .unwrap_or_else(|_| PathBuf::from(&path_rust_toolchain))
-                            .display(),
-                        path_rust_toolchain_toml
-                            .canonicalize()
-                            .unwrap_or_else(|_| PathBuf::from(&path_rust_toolchain_toml))
-                            .display(),
-                    );
-
-                    (path_rust_toolchain, Ok(contents), ParseMode::Both)
-                }
-            };
-
-            if let Ok(contents) = contents {
-                // XXX Should not return the unvalidated contents; but a new
-                // internal only safe struct
-                let override_file =
-                    Cfg::parse_override_file(contents, parse_mode).with_context(|| {
-                        RustupError::ParsingFile {
-                            name: "override",
-                            path: toolchain_file.clone(),
-                        }
-                    })?;
+            if let Some((toolchain_file, override_file)) = Self::read_toolchain_file(d)? {
+                override_file.check_min_rustup_version(&toolchain_file)?;
                 if let Some(toolchain_name_str) = &override_file.toolchain.channel {
                     let toolchain_name = ResolvableToolchainName::try_from(toolchain_name_str)
                         .map_err(|_| {
@@ -664,14 +985,117 @@ impl<'a> Cfg<'a> {
                 let override_cfg = OverrideCfg::from_file(self, override_file)?;
                 return Ok(Some((override_cfg, source)));
             }
+        }
+
+        Ok(None)
+    }
+
+    /// The directories searched for overrides from `start`, innermost first,
+    /// as limited by the `toolchain_file_search_limit` setting.
+    fn search_dirs<'p>(&self, start: &'p Path, settings: &Settings) -> Vec<&'p Path> {
+        let limit = settings.toolchain_file_search_limit.unwrap_or_default();
+        limit.search_dirs(start, self.process.home_dir().as_deref())
+    }
+
+    /// The first `rust-toolchain` or `rust-toolchain.toml` file found walking up
+    /// from `start`, ignoring directory overrides.
+    pub(crate) fn find_toolchain_file(
+        &self,
+        start: &Path,
+    ) -> Result<Option<(PathBuf, OverrideFile)>> {
+        let dirs = self
+            .settings_file
+            .with(|s| Ok(self.search_dirs(start, s)))?;
+        for dir in dirs {
+            if let Some(found) = Self::read_toolchain_file(dir)? {
+                return Ok(Some(found));
+            }
+        }
+        Ok(None)
+    }
 
-            dir = d.parent();
+    /// Reads the toolchain file in `d`, preferring `rust-toolchain` when both
+    /// it and `rust-toolchain.toml` exist.
+    /// The `RUSTUP_TOOLCHAIN` set by the nearest `.env` or `.cargo/.env` file
+    /// from `dir` upwards, and that file, when the `env_file` setting is on.
+    fn find_env_file_toolchain(
+        &self,
+        dir: &Path,
+        settings: &Settings,
+    ) -> Result<Option<(LocalToolchainName, PathBuf)>> {
+        if !settings.env_file {
+            return Ok(None);
         }
 
+        for d in self.search_dirs(dir, settings) {
+            for env_file in [d.join(".env"), d.join(".cargo").join(".env")] {
+                let Ok(contents) = utils::read_file("env file", &env_file) else {
+                    continue;
+                };
+                let Some(name) = env_file_var(&contents, "RUSTUP_TOOLCHAIN") else {
+                    continue;
+                };
+                let invalid = || format!("invalid RUSTUP_TOOLCHAIN in '{}'", env_file.display());
+                let name = ResolvableLocalToolchainName::try_from(&name)
+                    .with_context(invalid)?
+                    .resolve(&get_default_host_triple(settings, self.process))
+                    .with_context(invalid)?;
+                return Ok(Some((name, env_file)));
+            }
+        }
         Ok(None)
     }
 
-    fn parse_override_file<S: AsRef<str>>(
+    fn read_toolchain_file(d: &Path) -> Result<Option<(PathBuf, OverrideFile)>> {
+        let path_rust_toolchain = d.join("rust-toolchain");
+        let path_rust_toolchain_toml = d.join("rust-toolchain.toml");
+
+        let (toolchain_file, contents, parse_mode) = match (
+            utils::read_file("toolchain file", &path_rust_toolchain),
+            utils::read_file("toolchain file", &path_rust_toolchain_toml),
+        ) {
+            (contents, Err(_)) => {
+                // no `rust-toolchain.toml` exists
+                (path_rust_toolchain, contents, ParseMode::Both)
+            }
+            (Err(_), Ok(contents)) => {
+                // only `rust-toolchain.toml` exists
+                (path_rust_toolchain_toml, Ok(contents), ParseMode::OnlyToml)
+            }
+            (Ok(contents), Ok(_)) => {
+                // both `rust-toolchain` and `rust-toolchain.toml` exist
+
+                warn!(
+                    "both {} and {} exist; using contents of {0}",
+                    path_rust_toolchain
+                        .canonicalize()
+                        .unwrap_or_else(|_| PathBuf::from(&path_rust_toolchain))
+                        .display(),
+                    path_rust_toolchain_toml
+                        .canonicalize()
+                        .unwrap_or_else(|_| PathBuf::from(&path_rust_toolchain_toml))
+                        .display(),
+                );
+
+                (path_rust_toolchain, Ok(contents), ParseMode::Both)
+            }
+        };
+
+        let Ok(contents) = contents else {
+            return Ok(None);
+        };
+        // XXX Should not return the unvalidated contents; but a new
+        // internal only safe struct
+        let override_file = Cfg::parse_override_file(contents, parse_mode).with_context(|| {
+            RustupError::ParsingFile {
+                name: "override",
+                path: toolchain_file.clone(),
+            }
+        })?;
+        Ok(Some((toolchain_file, override_file)))
+    }
+
+    pub(crate) fn parse_override_file<S: AsRef<str>>(
         contents: S,
         parse_mode: ParseMode,
     ) -> Result<OverrideFile> {
@@ -823,6 +1247,46 @@ impl<'a> Cfg<'a> {
         Ok((status, toolchain.into()))
     }
 
+    /// Installs the toolchain, with its components and targets, that the
+    /// toolchain file at the root of the Cargo workspace containing `dir`
+    /// asks for. Directory overrides and toolchain files elsewhere are ignored.
+    pub(crate) async fn install_workspace_toolchain(
+        &self,
+        dir: &Path,
+        force_non_host: bool,
+    ) -> Result<(UpdateStatus, ToolchainDesc)> {
+        let root = find_workspace_root(dir)?;
+        let Some((path, file)) = Self::read_toolchain_file(&root)? else {
+            bail!(
+                "no `rust-toolchain.toml` or `rust-toolchain` file at the workspace root '{}'",
+                root.display()
+            );
+        };
+        let OverrideCfg::Official {
+            toolchain,
+            components,
+            targets,
+            profile,
+        } = OverrideCfg::from_file(self, file)?
+        else {
+            bail!(
+                "'{}' does not name an official toolchain to install",
+                path.display()
+            );
+        };
+        let (status, _) = self
+            .ensure_installed(
+                &toolchain,
+                components,
+                targets,
+                profile,
+                force_non_host,
+                true,
+            )
+            .await?;
+        Ok((status, toolchain))
+    }
+
     /// Get the configured default toolchain.
     /// If none is configured, returns None
     /// If a bad toolchain name is configured, errors.
@@ -856,12 +1320,20 @@ impl<'a> Cfg<'a> {
                 // correct? could we get a short-read and report less toolchains
                 // than exist?
                 .filter_map(io::Result::ok)
-                .filter(|e| e.file_type().map(|f| !f.is_file()).unwrap_or(false))
-                .filter_map(|e| e.file_name().into_string().ok())
-                .filter_map(|n| ToolchainName::try_from(&n).ok())
+                .filter_map(|e| {
+                    let name = e.file_name().into_string().ok()?;
+                    match e.file_type().ok()?.is_file() {
+                        false => ToolchainName::try_from(&name).ok(),
+                        true => name
+                            .strip_suffix(&format!(".{TOOLCHAIN_LINK_EXTENSION}"))
+                            .and_then(|name| CustomToolchainName::try_from(name).ok())
+                            .map(ToolchainName::Custom),
+                    }
+                })
                 .collect();
 
             toolchains.sort();
+            toolchains.dedup();
 
             Ok(toolchains)
         } else {
@@ -890,7 +1362,7 @@ impl<'a> Cfg<'a> {
 
     /// Create an override for a toolchain
     pub(crate) fn make_override(&self, path: &Path, toolchain: &ToolchainName) -> Result<()> {
-        self.settings_file.with_mut(|s| {
+        self.with_settings_mut(|s| {
             s.add_override(path, toolchain.to_string());
             Ok(())
         })
@@ -902,7 +1374,7 @@ impl<'a> Cfg<'a> {
         // if the supplied triple is insufficient / bad.
         PartialToolchainDesc::from_str("stable")?
             .resolve(&TargetTriple::new(host_triple.clone()))?;
-        self.settings_file.with_mut(|s| {
+        self.with_settings_mut(|s| {
             s.default_host_triple = Some(host_triple);
             Ok(())
         })
@@ -917,30 +1389,146 @@ impl<'a> Cfg<'a> {
     /// The path on disk of any concrete toolchain
     pub(crate) fn toolchain_path(&self, toolchain: &LocalToolchainName) -> PathBuf {
         match toolchain {
+            LocalToolchainName::Named(ToolchainName::Custom(name)) => {
+                let path = self.toolchains_dir.join(name.to_string());
+                match fs::symlink_metadata(&path) {
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                        match fs::read_to_string(self.toolchain_link_file(name)) {
+                            Ok(target) => PathBuf::from(target.trim_end_matches(['\n', '\r'])),
+                            Err(_) => path,
+                        }
+                    }
+                    _ => path,
+                }
+            }
             LocalToolchainName::Named(name) => self.toolchains_dir.join(name.to_string()),
             LocalToolchainName::Path(p) => p.to_path_buf(),
         }
     }
+
+    /// The file that links the custom toolchain `name` to its directory where
+    /// directory links are unsupported, holding the path of the directory.
+    ///
+    /// [`Self::toolchain_path`] follows it when there is no directory link.
+    pub(crate) fn toolchain_link_file(&self, name: &CustomToolchainName) -> PathBuf {
+        self.toolchains_dir
+            .join(format!("{name}.{TOOLCHAIN_LINK_EXTENSION}"))
+    }
+}
+
+/// The value of `key` in the `.env` file `contents`, in which each line is
+/// `KEY=value` or `export KEY=value`, values may be quoted, and `#` starts a
+/// comment. The last line setting `key` wins.
+fn env_file_var(contents: &str, key: &str) -> Option<String> {
+    let mut found = None;
+    for line in contents.lines() {
+        let line = line.trim();
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let Some((k, value)) = line.split_once('=') else {
+            continue;
+        };
+        if k.trim() != key {
+            continue;
+        }
+
+        let value = value.trim();
+        let value = match value.chars().next() {
+            Some(quote @ ('"' | '\'')) => value[1..].split(quote).next().unwrap_or_default(),
+            _ => value.split(" #").next().unwrap_or_default().trim_end(),
+        };
+        found = Some(value.to_owned());
+    }
+    found
+}
+
+/// The root of the Cargo workspace `dir` is in: the closest directory from
+/// `dir` upwards whose `Cargo.toml` has a `[workspace]` table, or else the
+/// directory of the closest `Cargo.toml`, as for a package of its own.
+fn find_workspace_root(dir: &Path) -> Result<PathBuf> {
+    let mut package = None;
+    for d in dir.ancestors() {
+        let manifest = d.join("Cargo.toml");
+        let Ok(contents) = fs::read_to_string(&manifest) else {
+            continue;
+        };
+        let table = contents
+            .parse::<toml::Table>()
+            .with_context(|| RustupError::ParsingFile {
+                name: "Cargo.toml",
+                path: manifest.clone(),
+            })?;
+        if table.contains_key("workspace") {
+            return Ok(d.to_owned());
+        }
+        package.get_or_insert_with(|| d.to_owned());
+    }
+    package.ok_or_else(|| {
+        anyhow!(
+            "could not find `Cargo.toml` in '{}' or any parent directory",
+            dir.display()
+        )
+    })
+}
+
+/// The release servers to download from, in the order they are tried: the
+/// `mirrors` setting, then the release server from [`dist_root_server`]
+/// unless `mirror_fallback` is turned off. There is always at least one.
+fn dist_servers(settings: &Settings, process: &Process) -> Result<Vec<String>> {
+    let mut servers = settings.mirrors.clone();
+    if servers.is_empty() || settings.mirror_fallback != Some(false) {
+        servers.push(dist_root_server(settings, process)?);
+    }
+    Ok(servers)
+}
+
+/// The selector of the fastest mirror, when there are mirrors to choose
+/// between and `mirror_selection` is `latency`.
+fn latency_mirror_selector(settings: &Settings, rustup_dir: &Path) -> Option<MirrorSelector> {
+    if settings.mirror_selection != Some(MirrorSelection::Latency) || settings.mirrors.len() < 2 {
+        return None;
+    }
+    let ttl = settings
+        .mirror_selection_ttl
+        .map_or(mirror_selector::DEFAULT_TTL, Duration::from_secs);
+    Some(MirrorSelector::new(
+        settings.mirrors.clone(),
+        rustup_dir.join("mirror-selection.toml"),
+        ttl,
+    ))
+}
+
+/// `server` without a trailing slash or `/dist` suffix.
+pub(crate) fn server_root(server: &str) -> String {
+    let server = server.trim_end_matches('/');
+    server.strip_suffix("/dist").unwrap_or(server).to_owned()
 }
 
 /// The root path of the release server, without the `/dist` suffix.
+/// The environment takes precedence over the `dist_server` setting.
 /// By default, it points to [`dist::DEFAULT_DIST_SERVER`].
-fn dist_root_server(process: &Process) -> Result<String> {
+fn dist_root_server(settings: &Settings, process: &Process) -> Result<String> {
     if let Some(s) = process.var_opt("RUSTUP_DIST_SERVER")? {
         trace!("`RUSTUP_DIST_SERVER` has been set to `{s}`");
         return Ok(s);
     }
 
-    // For backwards compatibility
-    let Some(mut root) = process.var_opt("RUSTUP_DIST_ROOT")? else {
-        return Ok(dist::DEFAULT_DIST_SERVER.to_owned());
+    // For backwards compatibility, and for local mirrors
+    let Some(root) = process.var_opt("RUSTUP_DIST_ROOT")? else {
+        return Ok(match &settings.dist_server {
+            Some(s) => s.clone(),
+            None => dist::DEFAULT_DIST_SERVER.to_owned(),
+        });
     };
 
     trace!("`RUSTUP_DIST_ROOT` has been set to `{root}`");
-    if let Some(stripped) = root.strip_suffix("/dist") {
-        root.truncate(stripped.len());
+    // A directory laid out like the release server, such as a mounted share
+    if Path::new(&root).is_absolute() || Url::parse(&root).is_err() {
+        let dir = process.current_dir()?.join(&root);
+        let url = Url::from_directory_path(&dir)
+            .map_err(|()| anyhow!("invalid RUSTUP_DIST_ROOT directory: '{}'", dir.display()))?;
+        return Ok(server_root(url.as_str()));
     }
-    Ok(root)
+    Ok(server_root(&root))
 }
 
 impl Debug for Cfg<'_> {
@@ -957,9 +1545,19 @@ impl Debug for Cfg<'_> {
             env_override,
             dist_root_server,
             dist_root_url,
+            dist_fallback_servers,
+            mirror_selector: _,
             quiet,
             current_dir,
             process: _,
+            progress_handler: _,
+            network,
+            download_config,
+            offline,
+            verify_after_install,
+            lock_timeout,
+            signature_keyring,
+            home_lock: _,
         } = self;
 
         f.debug_struct("Cfg")
@@ -974,8 +1572,15 @@ impl Debug for Cfg<'_> {
             .field("env_override", env_override)
             .field("dist_root_server", dist_root_server)
             .field("dist_root_url", dist_root_url)
+            .field("dist_fallback_servers", dist_fallback_servers)
             .field("quiet", quiet)
             .field("current_dir", current_dir)
+            .field("network", network)
+            .field("download_config", download_config)
+            .field("offline", offline)
+            .field("verify_after_install", verify_after_install)
+            .field("lock_timeout", lock_timeout)
+            .field("signature_keyring", signature_keyring)
             .finish()
     }
 }
@@ -992,7 +1597,7 @@ fn no_toolchain_error(process: &Process) -> anyhow::Error {
 }
 
 /// Specifies how a `rust-toolchain`/`rust-toolchain.toml` configuration file should be parsed.
-enum ParseMode {
+pub(crate) enum ParseMode {
     /// Only permit TOML format in a configuration file.
     ///
     /// This variant is used for `rust-toolchain.toml` files (with `.toml` extension).
@@ -1013,7 +1618,11 @@ pub(crate) enum InstalledPath<'a> {
 
 #[cfg(test)]
 mod tests {
+    use std::collections::HashMap;
+
     use super::*;
+    use crate::process::TestProcess;
+    use crate::test::with_rustup_home;
 
     #[test]
     fn parse_legacy_toolchain_file() {
@@ -1029,6 +1638,7 @@ mod tests {
                     components: None,
                     targets: None,
                     profile: None,
+                    min_rustup_version: None,
                 }
             }
         );
@@ -1056,6 +1666,7 @@ profile = "default"
                         "thumbv2-none-eabi".into()
                     ]),
                     profile: Some("default".into()),
+                    min_rustup_version: None,
                 }
             }
         );
@@ -1077,6 +1688,7 @@ channel = "nightly-2020-07-10"
                     components: None,
                     targets: None,
                     profile: None,
+                    min_rustup_version: None,
                 }
             }
         );
@@ -1098,6 +1710,7 @@ path = "foobar"
                     components: None,
                     targets: None,
                     profile: None,
+                    min_rustup_version: None,
                 }
             }
         );
@@ -1120,6 +1733,7 @@ components = []
                     components: Some(vec![]),
                     targets: None,
                     profile: None,
+                    min_rustup_version: None,
                 }
             }
         );
@@ -1142,6 +1756,7 @@ targets = []
                     components: None,
                     targets: Some(vec![]),
                     profile: None,
+                    min_rustup_version: None,
                 }
             }
         );
@@ -1163,6 +1778,7 @@ components = [ "rustfmt" ]
                     components: Some(vec!["rustfmt".into()]),
                     targets: None,
                     profile: None,
+                    min_rustup_version: None,
                 }
             }
         );
@@ -1215,4 +1831,219 @@ channel = nightly
             Ok(OverrideFileConfigError::Parsing)
         ));
     }
+
+    #[test]
+    fn env_file_values() {
+        let contents = r#"
+# RUSTUP_TOOLCHAIN=stable
+CARGO_TERM_COLOR=always
+export RUSTUP_TOOLCHAIN = nightly # for the new lints
+"#;
+        assert_eq!(
+            env_file_var(contents, "RUSTUP_TOOLCHAIN").as_deref(),
+            Some("nightly")
+        );
+        assert_eq!(
+            env_file_var(
+                "RUSTUP_TOOLCHAIN='1.80 # not a comment'",
+                "RUSTUP_TOOLCHAIN"
+            )
+            .as_deref(),
+            Some("1.80 # not a comment")
+        );
+        assert_eq!(
+            env_file_var("RUSTUP_TOOLCHAINS=beta", "RUSTUP_TOOLCHAIN"),
+            None
+        );
+    }
+
+    #[test]
+    fn dist_server_setting_yields_to_environment() {
+        with_rustup_home(|home| {
+            let mut vars = HashMap::new();
+            home.apply(&mut vars);
+            let tp = TestProcess::with_vars(vars.clone());
+            let mut cfg = Cfg::from_env(tp.process.current_dir()?, true, &tp.process)?;
+            cfg.set_dist_server(Some("https://mirror.example.com/dist/"))?;
+            assert_eq!(cfg.dist_root_server, "https://mirror.example.com");
+            assert_eq!(cfg.dist_root_url, "https://mirror.example.com/dist");
+
+            // The setting is picked up by later invocations...
+            let cfg = Cfg::from_env(tp.process.current_dir()?, true, &tp.process)?;
+            assert_eq!(cfg.dist_root_server, "https://mirror.example.com");
+
+            // ... unless the environment says otherwise.
+            vars.insert(
+                "RUSTUP_DIST_SERVER".to_owned(),
+                "https://other.example.com".to_owned(),
+            );
+            let tp = TestProcess::with_vars(vars);
+            let cfg = Cfg::from_env(tp.process.current_dir()?, true, &tp.process)?;
+            assert_eq!(cfg.dist_root_server, "https://other.example.com");
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn dist_root_can_be_a_local_directory() {
+        let cwd = tempfile::tempdir().unwrap();
+        let dist_root = |root: &str| {
+            let vars = HashMap::from([("RUSTUP_DIST_ROOT".to_owned(), root.to_owned())]);
+            let tp = TestProcess::new(cwd.path(), &["rustup"], vars, "");
+            dist_root_server(&Settings::default(), &tp.process).unwrap()
+        };
+        let mirror = Url::from_directory_path(cwd.path().join("mirror")).unwrap();
+        let mirror = mirror.as_str().trim_end_matches('/');
+
+        assert_eq!(
+            dist_root("https://mirror.example.com/dist"),
+            "https://mirror.example.com"
+        );
+        assert_eq!(dist_root(&format!("{mirror}/dist")), mirror);
+        let absolute = cwd.path().join("mirror").join("dist");
+        assert_eq!(dist_root(absolute.to_str().unwrap()), mirror);
+        assert_eq!(dist_root("mirror"), mirror);
+    }
+
+    #[test]
+    fn toolchain_files_are_parsed_by_their_extension() {
+        use crate::toolchain::{ToolchainSpec, parse_toolchain_file};
+
+        let dir = tempfile::tempdir().unwrap();
+        let legacy = dir.path().join("rust-toolchain");
+        utils::write_file("toolchain file", &legacy, "nightly-2020-07-10").unwrap();
+        let spec = parse_toolchain_file(&legacy).unwrap();
+        assert_eq!(spec.channel.as_deref(), Some("nightly-2020-07-10"));
+
+        // A single channel name is not valid TOML
+        let toml = dir.path().join("rust-toolchain.toml");
+        utils::write_file("toolchain file", &toml, "nightly-2020-07-10").unwrap();
+        assert!(parse_toolchain_file(&toml).is_err());
+
+        utils::write_file(
+            "toolchain file",
+            &toml,
+            r#"
+[toolchain]
+channel = "beta"
+components = ["rustfmt"]
+targets = ["wasm32-unknown-unknown"]
+profile = "minimal"
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            parse_toolchain_file(&toml).unwrap(),
+            ToolchainSpec {
+                channel: Some("beta".to_owned()),
+                path: None,
+                components: vec!["rustfmt".to_owned()],
+                targets: vec!["wasm32-unknown-unknown".to_owned()],
+                profile: Some(Profile::Minimal),
+            }
+        );
+    }
+
+    #[test]
+    fn home_lock_is_shared_within_a_process() {
+        with_rustup_home(|home| {
+            let mut vars = HashMap::new();
+            home.apply(&mut vars);
+            vars.insert("RUSTUP_LOCK_TIMEOUT".to_owned(), "1".to_owned());
+            let tp = TestProcess::with_vars(vars);
+            let mut cfg = Cfg::from_env(tp.process.current_dir()?, true, &tp.process)?;
+
+            let outer = cfg.lock_home()?;
+            // Nested changes reuse the lock instead of waiting for it...
+            cfg.set_profile(Profile::Minimal)?;
+            // ... while other processes have to wait.
+            let other = Cfg::from_env(tp.process.current_dir()?, true, &tp.process)?;
+            let err = other.lock_home().err().unwrap();
+            assert!(matches!(
+                err.downcast_ref::<RustupError>(),
+                Some(RustupError::LockTimeout { timeout: 1, .. })
+            ));
+
+            drop(outer);
+            other.lock_home()?;
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn home_lock_waits_without_blocking_the_runtime() {
+        with_rustup_home(|home| {
+            let mut vars = HashMap::new();
+            home.apply(&mut vars);
+            vars.insert("RUSTUP_LOCK_TIMEOUT".to_owned(), "10".to_owned());
+            let tp = TestProcess::with_vars(vars);
+            let cfg = Cfg::from_env(tp.process.current_dir()?, true, &tp.process)?;
+            let other = Cfg::from_env(tp.process.current_dir()?, true, &tp.process)?;
+
+            // Another task on the same thread releases the lock while it is waited on.
+            let outer = cfg.lock_home()?;
+            let release = async {
+                tokio::time::sleep(Duration::from_millis(200)).await;
+                drop(outer);
+            };
+            let (lock, ()) = tokio::runtime::Builder::new_current_thread()
+                .enable_time()
+                .build()?
+                .block_on(async { tokio::join!(other.lock_home_async(), release) });
+            lock?;
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn toolchains_can_be_linked_by_file() {
+        with_rustup_home(|home| {
+            let mut vars = HashMap::new();
+            home.apply(&mut vars);
+            let tp = TestProcess::with_vars(vars);
+            let cfg = Cfg::from_env(tp.process.current_dir()?, true, &tp.process)?;
+
+            let linked = tempfile::tempdir()?;
+            let name = CustomToolchainName::try_from("mine")?;
+            cfg.ensure_toolchains_dir()?;
+            let target = linked.path().to_str().unwrap();
+            fs::write(cfg.toolchain_link_file(&name), format!("{target}\n"))?;
+
+            let local = LocalToolchainName::from(&ToolchainName::Custom(name.clone()));
+            assert_eq!(cfg.list_toolchains()?, [ToolchainName::Custom(name)]);
+            assert_eq!(cfg.toolchain_path(&local), linked.path());
+            assert!(Toolchain::exists(&cfg, &local)?);
+
+            // Removing the toolchain leaves the linked directory alone
+            Toolchain::ensure_removed(&cfg, local)?;
+            assert!(cfg.list_toolchains()?.is_empty());
+            assert!(linked.path().is_dir());
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn toolchain_files_can_require_a_newer_rustup() {
+        let path = Path::new("rust-toolchain.toml");
+        let file = |min: &str| {
+            let contents =
+                format!("[toolchain]\nchannel = \"stable\"\nmin-rustup-version = \"{min}\"\n");
+            Cfg::parse_override_file(contents, ParseMode::OnlyToml).unwrap()
+        };
+
+        file("1.0.0").check_min_rustup_version(path).unwrap();
+        file(env!("CARGO_PKG_VERSION"))
+            .check_min_rustup_version(path)
+            .unwrap();
+        let err = file("999.0.0").check_min_rustup_version(path).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<RustupError>(),
+            Some(RustupError::RustupTooOld { required, .. }) if required == "999.0.0"
+        ));
+        assert!(file("latest").check_min_rustup_version(path).is_err());
+    }
 }
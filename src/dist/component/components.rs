@@ -5,14 +5,17 @@
 use std::borrow::Cow;
 use std::convert::Infallible;
 use std::fmt;
+use std::fs;
 use std::io::BufWriter;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
 use anyhow::{Result, bail};
+use rayon::prelude::*;
 
 use crate::dist::component::package::{INSTALLER_VERSION, VERSION_FILE};
 use crate::dist::component::transaction::Transaction;
+use crate::dist::download::file_hash;
 use crate::dist::prefix::InstallPrefix;
 use crate::errors::RustupError;
 use crate::utils;
@@ -46,6 +49,9 @@ impl Components {
     fn rel_component_manifest(&self, name: &str) -> PathBuf {
         self.prefix.rel_manifest_file(&format!("manifest-{name}"))
     }
+    fn rel_component_checksums(&self, name: &str) -> PathBuf {
+        self.prefix.rel_manifest_file(&format!("checksums-{name}"))
+    }
     fn read_version(&self) -> Result<Option<String>> {
         let p = self.prefix.manifest_file(VERSION_FILE);
         if utils::is_file(&p) {
@@ -136,12 +142,26 @@ impl ComponentBuilder {
         let path = self.components.rel_component_manifest(&self.name);
         let abs_path = self.components.prefix.abs_path(&path);
         let mut file = BufWriter::new(self.tx.add_file(&self.name, path)?);
-        for part in self.parts {
+        for part in &self.parts {
             // FIXME: This writes relative paths to the component manifest,
             // but rust-installer writes absolute paths.
             utils::write_line("component", &mut file, &abs_path, &part.encode())?;
         }
 
+        // Record the hash of every installed file, for `Component::verify`
+        let path = self.components.rel_component_checksums(&self.name);
+        let abs_path = self.components.prefix.abs_path(&path);
+        let checksums = file_checksums(&self.components.prefix, &self.parts)?;
+        let mut file = BufWriter::new(self.tx.add_file(&self.name, path)?);
+        for (hash, part) in checksums {
+            utils::write_line(
+                "component",
+                &mut file,
+                &abs_path,
+                &format!("{hash} {}", part.encode()),
+            )?;
+        }
+
         // Add component to components file
         let path = self.components.rel_components_file();
         let abs_path = self.components.prefix.abs_path(&path);
@@ -155,6 +175,52 @@ impl ComponentBuilder {
     }
 }
 
+/// Hashes every file of `parts`, including the contents of directories.
+fn file_checksums(
+    prefix: &InstallPrefix,
+    parts: &[ComponentPart],
+) -> Result<Vec<(String, ComponentPart)>> {
+    let mut files = Vec::new();
+    for part in parts {
+        match part.kind {
+            ComponentPartKind::File => files.push(part.path.clone()),
+            ComponentPartKind::Dir => list_files(prefix, part.path.clone(), &mut files)?,
+            ComponentPartKind::Unknown(_) => {}
+        }
+    }
+
+    files
+        .into_par_iter()
+        .map(|path| {
+            let hash = file_hash(&prefix.abs_path(&path))?;
+            let part = ComponentPart {
+                kind: ComponentPartKind::File,
+                path,
+            };
+            Ok((hash, part))
+        })
+        .collect()
+}
+
+/// Appends the files below the relative directory `dir` to `files`.
+fn list_files(prefix: &InstallPrefix, dir: PathBuf, files: &mut Vec<PathBuf>) -> Result<()> {
+    let abs_path = prefix.abs_path(&dir);
+    let entries = fs::read_dir(&abs_path).map_err(|_| RustupError::ReadingDirectory {
+        name: "component",
+        path: abs_path.clone(),
+    })?;
+    for entry in entries {
+        let entry = entry?;
+        let path = dir.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            list_files(prefix, path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
 #[derive(Debug)]
 pub struct ComponentPart {
     /// Kind of the [`ComponentPart`], such as `"file"` or `"dir"`.
@@ -244,6 +310,66 @@ impl Component {
     pub(crate) fn name(&self) -> &str {
         &self.name
     }
+    fn rel_checksums_file(&self) -> PathBuf {
+        self.components.rel_component_checksums(&self.name)
+    }
+    /// The recorded hash of each installed file, or `None` if the component
+    /// was installed without recording them.
+    fn checksums(&self) -> Result<Option<Vec<(String, ComponentPart)>>> {
+        let path = self.components.prefix.abs_path(self.rel_checksums_file());
+        if !utils::is_file(&path) {
+            return Ok(None);
+        }
+        let mut result = Vec::new();
+        for line in utils::read_file("component", &path)?.lines() {
+            let entry = line
+                .split_once(' ')
+                .and_then(|(hash, part)| Some((hash.to_owned(), ComponentPart::decode(part)?)))
+                .ok_or_else(|| RustupError::CorruptComponent(self.name.clone()))?;
+            result.push(entry);
+        }
+        Ok(Some(result))
+    }
+    /// Checks the installed files against the component manifest and the
+    /// recorded hashes, returning the absolute paths of the missing and the
+    /// corrupted files, in that order.
+    pub fn verify(&self) -> Result<(Vec<PathBuf>, Vec<PathBuf>)> {
+        let prefix = &self.components.prefix;
+        let mut missing = self
+            .parts()?
+            .into_iter()
+            .map(|part| prefix.abs_path(part.path))
+            .filter(|path| !path.exists())
+            .collect::<Vec<_>>();
+
+        let checked = self
+            .checksums()?
+            .unwrap_or_default()
+            .into_par_iter()
+            .filter_map(|(hash, part)| {
+                let path = prefix.abs_path(part.path);
+                if !path.exists() {
+                    return Some((path, false));
+                }
+                match file_hash(&path) {
+                    Ok(h) if h == hash => None,
+                    _ => Some((path, true)),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let mut corrupted = Vec::new();
+        for (path, is_corrupted) in checked {
+            match is_corrupted {
+                true => corrupted.push(path),
+                false => missing.push(path),
+            }
+        }
+        missing.sort();
+        missing.dedup();
+        corrupted.sort();
+        Ok((missing, corrupted))
+    }
     pub(crate) fn parts(&self) -> Result<Vec<ComponentPart>> {
         let mut result = Vec::new();
         for line in utils::read_file("component", &self.manifest_file())?.lines() {
@@ -380,6 +506,9 @@ impl Component {
 
         // Remove component manifest
         tx.remove_file(&self.name, self.rel_manifest_file())?;
+        if utils::is_file(self.components.prefix.abs_path(self.rel_checksums_file())) {
+            tx.remove_file(&self.name, self.rel_checksums_file())?;
+        }
 
         Ok(tx)
     }
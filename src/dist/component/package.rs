@@ -3,10 +3,11 @@
 //! prefix, represented by a `Components` instance.
 
 use std::collections::{HashMap, HashSet};
+use std::fs;
 use std::io::{self, ErrorKind as IOErrorKind, Read};
 use std::mem;
 use std::ops::Deref;
-use std::path::{Path, PathBuf};
+use std::path::{Component as PathComponent, Path, PathBuf};
 
 use anyhow::{Context, Result, anyhow, bail};
 use tar::EntryType;
@@ -15,6 +16,7 @@ use tracing::warn;
 use crate::diskio::{CompletedIo, Executor, FileBuffer, IO_CHUNK_SIZE, Item, Kind};
 use crate::dist::component::components::{ComponentPart, ComponentPartKind, Components};
 use crate::dist::component::transaction::Transaction;
+use crate::dist::download::file_hash;
 use crate::dist::manifest::CompressionKind;
 use crate::dist::temp;
 use crate::errors::RustupError;
@@ -23,6 +25,9 @@ use crate::utils;
 /// The current metadata revision used by rust-installer
 pub(crate) const INSTALLER_VERSION: &str = "3";
 pub(crate) const VERSION_FILE: &str = "rust-installer-version";
+/// The file of a delta package that lists the SHA-256 of every file of the
+/// component version it updates to, in the format of `sha256sum`.
+pub(crate) const DELTA_CHECKSUMS_FILE: &str = "sha256sums";
 
 #[derive(Debug)]
 pub struct DirectoryPackage<P> {
@@ -103,16 +108,7 @@ impl<P: Deref<Target = Path>> DirectoryPackage<P> {
         short_name: Option<&str>,
         tx: Transaction,
     ) -> Result<Transaction> {
-        let actual_name = if self.components.contains(name) {
-            name
-        } else if let Some(n) = short_name {
-            n
-        } else {
-            name
-        };
-
-        let root = self.path.join(actual_name);
-
+        let root = self.component_root(name, short_name);
         let manifest = utils::read_file("package manifest", &root.join("manifest.in"))?;
         let mut builder = target.add(name, tx);
 
@@ -147,6 +143,96 @@ impl<P: Deref<Target = Path>> DirectoryPackage<P> {
         Ok(tx)
     }
 
+    /// Turns a delta package into a full one, by taking the files it lacks
+    /// from the installation at `prefix`.
+    ///
+    /// A delta package only carries the files of the component that were
+    /// changed or added since the version it is a delta from, but its
+    /// `manifest.in` is that of the new version, and it lists the checksums of
+    /// every file of the new version. The unchanged files are copied from the
+    /// installation, and all of them are then verified against the checksums.
+    /// Removed files are simply not listed, and go away with the old version.
+    pub(crate) fn complete_from(
+        &self,
+        name: &str,
+        short_name: Option<&str>,
+        prefix: &Path,
+    ) -> Result<()> {
+        let root = self.component_root(name, short_name);
+        let sums = utils::read_file("delta checksums", &root.join(DELTA_CHECKSUMS_FILE))?;
+        for line in sums.lines() {
+            let (hash, path) = line
+                .split_once("  ")
+                .map(|(hash, path)| (hash, Path::new(path)))
+                .filter(|(_, path)| {
+                    path.components()
+                        .all(|c| matches!(c, PathComponent::Normal(_)))
+                })
+                .ok_or_else(|| RustupError::CorruptComponent(name.to_owned()))?;
+
+            let dest = root.join(path);
+            if !utils::is_file(&dest) {
+                if let Some(parent) = dest.parent() {
+                    utils::ensure_dir_exists("delta package", parent)?;
+                }
+                utils::copy_file(&prefix.join(path), &dest)?;
+            }
+            if file_hash(&dest)? != hash {
+                bail!(
+                    "checksum failed for '{}' of the patched {name}",
+                    path.display()
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// The checksums of the regular files that the component `name` installs,
+    /// by their path relative to the installation prefix.
+    pub(crate) fn checksums(
+        &self,
+        name: &str,
+        short_name: Option<&str>,
+    ) -> Result<Vec<(PathBuf, String)>> {
+        let root = self.component_root(name, short_name);
+        let manifest = utils::read_file("package manifest", &root.join("manifest.in"))?;
+        let mut checksums = Vec::new();
+        for l in manifest.lines() {
+            let part = ComponentPart::decode(l)
+                .ok_or_else(|| RustupError::CorruptComponent(name.to_owned()))?;
+
+            let mut pending = vec![part.path];
+            while let Some(path) = pending.pop() {
+                let src_path = root.join(&path);
+                let file_type = fs::symlink_metadata(&src_path)
+                    .with_context(|| RustupError::ReadingFile {
+                        name: "package",
+                        path: src_path.clone(),
+                    })?
+                    .file_type();
+                if file_type.is_dir() {
+                    for entry in utils::read_dir("package", &src_path)? {
+                        pending.push(path.join(entry?.file_name()));
+                    }
+                } else if file_type.is_file() {
+                    checksums.push((path, file_hash(&src_path)?));
+                }
+            }
+        }
+        Ok(checksums)
+    }
+
+    fn component_root(&self, name: &str, short_name: Option<&str>) -> PathBuf {
+        let actual_name = if self.components.contains(name) {
+            name
+        } else if let Some(n) = short_name {
+            n
+        } else {
+            name
+        };
+        self.path.join(actual_name)
+    }
+
     pub(crate) fn components(&self) -> Vec<String> {
         self.components.iter().cloned().collect()
     }
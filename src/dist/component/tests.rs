@@ -2,6 +2,9 @@ use std::fs;
 use std::io::Write;
 use std::path::PathBuf;
 
+use sha2::{Digest, Sha256};
+
+use crate::dist::component::DirectoryPackage;
 use crate::errors::RustupError;
 use crate::test::DistContext;
 use crate::utils::{self, raw as utils_raw};
@@ -697,3 +700,47 @@ fn transaction_copy_file_preserves_symlinks() {
         "Transaction::copy_file should preserve symlink target"
     );
 }
+
+#[test]
+fn delta_package_is_completed_from_the_installation() {
+    let cx = DistContext::new(None).unwrap();
+    let sha256 = |content: &str| format!("{:x}", Sha256::digest(content));
+
+    // The installed version of the component
+    let installed = cx.prefix.path();
+    fs::create_dir_all(installed.join("bin")).unwrap();
+    utils::write_file("", &installed.join("bin/same"), "same").unwrap();
+    utils::write_file("", &installed.join("bin/changed"), "old").unwrap();
+    utils::write_file("", &installed.join("bin/removed"), "removed").unwrap();
+
+    // A delta that changes a file, adds another one, and removes a third
+    let pkg = cx.pkg_dir.path();
+    utils::write_file("", &pkg.join("rust-installer-version"), "3").unwrap();
+    utils::write_file("", &pkg.join("components"), "comp").unwrap();
+    fs::create_dir_all(pkg.join("comp/bin")).unwrap();
+    let manifest = "file:bin/same\nfile:bin/changed\nfile:bin/added\n";
+    utils::write_file("", &pkg.join("comp/manifest.in"), manifest).unwrap();
+    utils::write_file("", &pkg.join("comp/bin/changed"), "new").unwrap();
+    utils::write_file("", &pkg.join("comp/bin/added"), "added").unwrap();
+    let sums = format!(
+        "{}  bin/same\n{}  bin/changed\n{}  bin/added\n",
+        sha256("same"),
+        sha256("new"),
+        sha256("added")
+    );
+    utils::write_file("", &pkg.join("comp/sha256sums"), &sums).unwrap();
+
+    let package = DirectoryPackage::new(pkg, true).unwrap();
+    package.complete_from("comp", None, installed).unwrap();
+    for (path, content) in [("same", "same"), ("changed", "new"), ("added", "added")] {
+        let path = pkg.join("comp/bin").join(path);
+        assert_eq!(fs::read_to_string(path).unwrap(), content);
+    }
+    assert!(!pkg.join("comp/bin/removed").exists());
+
+    // Installed files that do not match the base version fail the checksum
+    fs::remove_file(pkg.join("comp/bin/same")).unwrap();
+    utils::write_file("", &installed.join("bin/same"), "modified").unwrap();
+    let err = package.complete_from("comp", None, installed).unwrap_err();
+    assert!(err.to_string().contains("bin/same"), "{err}");
+}
@@ -1,8 +1,10 @@
 use std::borrow::Cow;
 use std::fs;
 use std::io::Read;
+use std::iter;
 use std::ops;
 use std::path::{Path, PathBuf};
+use std::process::Command;
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 
@@ -13,10 +15,15 @@ use tracing::{debug, info, warn};
 use url::Url;
 
 use crate::config::Cfg;
-use crate::dist::manifest::Manifest;
+use crate::dist::manifest::{CompressionKind, Manifest};
 use crate::dist::{Channel, DEFAULT_DIST_SERVER, ToolchainDesc, temp};
-use crate::download::{download_file, download_file_with_resume, is_network_failure};
+use crate::download::mirror_selector::MirrorSelector;
+use crate::download::{
+    download_file, download_file_cached, download_file_with_resume, is_download_failure,
+    is_network_failure, validator_path,
+};
 use crate::errors::RustupError;
+use crate::installer::{DownloadConfig, InstallProgress, NetworkConfig, ProgressHandler};
 use crate::process::Process;
 use crate::utils;
 
@@ -25,8 +32,23 @@ const UPDATE_HASH_LEN: usize = 20;
 pub struct DownloadCfg<'a> {
     pub tmp_cx: Arc<temp::Context>,
     pub download_dir: &'a PathBuf,
+    /// Where dist metadata such as manifests is kept between runs.
+    pub(crate) cache_dir: PathBuf,
+    /// Whether to only use cached files, and never reach out to the network.
+    pub(crate) offline: bool,
     pub(super) tracker: DownloadTracker,
     pub(super) permit_copy_rename: bool,
+    /// Whether installed files are rehashed against their packages.
+    pub(super) verify_after_install: bool,
+    pub network: Option<&'a NetworkConfig>,
+    pub download_config: Option<&'a DownloadConfig>,
+    /// The keyring to verify signatures against, if they are verified.
+    pub(crate) signature_keyring: Option<&'a Path>,
+    /// The mirrors to try, in order, when a file fails to download from the
+    /// release server in `tmp_cx`.
+    pub(crate) fallback_servers: &'a [String],
+    /// Picks which of the release server and its fallbacks to try first.
+    pub(crate) mirror_selector: Option<&'a MirrorSelector>,
     pub process: &'a Process,
 }
 
@@ -39,12 +61,29 @@ impl<'a> DownloadCfg<'a> {
                 cfg.dist_root_server.as_str(),
             )),
             download_dir: &cfg.download_dir,
-            tracker: DownloadTracker::new(!cfg.quiet, cfg.process),
+            cache_dir: cfg.rustup_dir.join("cache"),
+            offline: cfg.offline,
+            tracker: DownloadTracker::new(!cfg.quiet, cfg.process)
+                .with_progress_handler(cfg.progress_handler.clone()),
             permit_copy_rename: cfg.process.permit_copy_rename(),
+            verify_after_install: cfg.verify_after_install,
+            network: cfg.network.as_ref(),
+            download_config: cfg.download_config.as_ref(),
+            signature_keyring: cfg.signature_keyring.as_deref(),
+            fallback_servers: &cfg.dist_fallback_servers,
+            mirror_selector: cfg.mirror_selector.as_ref(),
             process: cfg.process,
         }
     }
 
+    /// The format to download component archives in, where there is a choice.
+    pub(crate) fn preferred_compression(&self) -> CompressionKind {
+        self.download_config
+            .map(|config| config.preferred_compression)
+            .unwrap_or_default()
+            .into()
+    }
+
     /// Downloads a file and validates its hash. Resumes interrupted downloads.
     /// Partial downloads are stored in `self.download_dir`, keyed by hash. If the
     /// target file already exists, then the hash is checked and it is returned
@@ -55,6 +94,14 @@ impl<'a> DownloadCfg<'a> {
         hash: &str,
         status: &DownloadStatus,
     ) -> Result<File> {
+        self.with_fallback(url.as_str(), async |url| {
+            self.download_from(&utils::parse_url(url)?, hash, status)
+                .await
+        })
+        .await
+    }
+
+    async fn download_from(&self, url: &Url, hash: &str, status: &DownloadStatus) -> Result<File> {
         utils::ensure_dir_exists("Download Directory", self.download_dir)?;
         let target_file = self.download_dir.join(Path::new(hash));
 
@@ -70,6 +117,13 @@ impl<'a> DownloadCfg<'a> {
             }
         }
 
+        if self.offline {
+            return Err(RustupError::Offline {
+                url: url.to_string(),
+            }
+            .into());
+        }
+
         let partial_file_path = target_file.with_file_name(
             target_file
                 .file_name()
@@ -89,6 +143,9 @@ impl<'a> DownloadCfg<'a> {
             Some(&mut hasher),
             true,
             Some(status),
+            self.network,
+            // Archives are split into parts by default
+            Some(self.download_config.unwrap_or(&DownloadConfig::default())),
             self.process,
         )
         .await
@@ -133,7 +190,11 @@ impl<'a> DownloadCfg<'a> {
         for hash in hashes.iter() {
             let used_file = self.download_dir.join(hash);
             if self.download_dir.join(&used_file).exists() {
-                fs::remove_file(used_file).context("cleaning up cached downloads")?;
+                fs::remove_file(&used_file).context("cleaning up cached downloads")?;
+            }
+            let validator = validator_path(&used_file);
+            if validator.exists() {
+                fs::remove_file(validator).context("cleaning up cached downloads")?;
             }
         }
         Ok(())
@@ -143,7 +204,22 @@ impl<'a> DownloadCfg<'a> {
         let hash_url = utils::parse_url(&(url.to_owned() + ".sha256"))?;
         let hash_file = self.tmp_cx.new_file()?;
 
-        download_file(&hash_url, &hash_file, None, None, self.process).await?;
+        let cache = self.cache_path(&hash_url);
+        match self.offline {
+            true => self.read_cached(&hash_url, &cache, &hash_file, None)?,
+            false => {
+                download_file_cached(
+                    &hash_url,
+                    &hash_file,
+                    &cache,
+                    None,
+                    self.network,
+                    self.download_config,
+                    self.process,
+                )
+                .await?
+            }
+        }
 
         utils::read_file("hash", &hash_file).map(|s| s[0..64].to_owned())
     }
@@ -233,14 +309,30 @@ impl<'a> DownloadCfg<'a> {
     /// Downloads a file, sourcing its hash from the same url with a `.sha256` suffix.
     /// If `update_hash` is present, then that will be compared to the downloaded hash,
     /// and if they match, the download is skipped.
-    /// Verifies the signature found at the same url with a `.asc` suffix, and prints a
-    /// warning when the signature does not verify, or is not found.
+    /// Files downloaded without a progress `status` are small metadata files, which
+    /// are cached so that they are only transferred again once they changed.
+    /// When signatures are verified, the file must also match the signature found at
+    /// the same url with a `.asc` suffix.
     pub(crate) async fn download_and_check(
         &self,
         url_str: &str,
         update_hash: Option<&Path>,
         status: Option<&DownloadStatus>,
         ext: &str,
+    ) -> Result<Option<(temp::File, String)>> {
+        self.with_fallback(url_str, async |url_str| {
+            self.download_and_check_from(url_str, update_hash, status, ext)
+                .await
+        })
+        .await
+    }
+
+    async fn download_and_check_from(
+        &self,
+        url_str: &str,
+        update_hash: Option<&Path>,
+        status: Option<&DownloadStatus>,
+        ext: &str,
     ) -> Result<Option<(temp::File, String)>> {
         let hash = self.download_hash(url_str).await?;
         let partial_hash: String = hash.chars().take(UPDATE_HASH_LEN).collect();
@@ -267,10 +359,38 @@ impl<'a> DownloadCfg<'a> {
         let file = self.tmp_cx.new_file_with_ext("", ext)?;
 
         let mut hasher = Sha256::new();
-        download_file(&url, &file, Some(&mut hasher), status, self.process).await?;
+        let cache = self.cache_path(&url);
+        match status {
+            _ if self.offline => self.read_cached(&url, &cache, &file, Some(&mut hasher))?,
+            Some(_) => {
+                download_file(
+                    &url,
+                    &file,
+                    Some(&mut hasher),
+                    status,
+                    self.network,
+                    self.process,
+                )
+                .await?
+            }
+            None => {
+                download_file_cached(
+                    &url,
+                    &file,
+                    &cache,
+                    Some(&mut hasher),
+                    self.network,
+                    self.download_config,
+                    self.process,
+                )
+                .await?
+            }
+        }
         let actual_hash = format!("{:x}", hasher.finalize());
 
         if hash != actual_hash {
+            // Don't let a damaged copy be reused on the next attempt
+            let _ = fs::remove_file(&cache);
             // Incorrect hash
             return Err(RustupError::ChecksumFailed {
                 url: url_str.to_owned(),
@@ -282,9 +402,140 @@ impl<'a> DownloadCfg<'a> {
             debug!(url = url_str, "checksum passed");
         }
 
+        if let Some(keyring) = self.signature_keyring {
+            self.verify_signature(url_str, &file, keyring).await?;
+        }
+
         Ok(Some((file, partial_hash)))
     }
 
+    /// Verifies `file`, downloaded from `url_str`, against the detached signature at
+    /// the same url with a `.asc` suffix, with `gpgv` and the keys in `keyring`.
+    ///
+    /// Only OpenPGP signatures are supported for now; other schemes, such as
+    /// sigstore, would be told apart by their suffix.
+    async fn verify_signature(&self, url_str: &str, file: &Path, keyring: &Path) -> Result<()> {
+        let failed = |reason: String| RustupError::SignatureVerificationFailed {
+            url: url_str.to_owned(),
+            reason,
+        };
+        let signature = self
+            .download_cached(&format!("{url_str}.asc"), ".asc")
+            .await
+            .map_err(|e| failed(format!("could not download the signature: {e:#}")))?;
+
+        let output = Command::new("gpgv")
+            .arg("--keyring")
+            .arg(keyring)
+            .arg(&*signature)
+            .arg(file)
+            .output()
+            .map_err(|e| failed(format!("could not run `gpgv`: {e}")))?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(failed(stderr.trim().to_owned()).into());
+        }
+
+        debug!(url = url_str, "signature verified");
+        Ok(())
+    }
+
+    /// Downloads the metadata file at `url_str`, which has no `.sha256` file to
+    /// check it against, caching it like [`Self::download_and_check`] does.
+    pub(crate) async fn download_cached(&self, url_str: &str, ext: &str) -> Result<temp::File> {
+        let url = utils::parse_url(url_str)?;
+        let file = self.tmp_cx.new_file_with_ext("", ext)?;
+        let cache = self.cache_path(&url);
+        match self.offline {
+            true => self.read_cached(&url, &cache, &file, None)?,
+            false => {
+                download_file_cached(
+                    &url,
+                    &file,
+                    &cache,
+                    None,
+                    self.network,
+                    self.download_config,
+                    self.process,
+                )
+                .await?
+            }
+        }
+        Ok(file)
+    }
+
+    /// Runs `download` on `url`, then, for as long as it fails to fetch the file,
+    /// on the same path on each of the [`Self::fallback_servers`] in turn.
+    /// Only urls on the release server being downloaded from have fallbacks.
+    ///
+    /// With a [`Self::mirror_selector`], the server it picks is tried first.
+    async fn with_fallback<T>(
+        &self,
+        url: &str,
+        download: impl AsyncFn(&str) -> Result<T>,
+    ) -> Result<T> {
+        let primary = self.tmp_cx.dist_server.as_str();
+        let path = match url.strip_prefix(primary) {
+            Some(path) if !self.offline && !self.fallback_servers.is_empty() => path,
+            _ => return download(url).await,
+        };
+
+        let mut servers = iter::once(primary)
+            .chain(self.fallback_servers.iter().map(String::as_str))
+            .collect::<Vec<_>>();
+        if let Some(selector) = self.mirror_selector
+            && let Some(fastest) = selector.fastest(&self.tmp_cx, self.process).await
+            && let Some(at) = servers.iter().position(|&server| server == fastest)
+        {
+            let fastest = servers.remove(at);
+            servers.insert(0, fastest);
+        }
+
+        let mut urls = servers.iter().map(|server| format!("{server}{path}"));
+        let mut url = urls.next().unwrap();
+        for fallback in urls {
+            match download(&url).await {
+                Err(e) if is_download_failure(&e) => {
+                    warn!("could not download {url}, trying the next mirror: {e}");
+                    url = fallback;
+                }
+                result => return result,
+            }
+        }
+        download(&url).await
+    }
+
+    /// Where the latest copy of `url` is cached: the host and path of the url
+    /// under [`Self::cache_dir`], e.g. `static.rust-lang.org/dist/channel-rust-stable.toml`.
+    fn cache_path(&self, url: &Url) -> PathBuf {
+        let mut path = self.cache_dir.join(url.host_str().unwrap_or("localhost"));
+        path.extend(url.path_segments().into_iter().flatten());
+        path
+    }
+
+    /// Fills `file` from the cached copy of `url`, for use in offline mode.
+    fn read_cached(
+        &self,
+        url: &Url,
+        cache: &Path,
+        file: &Path,
+        hasher: Option<&mut Sha256>,
+    ) -> Result<()> {
+        if !cache.is_file() {
+            return Err(RustupError::Offline {
+                url: url.to_string(),
+            }
+            .into());
+        }
+
+        debug!(url = %url, "using cached file in offline mode");
+        utils::copy_file(cache, file)?;
+        if let Some(hasher) = hasher {
+            hasher.update(fs::read(file).context("reading cached file")?);
+        }
+        Ok(())
+    }
+
     pub(crate) fn status_for(&self, component: impl Into<Cow<'static, str>>) -> DownloadStatus {
         let progress = ProgressBar::hidden();
         progress.set_style(
@@ -300,6 +551,7 @@ impl<'a> DownloadCfg<'a> {
         DownloadStatus {
             progress,
             retry_time: Mutex::new(None),
+            progress_handler: self.tracker.progress_handler.clone(),
         }
     }
 
@@ -317,6 +569,8 @@ impl<'a> DownloadCfg<'a> {
 pub(crate) struct DownloadTracker {
     /// MultiProgress bar for the downloads.
     multi_progress_bars: MultiProgress,
+    /// Callback notified of progress, for library embedders.
+    progress_handler: Option<ProgressHandler>,
 }
 
 impl DownloadTracker {
@@ -330,8 +584,15 @@ impl DownloadTracker {
 
         Self {
             multi_progress_bars,
+            progress_handler: None,
         }
     }
+
+    /// Forwards the progress of every download to `handler`.
+    pub(crate) fn with_progress_handler(mut self, handler: Option<ProgressHandler>) -> Self {
+        self.progress_handler = handler;
+        self
+    }
 }
 
 pub(crate) struct DownloadStatus {
@@ -343,16 +604,34 @@ pub(crate) struct DownloadStatus {
     /// bar would reappear immediately, not allowing the user to correctly see the message,
     /// before the progress bar starts again.
     retry_time: Mutex<Option<Instant>>,
+    progress_handler: Option<ProgressHandler>,
 }
 
 impl DownloadStatus {
+    fn notify(&self, progress: impl FnOnce(String) -> InstallProgress) {
+        if let Some(handler) = &self.progress_handler {
+            handler(progress(self.progress.message()));
+        }
+    }
+
+    fn notify_downloading(&self) {
+        self.notify(|component| InstallProgress::Downloading {
+            component,
+            bytes_received: self.progress.position(),
+            total_bytes: self.progress.length(),
+            bytes_per_second: self.progress.per_sec() as u64,
+        });
+    }
+
     pub(crate) fn received_length(&self, len: u64) {
         self.progress.reset();
         self.progress.set_length(len);
+        self.notify_downloading();
     }
 
     pub(crate) fn received_data(&self, len: usize) {
         self.progress.inc(len as u64);
+        self.notify_downloading();
         let mut retry_time = self.retry_time.lock().unwrap();
         if !retry_time.is_some_and(|instant| instant.elapsed() > Duration::from_secs(1)) {
             return;
@@ -391,6 +670,7 @@ impl DownloadStatus {
     }
 
     pub(crate) fn unpack<T: Read>(&self, inner: T) -> ProgressBarIter<T> {
+        self.notify(|component| InstallProgress::Extracting { component });
         self.progress.reset();
         self.progress.set_style(
             ProgressStyle::with_template(
@@ -421,7 +701,7 @@ impl DownloadStatus {
     }
 }
 
-fn file_hash(path: &Path) -> Result<String> {
+pub(crate) fn file_hash(path: &Path) -> Result<String> {
     let mut hasher = Sha256::new();
     let mut downloaded = utils::buffered(path)?;
     let mut buf = vec![0; 32768];
@@ -21,6 +21,7 @@ use std::{
 
 use anyhow::{Context, Result, anyhow, bail};
 use serde::{Deserialize, Serialize};
+use tracing::warn;
 
 use crate::{
     dist::{Profile, TargetTriple, ToolchainDesc, config::Config},
@@ -131,6 +132,9 @@ pub struct TargetedPackage {
     #[serde(default)]
     pub bins: Vec<HashedBinary>,
     pub components: Vec<Component>,
+    /// A package that updates the previous version of this one to the
+    /// current version, shipping only the files that differ between them.
+    pub delta: Option<DeltaBinary>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -142,6 +146,10 @@ struct Target {
     xz_hash: Option<String>,
     zst_url: Option<String>,
     zst_hash: Option<String>,
+    #[serde(rename = "delta-from")]
+    delta_from: Option<String>,
+    delta_url: Option<String>,
+    delta_hash: Option<String>,
     components: Option<Vec<Component>>,
     extensions: Option<Vec<Component>>,
 }
@@ -158,7 +166,11 @@ impl From<Target> for TargetedPackage {
 
         let mut bins = Vec::new();
         if !target.available {
-            return Self { bins, components };
+            return Self {
+                bins,
+                components,
+                delta: None,
+            };
         }
 
         if let (Some(url), Some(hash)) = (target.zst_url, target.zst_hash) {
@@ -185,7 +197,16 @@ impl From<Target> for TargetedPackage {
             });
         }
 
-        Self { bins, components }
+        let delta = match (target.delta_from, target.delta_url, target.delta_hash) {
+            (Some(from), Some(url), Some(hash)) => Some(DeltaBinary { from, url, hash }),
+            _ => None,
+        };
+
+        Self {
+            bins,
+            components,
+            delta,
+        }
     }
 }
 
@@ -195,6 +216,10 @@ impl From<TargetedPackage> for Target {
         let (mut xz_url, mut xz_hash) = (None, None);
         let (mut zst_url, mut zst_hash) = (None, None);
         let available = !tpkg.bins.is_empty();
+        let (delta_from, delta_url, delta_hash) = match tpkg.delta {
+            Some(DeltaBinary { from, url, hash }) => (Some(from), Some(url), Some(hash)),
+            None => (None, None, None),
+        };
         for bin in tpkg.bins {
             match bin.compression {
                 CompressionKind::GZip => {
@@ -230,6 +255,9 @@ impl From<TargetedPackage> for Target {
             xz_hash,
             zst_url,
             zst_hash,
+            delta_from,
+            delta_url,
+            delta_hash,
             components: Some(components),
             extensions: Some(extensions),
         }
@@ -250,6 +278,29 @@ pub struct HashedBinary {
     pub compression: CompressionKind,
 }
 
+/// A delta package, as described by [`TargetedPackage::delta`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DeltaBinary {
+    /// The version of the package that the delta applies to, as given by
+    /// [`Package::version`].
+    pub from: String,
+    pub url: String,
+    pub hash: String,
+}
+
+impl DeltaBinary {
+    /// The compression of the delta package, going by its file name.
+    pub(crate) fn compression(&self) -> CompressionKind {
+        if self.url.ends_with(".tar.zst") {
+            CompressionKind::ZStd
+        } else if self.url.ends_with(".tar.gz") {
+            CompressionKind::GZip
+        } else {
+            CompressionKind::XZ
+        }
+    }
+}
+
 #[derive(Clone, Debug, Deserialize, Eq, Ord, PartialOrd, Serialize)]
 pub struct Component {
     pub pkg: String,
@@ -316,12 +367,31 @@ impl Manifest {
         Ok(toml::to_string(&self)?)
     }
 
-    pub(super) fn binary(&self, component: &Component) -> Result<Option<&HashedBinary>> {
+    pub(crate) fn binary(
+        &self,
+        component: &Component,
+        preferred: CompressionKind,
+    ) -> Result<Option<&HashedBinary>> {
         let package = self.get_package(component.short_name_in_manifest())?;
         let target_package = package.get_target(component.target.as_ref())?;
-        // We prefer the first format in the list, since the parsing of the
-        // manifest leaves us with the files/hash pairs in preference order.
-        Ok(target_package.bins.first())
+        // Failing the preferred format, we take the first one in the list, since
+        // the parsing of the manifest leaves us with the files/hash pairs in
+        // preference order.
+        let bins = &target_package.bins;
+        Ok(bins
+            .iter()
+            .find(|bin| bin.compression == preferred)
+            .or(bins.first()))
+    }
+
+    /// The delta package that updates `component` from version `from`, if any.
+    pub(crate) fn delta(&self, component: &Component, from: &str) -> Option<&DeltaBinary> {
+        let package = self.get_package(component.short_name_in_manifest()).ok()?;
+        let target_package = package.get_target(component.target.as_ref()).ok()?;
+        target_package
+            .delta
+            .as_ref()
+            .filter(|delta| delta.from == from)
     }
 
     pub fn get_package(&self, name: &str) -> Result<&Package> {
@@ -352,6 +422,10 @@ impl Manifest {
         profile: Profile,
         target: &TargetTriple,
     ) -> Result<Vec<Component>> {
+        if let Profile::Custom(names) = &profile {
+            return self.get_custom_profile_components(names, target);
+        }
+
         // An older manifest with no profiles section.
         if self.profiles.is_empty() {
             return self.get_legacy_components(target);
@@ -422,6 +496,51 @@ impl Manifest {
 
     // If the component should be renamed by this manifest, then return a new
     // component with the new name. If not, return `None`.
+    /// Looks up the components named by a custom profile.
+    ///
+    /// All names missing from the manifest are reported at once, while
+    /// components that are not built for `target` are skipped.
+    fn get_custom_profile_components(
+        &self,
+        names: &[String],
+        target: &TargetTriple,
+    ) -> Result<Vec<Component>> {
+        let rust_pkg = self.get_package("rust")?.get_target(Some(target))?;
+
+        let mut unknown = Vec::new();
+        let mut result = Vec::new();
+        for name in names {
+            let mut component = Component::new(name.clone(), Some(target.clone()), false);
+            if let Some(renamed) = self.rename_component(&component) {
+                component = renamed;
+            }
+            let Ok(pkg) = self.get_package(&component.pkg) else {
+                unknown.push(name.clone());
+                continue;
+            };
+
+            let listed = rust_pkg
+                .components
+                .iter()
+                .find(|c| c.pkg == component.pkg && c.target.as_ref().is_none_or(|t| t == target));
+            match listed {
+                Some(c) if pkg.get_target(Some(target)).is_ok_and(|t| t.available()) => {
+                    result.push(Component::new(c.pkg.clone(), c.target.clone(), false))
+                }
+                _ => warn!("component '{name}' is not available for target '{target}', skipping"),
+            }
+        }
+
+        if !unknown.is_empty() {
+            return Err(RustupError::ComponentNotAvailable {
+                components: unknown,
+                target: target.clone(),
+            }
+            .into());
+        }
+        Ok(result)
+    }
+
     pub(crate) fn rename_component(&self, component: &Component) -> Option<Component> {
         self.renames.get(&component.pkg).map(|r| {
             let mut c = component.clone();
@@ -635,8 +754,8 @@ impl fmt::Display for ManifestVersion {
 #[cfg(test)]
 mod tests {
     use crate::RustupError;
-    use crate::dist::TargetTriple;
-    use crate::dist::manifest::Manifest;
+    use crate::dist::manifest::{Component, CompressionKind, Manifest};
+    use crate::dist::{Profile, TargetTriple};
 
     // Example manifest from https://public.etherpad-mozilla.org/p/Rust-infra-work-week
     static EXAMPLE: &str = include_str!("manifest/tests/channel-rust-nightly-example.toml");
@@ -684,6 +803,96 @@ mod tests {
         assert_eq!(docs_target_pkg.bins[0].url, "example.com");
     }
 
+    #[test]
+    fn custom_profile_components() {
+        let target = TargetTriple::new("x86_64-unknown-linux-gnu");
+        let custom = |names: &[&str]| Profile::Custom(names.iter().map(|&n| n.into()).collect());
+
+        let manifest = Manifest::parse(EXAMPLE).unwrap();
+        let components = manifest
+            .get_profile_components(custom(&["rustc", "cargo"]), &target)
+            .unwrap();
+        assert_eq!(
+            components
+                .iter()
+                .map(|c| c.short_name_in_manifest().as_str())
+                .collect::<Vec<_>>(),
+            ["rustc", "cargo"]
+        );
+
+        let err = manifest
+            .get_profile_components(custom(&["rustc", "bogus", "cargo", "nope"]), &target)
+            .unwrap_err();
+        match err.downcast::<RustupError>() {
+            Ok(RustupError::ComponentNotAvailable { components, .. }) => {
+                assert_eq!(components, ["bogus", "nope"])
+            }
+            _ => panic!("expected ComponentNotAvailable"),
+        }
+
+        // Components that are not built for the target are skipped
+        let unavailable = EXAMPLE.replace(
+            "[pkg.cargo.target.x86_64-unknown-linux-gnu]\navailable = true",
+            "[pkg.cargo.target.x86_64-unknown-linux-gnu]\navailable = false",
+        );
+        let manifest = Manifest::parse(&unavailable).unwrap();
+        let components = manifest
+            .get_profile_components(custom(&["rustc", "cargo"]), &target)
+            .unwrap();
+        assert_eq!(components.len(), 1);
+        assert_eq!(components[0].short_name_in_manifest(), "rustc");
+    }
+
+    #[test]
+    fn binary_prefers_the_requested_compression() {
+        let manifest = EXAMPLE.replace(
+            "url = \"example.com\"\nhash = \"...\"\n[pkg.cargo]",
+            "url = \"example.com\"\nhash = \"...\"\n\
+             xz_url = \"example.com/xz\"\nxz_hash = \"xz\"\n\
+             zst_url = \"example.com/zst\"\nzst_hash = \"zst\"\n[pkg.cargo]",
+        );
+        let manifest = Manifest::parse(&manifest).unwrap();
+        let target = TargetTriple::new("x86_64-unknown-linux-gnu");
+        let rustc = Component::new("rustc".to_owned(), Some(target.clone()), false);
+        let url = |preferred| {
+            manifest
+                .binary(&rustc, preferred)
+                .unwrap()
+                .unwrap()
+                .url
+                .as_str()
+        };
+        assert_eq!(url(CompressionKind::ZStd), "example.com/zst");
+        assert_eq!(url(CompressionKind::XZ), "example.com/xz");
+        assert_eq!(url(CompressionKind::GZip), "example.com");
+
+        // Formats that are not offered fall back to the best one that is
+        let cargo = Component::new("cargo".to_owned(), Some(target), false);
+        let bin = manifest.binary(&cargo, CompressionKind::ZStd).unwrap();
+        assert_eq!(bin.unwrap().compression, CompressionKind::GZip);
+    }
+
+    #[test]
+    fn delta_only_applies_to_its_base_version() {
+        let manifest = EXAMPLE.replace(
+            "url = \"example.com\"\nhash = \"...\"\n[pkg.cargo]",
+            "url = \"example.com\"\nhash = \"...\"\n\
+             delta-from = \"1.2.0-nightly\"\n\
+             delta_url = \"example.com/delta.tar.zst\"\ndelta_hash = \"delta\"\n[pkg.cargo]",
+        );
+        let manifest = Manifest::parse(&manifest).unwrap();
+        let target = TargetTriple::new("x86_64-unknown-linux-gnu");
+        let rustc = Component::new("rustc".to_owned(), Some(target), false);
+
+        let delta = manifest.delta(&rustc, "1.2.0-nightly").unwrap();
+        assert_eq!(delta.url, "example.com/delta.tar.zst");
+        assert_eq!(delta.compression(), CompressionKind::ZStd);
+        assert!(manifest.delta(&rustc, "1.1.0-nightly").is_none());
+
+        let serialized = manifest.clone().stringify().unwrap();
+        assert_eq!(Manifest::parse(&serialized).unwrap(), manifest);
+    }
+
     #[test]
     fn renames() {
         let manifest = Manifest::parse(EXAMPLE2).unwrap();
@@ -5,23 +5,24 @@
 mod tests;
 
 use std::collections::VecDeque;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::pin::Pin;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::task::{Context, Poll, ready};
+use std::time::{Duration, Instant};
 use std::vec;
 
 use anyhow::{Context as _, Result, anyhow, bail};
 use futures_util::Stream;
 use futures_util::stream::{FuturesUnordered, StreamExt};
 use tokio::task::{JoinHandle, spawn_blocking};
-use tracing::{info, warn};
+use tracing::{Instrument, debug, info, info_span, warn};
 
 use crate::diskio::{Executor, IO_CHUNK_SIZE, get_executor, unpack_ram};
 use crate::dist::component::{Components, DirectoryPackage, Transaction};
 use crate::dist::config::Config;
-use crate::dist::download::{DownloadCfg, DownloadStatus, File};
-use crate::dist::manifest::{Component, CompressionKind, HashedBinary, Manifest};
+use crate::dist::download::{DownloadCfg, DownloadStatus, File, file_hash};
+use crate::dist::manifest::{Component, CompressionKind, DeltaBinary, HashedBinary, Manifest};
 use crate::dist::prefix::InstallPrefix;
 use crate::dist::temp;
 use crate::dist::{DEFAULT_DIST_SERVER, Profile, TargetTriple};
@@ -31,6 +32,9 @@ use crate::utils;
 pub(crate) const DIST_MANIFEST: &str = "multirust-channel-manifest.toml";
 pub(crate) const CONFIG_FILE: &str = "multirust-config.toml";
 
+/// The number of component archives downloaded at once, unless configured otherwise.
+pub(crate) const DEFAULT_CONCURRENT_DOWNLOADS: usize = 4;
+
 #[derive(Debug)]
 pub struct Manifestation {
     installation: Components,
@@ -160,19 +164,56 @@ impl Manifestation {
             .filter_map(|component| ComponentBinary::new(component, &new_manifest, download_cfg))
             .collect::<Result<Vec<_>>>()?;
 
-        const DEFAULT_CONCURRENT_DOWNLOADS: usize = 2;
-        let concurrent_downloads = download_cfg
-            .process
-            .concurrent_downloads()
-            .unwrap_or(DEFAULT_CONCURRENT_DOWNLOADS);
+        // Patching components needs the files of their installed versions, so
+        // happens before any of them are uninstalled
+        let mut patched = Vec::new();
+        let mut delta_downloads = Vec::new();
+        let components = match self.load_manifest()? {
+            Some(old_manifest) => {
+                let mut full = Vec::with_capacity(components.len());
+                for bin in components {
+                    let Some(delta) = bin.delta(&self, &old_manifest)? else {
+                        full.push(bin);
+                        continue;
+                    };
+                    delta_downloads.push(&*delta.hash);
+                    match bin.patch(delta, prefix.path()).await {
+                        Ok(package) => patched.push(bin.into_install(package)),
+                        Err(e) => {
+                            warn!(
+                                "could not patch component {}, downloading it in full: {e:#}",
+                                bin.manifest.short_name(&bin.component)
+                            );
+                            full.push(bin);
+                        }
+                    }
+                }
+                full
+            }
+            None => components,
+        };
+
+        let concurrent_downloads = match download_cfg.download_config {
+            Some(config) => config.max_parallel_downloads.max(1),
+            None => download_cfg
+                .process
+                .concurrent_downloads()
+                .unwrap_or(DEFAULT_CONCURRENT_DOWNLOADS),
+        };
 
         const DEFAULT_MAX_RETRIES: usize = 3;
-        let max_retries: usize = download_cfg
-            .process
-            .var("RUSTUP_MAX_RETRIES")
-            .ok()
-            .and_then(|s| s.parse().ok())
-            .unwrap_or(DEFAULT_MAX_RETRIES);
+        let (max_retries, retry_backoff) = match download_cfg.network {
+            Some(network) => (network.max_retries as usize, network.retry_backoff),
+            None => (
+                download_cfg
+                    .process
+                    .var("RUSTUP_MAX_RETRIES")
+                    .ok()
+                    .and_then(|s| s.parse().ok())
+                    .unwrap_or(DEFAULT_MAX_RETRIES),
+                Duration::ZERO,
+            ),
+        };
 
         // Begin transaction
         let mut tx = Transaction::new(
@@ -216,24 +257,32 @@ impl Manifestation {
             tx = self.uninstall_component(component, &new_manifest, tx)?;
         }
 
-        if !components.is_empty() {
+        if !components.is_empty() || !patched.is_empty() {
             if components.len() > 2 {
                 info!("downloading {} components", components.len());
-            } else {
+            } else if let Some(bin) = components.first() {
                 info!(
                     "downloading component {}",
-                    components[0].manifest.short_name(&components[0].component),
+                    bin.manifest.short_name(&bin.component),
                 );
             };
 
-            let mut stream = InstallEvents::new(components.into_iter(), Arc::new(self));
+            let mut stream = InstallEvents::new(
+                components.into_iter(),
+                Arc::new(self),
+                download_cfg.verify_after_install,
+            );
+            stream.install_queue.extend(patched);
+            stream.cleanup_downloads.extend(delta_downloads);
             let mut transaction = Some(tx);
             tx = loop {
                 // Refill downloads when there's capacity
                 // Must live outside of `InstallEvents` because we can't write the type of future
                 while stream.components.len() > 0 && stream.downloads.len() < concurrent_downloads {
                     if let Some(bin) = stream.components.next() {
-                        stream.downloads.push(bin.download(max_retries));
+                        stream
+                            .downloads
+                            .push(bin.download(max_retries, retry_backoff));
                     }
                 }
 
@@ -252,7 +301,14 @@ impl Manifestation {
                 }
             };
 
-            download_cfg.clean(&stream.cleanup_downloads)?;
+            if let Some(checksums) = &stream.checksums {
+                verify_installed(prefix.path(), &checksums.lock().unwrap())?;
+            }
+
+            // Offline installs rely on the downloads, so keep them for the next one
+            if !download_cfg.offline {
+                download_cfg.clean(&stream.cleanup_downloads)?;
+            }
             drop(stream);
         }
 
@@ -466,8 +522,28 @@ impl Manifestation {
     }
 }
 
+/// The checksums of installed files, by their path relative to the prefix.
+type Checksums = Arc<Mutex<Vec<(PathBuf, String)>>>;
+
+/// Rehashes the files installed in `prefix` to check they still match the
+/// `checksums` taken from the packages they were installed from.
+fn verify_installed(prefix: &Path, checksums: &[(PathBuf, String)]) -> Result<()> {
+    let files = checksums
+        .iter()
+        .filter(|(path, hash)| file_hash(&prefix.join(path)).ok().as_ref() != Some(hash))
+        .map(|(path, _)| path.clone())
+        .collect::<Vec<_>>();
+    if !files.is_empty() {
+        return Err(RustupError::VerificationFailed { files }.into());
+    }
+    debug!(files = checksums.len(), "installed files verified");
+    Ok(())
+}
+
 struct InstallEvents<'a, F> {
     manifestation: Arc<Manifestation>,
+    /// Filled with the checksums of the installed files, when they are verified.
+    checksums: Option<Checksums>,
     components: vec::IntoIter<ComponentBinary<'a>>,
     cleanup_downloads: Vec<&'a str>,
     install_queue: VecDeque<ComponentInstall>,
@@ -479,9 +555,11 @@ impl<'a, F> InstallEvents<'a, F> {
     fn new(
         components: vec::IntoIter<ComponentBinary<'a>>,
         manifestation: Arc<Manifestation>,
+        verify: bool,
     ) -> Self {
         Self {
             manifestation,
+            checksums: verify.then(Checksums::default),
             cleanup_downloads: Vec::with_capacity(components.len()),
             components,
             install_queue: VecDeque::new(),
@@ -497,7 +575,10 @@ impl<'a, F> InstallEvents<'a, F> {
 
         if let Some(tx) = tx.take() {
             let manifestation = self.manifestation.clone();
-            self.installing = Some(spawn_blocking(|| installable.install(tx, manifestation)));
+            let checksums = self.checksums.clone();
+            self.installing = Some(spawn_blocking(|| {
+                installable.install(tx, manifestation, checksums)
+            }));
         } else {
             self.install_queue.push_front(installable);
         }
@@ -737,7 +818,7 @@ impl<'a> ComponentBinary<'a> {
         download_cfg: &'a DownloadCfg<'a>,
     ) -> Option<Result<Self>> {
         Some(Ok(ComponentBinary {
-            binary: match manifest.binary(&component) {
+            binary: match manifest.binary(&component, download_cfg.preferred_compression()) {
                 Ok(Some(b)) => b,
                 Ok(None) => return None,
                 Err(e) => return Some(Err(e)),
@@ -749,21 +830,118 @@ impl<'a> ComponentBinary<'a> {
         }))
     }
 
-    async fn download(self, max_retries: usize) -> Result<(ComponentInstall, &'a str)> {
+    /// The delta package that updates the installed version of the component,
+    /// as described by `old_manifest`, to the new one.
+    fn delta(
+        &self,
+        manifestation: &Manifestation,
+        old_manifest: &Manifest,
+    ) -> Result<Option<&'a DeltaBinary>> {
+        if manifestation
+            .installation
+            .find(&self.component.name_in_manifest())?
+            .is_none()
+        {
+            return Ok(None);
+        }
+        let Ok(old_package) = old_manifest.get_package(self.component.short_name_in_manifest())
+        else {
+            return Ok(None);
+        };
+        Ok(self.manifest.delta(&self.component, &old_package.version))
+    }
+
+    /// Downloads `delta` and applies it to the installed files under `prefix`,
+    /// yielding the full package of the new version.
+    async fn patch(
+        &self,
+        delta: &DeltaBinary,
+        prefix: &Path,
+    ) -> Result<DirectoryPackage<temp::Dir>> {
+        let url = self.download_cfg.url(&delta.url)?;
+        let file = self
+            .download_cfg
+            .download(&url, &delta.hash, &self.status)
+            .await?;
+        let package = DirectoryPackage::compressed(
+            self.status.unpack(utils::buffered(&file)?),
+            delta.compression(),
+            self.download_cfg.tmp_cx.new_directory()?,
+            self.io_executor()?,
+        )?;
+        package.complete_from(
+            &self.component.name_in_manifest(),
+            Some(self.component.short_name_in_manifest()),
+            prefix,
+        )?;
+        Ok(package)
+    }
+
+    fn into_install(self, package: DirectoryPackage<temp::Dir>) -> ComponentInstall {
+        ComponentInstall {
+            short_name: self.manifest.short_name(&self.component).to_owned(),
+            status: self.status,
+            source: PackageSource::Patched(package),
+            component: self.component,
+        }
+    }
+
+    fn io_executor(&self) -> Result<Box<dyn Executor>> {
+        let process = self.download_cfg.process;
+        Ok(get_executor(
+            unpack_ram(IO_CHUNK_SIZE, process.unpack_ram()?),
+            process.io_thread_count()?,
+        ))
+    }
+
+    async fn download(
+        self,
+        max_retries: usize,
+        retry_backoff: Duration,
+    ) -> Result<(ComponentInstall, &'a str)> {
         use tokio_retry::{RetryIf, strategy::FixedInterval};
 
+        let short_name = self.manifest.short_name(&self.component).to_owned();
+        let span = info_span!("download_component", component = %short_name);
+        let start = Instant::now();
         let url = self.download_cfg.url(&self.binary.url)?;
+        let timeout = self.download_cfg.download_config.and_then(|c| c.timeout);
+        let attempts = std::cell::Cell::new(0);
         let installer = RetryIf::spawn(
-            FixedInterval::from_millis(0).take(max_retries),
+            FixedInterval::new(retry_backoff).take(max_retries),
             || {
-                self.download_cfg
-                    .download(&url, &self.binary.hash, &self.status)
+                attempts.set(attempts.get() + 1);
+                let download = self
+                    .download_cfg
+                    .download(&url, &self.binary.hash, &self.status);
+                async {
+                    let Some(timeout) = timeout else {
+                        return download.await;
+                    };
+                    // Dropping the download keeps the partial file for the
+                    // next attempt to resume from
+                    tokio::time::timeout(timeout, download)
+                        .await
+                        .unwrap_or_else(|_| {
+                            Err(RustupError::ComponentDownloadTimedOut {
+                                component: short_name.clone(),
+                                timeout,
+                            }
+                            .into())
+                        })
+                }
             },
             |e: &anyhow::Error| {
+                // The last attempt is checked too, but not retried
+                if attempts.get() > max_retries {
+                    return false;
+                }
                 // retry only known retriable cases
                 match e.downcast_ref::<RustupError>() {
                     Some(RustupError::BrokenPartialFile)
-                    | Some(RustupError::DownloadingFile { .. }) => {
+                    | Some(RustupError::DownloadingFile { .. })
+                    | Some(RustupError::ComponentDownloadTimedOut { .. }) => {
+                        warn!(url = %url, "retrying download: {e}");
                         self.status.retrying();
                         true
                     }
@@ -771,22 +949,28 @@ impl<'a> ComponentBinary<'a> {
                 }
             },
         )
+        .instrument(span)
         .await
         .with_context(|| {
             RustupError::ComponentDownloadFailed(self.manifest.name(&self.component))
         })?;
+        debug!(
+            component = %short_name,
+            bytes = installer.metadata().map(|m| m.len()).ok(),
+            elapsed_ms = start.elapsed().as_millis() as u64,
+            "component downloaded"
+        );
 
         let install = ComponentInstall {
+            source: PackageSource::Archive {
+                compression: self.binary.compression,
+                installer,
+                temp_dir: self.download_cfg.tmp_cx.new_directory()?,
+                io_executor: self.io_executor()?,
+            },
             status: self.status,
-            compression: self.binary.compression,
-            installer,
-            short_name: self.manifest.short_name(&self.component).to_owned(),
+            short_name,
             component: self.component,
-            temp_dir: self.download_cfg.tmp_cx.new_directory()?,
-            io_executor: get_executor(
-                unpack_ram(IO_CHUNK_SIZE, self.download_cfg.process.unpack_ram()?),
-                self.download_cfg.process.io_thread_count()?,
-            ),
         };
 
         Ok((install, &self.binary.hash))
@@ -796,28 +980,50 @@ impl<'a> ComponentBinary<'a> {
 struct ComponentInstall {
     component: Component,
     status: DownloadStatus,
-    compression: CompressionKind,
-    installer: File,
+    source: PackageSource,
     short_name: String,
-    temp_dir: temp::Dir,
-    io_executor: Box<dyn Executor>,
+}
+
+enum PackageSource {
+    /// A downloaded package archive, yet to be unpacked.
+    Archive {
+        compression: CompressionKind,
+        installer: File,
+        temp_dir: temp::Dir,
+        io_executor: Box<dyn Executor>,
+    },
+    /// An installed component patched into its new version.
+    Patched(DirectoryPackage<temp::Dir>),
 }
 
 impl ComponentInstall {
-    fn install(self, tx: Transaction, manifestation: Arc<Manifestation>) -> Result<Transaction> {
+    fn install(
+        self,
+        tx: Transaction,
+        manifestation: Arc<Manifestation>,
+        checksums: Option<Checksums>,
+    ) -> Result<Transaction> {
+        let _span = info_span!("install_component", component = %self.short_name).entered();
+        let start = Instant::now();
+
         // For historical reasons, the rust-installer component
         // names are not the same as the dist manifest component
         // names. Some are just the component name some are the
         // component name plus the target tuple.
         let pkg_name = self.component.name_in_manifest();
         let short_pkg_name = self.component.short_name_in_manifest();
-        let reader = self.status.unpack(utils::buffered(&self.installer)?);
-        let package = DirectoryPackage::compressed(
-            reader,
-            self.compression,
-            self.temp_dir,
-            self.io_executor,
-        )?;
+        let package = match self.source {
+            PackageSource::Archive {
+                compression,
+                installer,
+                temp_dir,
+                io_executor,
+            } => {
+                let reader = self.status.unpack(utils::buffered(&installer)?);
+                DirectoryPackage::compressed(reader, compression, temp_dir, io_executor)?
+            }
+            PackageSource::Patched(package) => package,
+        };
 
         // If the package doesn't contain the component that the
         // manifest says it does then somebody must be playing a joke on us.
@@ -825,6 +1031,11 @@ impl ComponentInstall {
             return Err(RustupError::CorruptComponent(self.short_name).into());
         }
 
+        if let Some(checksums) = checksums {
+            let package_checksums = package.checksums(&pkg_name, Some(short_pkg_name))?;
+            checksums.lock().unwrap().extend(package_checksums);
+        }
+
         self.status.installing();
         let tx = package.install(
             &manifestation.installation,
@@ -833,6 +1044,13 @@ impl ComponentInstall {
             tx,
         );
         self.status.installed();
+        if tx.is_ok() {
+            debug!(
+                component = %self.short_name,
+                elapsed_ms = start.elapsed().as_millis() as u64,
+                "component installed"
+            );
+        }
         tx
     }
 }
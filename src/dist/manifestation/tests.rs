@@ -16,9 +16,10 @@ use url::Url;
 use crate::{
     dist::{
         DEFAULT_DIST_SERVER, Profile, TargetTriple, ToolchainDesc,
+        download::file_hash,
         download::{DownloadCfg, DownloadTracker},
         manifest::{Component, Manifest},
-        manifestation::{Changes, Manifestation, UpdateStatus},
+        manifestation::{Changes, Manifestation, UpdateStatus, verify_installed},
         prefix::InstallPrefix,
         temp,
     },
@@ -409,6 +410,7 @@ struct TestContext {
     toolchain: ToolchainDesc,
     prefix: InstallPrefix,
     download_dir: PathBuf,
+    offline: bool,
     tp: TestProcess,
     tmp_cx: Arc<temp::Context>,
     _tempdirs: Vec<tempfile::TempDir>,
@@ -459,6 +461,7 @@ impl TestContext {
             url,
             toolchain,
             download_dir: prefix.path().join("downloads"),
+            offline: false,
             prefix,
             tp,
             tmp_cx: Arc::new(temp::Context::new(
@@ -482,15 +485,31 @@ impl TestContext {
         let dl_cfg = DownloadCfg {
             tmp_cx: self.tmp_cx.clone(),
             download_dir: &self.download_dir,
+            cache_dir: self.download_dir.with_file_name("cache"),
+            offline: self.offline,
             tracker: DownloadTracker::new(false, &self.tp.process),
             permit_copy_rename: self.tp.process.permit_copy_rename(),
+            verify_after_install: true,
+            network: None,
+            download_config: None,
+            signature_keyring: None,
+            fallback_servers: &[],
+            mirror_selector: None,
             process: &self.tp.process,
         };
 
         // Download the dist manifest and place it into the installation prefix
         let manifest_url = make_manifest_url(&self.url, &self.toolchain)?;
         let manifest_file = self.tmp_cx.new_file()?;
-        download_file(&manifest_url, &manifest_file, None, None, dl_cfg.process).await?;
+        download_file(
+            &manifest_url,
+            &manifest_file,
+            None,
+            None,
+            None,
+            dl_cfg.process,
+        )
+        .await?;
         let manifest_str = utils::read_file("manifest", &manifest_file)?;
         let manifest = Manifest::parse(&manifest_str)?;
 
@@ -1510,6 +1529,33 @@ async fn checks_files_hashes_before_reuse() {
     assert!(cx.stderr_line_contains(EXPECTED_LOG));
 }
 
+#[tokio::test]
+async fn offline_installs_only_use_previous_downloads() {
+    let mut cx = TestContext::new(None, GZOnly);
+    cx.offline = true;
+
+    let err = cx.update_from_dist(&[], &[], false).await.unwrap_err();
+    assert!(
+        err.chain()
+            .any(|e| matches!(e.downcast_ref(), Some(RustupError::Offline { .. })))
+    );
+
+    // Pre-populate the downloads, keyed by hash as rustup stores them
+    utils::ensure_dir_exists("download dir", &cx.download_dir).unwrap();
+    let dist_dir = cx.url.to_file_path().unwrap().join("dist/2016-02-02");
+    for entry in fs::read_dir(&dist_dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_some_and(|ext| ext == "sha256") {
+            let hash = utils::read_file("hash", &path).unwrap()[..SHA256_HASH_LEN].to_owned();
+            fs::copy(path.with_extension(""), cx.download_dir.join(hash)).unwrap();
+        }
+    }
+
+    cx.update_from_dist(&[], &[], false).await.unwrap();
+    assert!(utils::path_exists(cx.prefix.path().join("bin/rustc")));
+    assert!(cx.download_dir.read_dir().unwrap().next().is_some());
+}
+
 #[tokio::test]
 async fn handle_corrupt_partial_downloads() {
     let cx = TestContext::new(None, GZOnly);
@@ -1536,3 +1582,21 @@ async fn handle_corrupt_partial_downloads() {
     assert!(utils::path_exists(cx.prefix.path().join("bin/rustc")));
     assert!(utils::path_exists(cx.prefix.path().join("lib/libstd.rlib")));
 }
+
+#[tokio::test]
+async fn verification_finds_corrupt_installed_files() {
+    let cx = TestContext::new(None, GZOnly);
+    cx.update_from_dist(&[], &[], false).await.unwrap();
+
+    let rustc = PathBuf::from("bin/rustc");
+    let installed = cx.prefix.path().join(&rustc);
+    let checksums = [(rustc.clone(), file_hash(&installed).unwrap())];
+    verify_installed(cx.prefix.path(), &checksums).unwrap();
+
+    utils_raw::write_file(&installed, "corrupt").unwrap();
+    let err = verify_installed(cx.prefix.path(), &checksums).unwrap_err();
+    match err.downcast::<RustupError>() {
+        Ok(RustupError::VerificationFailed { files }) => assert_eq!(files, [rustc]),
+        _ => panic!(),
+    }
+}
@@ -312,6 +312,18 @@ impl FromStr for ParsedToolchainDesc {
             .captures(desc)
             .ok_or_else(|| RustupError::InvalidToolchainName(desc.to_string()))?;
 
+        // A date that is not zero-padded, e.g. `nightly-2024-1-5`, would
+        // otherwise be taken for the target
+        static UNPADDED_DATE_RE: LazyLock<Regex> = LazyLock::new(|| {
+            Regex::new(r"^[0-9]{4}-(?:[0-9]-[0-9]{1,2}|[0-9]{2}-[0-9])(?:-|$)").unwrap()
+        });
+        if d.get(3)
+            .is_some_and(|target| UNPADDED_DATE_RE.is_match(target.as_str()))
+        {
+            return Err(anyhow!("dates must be written as YYYY-MM-DD")
+                .context(RustupError::InvalidToolchainName(desc.to_string())));
+        }
+
         // These versions don't have v2 manifests, but they don't have point releases either,
         // so to make the two-part version numbers work for these versions, specially turn
         // them into their corresponding ".0" version.
@@ -725,15 +737,18 @@ impl TryFrom<&ToolchainName> for ToolchainDesc {
     }
 }
 
-#[derive(
-    Clone, Copy, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize, PartialOrd, Ord,
-)]
+#[derive(Clone, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize, PartialOrd, Ord)]
 #[serde(rename_all = "kebab-case")]
 pub enum Profile {
     Minimal,
     #[default]
     Default,
     Complete,
+    /// An explicit list of component names, e.g. `rustc` and `rust-std`.
+    ///
+    /// Names unknown to the release fail the install, while components the
+    /// release lacks for the target are skipped with a warning.
+    Custom(Vec<String>),
 }
 
 impl Profile {
@@ -742,6 +757,7 @@ impl Profile {
             Self::Minimal => "minimal",
             Self::Default => "default",
             Self::Complete => "complete",
+            Self::Custom(_) => "custom",
         }
     }
 }
@@ -830,6 +846,68 @@ impl fmt::Display for AutoInstallMode {
     }
 }
 
+/// What installing a toolchain does with the requested components, including
+/// those of the profile, that the release has but cannot be downloaded.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ComponentPolicy {
+    /// Fail the installation, possibly trying an older nightly first.
+    #[default]
+    Strict,
+    /// Warn about each unavailable component and install the others.
+    Lenient,
+}
+
+impl ComponentPolicy {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            Self::Strict => "strict",
+            Self::Lenient => "lenient",
+        }
+    }
+
+    /// Whether unavailable components are skipped rather than failing the install.
+    pub(crate) fn skips_unavailable(&self) -> bool {
+        *self == Self::Lenient
+    }
+}
+
+impl ValueEnum for ComponentPolicy {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[Self::Strict, Self::Lenient]
+    }
+
+    fn to_possible_value(&self) -> Option<PossibleValue> {
+        Some(PossibleValue::new(self.as_str()))
+    }
+
+    fn from_str(input: &str, _: bool) -> Result<Self, String> {
+        <Self as FromStr>::from_str(input).map_err(|e| e.to_string())
+    }
+}
+
+impl FromStr for ComponentPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(policy: &str) -> Result<Self> {
+        match policy {
+            "strict" => Ok(Self::Strict),
+            "lenient" => Ok(Self::Lenient),
+            _ => Err(anyhow!(format!(
+                "unknown component policy: '{}'; valid policies are {}",
+                policy,
+                Self::value_variants().iter().join(", ")
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for ComponentPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
 impl fmt::Display for TargetTriple {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         self.0.fmt(f)
@@ -945,6 +1023,10 @@ impl<'cfg, 'a> DistOptions<'cfg, 'a> {
         self
     }
 
+    pub(crate) fn profile(&self) -> &Profile {
+        &self.profile
+    }
+
     // Installs or updates a toolchain from a dist server. If an initial
     // install then it will be installed with the default components. If
     // an upgrade then all the existing components will be upgraded.
@@ -1015,7 +1097,7 @@ impl<'cfg, 'a> DistOptions<'cfg, 'a> {
                 &self.update_hash,
                 &toolchain,
                 match self.exists {
-                    false => Some(self.profile),
+                    false => Some(self.profile.clone()),
                     true => None,
                 },
                 prefix,
@@ -1110,6 +1192,55 @@ impl<'cfg, 'a> DistOptions<'cfg, 'a> {
     }
 }
 
+/// Collects the components that installing `toolchain` from `m` should add: those of `profile`
+/// (if any) along with the explicitly requested `components` and `targets`.
+pub(crate) fn requested_components(
+    m: &ManifestV2,
+    toolchain: &ToolchainDesc,
+    profile: Option<Profile>,
+    components: &[&str],
+    targets: &[&str],
+) -> Result<Vec<Component>> {
+    let profile_components = match profile {
+        Some(profile) => m.get_profile_components(profile, &toolchain.target)?,
+        None => Vec::new(),
+    };
+
+    let mut all_components: HashSet<Component> = profile_components.into_iter().collect();
+
+    let rust_package = m.get_package("rust")?;
+    let rust_target_package = rust_package.get_target(Some(&toolchain.target.clone()))?;
+
+    for component in components {
+        let mut component =
+            Component::new(component.to_string(), Some(toolchain.target.clone()), false);
+        if let Some(renamed) = m.rename_component(&component) {
+            component = renamed;
+        }
+        // Look up the newly constructed/renamed component and ensure that
+        // if it's a wildcard component we note such, otherwise we end up
+        // exacerbating the problem we thought we'd fixed with #2087 and #2115
+        if let Some(c) = rust_target_package
+            .components
+            .iter()
+            .find(|c| c.short_name_in_manifest() == component.short_name_in_manifest())
+            && c.target.is_none()
+        {
+            component = component.wildcard();
+        }
+        all_components.insert(component);
+    }
+
+    for &target in targets {
+        let triple = TargetTriple::new(target);
+        all_components.insert(Component::new("rust-std".to_string(), Some(triple), false));
+    }
+
+    let mut explicit_add_components: Vec<_> = all_components.into_iter().collect();
+    explicit_add_components.sort();
+    Ok(explicit_add_components)
+}
+
 #[allow(clippy::too_many_arguments)]
 async fn try_update_from_dist_(
     download: &DownloadCfg<'_>,
@@ -1149,43 +1280,8 @@ async fn try_update_from_dist_(
                 Err(_) => info!("latest update on {}", m.date),
             }
 
-            let profile_components = match profile {
-                Some(profile) => m.get_profile_components(profile, &toolchain.target)?,
-                None => Vec::new(),
-            };
-
-            let mut all_components: HashSet<Component> = profile_components.into_iter().collect();
-
-            let rust_package = m.get_package("rust")?;
-            let rust_target_package = rust_package.get_target(Some(&toolchain.target.clone()))?;
-
-            for component in components {
-                let mut component =
-                    Component::new(component.to_string(), Some(toolchain.target.clone()), false);
-                if let Some(renamed) = m.rename_component(&component) {
-                    component = renamed;
-                }
-                // Look up the newly constructed/renamed component and ensure that
-                // if it's a wildcard component we note such, otherwise we end up
-                // exacerbating the problem we thought we'd fixed with #2087 and #2115
-                if let Some(c) = rust_target_package
-                    .components
-                    .iter()
-                    .find(|c| c.short_name_in_manifest() == component.short_name_in_manifest())
-                    && c.target.is_none()
-                {
-                    component = component.wildcard();
-                }
-                all_components.insert(component);
-            }
-
-            for &target in targets {
-                let triple = TargetTriple::new(target);
-                all_components.insert(Component::new("rust-std".to_string(), Some(triple), false));
-            }
-
-            let mut explicit_add_components: Vec<_> = all_components.into_iter().collect();
-            explicit_add_components.sort();
+            let explicit_add_components =
+                requested_components(&m, toolchain, profile, components, targets)?;
 
             let changes = Changes {
                 explicit_add_components,
@@ -1360,6 +1456,9 @@ mod tests {
             "--",
             "0.0.0-",
             "1.90.01",
+            "nightly-2024-1-5",
+            "nightly-2024-01-5-x86_64-unknown-linux-gnu",
+            "1.80.0-2024-7-25",
         ];
 
         for input in failure_cases {
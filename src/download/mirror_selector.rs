@@ -0,0 +1,139 @@
+//! Picking the fastest mirror of the release server, for the `latency`
+//! `mirror_selection` setting.
+//!
+//! All the mirrors are probed at once by fetching the checksum of the stable
+//! channel manifest from each of them. The one that responds first is tried
+//! before the others, and is remembered in a session file so that later
+//! invocations of rustup do not probe again until the choice expires.
+
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use futures_util::future::join_all;
+use serde::{Deserialize, Serialize};
+use tokio::sync::OnceCell;
+use tracing::{debug, info};
+
+use super::download_file;
+use crate::dist::temp;
+use crate::installer::NetworkConfig;
+use crate::process::Process;
+use crate::utils;
+
+/// How long a mirror chosen by latency is remembered, unless configured otherwise.
+pub(crate) const DEFAULT_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// How long each mirror is given to respond to the probe.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The file fetched from each mirror to measure how fast it responds.
+const PROBE_PATH: &str = "/dist/channel-rust-stable.toml.sha256";
+
+pub(crate) struct MirrorSelector {
+    mirrors: Vec<String>,
+    session_file: PathBuf,
+    ttl: Duration,
+    fastest: OnceCell<Option<String>>,
+}
+
+/// The contents of the session file.
+#[derive(Deserialize, Serialize)]
+struct Session {
+    /// The mirrors the choice was made between.
+    mirrors: Vec<String>,
+    fastest: String,
+    /// When the choice was made, in seconds since the Unix epoch.
+    probed_at: u64,
+}
+
+impl MirrorSelector {
+    pub(crate) fn new(mirrors: Vec<String>, session_file: PathBuf, ttl: Duration) -> Self {
+        Self {
+            mirrors,
+            session_file,
+            ttl,
+            fastest: OnceCell::new(),
+        }
+    }
+
+    /// The mirror to download from first: the one remembered in the session
+    /// file if it has not expired, and otherwise the fastest one to respond
+    /// to the probe, if any of them did.
+    pub(crate) async fn fastest(&self, tmp_cx: &temp::Context, process: &Process) -> Option<&str> {
+        self.fastest
+            .get_or_init(async || {
+                if let Some(fastest) = self.remembered() {
+                    debug!(mirror = fastest, "using the mirror chosen earlier");
+                    return Some(fastest);
+                }
+
+                let fastest = self.probe(tmp_cx, process).await?;
+                info!("downloading from the fastest mirror, {fastest}");
+                if let Err(e) = self.remember(&fastest) {
+                    debug!(error = %e, "could not save the mirror selection");
+                }
+                Some(fastest)
+            })
+            .await
+            .as_deref()
+    }
+
+    fn remembered(&self) -> Option<String> {
+        let contents = utils::read_file("mirror selection", &self.session_file).ok()?;
+        let session = toml::from_str::<Session>(&contents).ok()?;
+        let age = now().checked_sub(session.probed_at)?;
+        (session.mirrors == self.mirrors && age < self.ttl.as_secs()).then_some(session.fastest)
+    }
+
+    fn remember(&self, fastest: &str) -> Result<()> {
+        let session = Session {
+            mirrors: self.mirrors.clone(),
+            fastest: fastest.to_owned(),
+            probed_at: now(),
+        };
+        let contents = toml::to_string(&session).context("serializing the mirror selection")?;
+        utils::write_file("mirror selection", &self.session_file, &contents)
+    }
+
+    /// Fetches [`PROBE_PATH`] from every mirror concurrently, and returns the
+    /// one that finished first.
+    async fn probe(&self, tmp_cx: &temp::Context, process: &Process) -> Option<String> {
+        let network = &NetworkConfig {
+            connect_timeout: PROBE_TIMEOUT,
+            read_timeout: PROBE_TIMEOUT,
+            ..NetworkConfig::default()
+        };
+
+        let probes = self.mirrors.iter().map(|mirror| async move {
+            let url = utils::parse_url(&format!("{mirror}{PROBE_PATH}")).ok()?;
+            let file = tmp_cx.new_file().ok()?;
+            let start = Instant::now();
+            let download = download_file(&url, &file, None, None, Some(network), process);
+            match tokio::time::timeout(PROBE_TIMEOUT, download).await {
+                Ok(Ok(())) => {
+                    let latency = start.elapsed();
+                    debug!(mirror, ?latency, "probed mirror");
+                    Some((latency, mirror))
+                }
+                Ok(Err(e)) => {
+                    debug!(mirror, error = %e, "mirror probe failed");
+                    None
+                }
+                Err(_) => {
+                    debug!(mirror, "mirror probe timed out");
+                    None
+                }
+            }
+        });
+
+        let (_, fastest) = join_all(probes).await.into_iter().flatten().min()?;
+        Some(fastest.clone())
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
@@ -2,7 +2,7 @@
 
 use std::fs::remove_file;
 use std::num::NonZero;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::time::Duration;
 
@@ -15,13 +15,22 @@ use anyhow::Context;
 use anyhow::anyhow;
 use sha2::Sha256;
 use thiserror::Error;
-use tracing::debug;
 #[cfg(any(feature = "reqwest-rustls-tls", feature = "reqwest-native-tls"))]
 use tracing::info;
 use tracing::warn;
+use tracing::{Instrument, Span, debug, field, info_span};
 use url::Url;
 
-use crate::{dist::download::DownloadStatus, errors::RustupError, process::Process};
+use crate::{
+    dist::download::DownloadStatus,
+    errors::RustupError,
+    installer::{DownloadConfig, NetworkConfig},
+    process::Process,
+    settings::SettingsFile,
+    utils,
+};
+
+pub(crate) mod mirror_selector;
 
 #[cfg(test)]
 mod tests;
@@ -31,20 +40,164 @@ pub(crate) async fn download_file(
     path: &Path,
     hasher: Option<&mut Sha256>,
     status: Option<&DownloadStatus>,
+    network: Option<&NetworkConfig>,
     process: &Process,
 ) -> anyhow::Result<()> {
-    download_file_with_resume(url, path, hasher, false, status, process).await
+    download_file_with_resume(url, path, hasher, false, status, network, None, process).await
 }
 
+/// Like [`download_file`], optionally resuming a partial download at `path`
+/// and following the bandwidth limit and HTTP/2 setting of `download`.
+#[allow(clippy::too_many_arguments)]
 pub(crate) async fn download_file_with_resume(
     url: &Url,
     path: &Path,
     hasher: Option<&mut Sha256>,
     resume_from_partial: bool,
     status: Option<&DownloadStatus>,
+    network: Option<&NetworkConfig>,
+    download: Option<&DownloadConfig>,
+    process: &Process,
+) -> anyhow::Result<()> {
+    let res = download_file_(
+        url,
+        path,
+        hasher,
+        resume_from_partial,
+        status,
+        network,
+        download,
+        process,
+    )
+    .instrument(download_span(url))
+    .await;
+    with_download_context(res, url, path)
+}
+
+/// The span around downloading `url`, whose `bytes_expected` and
+/// `bytes_received` fields are kept up to date by [`record_progress`].
+fn download_span(url: &Url) -> Span {
+    info_span!(
+        "download",
+        url = %url,
+        bytes_expected = field::Empty,
+        bytes_received = field::Empty,
+    )
+}
+
+/// Records the progress reported by `event` on `span`, with `received`
+/// counting the bytes received so far.
+fn record_progress(span: &Span, received: &std::cell::Cell<u64>, event: &Event<'_>) {
+    match event {
+        Event::DownloadContentLengthReceived(len) => {
+            span.record("bytes_expected", len);
+        }
+        Event::DownloadDataReceived(data) => {
+            received.set(received.get() + data.len() as u64);
+            span.record("bytes_received", received.get());
+        }
+        Event::DownloadRestarted => {
+            received.set(0);
+            span.record("bytes_received", 0);
+        }
+        Event::ResumingPartialDownload | Event::DownloadValidatorReceived(_) => {}
+    }
+}
+
+/// Downloads `url` to `path`, keeping a copy of the file in `cache`.
+///
+/// If `cache` already holds the file, the server is asked to only send it
+/// again if it changed, based on the `ETag` or `Last-Modified` header it was
+/// last served with. A `304 Not Modified` answer reuses the cached copy.
+pub(crate) async fn download_file_cached(
+    url: &Url,
+    path: &Path,
+    cache: &Path,
+    hasher: Option<&mut Sha256>,
+    network: Option<&NetworkConfig>,
+    download: Option<&DownloadConfig>,
     process: &Process,
 ) -> anyhow::Result<()> {
-    match download_file_(url, path, hasher, resume_from_partial, status, process).await {
+    use sha2::Digest;
+    use std::cell::RefCell;
+    use std::io::Write;
+
+    // There is nothing to save by caching a file that is already local
+    if url.scheme() == "file" {
+        return download_file_with_resume(
+            url, path, hasher, false, None, network, download, process,
+        )
+        .await;
+    }
+
+    let validator_path = validator_path(cache);
+    let validator = match cache.is_file() {
+        true => std::fs::read_to_string(&validator_path).ok(),
+        false => None,
+    };
+
+    debug!(url = %url, "downloading file");
+    let file = std::fs::File::create(path).context("error creating file for download")?;
+    let file = RefCell::new(file);
+    let new_validator = RefCell::new(None);
+    let condition = validator.as_deref().map(Condition::IfChanged);
+    let span = download_span(url);
+    let received = std::cell::Cell::new(0);
+    let res = backend(process)?
+        .download(
+            url,
+            0,
+            condition,
+            client_options(network, download, process)?,
+            None,
+            &|event| {
+                record_progress(&span, &received, &event);
+                match event {
+                    Event::DownloadDataReceived(data) => file
+                        .borrow_mut()
+                        .write_all(data)
+                        .context("unable to write download to disk")?,
+                    Event::DownloadValidatorReceived(v) => {
+                        *new_validator.borrow_mut() = Some(v.to_owned())
+                    }
+                    _ => {}
+                }
+                Ok(())
+            },
+        )
+        .instrument(span.clone())
+        .await;
+    drop(file);
+
+    match res {
+        Err(e) if matches!(e.downcast_ref(), Some(DownloadError::HttpStatus(304))) => {
+            debug!(url = %url, "file not modified, reusing cached download");
+            utils::copy_file(cache, path)?;
+        }
+        Err(e) => return with_download_context(Err(e), url, path),
+        Ok(()) => {
+            utils::ensure_dir_exists("cache", cache.parent().unwrap_or(cache))?;
+            utils::copy_file(path, cache)?;
+            match new_validator.into_inner() {
+                Some(v) => std::fs::write(&validator_path, v)
+                    .context("unable to save download validator")?,
+                None => {
+                    let _ = remove_file(&validator_path);
+                }
+            }
+        }
+    }
+
+    if let Some(hasher) = hasher {
+        hasher.update(std::fs::read(path).context("unable to read download")?);
+    }
+    Ok(())
+}
+
+/// Marks failures to download `url` to `path` as such, telling apart the
+/// files the server does not have.
+fn with_download_context(res: anyhow::Result<()>, url: &Url, path: &Path) -> anyhow::Result<()> {
+    match res {
         Ok(_) => Ok(()),
         Err(e) => {
             if e.downcast_ref::<std::io::Error>().is_some() {
@@ -76,6 +229,12 @@ pub(crate) async fn download_file_with_resume(
     }
 }
 
+/// Whether `err` is a failure to fetch a file, such as an HTTP error status or
+/// a timeout, rather than a problem with what was fetched.
+pub(crate) fn is_download_failure(err: &anyhow::Error) -> bool {
+    err.chain().any(|e| e.is::<DownloadError>())
+}
+
 pub(crate) fn is_network_failure(err: &anyhow::Error) -> bool {
     match err.downcast_ref::<DownloadError>() {
         #[cfg(any(feature = "reqwest-rustls-tls", feature = "reqwest-native-tls"))]
@@ -84,31 +243,41 @@ pub(crate) fn is_network_failure(err: &anyhow::Error) -> bool {
     }
 }
 
+/// Whether `err` is a download that gave up waiting on the server.
+pub(crate) fn is_timeout(err: &(dyn std::error::Error + 'static)) -> bool {
+    match err.downcast_ref::<DownloadError>() {
+        #[cfg(any(feature = "reqwest-rustls-tls", feature = "reqwest-native-tls"))]
+        Some(DownloadError::Reqwest(e)) => e.is_timeout(),
+        #[cfg(feature = "curl-backend")]
+        Some(DownloadError::CurlError(e)) => e.is_operation_timedout(),
+        _ => false,
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 async fn download_file_(
     url: &Url,
     path: &Path,
     hasher: Option<&mut Sha256>,
     resume_from_partial: bool,
     status: Option<&DownloadStatus>,
+    network: Option<&NetworkConfig>,
+    download: Option<&DownloadConfig>,
     process: &Process,
 ) -> anyhow::Result<()> {
     #[cfg(any(feature = "reqwest-rustls-tls", feature = "reqwest-native-tls"))]
-    use crate::download::{Backend, Event, TlsBackend};
+    use crate::download::{Backend, Event};
     use sha2::Digest;
     use std::cell::RefCell;
 
     debug!(url = %url, "downloading file");
     let hasher = RefCell::new(hasher);
+    let span = Span::current();
+    let received = std::cell::Cell::new(0);
 
-    // This callback will write the download to disk and optionally
-    // hash the contents, then forward the notification up the stack
-    let callback: &dyn Fn(Event<'_>) -> anyhow::Result<()> = &|msg| {
-        if let Event::DownloadDataReceived(data) = msg
-            && let Some(h) = hasher.borrow_mut().as_mut()
-        {
-            h.update(data);
-        }
-
+    // This callback forwards the progress of the download up the stack
+    let progress: &dyn Fn(Event<'_>) -> anyhow::Result<()> = &|msg| {
+        record_progress(&span, &received, &msg);
         match msg {
             Event::DownloadContentLengthReceived(len) => {
                 if let Some(status) = status {
@@ -121,13 +290,189 @@ async fn download_file_(
                 }
             }
             Event::ResumingPartialDownload => debug!("resuming partial download"),
+            Event::DownloadRestarted => debug!("partial download is stale, restarting"),
+            Event::DownloadValidatorReceived(_) => {}
         }
 
         Ok(())
     };
 
+    // This callback will optionally hash the contents as they are written
+    // to disk, then report the progress
+    let callback: &dyn Fn(Event<'_>) -> anyhow::Result<()> = &|msg| {
+        if let Some(h) = hasher.borrow_mut().as_mut() {
+            match msg {
+                Event::DownloadDataReceived(data) => h.update(data),
+                Event::DownloadRestarted => h.reset(),
+                _ => {}
+            }
+        }
+        progress(msg)
+    };
+
+    // Local mirrors are read directly, without setting up a client
+    if url.scheme() == "file" {
+        let res = copy_from_file_url(url, path, callback);
+        if let Some(status) = status {
+            match &res {
+                Ok(_) => status.finished(),
+                Err(_) => status.failed(),
+            };
+        }
+        return res;
+    }
+
     // Download the file
+    let backend = backend(process)?;
+    let options = client_options(network, download, process)?;
+
+    match backend {
+        #[cfg(feature = "curl-backend")]
+        Backend::Curl => debug!("downloading with curl"),
+        #[cfg(any(feature = "reqwest-rustls-tls", feature = "reqwest-native-tls"))]
+        Backend::Reqwest(_) => debug!("downloading with reqwest"),
+    };
+
+    let bandwidth_limit = download.and_then(DownloadConfig::bandwidth_limit_per_download);
+    let in_parts = match backend {
+        #[cfg(any(feature = "reqwest-rustls-tls", feature = "reqwest-native-tls"))]
+        Backend::Reqwest(tls) => match download.map_or(1, |d| d.download_parts) {
+            // Partial downloads are resumed as a single stream instead
+            parts @ 2.. if !resume_from_partial || !has_partial_download(path) => {
+                match tls
+                    .download_in_parts(url, path, parts, options.clone(), bandwidth_limit, progress)
+                    .await
+                {
+                    // The parts arrive out of order, so hash the assembled file
+                    Ok(true) => match hasher.borrow_mut().as_mut() {
+                        Some(h) => hash_file(path, h).map(|()| true),
+                        None => Ok(true),
+                    },
+                    res => res,
+                }
+            }
+            _ => Ok(false),
+        },
+        #[cfg(feature = "curl-backend")]
+        Backend::Curl => Ok(false),
+    };
+
+    let res = match in_parts {
+        Ok(true) => Ok(()),
+        Ok(false) => {
+            backend
+                .download_to_path(
+                    url,
+                    path,
+                    resume_from_partial,
+                    Some(callback),
+                    options,
+                    bandwidth_limit,
+                )
+                .await
+        }
+        // The parts received so far cannot be resumed from
+        Err(e) => {
+            let _ = remove_file(path);
+            Err(e)
+        }
+    };
+
+    // The notification should only be sent if the download was successful (i.e. didn't timeout)
+    if let Some(status) = status {
+        match &res {
+            Ok(_) => status.finished(),
+            Err(_) => status.failed(),
+        };
+    }
+
+    res
+}
+
+/// Copies the file at the `file:` URL `url` to `path`, reporting it to
+/// `callback` as if it had been downloaded.
+fn copy_from_file_url(
+    url: &Url,
+    path: &Path,
+    callback: DownloadCallback<'_>,
+) -> anyhow::Result<()> {
+    use std::fs::File;
+    use std::io::{Read, Write};
+
+    let src = url
+        .to_file_path()
+        .map_err(|_| DownloadError::Message(format!("bogus file url: '{url}'")))?;
+    if !src.is_file() {
+        // Report a missing file the way a server would, as the dist code
+        // checks for it
+        return Err(DownloadError::FileNotFound.into());
+    }
+
+    let mut src = File::open(src).context("unable to open downloaded file")?;
+    let len = src
+        .metadata()
+        .context("unable to open downloaded file")?
+        .len();
+    callback(Event::DownloadContentLengthReceived(len))?;
+    let mut dest = File::create(path).context("error creating file for download")?;
+    let mut buffer = vec![0u8; 0x10000];
+    loop {
+        let bytes_read = src.read(&mut buffer)?;
+        if bytes_read == 0 {
+            return Ok(());
+        }
+        dest.write_all(&buffer[..bytes_read])
+            .context("unable to write download to disk")?;
+        callback(Event::DownloadDataReceived(&buffer[..bytes_read]))?;
+    }
+}
+
+/// Whether an earlier attempt left some of the download at `path`.
+#[cfg(any(feature = "reqwest-rustls-tls", feature = "reqwest-native-tls"))]
+fn has_partial_download(path: &Path) -> bool {
+    std::fs::metadata(path).is_ok_and(|m| m.len() > 0)
+}
+
+/// Feeds the contents of the file at `path` to `hasher`.
+#[cfg(any(feature = "reqwest-rustls-tls", feature = "reqwest-native-tls"))]
+fn hash_file(path: &Path, hasher: &mut Sha256) -> anyhow::Result<()> {
+    use sha2::Digest;
+    use std::io::Read;
+
+    let mut file = std::fs::File::open(path).context("unable to read download")?;
+    let mut buf = vec![0; 65536];
+    loop {
+        match file.read(&mut buf).context("unable to read download")? {
+            0 => return Ok(()),
+            n => hasher.update(&buf[..n]),
+        }
+    }
+}
+
+/// Fetches the size of the file at `url` without downloading it, if the server reports it.
+pub(crate) async fn content_length(
+    url: &Url,
+    network: Option<&NetworkConfig>,
+    download: Option<&DownloadConfig>,
+    process: &Process,
+) -> anyhow::Result<Option<u64>> {
+    debug!(url = %url, "fetching content length");
+    if url.scheme() == "file" {
+        let path = url
+            .to_file_path()
+            .map_err(|_| DownloadError::Message(format!("bogus file url: '{url}'")))?;
+        return Ok(std::fs::metadata(path).ok().map(|m| m.len()));
+    }
 
+    backend(process)?
+        .content_length(url, client_options(network, download, process)?)
+        .instrument(download_span(url))
+        .await
+        .with_context(|| format!("failed to fetch the size of '{url}'"))
+}
+
+/// Selects the download backend according to `RUSTUP_USE_CURL` and `RUSTUP_USE_RUSTLS`.
+fn backend(process: &Process) -> anyhow::Result<Backend> {
     // Keep the curl env var around for a bit
     let use_curl_backend = process.var_os("RUSTUP_USE_CURL").map(|it| it != "0");
     if use_curl_backend == Some(true) {
@@ -145,7 +490,7 @@ async fn download_file_(
         );
     }
 
-    let backend = match (use_curl_backend, use_rustls) {
+    Ok(match (use_curl_backend, use_rustls) {
         // If environment specifies a backend that's unavailable, error out
         #[cfg(not(feature = "reqwest-rustls-tls"))]
         (_, Some(true)) => {
@@ -197,37 +542,158 @@ async fn download_file_(
             feature = "curl-backend"
         ))]
         _ => Backend::Curl,
-    };
+    })
+}
+
+/// Picks the timeouts from `network`, or else from `RUSTUP_DOWNLOAD_TIMEOUT`.
+fn timeouts(network: Option<&NetworkConfig>, process: &Process) -> anyhow::Result<Timeouts> {
+    Ok(match network {
+        Some(network) => Timeouts {
+            connect: network.connect_timeout,
+            read: network.read_timeout,
+        },
+        None => timeout(process)?.into(),
+    })
+}
 
-    let timeout = Duration::from_secs(match process.var("RUSTUP_DOWNLOAD_TIMEOUT") {
+/// Reads the download timeout from `RUSTUP_DOWNLOAD_TIMEOUT`, defaulting to 3 minutes.
+fn timeout(process: &Process) -> anyhow::Result<Duration> {
+    Ok(Duration::from_secs(match process.var("RUSTUP_DOWNLOAD_TIMEOUT") {
         Ok(s) => NonZero::from_str(&s)
             .context(
                 "invalid value in RUSTUP_DOWNLOAD_TIMEOUT -- must be a natural number greater than zero",
             )?
             .get(),
         Err(_) => 180,
-    });
+    }))
+}
 
-    match backend {
-        #[cfg(feature = "curl-backend")]
-        Backend::Curl => debug!("downloading with curl"),
-        #[cfg(any(feature = "reqwest-rustls-tls", feature = "reqwest-native-tls"))]
-        Backend::Reqwest(_) => debug!("downloading with reqwest"),
+/// Picks the timeouts as [`timeouts`] does, whether `download` asks for HTTP/2
+/// and the proxies as [`proxies`] does.
+fn client_options(
+    network: Option<&NetworkConfig>,
+    download: Option<&DownloadConfig>,
+    process: &Process,
+) -> anyhow::Result<ClientOptions> {
+    Ok(ClientOptions {
+        timeouts: timeouts(network, process)?,
+        http2: download.is_some_and(|d| d.use_http2),
+        proxies: proxies(process)?,
+    })
+}
+
+/// Reads the proxies from `settings.toml`, dropping those for the schemes the
+/// environment already sets a proxy for, e.g. with `HTTPS_PROXY`.
+fn proxies(process: &Process) -> anyhow::Result<Proxies> {
+    // Don't create the settings file when installing rustup itself
+    let path = process.rustup_home().map(|home| home.join("settings.toml"));
+    let Ok(path) = path else {
+        return Ok(Proxies::default());
     };
+    if !utils::is_file(&path) {
+        return Ok(Proxies::default());
+    }
+    let (proxy, https_proxy, no_proxy) = SettingsFile::new(path)
+        .with(|s| Ok((s.proxy.clone(), s.https_proxy.clone(), s.no_proxy.clone())))?;
 
-    let res = backend
-        .download_to_path(url, path, resume_from_partial, Some(callback), timeout)
-        .await;
+    let env = |names: &[&str]| {
+        names
+            .iter()
+            .find_map(|name| process.var(name).ok().filter(|v| !v.is_empty()))
+    };
+    let parse = |proxy: Option<String>| {
+        proxy
+            .map(|p| Url::parse(&p).with_context(|| format!("invalid proxy in settings: '{p}'")))
+            .transpose()
+    };
+    Ok(Proxies {
+        http: match env(&["http_proxy", "HTTP_PROXY", "all_proxy", "ALL_PROXY"]) {
+            Some(_) => None,
+            None => parse(proxy.clone())?,
+        },
+        https: match env(&["https_proxy", "HTTPS_PROXY", "all_proxy", "ALL_PROXY"]) {
+            Some(_) => None,
+            None => parse(https_proxy.or(proxy))?,
+        },
+        no_proxy: env(&["no_proxy", "NO_PROXY"]).or(no_proxy),
+    })
+}
 
-    // The notification should only be sent if the download was successful (i.e. didn't timeout)
-    if let Some(status) = status {
-        match &res {
-            Ok(_) => status.finished(),
-            Err(_) => status.failed(),
+/// How the backends connect to the server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ClientOptions {
+    timeouts: Timeouts,
+    /// Whether to speak HTTP/2 to HTTPS servers without negotiating it first.
+    http2: bool,
+    proxies: Proxies,
+}
+
+/// The proxies from `settings.toml` that apply where the environment sets none.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Proxies {
+    http: Option<Url>,
+    /// The `https_proxy` setting, or else the `proxy` one.
+    https: Option<Url>,
+    /// The hosts to connect to directly, from `NO_PROXY` or else the settings.
+    no_proxy: Option<String>,
+}
+
+impl Proxies {
+    /// The proxy to download `url` through, if the settings give one.
+    fn for_url(&self, url: &Url) -> Option<Url> {
+        let proxy = match url.scheme() {
+            "http" => self.http.as_ref()?,
+            "https" => self.https.as_ref()?,
+            _ => return None,
         };
+        let host = url.host_str()?;
+        let bypassed = self.no_proxy.as_deref().is_some_and(|no_proxy| {
+            no_proxy.split(',').map(str::trim).any(|entry| {
+                let entry = entry.trim_start_matches('.');
+                entry == "*"
+                    || host == entry
+                    || host.strip_suffix(entry).is_some_and(|h| h.ends_with('.'))
+            })
+        });
+        (!bypassed).then(|| proxy.clone())
     }
+}
 
-    res
+impl ClientOptions {
+    /// The options that apply to downloading `url`; HTTP/2 is only used over TLS.
+    #[cfg(any(feature = "reqwest-rustls-tls", feature = "reqwest-native-tls"))]
+    fn for_url(&self, url: &Url) -> Self {
+        Self {
+            http2: self.http2 && url.scheme() == "https",
+            ..self.clone()
+        }
+    }
+}
+
+impl From<Duration> for ClientOptions {
+    fn from(timeout: Duration) -> Self {
+        Self {
+            timeouts: timeout.into(),
+            http2: false,
+            proxies: Proxies::default(),
+        }
+    }
+}
+
+/// How long the backends wait for a connection and for data on that connection.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct Timeouts {
+    connect: Duration,
+    read: Duration,
+}
+
+impl From<Duration> for Timeouts {
+    fn from(timeout: Duration) -> Self {
+        Self {
+            connect: timeout,
+            read: timeout,
+        }
+    }
 }
 
 /// User agent header value for HTTP request.
@@ -261,12 +727,21 @@ impl Backend {
         path: &Path,
         resume_from_partial: bool,
         callback: Option<DownloadCallback<'_>>,
-        timeout: Duration,
+        options: ClientOptions,
+        bandwidth_limit: Option<u64>,
     ) -> anyhow::Result<()> {
         let Err(err) = self
-            .download_impl(url, path, resume_from_partial, callback, timeout)
+            .download_impl(
+                url,
+                path,
+                resume_from_partial,
+                callback,
+                options,
+                bandwidth_limit,
+            )
             .await
         else {
+            let _ = remove_file(validator_path(path));
             return Ok(());
         };
 
@@ -275,7 +750,12 @@ impl Backend {
         // It may be worth looking for other cases where removal is also not desired.
         Err(
             if !(resume_from_partial && is_network_failure(&err))
-                && let Err(file_err) = remove_file(path).context("cleaning up cached downloads")
+                && let Err(file_err) = remove_file(path)
+                    .and_then(|()| match remove_file(validator_path(path)) {
+                        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+                        _ => Ok(()),
+                    })
+                    .context("cleaning up cached downloads")
             {
                 file_err.context(err)
             } else {
@@ -290,7 +770,8 @@ impl Backend {
         path: &Path,
         resume_from_partial: bool,
         callback: Option<DownloadCallback<'_>>,
-        timeout: Duration,
+        options: ClientOptions,
+        bandwidth_limit: Option<u64>,
     ) -> anyhow::Result<()> {
         use std::cell::RefCell;
         use std::fs::OpenOptions;
@@ -347,20 +828,47 @@ impl Backend {
             )
         };
 
+        // Only resume if the file is still the one the partial download was taken from.
+        let validator_path = validator_path(path);
+        let validator = match resume_from {
+            0 => None,
+            _ => std::fs::read_to_string(&validator_path).ok(),
+        };
+
         let file = RefCell::new(file);
 
         // TODO: the sync callback will stall the async runtime if IO calls block, which is OS dependent. Rearrange.
-        self.download(url, resume_from, timeout, &|event| {
-            if let Event::DownloadDataReceived(data) = event {
-                file.borrow_mut()
-                    .write_all(data)
-                    .context("unable to write download to disk")?;
-            }
-            match callback {
-                Some(cb) => cb(event),
-                None => Ok(()),
-            }
-        })
+        let condition = validator.as_deref().map(Condition::IfRange);
+        self.download(
+            url,
+            resume_from,
+            condition,
+            options,
+            bandwidth_limit,
+            &|event| {
+                match event {
+                    Event::DownloadDataReceived(data) => file
+                        .borrow_mut()
+                        .write_all(data)
+                        .context("unable to write download to disk")?,
+                    Event::DownloadRestarted => {
+                        let mut file = file.borrow_mut();
+                        file.set_len(0)
+                            .and_then(|()| file.rewind())
+                            .context("unable to truncate stale partial download")?;
+                    }
+                    Event::DownloadValidatorReceived(validator) if resume_from_partial => {
+                        std::fs::write(&validator_path, validator)
+                            .context("unable to save download validator")?;
+                    }
+                    _ => {}
+                }
+                match callback {
+                    Some(cb) => cb(event),
+                    None => Ok(()),
+                }
+            },
+        )
         .await?;
 
         file.borrow_mut()
@@ -382,14 +890,55 @@ impl Backend {
         self,
         url: &Url,
         resume_from: u64,
-        timeout: Duration,
+        condition: Option<Condition<'_>>,
+        options: ClientOptions,
+        bandwidth_limit: Option<u64>,
         callback: DownloadCallback<'_>,
     ) -> anyhow::Result<()> {
         match self {
             #[cfg(feature = "curl-backend")]
-            Self::Curl => curl::download(url, resume_from, callback, timeout),
+            Self::Curl => curl::download(
+                url,
+                resume_from,
+                condition,
+                callback,
+                options.timeouts,
+                &options.proxies,
+                bandwidth_limit,
+            ),
+            #[cfg(any(feature = "reqwest-rustls-tls", feature = "reqwest-native-tls"))]
+            Self::Reqwest(tls) => {
+                tls.download(
+                    url,
+                    resume_from,
+                    condition,
+                    callback,
+                    options,
+                    bandwidth_limit,
+                )
+                .await
+            }
+        }
+    }
+
+    #[cfg_attr(
+        all(
+            not(feature = "curl-backend"),
+            not(feature = "reqwest-rustls-tls"),
+            not(feature = "reqwest-native-tls")
+        ),
+        allow(unused_variables)
+    )]
+    async fn content_length(
+        self,
+        url: &Url,
+        options: ClientOptions,
+    ) -> anyhow::Result<Option<u64>> {
+        match self {
+            #[cfg(feature = "curl-backend")]
+            Self::Curl => curl::content_length(url, options.timeouts, &options.proxies),
             #[cfg(any(feature = "reqwest-rustls-tls", feature = "reqwest-native-tls"))]
-            Self::Reqwest(tls) => tls.download(url, resume_from, callback, timeout).await,
+            Self::Reqwest(tls) => tls.content_length(url, options).await,
         }
     }
 }
@@ -409,23 +958,210 @@ impl TlsBackend {
         self,
         url: &Url,
         resume_from: u64,
+        condition: Option<Condition<'_>>,
         callback: DownloadCallback<'_>,
-        timeout: Duration,
+        options: ClientOptions,
+        bandwidth_limit: Option<u64>,
     ) -> anyhow::Result<()> {
         let client = match self {
             #[cfg(feature = "reqwest-rustls-tls")]
-            Self::Rustls => reqwest_be::rustls_client(timeout)?,
+            Self::Rustls => reqwest_be::rustls_client(options.for_url(url))?,
+            #[cfg(feature = "reqwest-native-tls")]
+            Self::NativeTls => reqwest_be::native_tls_client(options.for_url(url))?,
+        };
+
+        let throttle = bandwidth_limit.map(Throttle::new);
+        reqwest_be::download(url, resume_from, condition, callback, &client, throttle).await
+    }
+
+    async fn content_length(
+        self,
+        url: &Url,
+        options: ClientOptions,
+    ) -> anyhow::Result<Option<u64>> {
+        let client = match self {
+            #[cfg(feature = "reqwest-rustls-tls")]
+            Self::Rustls => reqwest_be::rustls_client(options.for_url(url))?,
+            #[cfg(feature = "reqwest-native-tls")]
+            Self::NativeTls => reqwest_be::native_tls_client(options.for_url(url))?,
+        };
+
+        reqwest_be::content_length(url, &client).await
+    }
+
+    /// Downloads `url` to `path` as up to `parts` byte ranges fetched
+    /// concurrently, each written in place as it arrives.
+    ///
+    /// Returns `false` without downloading anything when the file is too
+    /// small to be split, or the server does not support range requests.
+    async fn download_in_parts(
+        self,
+        url: &Url,
+        path: &Path,
+        parts: usize,
+        options: ClientOptions,
+        bandwidth_limit: Option<u64>,
+        callback: DownloadCallback<'_>,
+    ) -> anyhow::Result<bool> {
+        use std::cell::RefCell;
+        use std::fs::OpenOptions;
+        use std::io::{Seek, SeekFrom, Write};
+
+        if !matches!(url.scheme(), "http" | "https") {
+            return Ok(false);
+        }
+        let client = match self {
+            #[cfg(feature = "reqwest-rustls-tls")]
+            Self::Rustls => reqwest_be::rustls_client(options.for_url(url))?,
             #[cfg(feature = "reqwest-native-tls")]
-            Self::NativeTls => reqwest_be::native_tls_client(timeout)?,
+            Self::NativeTls => reqwest_be::native_tls_client(options.for_url(url))?,
+        };
+        let (len, validator) = match reqwest_be::ranged_length(url, &client).await {
+            Ok(Some(ranged)) => ranged,
+            Ok(None) => return Ok(false),
+            Err(e) => {
+                debug!("not downloading in parts: {e:#}");
+                return Ok(false);
+            }
         };
+        let parts = (parts as u64).min(len / MIN_PART_SIZE);
+        if parts < 2 {
+            return Ok(false);
+        }
+        debug!(url = %url, parts, "downloading in parts");
+
+        let file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)
+            .context("error creating file for download")?;
+        file.set_len(len)
+            .context("error creating file for download")?;
+        drop(file);
+        callback(Event::DownloadContentLengthReceived(len))?;
+
+        let part_len = len.div_ceil(parts);
+        let bandwidth_limit = bandwidth_limit.map(|limit| (limit / parts).max(1));
+        let validator = validator.as_deref();
+        let client = &client;
+        futures_util::future::try_join_all((0..parts).map(|i| async move {
+            let range = i * part_len..len.min((i + 1) * part_len);
+            // TODO: blocking call
+            let mut file = OpenOptions::new()
+                .write(true)
+                .open(path)
+                .context("error opening file for download")?;
+            file.seek(SeekFrom::Start(range.start))?;
+            let file = RefCell::new(file);
+
+            reqwest_be::download_range(
+                url,
+                range,
+                validator,
+                &|event| {
+                    if let Event::DownloadDataReceived(data) = event {
+                        file.borrow_mut()
+                            .write_all(data)
+                            .context("unable to write download to disk")?;
+                    }
+                    callback(event)
+                },
+                client,
+                bandwidth_limit.map(Throttle::new),
+            )
+            .await?;
+
+            file.into_inner()
+                .sync_data()
+                .context("unable to sync download to disk")
+        }))
+        .await?;
+
+        Ok(true)
+    }
+}
+
+/// The smallest range [`TlsBackend::download_in_parts`] splits a download into.
+#[cfg(any(feature = "reqwest-rustls-tls", feature = "reqwest-native-tls"))]
+const MIN_PART_SIZE: u64 = 4 * 1024 * 1024;
+
+/// A token bucket pacing a download to an average number of bytes per second.
+///
+/// Bursts of up to one second worth of data are let through unthrottled.
+#[cfg(any(feature = "reqwest-rustls-tls", feature = "reqwest-native-tls"))]
+struct Throttle {
+    rate: f64,
+    tokens: f64,
+    last: std::time::Instant,
+}
+
+#[cfg(any(feature = "reqwest-rustls-tls", feature = "reqwest-native-tls"))]
+impl Throttle {
+    fn new(bytes_per_second: u64) -> Self {
+        let rate = bytes_per_second.max(1) as f64;
+        Self {
+            rate,
+            tokens: rate,
+            last: std::time::Instant::now(),
+        }
+    }
+
+    /// Takes `len` bytes out of the bucket, returning how long to wait
+    /// before receiving more data.
+    fn delay(&mut self, len: usize) -> Duration {
+        let now = std::time::Instant::now();
+        let refill = now.duration_since(self.last).as_secs_f64() * self.rate;
+        self.tokens = (self.tokens + refill).min(self.rate) - len as f64;
+        self.last = now;
+        match self.tokens {
+            t if t >= 0.0 => Duration::ZERO,
+            t => Duration::from_secs_f64(-t / self.rate),
+        }
+    }
+}
+
+/// Where the validator of a partial download at `path` is kept.
+pub(crate) fn validator_path(path: &Path) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(".validator");
+    PathBuf::from(path)
+}
+
+/// A precondition for the server to send a file.
+#[derive(Debug, Copy, Clone)]
+enum Condition<'a> {
+    /// Only send the requested range if the file still has this validator.
+    IfRange(&'a str),
+    /// Only send the file if it no longer has this validator.
+    IfChanged(&'a str),
+}
 
-        reqwest_be::download(url, resume_from, callback, client).await
+impl<'a> Condition<'a> {
+    /// The request header expressing the condition, if it applies to a
+    /// request starting at `resume_from`.
+    fn header(self, resume_from: u64) -> Option<(&'static str, &'a str)> {
+        match self {
+            Self::IfRange(_) if resume_from == 0 => None,
+            Self::IfRange(v) => Some(("If-Range", v)),
+            // Entity tags are quoted, unlike the dates of `Last-Modified`.
+            Self::IfChanged(v) if v.starts_with('"') || v.starts_with("W/") => {
+                Some(("If-None-Match", v))
+            }
+            Self::IfChanged(v) => Some(("If-Modified-Since", v)),
+        }
     }
 }
 
 #[derive(Debug, Copy, Clone)]
 enum Event<'a> {
     ResumingPartialDownload,
+    /// The server sent the whole file instead of the requested range, so the
+    /// data received so far must be discarded.
+    DownloadRestarted,
+    /// The `ETag` or else `Last-Modified` value of the file, used to check that
+    /// a later request is about the same version of it.
+    DownloadValidatorReceived(&'a str),
     /// Received the Content-Length of the to-be downloaded data.
     DownloadContentLengthReceived(u64),
     /// Received some data.
@@ -440,19 +1176,22 @@ type DownloadCallback<'a> = &'a dyn Fn(Event<'_>) -> anyhow::Result<()>;
 mod curl {
     use std::cell::RefCell;
     use std::str;
-    use std::time::Duration;
 
     use anyhow::{Context, Result};
-    use curl::easy::Easy;
+    use curl::easy::{Easy, List};
+    use tracing::debug;
     use url::Url;
 
-    use super::{DownloadError, Event};
+    use super::{Condition, DownloadError, Event, Proxies, Timeouts};
 
     pub(super) fn download(
         url: &Url,
         resume_from: u64,
+        condition: Option<Condition<'_>>,
         callback: &dyn Fn(Event<'_>) -> Result<()>,
-        timeout: Duration,
+        timeouts: Timeouts,
+        proxies: &Proxies,
+        bandwidth_limit: Option<u64>,
     ) -> Result<()> {
         // Fetch either a cached libcurl handle (which will preserve open
         // connections) or create a new one if it isn't listed.
@@ -466,6 +1205,7 @@ mod curl {
             handle.url(url.as_ref())?;
             handle.follow_location(true)?;
             handle.useragent(super::CURL_USER_AGENT)?;
+            set_proxy(&mut handle, url, proxies)?;
 
             if resume_from > 0 {
                 handle.resume_from(resume_from)?;
@@ -475,11 +1215,25 @@ mod curl {
                 let _ = handle.resume_from(0);
             }
 
+            // libcurl fails the transfer if the server answers a resume with
+            // the whole file, which happens when `If-Range` no longer matches.
+            let mut headers = List::new();
+            if let Some((name, value)) = condition.and_then(|c| c.header(resume_from)) {
+                headers.append(&format!("{name}: {value}"))?;
+            }
+            handle.http_headers(headers)?;
+
             // Take at most 3m to connect if the `RUSTUP_DOWNLOAD_TIMEOUT` env var is not set.
-            handle.connect_timeout(timeout)?;
+            handle.connect_timeout(timeouts.connect)?;
+            // Give up if the transfer stalls for longer than the read timeout.
+            handle.low_speed_limit(1)?;
+            handle.low_speed_time(timeouts.read)?;
+            // libcurl paces the transfer itself; 0 lifts the limit of a reused handle.
+            handle.max_recv_speed(bandwidth_limit.unwrap_or(0))?;
 
             {
                 let cberr = RefCell::new(None);
+                let found_validator = RefCell::new(None::<String>);
                 let mut transfer = handle.transfer();
 
                 // Data callback for libcurl which is called with data that's
@@ -496,11 +1250,38 @@ mod curl {
                 })?;
 
                 // Listen for headers and parse out a `Content-Length` (case-insensitive) if it
-                // comes so we know how much we're downloading.
+                // comes so we know how much we're downloading. The validator of the
+                // file is reported once all headers of a response have arrived.
                 transfer.header_function(|header| {
                     let Ok(data) = str::from_utf8(header) else {
                         return true;
                     };
+                    if data.starts_with("HTTP/") {
+                        *found_validator.borrow_mut() = None;
+                        return true;
+                    }
+                    if data.trim().is_empty() {
+                        let validator = found_validator.borrow_mut().take();
+                        if let Some(v) = validator
+                            && let Err(e) = callback(Event::DownloadValidatorReceived(&v))
+                        {
+                            *cberr.borrow_mut() = Some(e);
+                            return false;
+                        }
+                        return true;
+                    }
+                    if let Some((name, value)) = data.split_once(':') {
+                        let (name, value) = (name.trim(), value.trim());
+                        if name.eq_ignore_ascii_case("location") {
+                            debug!(to = value, "following redirect");
+                        }
+                        if name.eq_ignore_ascii_case("etag")
+                            || (name.eq_ignore_ascii_case("last-modified")
+                                && found_validator.borrow().is_none())
+                        {
+                            *found_validator.borrow_mut() = Some(value.to_owned());
+                        }
+                    }
                     let prefix = "content-length: ";
                     let Some((dp, ds)) = data.split_at_checked(prefix.len()) else {
                         return true;
@@ -550,40 +1331,80 @@ mod curl {
             Ok(())
         })
     }
+
+    pub(super) fn content_length(
+        url: &Url,
+        timeouts: Timeouts,
+        proxies: &Proxies,
+    ) -> Result<Option<u64>> {
+        let mut handle = Easy::new();
+        handle.url(url.as_ref())?;
+        handle.follow_location(true)?;
+        handle.useragent(super::CURL_USER_AGENT)?;
+        set_proxy(&mut handle, url, proxies)?;
+        handle.connect_timeout(timeouts.connect)?;
+        handle.low_speed_limit(1)?;
+        handle.low_speed_time(timeouts.read)?;
+        // Only fetch the headers.
+        handle.nobody(true)?;
+        handle.perform().context("error fetching headers")?;
+
+        let code = handle.response_code()?;
+        match code {
+            0 | 200..=299 => {}
+            _ => return Err(DownloadError::HttpStatus(code).into()),
+        };
+
+        let len = handle.content_length_download()?;
+        Ok((len >= 0.0).then_some(len as u64))
+    }
+
+    /// Uses the proxy from the settings for `url`, if any; libcurl reads the
+    /// proxy environment variables itself.
+    fn set_proxy(handle: &mut Easy, url: &Url, proxies: &Proxies) -> Result<()> {
+        if let Some(proxy) = proxies.for_url(url) {
+            handle.proxy(proxy.as_str())?;
+            // Keep bypassing the right hosts with a handle reused for another URL
+            handle.noproxy(proxies.no_proxy.as_deref().unwrap_or(""))?;
+        }
+        Ok(())
+    }
 }
 
 #[cfg(any(feature = "reqwest-rustls-tls", feature = "reqwest-native-tls"))]
 mod reqwest_be {
     use std::io;
     #[cfg(any(feature = "reqwest-rustls-tls", feature = "reqwest-native-tls"))]
-    use std::sync::{Arc, OnceLock};
-    use std::time::Duration;
+    use std::sync::{Arc, Mutex};
 
     #[cfg(all(feature = "reqwest-rustls-tls", not(target_os = "android")))]
     use crate::anchors::RUSTUP_TRUST_ANCHORS;
     use anyhow::{Context, anyhow};
-    use reqwest::{Client, ClientBuilder, Proxy, Response, header};
+    use reqwest::{Client, ClientBuilder, Proxy, Response, StatusCode, header, redirect};
     #[cfg(feature = "reqwest-rustls-tls")]
     use rustls::crypto::aws_lc_rs;
     #[cfg(feature = "reqwest-rustls-tls")]
     use rustls_platform_verifier::Verifier;
     use tokio_stream::StreamExt;
+    use tracing::debug;
     use url::Url;
 
-    use super::{DownloadError, Event};
+    use super::{ClientOptions, Condition, DownloadError, Event, Throttle};
 
     pub(super) async fn download(
         url: &Url,
         resume_from: u64,
+        condition: Option<Condition<'_>>,
         callback: &dyn Fn(Event<'_>) -> anyhow::Result<()>,
         client: &Client,
+        mut throttle: Option<Throttle>,
     ) -> anyhow::Result<()> {
         // Short-circuit reqwest for the "file:" URL scheme
         if download_from_file_url(url, resume_from, callback)? {
             return Ok(());
         }
 
-        let res = request(url, resume_from, client)
+        let res = request(url, resume_from, condition, client)
             .await
             .context("error downloading file")?;
 
@@ -592,6 +1413,23 @@ mod reqwest_be {
             return Err(anyhow!(DownloadError::HttpStatus(u32::from(code))));
         }
 
+        let partial = res.status() == StatusCode::PARTIAL_CONTENT;
+        let resume_from = match resume_from {
+            // Either ranges are unsupported or the file changed (see `If-Range`).
+            1.. if !partial => {
+                callback(Event::DownloadRestarted)?;
+                0
+            }
+            _ => resume_from,
+        };
+
+        if let Some(validator) = [header::ETAG, header::LAST_MODIFIED]
+            .iter()
+            .find_map(|name| res.headers().get(name)?.to_str().ok())
+        {
+            callback(Event::DownloadValidatorReceived(validator))?;
+        }
+
         if let Some(len) = res.content_length() {
             let len = len + resume_from;
             callback(Event::DownloadContentLengthReceived(len))?;
@@ -601,25 +1439,153 @@ mod reqwest_be {
         while let Some(item) = stream.next().await {
             let bytes = item.map_err(DownloadError::Reqwest)?;
             callback(Event::DownloadDataReceived(&bytes))?;
+            if let Some(delay) = throttle.as_mut().map(|t| t.delay(bytes.len())) {
+                tokio::time::sleep(delay).await;
+            }
+        }
+        Ok(())
+    }
+
+    pub(super) async fn content_length(url: &Url, client: &Client) -> anyhow::Result<Option<u64>> {
+        let res = client
+            .head(url.as_str())
+            .send()
+            .await
+            .context("error fetching headers")?;
+
+        if !res.status().is_success() {
+            let code: u16 = res.status().into();
+            return Err(anyhow!(DownloadError::HttpStatus(u32::from(code))));
+        }
+
+        // `Response::content_length()` reports the size of the (empty) body for `HEAD`.
+        Ok(res
+            .headers()
+            .get(header::CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse().ok()))
+    }
+
+    /// The size and validator of the file at `url`, if the server can send
+    /// parts of it.
+    pub(super) async fn ranged_length(
+        url: &Url,
+        client: &Client,
+    ) -> anyhow::Result<Option<(u64, Option<String>)>> {
+        let res = client
+            .head(url.as_str())
+            .send()
+            .await
+            .context("error fetching headers")?;
+        let headers = res.headers();
+        let accepts_ranges = headers
+            .get(header::ACCEPT_RANGES)
+            .is_some_and(|v| v.as_bytes() == b"bytes");
+        let len = headers
+            .get(header::CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse().ok());
+        let validator = [header::ETAG, header::LAST_MODIFIED]
+            .iter()
+            .find_map(|name| headers.get(name)?.to_str().ok())
+            .map(str::to_owned);
+
+        Ok(match len {
+            Some(len) if res.status().is_success() && accepts_ranges => Some((len, validator)),
+            _ => None,
+        })
+    }
+
+    /// Downloads the bytes of `url` in `range`, failing if the file no
+    /// longer has `validator`.
+    pub(super) async fn download_range(
+        url: &Url,
+        range: std::ops::Range<u64>,
+        validator: Option<&str>,
+        callback: &dyn Fn(Event<'_>) -> anyhow::Result<()>,
+        client: &Client,
+        mut throttle: Option<Throttle>,
+    ) -> anyhow::Result<()> {
+        let mut req = client.get(url.as_str()).header(
+            header::RANGE,
+            format!("bytes={}-{}", range.start, range.end - 1),
+        );
+        if let Some(validator) = validator {
+            req = req.header(header::IF_RANGE, validator);
+        }
+        let res = req.send().await.context("error downloading file")?;
+
+        match res.status() {
+            StatusCode::PARTIAL_CONTENT => {}
+            // The file changed since its size was fetched
+            status if status.is_success() => {
+                return Err(anyhow!(DownloadError::Message(format!(
+                    "server did not send the requested range of '{url}'"
+                ))));
+            }
+            status => {
+                let code: u16 = status.into();
+                return Err(anyhow!(DownloadError::HttpStatus(u32::from(code))));
+            }
+        }
+
+        let mut received = 0;
+        let mut stream = res.bytes_stream();
+        while let Some(item) = stream.next().await {
+            let bytes = item.map_err(DownloadError::Reqwest)?;
+            received += bytes.len() as u64;
+            if received > range.end - range.start {
+                return Err(anyhow!(DownloadError::Message(format!(
+                    "server sent more than the requested range of '{url}'"
+                ))));
+            }
+            callback(Event::DownloadDataReceived(&bytes))?;
+            if let Some(delay) = throttle.as_mut().map(|t| t.delay(bytes.len())) {
+                tokio::time::sleep(delay).await;
+            }
+        }
+        if received < range.end - range.start {
+            return Err(anyhow!(DownloadError::Message(format!(
+                "server sent less than the requested range of '{url}'"
+            ))));
         }
         Ok(())
     }
 
-    fn client_generic() -> ClientBuilder {
-        Client::builder()
+    fn client_generic(options: &ClientOptions) -> ClientBuilder {
+        let proxies = options.proxies.clone();
+        let builder = Client::builder()
+            .connect_timeout(options.timeouts.connect)
+            .read_timeout(options.timeouts.read)
+            .gzip(false)
+            .proxy(Proxy::custom(move |url| {
+                env_proxy(url).or_else(|| proxies.for_url(url))
+            }))
+            .redirect(redirect::Policy::custom(|attempt| {
+                // The same limit as the default policy
+                if attempt.previous().len() >= 10 {
+                    return attempt.error("too many redirects");
+                }
+                debug!(status = %attempt.status(), to = %attempt.url(), "following redirect");
+                attempt.follow()
+            }));
+        match options.http2 {
+            // Keep the connection pooled so that concurrent downloads share it.
+            true => builder.http2_prior_knowledge(),
             // HACK: set `pool_max_idle_per_host` to `0` to avoid an issue in the underlying
             // `hyper` library that causes the `reqwest` client to hang in some cases.
             // See <https://github.com/hyperium/hyper/issues/2312> for more details.
-            .pool_max_idle_per_host(0)
-            .gzip(false)
-            .proxy(Proxy::custom(env_proxy))
+            false => builder.pool_max_idle_per_host(0),
+        }
     }
 
     #[cfg(feature = "reqwest-rustls-tls")]
-    pub(super) fn rustls_client(timeout: Duration) -> Result<&'static Client, DownloadError> {
-        // If the client is already initialized, the passed timeout is ignored.
-        if let Some(client) = CLIENT_RUSTLS_TLS.get() {
-            return Ok(client);
+    pub(super) fn rustls_client(options: ClientOptions) -> Result<Client, DownloadError> {
+        let mut cached = CLIENT_RUSTLS_TLS.lock().unwrap();
+        if let Some((t, client)) = &*cached
+            && *t == options
+        {
+            return Ok(client.clone());
         }
 
         let provider = Arc::new(aws_lc_rs::default_provider());
@@ -640,43 +1606,41 @@ mod reqwest_be {
             .with_no_client_auth();
         tls_config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
 
-        let client = client_generic()
-            .read_timeout(timeout)
+        let client = client_generic(&options)
             .use_preconfigured_tls(tls_config)
             .user_agent(super::REQWEST_RUSTLS_TLS_USER_AGENT)
             .build()
             .map_err(DownloadError::Reqwest)?;
 
-        let _ = CLIENT_RUSTLS_TLS.set(client);
-        // "The cell is guaranteed to contain a value when `set` returns, though not necessarily
-        // the one provided."
-        Ok(CLIENT_RUSTLS_TLS.get().unwrap())
+        *cached = Some((options, client.clone()));
+        Ok(client)
     }
 
+    /// The last client built, reused for as long as the requested options are unchanged.
     #[cfg(feature = "reqwest-rustls-tls")]
-    static CLIENT_RUSTLS_TLS: OnceLock<Client> = OnceLock::new();
+    static CLIENT_RUSTLS_TLS: Mutex<Option<(ClientOptions, Client)>> = Mutex::new(None);
 
     #[cfg(feature = "reqwest-native-tls")]
-    pub(super) fn native_tls_client(timeout: Duration) -> Result<&'static Client, DownloadError> {
-        // If the client is already initialized, the passed timeout is ignored.
-        if let Some(client) = CLIENT_NATIVE_TLS.get() {
-            return Ok(client);
+    pub(super) fn native_tls_client(options: ClientOptions) -> Result<Client, DownloadError> {
+        let mut cached = CLIENT_NATIVE_TLS.lock().unwrap();
+        if let Some((t, client)) = &*cached
+            && *t == options
+        {
+            return Ok(client.clone());
         }
 
-        let client = client_generic()
-            .read_timeout(timeout)
+        let client = client_generic(&options)
             .user_agent(super::REQWEST_DEFAULT_TLS_USER_AGENT)
             .build()
             .map_err(DownloadError::Reqwest)?;
 
-        let _ = CLIENT_NATIVE_TLS.set(client);
-        // "The cell is guaranteed to contain a value when `set` returns, though not necessarily
-        // the one provided."
-        Ok(CLIENT_NATIVE_TLS.get().unwrap())
+        *cached = Some((options, client.clone()));
+        Ok(client)
     }
 
+    /// The last client built, reused for as long as the requested options are unchanged.
     #[cfg(feature = "reqwest-native-tls")]
-    static CLIENT_NATIVE_TLS: OnceLock<Client> = OnceLock::new();
+    static CLIENT_NATIVE_TLS: Mutex<Option<(ClientOptions, Client)>> = Mutex::new(None);
 
     fn env_proxy(url: &Url) -> Option<Url> {
         env_proxy::for_url(url).to_url()
@@ -685,6 +1649,7 @@ mod reqwest_be {
     async fn request(
         url: &Url,
         resume_from: u64,
+        condition: Option<Condition<'_>>,
         client: &Client,
     ) -> Result<Response, DownloadError> {
         let mut req = client.get(url.as_str());
@@ -692,6 +1657,9 @@ mod reqwest_be {
         if resume_from != 0 {
             req = req.header(header::RANGE, format!("bytes={resume_from}-"));
         }
+        if let Some((name, value)) = condition.and_then(|c| c.header(resume_from)) {
+            req = req.header(name, value);
+        }
 
         Ok(req.send().await?)
     }
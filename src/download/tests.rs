@@ -42,7 +42,8 @@ mod curl {
                 &target_path,
                 true,
                 None,
-                Duration::from_secs(180),
+                Duration::from_secs(180).into(),
+                None,
             )
             .await
             .expect("Test download failed");
@@ -86,11 +87,13 @@ mod curl {
                                 received_in_callback.lock().unwrap().push(*b);
                             }
                         }
+                        Event::DownloadRestarted | Event::DownloadValidatorReceived(_) => {}
                     }
 
                     Ok(())
                 }),
-                Duration::from_secs(180),
+                Duration::from_secs(180).into(),
+                None,
             )
             .await
             .expect("Test download failed");
@@ -111,14 +114,47 @@ mod reqwest {
     use std::sync::Mutex;
     use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
     use std::thread;
-    use std::time::Duration;
+    use std::time::{Duration, Instant};
 
     use env_proxy::for_url;
     use reqwest::{Client, Proxy};
     use url::Url;
 
-    use super::{scrub_env, serve_file, tmp_dir, write_file};
-    use crate::download::{Backend, Event, TlsBackend};
+    use super::{TEST_ETAG, scrub_env, serve_file, tmp_dir, write_file};
+    use crate::download::{Backend, Event, Throttle, TlsBackend};
+
+    #[test]
+    fn throttle_lets_a_burst_through_then_paces() {
+        let mut throttle = Throttle::new(1000);
+        assert_eq!(throttle.delay(1000), Duration::ZERO);
+        let delay = throttle.delay(500);
+        assert!(delay > Duration::from_millis(450) && delay <= Duration::from_millis(500));
+    }
+
+    #[tokio::test]
+    async fn bandwidth_limit_slows_down_the_download() {
+        let _guard = scrub_env().await;
+        let tmpdir = tmp_dir();
+        let target_path = tmpdir.path().join("downloaded");
+        let addr = serve_file(vec![b'x'; 1500]);
+        let from_url = format!("http://{addr}").parse().unwrap();
+
+        let start = Instant::now();
+        Backend::Reqwest(TlsBackend::NativeTls)
+            .download_to_path(
+                &from_url,
+                &target_path,
+                false,
+                None,
+                Duration::from_secs(180).into(),
+                Some(1000),
+            )
+            .await
+            .expect("Test download failed");
+
+        assert!(start.elapsed() >= Duration::from_millis(450));
+        assert_eq!(std::fs::read(&target_path).unwrap().len(), 1500);
+    }
 
     // Tests for correctly retrieving the proxy (host, port) tuple from $https_proxy
     #[tokio::test]
@@ -198,7 +234,8 @@ mod reqwest {
                 &target_path,
                 true,
                 None,
-                Duration::from_secs(180),
+                Duration::from_secs(180).into(),
+                None,
             )
             .await
             .expect("Test download failed");
@@ -242,11 +279,13 @@ mod reqwest {
                                 received_in_callback.lock().unwrap().push(*b);
                             }
                         }
+                        Event::DownloadRestarted | Event::DownloadValidatorReceived(_) => {}
                     }
 
                     Ok(())
                 }),
-                Duration::from_secs(180),
+                Duration::from_secs(180).into(),
+                None,
             )
             .await
             .expect("Test download failed");
@@ -258,6 +297,85 @@ mod reqwest {
         assert_eq!(std::fs::read_to_string(&target_path).unwrap(), "12345");
     }
 
+    #[tokio::test]
+    async fn partial_download_of_a_changed_file_is_restarted() {
+        let _guard = scrub_env().await;
+        let tmpdir = tmp_dir();
+        let target_path = tmpdir.path().join("downloaded");
+        let validator_path = tmpdir.path().join("downloaded.validator");
+        write_file(&target_path, "xxx");
+        write_file(&validator_path, "\"outdated\"");
+
+        let addr = serve_file(b"12345".to_vec());
+        let from_url = format!("http://{addr}").parse().unwrap();
+
+        let restarted = AtomicBool::new(false);
+        Backend::Reqwest(TlsBackend::NativeTls)
+            .download_to_path(
+                &from_url,
+                &target_path,
+                true,
+                Some(&|msg| {
+                    if let Event::DownloadRestarted = msg {
+                        restarted.store(true, Ordering::SeqCst);
+                    }
+                    Ok(())
+                }),
+                Duration::from_secs(180).into(),
+                None,
+            )
+            .await
+            .expect("Test download failed");
+
+        assert!(restarted.into_inner());
+        assert_eq!(std::fs::read_to_string(&target_path).unwrap(), "12345");
+        assert!(!validator_path.exists(), "validator should be cleaned up");
+    }
+
+    #[tokio::test]
+    async fn validator_is_saved_for_resumable_downloads() {
+        let _guard = scrub_env().await;
+        let tmpdir = tmp_dir();
+        let target_path = tmpdir.path().join("downloaded");
+
+        let addr = serve_file(b"12345".to_vec());
+        let from_url = format!("http://{addr}").parse().unwrap();
+
+        let validator = Mutex::new(None);
+        Backend::Reqwest(TlsBackend::NativeTls)
+            .download_to_path(
+                &from_url,
+                &target_path,
+                true,
+                Some(&|msg| {
+                    if let Event::DownloadValidatorReceived(v) = msg {
+                        *validator.lock().unwrap() = Some(v.to_owned());
+                    }
+                    Ok(())
+                }),
+                Duration::from_secs(180).into(),
+                None,
+            )
+            .await
+            .expect("Test download failed");
+
+        assert_eq!(validator.into_inner().unwrap().as_deref(), Some(TEST_ETAG));
+    }
+
+    #[tokio::test]
+    async fn content_length_is_read_without_downloading() {
+        let _guard = scrub_env().await;
+        let addr = serve_file(b"12345".to_vec());
+
+        let from_url = format!("http://{addr}").parse().unwrap();
+        let len = Backend::Reqwest(TlsBackend::NativeTls)
+            .content_length(&from_url, Duration::from_secs(180).into())
+            .await
+            .expect("Test request failed");
+
+        assert_eq!(len, Some(5));
+    }
+
     #[tokio::test]
     async fn network_failure_does_not_delete_partial_file() {
         let _guard = scrub_env().await;
@@ -267,7 +385,14 @@ mod reqwest {
 
         let from_url = "http://240.0.0.0:1080".parse().unwrap();
         Backend::Reqwest(TlsBackend::NativeTls)
-            .download_to_path(&from_url, &target_path, true, None, Duration::from_secs(1))
+            .download_to_path(
+                &from_url,
+                &target_path,
+                true,
+                None,
+                Duration::from_secs(1).into(),
+                None,
+            )
             .await
             .expect_err("download should fail with a connect error");
 
@@ -276,6 +401,136 @@ mod reqwest {
     }
 }
 
+#[test]
+fn network_config_overrides_download_timeout() {
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    use super::{Timeouts, timeouts};
+    use crate::installer::NetworkConfig;
+    use crate::process::TestProcess;
+
+    let vars = HashMap::from([("RUSTUP_DOWNLOAD_TIMEOUT".to_owned(), "7".to_owned())]);
+    let tp = TestProcess::with_vars(vars);
+    assert_eq!(
+        timeouts(None, &tp.process).unwrap(),
+        Duration::from_secs(7).into()
+    );
+
+    let network = NetworkConfig {
+        connect_timeout: Duration::from_secs(1),
+        read_timeout: Duration::from_secs(2),
+        ..NetworkConfig::default()
+    };
+    assert_eq!(
+        timeouts(Some(&network), &tp.process).unwrap(),
+        Timeouts {
+            connect: Duration::from_secs(1),
+            read: Duration::from_secs(2),
+        }
+    );
+}
+
+#[cfg(feature = "reqwest-rustls-tls")]
+#[tokio::test]
+async fn large_downloads_are_fetched_in_parts() {
+    use sha2::{Digest, Sha256};
+
+    use super::download_file_with_resume;
+    use crate::installer::DownloadConfig;
+    use crate::process::TestProcess;
+
+    let _guard = scrub_env().await;
+    let tmpdir = tmp_dir();
+    let target_path = tmpdir.path().join("downloaded");
+    // Large enough for three parts, with a short last one
+    let contents = (0..12 * 1024 * 1024 + 5)
+        .map(|i: u32| (i % 251) as u8)
+        .collect::<Vec<_>>();
+    let addr = serve_file(contents.clone());
+    let from_url = format!("http://{addr}").parse().unwrap();
+
+    let tp = TestProcess::default();
+    let download = DownloadConfig {
+        download_parts: 8,
+        ..DownloadConfig::default()
+    };
+    let mut hasher = Sha256::new();
+    download_file_with_resume(
+        &from_url,
+        &target_path,
+        Some(&mut hasher),
+        true,
+        None,
+        None,
+        Some(&download),
+        &tp.process,
+    )
+    .await
+    .expect("download in parts failed");
+
+    assert!(fs::read(&target_path).unwrap() == contents);
+    assert_eq!(hasher.finalize(), Sha256::digest(&contents));
+}
+
+#[cfg(feature = "reqwest-rustls-tls")]
+#[test]
+fn http2_is_only_used_for_https() {
+    use std::time::Duration;
+
+    use super::{ClientOptions, client_options};
+    use crate::installer::DownloadConfig;
+    use crate::process::TestProcess;
+
+    let tp = TestProcess::default();
+    let download = DownloadConfig {
+        use_http2: true,
+        ..DownloadConfig::default()
+    };
+    let options = client_options(None, Some(&download), &tp.process).unwrap();
+    let https = url::Url::parse("https://static.rust-lang.org/dist/").unwrap();
+    let http = url::Url::parse("http://127.0.0.1:8080/dist/").unwrap();
+    assert!(options.for_url(&https).http2);
+    assert!(!options.for_url(&http).http2);
+
+    let options = client_options(None, None, &tp.process).unwrap();
+    assert_eq!(options, ClientOptions::from(Duration::from_secs(180)));
+    assert!(!options.for_url(&https).http2);
+}
+
+#[cfg(feature = "reqwest-rustls-tls")]
+#[tokio::test]
+async fn cached_download_is_reused_when_not_modified() {
+    use super::{download_file_cached, validator_path};
+    use crate::process::TestProcess;
+
+    let _guard = scrub_env().await;
+    let tmpdir = tmp_dir();
+    let cache = tmpdir.path().join("cache").join("manifest");
+    let addr = serve_file(b"fresh".to_vec());
+    let from_url = format!("http://{addr}").parse().unwrap();
+    let tp = TestProcess::default();
+
+    let first = tmpdir.path().join("first");
+    download_file_cached(&from_url, &first, &cache, None, None, None, &tp.process)
+        .await
+        .expect("first download failed");
+    assert_eq!(fs::read_to_string(&first).unwrap(), "fresh");
+    assert_eq!(fs::read_to_string(&cache).unwrap(), "fresh");
+    assert_eq!(
+        fs::read_to_string(validator_path(&cache)).unwrap(),
+        TEST_ETAG
+    );
+
+    // The server answers `304 Not Modified`, so the cached copy must be used
+    write_file(&cache, "cached");
+    let second = tmpdir.path().join("second");
+    download_file_cached(&from_url, &second, &cache, None, None, None, &tp.process)
+        .await
+        .expect("cached download failed");
+    assert_eq!(fs::read_to_string(&second).unwrap(), "cached");
+}
+
 pub fn tmp_dir() -> TempDir {
     tempfile::Builder::new()
         .prefix("rustup-download-test-")
@@ -345,25 +600,47 @@ pub fn serve_file(contents: Vec<u8>) -> SocketAddr {
     addr.unwrap()
 }
 
+/// The `ETag` sent by the test server for every file.
+const TEST_ETAG: &str = "\"rustup-test\"";
+
 fn serve_contents(
     req: Request<hyper::body::Incoming>,
     contents: Vec<u8>,
 ) -> hyper::Response<Full<Bytes>> {
+    if req
+        .headers()
+        .get(hyper::header::IF_NONE_MATCH)
+        .is_some_and(|v| v == TEST_ETAG)
+    {
+        return hyper::Response::builder()
+            .status(hyper::StatusCode::NOT_MODIFIED)
+            .header(hyper::header::ETAG, TEST_ETAG)
+            .body(Full::new(Bytes::new()))
+            .unwrap();
+    }
+
     let mut range_header = None;
-    let (status, body) = if let Some(range) = req.headers().get(hyper::header::RANGE) {
-        // extract range "bytes={start}-"
+    let if_range = req.headers().get(hyper::header::IF_RANGE);
+    let range = req
+        .headers()
+        .get(hyper::header::RANGE)
+        .filter(|_| if_range.is_none_or(|v| v == TEST_ETAG));
+    let (status, body) = if let Some(range) = range {
+        // extract range "bytes={start}-" or "bytes={start}-{end}"
         let range = range.to_str().expect("unexpected Range header");
         assert!(range.starts_with("bytes="));
         let range = range.trim_start_matches("bytes=");
-        assert!(range.ends_with('-'));
-        let range = range.trim_end_matches('-');
-        assert_eq!(range.split('-').count(), 1);
-        let start: u64 = range.parse().expect("unexpected Range header");
-
-        range_header = Some(format!("bytes {}-{len}/{len}", start, len = contents.len()));
+        let (start, end) = range.split_once('-').expect("unexpected Range header");
+        let start: usize = start.parse().expect("unexpected Range header");
+        let end = match end {
+            "" => contents.len(),
+            end => end.parse::<usize>().expect("unexpected Range header") + 1,
+        };
+
+        range_header = Some(format!("bytes {}-{}/{}", start, end - 1, contents.len()));
         (
             hyper::StatusCode::PARTIAL_CONTENT,
-            contents[start as usize..].to_vec(),
+            contents[start..end].to_vec(),
         )
     } else {
         (hyper::StatusCode::OK, contents)
@@ -372,6 +649,8 @@ fn serve_contents(
     let mut res = hyper::Response::builder()
         .status(status)
         .header(hyper::header::CONTENT_LENGTH, body.len())
+        .header(hyper::header::ACCEPT_RANGES, "bytes")
+        .header(hyper::header::ETAG, TEST_ETAG)
         .body(Full::new(Bytes::from(body)))
         .unwrap();
     if let Some(range) = range_header {
@@ -408,3 +687,54 @@ async fn scrub_env() -> tokio::sync::MutexGuard<'static, ()> {
 
     guard
 }
+
+#[test]
+fn proxies_from_settings_give_way_to_the_environment() {
+    use std::collections::HashMap;
+
+    use super::proxies;
+    use crate::process::TestProcess;
+
+    let tmpdir = tmp_dir();
+    fs::write(
+        tmpdir.path().join("settings.toml"),
+        r#"version = "12"
+proxy = "http://proxy.example:3128"
+no_proxy = "internal.example, localhost"
+"#,
+    )
+    .unwrap();
+    let home = tmpdir.path().to_str().unwrap().to_owned();
+    let url = |s: &str| url::Url::parse(s).unwrap();
+
+    let tp = TestProcess::with_vars(HashMap::from([("RUSTUP_HOME".to_owned(), home.clone())]));
+    let settings = proxies(&tp.process).unwrap();
+    let proxy = Some(url("http://proxy.example:3128"));
+    assert_eq!(
+        settings.for_url(&url("https://static.rust-lang.org/dist/")),
+        proxy
+    );
+    assert_eq!(settings.for_url(&url("http://example.com/")), proxy);
+    assert_eq!(
+        settings.for_url(&url("https://dist.internal.example/")),
+        None
+    );
+    assert_eq!(settings.for_url(&url("http://localhost:8080/")), None);
+    assert_eq!(settings.for_url(&url("file:///dist/")), None);
+
+    let tp = TestProcess::with_vars(HashMap::from([
+        ("RUSTUP_HOME".to_owned(), home),
+        (
+            "HTTPS_PROXY".to_owned(),
+            "http://other.example:8080".to_owned(),
+        ),
+        ("NO_PROXY".to_owned(), "example.com".to_owned()),
+    ]));
+    let settings = proxies(&tp.process).unwrap();
+    assert_eq!(
+        settings.for_url(&url("https://static.rust-lang.org/dist/")),
+        None
+    );
+    assert_eq!(settings.for_url(&url("http://example.com/")), None);
+    assert_eq!(settings.for_url(&url("http://localhost:8080/")), proxy);
+}
@@ -5,8 +5,10 @@ use std::fmt::Debug;
 use std::io;
 use std::io::Write;
 use std::path::PathBuf;
+use std::time::Duration;
 
 use platforms::Platform;
+use serde::ser::{Serialize, SerializeStruct, Serializer};
 use thiserror::Error as ThisError;
 use url::Url;
 
@@ -15,6 +17,7 @@ use crate::{
         Channel, TargetTriple, ToolchainDesc,
         manifest::{Component, Manifest},
     },
+    download,
     toolchain::{PathBasedToolchainName, ToolchainName},
 };
 
@@ -31,12 +34,33 @@ pub enum RustupError {
     IncompletePartialFile,
     #[error("component download failed for {0}")]
     ComponentDownloadFailed(String),
+    #[error(
+        "downloading {component} took longer than {}s; try again with `--retry` or a longer `--timeout`",
+        .timeout.as_secs()
+    )]
+    ComponentDownloadTimedOut {
+        component: String,
+        timeout: Duration,
+    },
     #[error("failure removing component '{name}', directory does not exist: '{}'", .path.display())]
     ComponentMissingDir { name: String, path: PathBuf },
     #[error("failure removing component '{name}', directory does not exist: '{}'", .path.display())]
     ComponentMissingFile { name: String, path: PathBuf },
     #[error("could not create {name} directory: '{}'", .path.display())]
     CreatingDirectory { name: &'static str, path: PathBuf },
+    #[error(
+        "unknown component{} for target '{target}': {}",
+        if .components.len() > 1 { "s" } else { "" },
+        .components.join(", ")
+    )]
+    ComponentNotAvailable {
+        components: Vec<String>,
+        target: TargetTriple,
+    },
+    #[error("'{binary}' is not installed for the toolchain '{toolchain}'")]
+    BinaryNotInstalled { binary: String, toolchain: String },
+    #[error("no space left on the device")]
+    DiskFull { source: anyhow::Error },
     #[error("invalid toolchain name: '{0}'")]
     InvalidToolchainName(String),
     #[error("could not create link from '{}' to '{}'", .src.display(), .dest.display())]
@@ -52,6 +76,12 @@ pub enum RustupError {
         expected: String,
         calculated: String,
     },
+    #[error("signature verification failed for '{url}': {reason}")]
+    SignatureVerificationFailed { url: String, reason: String },
+    #[error(
+        "RUSTUP_VERIFY_SIGNATURES is set, but RUSTUP_SIGNATURE_KEYRING does not name a keyring to verify signatures against"
+    )]
+    SignatureKeyringMissing,
     #[error("failed to install component: '{name}', detected conflict: '{}'", .path.display())]
     ComponentConflict { name: String, path: PathBuf },
     #[error("toolchain '{0}' does not support components")]
@@ -60,10 +90,17 @@ pub enum RustupError {
     ComponentsUnsupportedV1(String),
     #[error("component manifest for '{0}' is corrupt")]
     CorruptComponent(String),
+    #[error(
+        "installed files do not match the packages they were installed from: {}",
+        .files.iter().map(|f| format!("'{}'", f.display())).collect::<Vec<_>>().join(", ")
+    )]
+    VerificationFailed { files: Vec<PathBuf> },
     #[error("could not download file from '{url}' to '{}'", .path.display())]
     DownloadingFile { url: Url, path: PathBuf },
     #[error("could not download file from '{url}' to '{}'", .path.display())]
     DownloadNotExists { url: Url, path: PathBuf },
+    #[error("network operation timed out")]
+    NetworkTimeout { source: anyhow::Error },
     #[error("Missing manifest in toolchain '{}'", .0)]
     MissingManifest(ToolchainDesc),
     #[error("server sent a broken manifest: missing package for component {0}")]
@@ -91,6 +128,27 @@ pub enum RustupError {
         manifest: Manifest,
         toolchain: String,
     },
+    #[error("'{url}' is not cached, and cannot be downloaded in offline mode")]
+    Offline { url: String },
+    #[error(
+        "another rustup process is still running; gave up waiting for it after {timeout} seconds (lock file: '{}')",
+        .path.display()
+    )]
+    LockTimeout { path: PathBuf, timeout: u64 },
+    #[error(
+        "'{}' requires rustup {required} or later, but this is rustup {current}\n\
+        help: run `rustup self update`, see https://rust-lang.github.io/rustup/basics.html#keeping-rustup-up-to-date",
+        .path.display()
+    )]
+    RustupTooOld {
+        path: PathBuf,
+        required: String,
+        current: String,
+    },
+    #[error("toolchain '{0}' is already installed")]
+    ToolchainAlreadyInstalled(String),
+    #[error("rustup {operation} exited with code {code}")]
+    OperationFailed { operation: &'static str, code: i32 },
     #[error("command failed: '{}'", PathBuf::from(.name).display())]
     RunningCommand { name: OsString },
     #[error(
@@ -117,6 +175,8 @@ pub enum RustupError {
         name: ToolchainName,
         is_active: bool,
     },
+    #[error("refusing to remove the default toolchain '{0}'")]
+    ToolchainIsDefault(ToolchainName),
     #[error("path '{0}' not found")]
     PathToolchainNotInstalled(PathBasedToolchainName),
     #[error(
@@ -159,6 +219,11 @@ pub enum RustupError {
         target: TargetTriple,
         suggestion: Option<String>,
     },
+    #[error("refusing to remove the host target '{target}' of toolchain '{desc}'")]
+    TargetIsHost {
+        desc: ToolchainDesc,
+        target: TargetTriple,
+    },
     #[error(
         "rustup executable proxies don't seem to work\n\
         help: this might be a bug in rustup, please open a new issue here:\n\
@@ -173,6 +238,108 @@ pub enum RustupError {
     WritingFile { name: &'static str, path: PathBuf },
     #[error("I/O Error")]
     IOError(#[from] io::Error),
+    #[error(transparent)]
+    Other(anyhow::Error),
+}
+
+/// Recovers a typed error from an [`anyhow::Error`] chain.
+///
+/// Running out of disk space and network timeouts are recognized anywhere in
+/// the chain; otherwise the outermost `RustupError` is kept, and anything else
+/// becomes [`RustupError::Other`].
+impl From<anyhow::Error> for RustupError {
+    fn from(err: anyhow::Error) -> Self {
+        let io_kind = err.chain().find_map(|cause| {
+            if download::is_timeout(cause) {
+                return Some(io::ErrorKind::TimedOut);
+            }
+            cause
+                .downcast_ref::<io::Error>()
+                .map(io::Error::kind)
+                .filter(|k| matches!(k, io::ErrorKind::StorageFull | io::ErrorKind::TimedOut))
+        });
+        match io_kind {
+            Some(io::ErrorKind::StorageFull) => Self::DiskFull { source: err },
+            Some(_) => Self::NetworkTimeout { source: err },
+            None => err.downcast::<Self>().unwrap_or_else(Self::Other),
+        }
+    }
+}
+
+impl RustupError {
+    /// The name of the variant, e.g. `ToolchainNotInstalled`, for matching by
+    /// callers that only see the serialized error.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::BrokenPartialFile => "BrokenPartialFile",
+            Self::IncompletePartialFile => "IncompletePartialFile",
+            Self::ComponentDownloadFailed(..) => "ComponentDownloadFailed",
+            Self::ComponentDownloadTimedOut { .. } => "ComponentDownloadTimedOut",
+            Self::ComponentMissingDir { .. } => "ComponentMissingDir",
+            Self::ComponentMissingFile { .. } => "ComponentMissingFile",
+            Self::CreatingDirectory { .. } => "CreatingDirectory",
+            Self::ComponentNotAvailable { .. } => "ComponentNotAvailable",
+            Self::BinaryNotInstalled { .. } => "BinaryNotInstalled",
+            Self::DiskFull { .. } => "DiskFull",
+            Self::InvalidToolchainName(..) => "InvalidToolchainName",
+            Self::LinkingFile { .. } => "LinkingFile",
+            Self::LocatingWorkingDir => "LocatingWorkingDir",
+            Self::SettingPermissions { .. } => "SettingPermissions",
+            Self::ChecksumFailed { .. } => "ChecksumFailed",
+            Self::SignatureVerificationFailed { .. } => "SignatureVerificationFailed",
+            Self::SignatureKeyringMissing => "SignatureKeyringMissing",
+            Self::ComponentConflict { .. } => "ComponentConflict",
+            Self::ComponentsUnsupported(..) => "ComponentsUnsupported",
+            Self::ComponentsUnsupportedV1(..) => "ComponentsUnsupportedV1",
+            Self::CorruptComponent(..) => "CorruptComponent",
+            Self::VerificationFailed { .. } => "VerificationFailed",
+            Self::DownloadingFile { .. } => "DownloadingFile",
+            Self::DownloadNotExists { .. } => "DownloadNotExists",
+            Self::NetworkTimeout { .. } => "NetworkTimeout",
+            Self::MissingManifest(..) => "MissingManifest",
+            Self::MissingPackageForComponent(..) => "MissingPackageForComponent",
+            Self::ReadingDirectory { .. } => "ReadingDirectory",
+            Self::ReadingFile { .. } => "ReadingFile",
+            Self::ParsingFile { .. } => "ParsingFile",
+            Self::RemovingDirectory { .. } => "RemovingDirectory",
+            Self::RemovingFile { .. } => "RemovingFile",
+            Self::RenamingFile { .. } => "RenamingFile",
+            Self::RequestedComponentsUnavailable { .. } => "RequestedComponentsUnavailable",
+            Self::Offline { .. } => "Offline",
+            Self::LockTimeout { .. } => "LockTimeout",
+            Self::RustupTooOld { .. } => "RustupTooOld",
+            Self::ToolchainAlreadyInstalled(..) => "ToolchainAlreadyInstalled",
+            Self::OperationFailed { .. } => "OperationFailed",
+            Self::RunningCommand { .. } => "RunningCommand",
+            Self::ToolchainIncompatible { .. } => "ToolchainIncompatible",
+            Self::ToolchainNotInstallable(..) => "ToolchainNotInstallable",
+            Self::ToolchainNotInstalled { .. } => "ToolchainNotInstalled",
+            Self::ToolchainIsDefault(..) => "ToolchainIsDefault",
+            Self::PathToolchainNotInstalled(..) => "PathToolchainNotInstalled",
+            Self::ToolchainNotSelected(..) => "ToolchainNotSelected",
+            Self::UnknownComponent { .. } => "UnknownComponent",
+            Self::UnavailableTarget { .. } => "UnavailableTarget",
+            Self::UnknownTarget { .. } => "UnknownTarget",
+            Self::TargetNotInstalled { .. } => "TargetNotInstalled",
+            Self::TargetIsHost { .. } => "TargetIsHost",
+            Self::BrokenProxy => "BrokenProxy",
+            Self::UnknownMetadataVersion(..) => "UnknownMetadataVersion",
+            Self::UnsupportedVersion(..) => "UnsupportedVersion",
+            Self::WritingFile { .. } => "WritingFile",
+            Self::IOError(..) => "IOError",
+            Self::Other(..) => "Other",
+        }
+    }
+}
+
+/// Errors are serialized as their [`kind`](RustupError::kind) and message.
+impl Serialize for RustupError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut s = serializer.serialize_struct("RustupError", 2)?;
+        s.serialize_field("kind", &self.kind())?;
+        s.serialize_field("message", &self.to_string())?;
+        s.end()
+    }
 }
 
 fn suggest_message(suggestion: &Option<String>) -> String {
@@ -242,3 +409,53 @@ fn component_unavailable_msg(cs: &[Component], manifest: &Manifest, toolchain: &
 
     String::from_utf8(buf).unwrap()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serialized_error_has_kind_and_message() {
+        let err = RustupError::ToolchainNotSelected("cargo".to_owned());
+        let json = serde_json::to_value(&err).unwrap();
+        assert_eq!(json["kind"], "ToolchainNotSelected");
+        assert_eq!(json["message"], err.to_string());
+        assert_eq!(RustupError::BrokenProxy.kind(), "BrokenProxy");
+        let err = RustupError::OperationFailed {
+            operation: "install",
+            code: 1,
+        };
+        assert_eq!(err.kind(), "OperationFailed");
+    }
+
+    #[test]
+    fn typed_errors_are_recovered_from_anyhow() {
+        use anyhow::Context;
+
+        let err = Err::<(), _>(RustupError::BrokenProxy)
+            .context("could not run the proxy")
+            .unwrap_err();
+        assert!(matches!(RustupError::from(err), RustupError::BrokenProxy));
+
+        let err = Err::<(), _>(io::Error::from(io::ErrorKind::StorageFull))
+            .context(RustupError::WritingFile {
+                name: "settings",
+                path: PathBuf::from("settings.toml"),
+            })
+            .unwrap_err();
+        assert!(matches!(
+            RustupError::from(err),
+            RustupError::DiskFull { .. }
+        ));
+
+        let err = anyhow::Error::from(io::Error::from(io::ErrorKind::TimedOut));
+        assert!(matches!(
+            RustupError::from(err),
+            RustupError::NetworkTimeout { .. }
+        ));
+
+        let err = RustupError::from(anyhow::anyhow!("something else"));
+        assert!(matches!(err, RustupError::Other(_)));
+        assert_eq!(err.to_string(), "something else");
+    }
+}
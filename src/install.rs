@@ -7,9 +7,9 @@ use tracing::debug;
 
 use crate::{
     config::Cfg,
-    dist::{DistOptions, prefix::InstallPrefix},
+    dist::{DistOptions, component::Components, prefix::InstallPrefix, temp},
     errors::RustupError,
-    toolchain::{CustomToolchainName, LocalToolchainName, Toolchain},
+    toolchain::{self, CustomToolchainName, InstallMetadata, LocalToolchainName, Toolchain},
     utils,
 };
 
@@ -20,6 +20,8 @@ pub(crate) enum UpdateStatus {
     Unchanged,
 }
 
+// Only ever constructed once per install, so the size is of no concern.
+#[allow(clippy::large_enum_variant)]
 pub(crate) enum InstallMethod<'cfg, 'a> {
     Copy {
         src: &'a Path,
@@ -43,6 +45,7 @@ impl InstallMethod<'_, '_> {
         let _ = rayon::ThreadPoolBuilder::new()
             .num_threads(self.cfg().process.io_thread_count()?)
             .build_global();
+        let _lock = self.cfg().lock_home_async().await?;
         match &self {
             InstallMethod::Copy { .. }
             | InstallMethod::Link { .. }
@@ -53,8 +56,12 @@ impl InstallMethod<'_, '_> {
             _ => debug!("updating existing install for '{}'", self.dest_basename()),
         }
 
-        debug!("toolchain directory: {}", self.dest_path().display());
-        let updated = self.run(&self.dest_path()).await?;
+        let dest_path = self.dest_path();
+        debug!("toolchain directory: {}", dest_path.display());
+        let updated = match self.is_staged(&dest_path) {
+            true => self.run_staged(&dest_path).await?,
+            false => self.run(&dest_path).await?,
+        };
 
         let status = match updated {
             false => {
@@ -98,8 +105,19 @@ impl InstallMethod<'_, '_> {
                 utils::copy_dir(src, path)?;
                 Ok(true)
             }
-            InstallMethod::Link { src, .. } => {
-                utils::symlink_dir(src, path)?;
+            InstallMethod::Link { src, dest, cfg } => {
+                let link_file = cfg.toolchain_link_file(dest);
+                match toolchain::link_dir(src, path) {
+                    Ok(()) => utils::ensure_file_removed("toolchain link", &link_file)?,
+                    // Fall back to a link file where directories cannot be linked
+                    Err(e) => {
+                        debug!("{e:#}; linking the toolchain with a file instead");
+                        let Some(src) = src.to_str() else {
+                            return Err(e);
+                        };
+                        utils::write_file("toolchain link", &link_file, src)?;
+                    }
+                }
                 Ok(true)
             }
             InstallMethod::Dist(opts) => {
@@ -108,6 +126,19 @@ impl InstallMethod<'_, '_> {
 
                 if let Some(hash) = maybe_new_hash {
                     utils::write_file("update hash", &opts.update_hash, &hash)?;
+                    if !opts.exists {
+                        let components = Components::open(prefix.clone())?
+                            .list()?
+                            .iter()
+                            .map(|c| c.name().to_owned())
+                            .collect();
+                        InstallMetadata::new(
+                            opts.profile().clone(),
+                            &opts.cfg.dist_root_server,
+                            components,
+                        )
+                        .write(path)?;
+                    }
                     Ok(true)
                 } else {
                     Ok(false)
@@ -116,6 +147,47 @@ impl InstallMethod<'_, '_> {
         }
     }
 
+    /// Whether the toolchain is first assembled in a staging directory.
+    ///
+    /// A link is created in a single step and updates of an existing toolchain
+    /// are covered by the component transactions, but if rustup is killed while
+    /// populating a new toolchain directory, it would be left half-installed.
+    fn is_staged(&self, path: &Path) -> bool {
+        match self {
+            InstallMethod::Copy { .. } => true,
+            InstallMethod::Link { .. } => false,
+            InstallMethod::Dist(_) => !path.exists(),
+        }
+    }
+
+    /// Installs into a staging directory below `RUSTUP_HOME/tmp`, then moves
+    /// the complete toolchain to `path`.
+    ///
+    /// The staging directory is removed if the installation fails.
+    async fn run_staged(&self, path: &Path) -> Result<bool> {
+        let cfg = self.cfg();
+        let tmp_cx = temp::Context::new(cfg.rustup_dir.join("tmp"), cfg.dist_root_server.as_str());
+        let staging_dir = tmp_cx.new_directory()?;
+        // The toolchain directory itself must not exist yet, see `DistOptions::install_into`.
+        let staging_path = staging_dir.join("toolchain");
+        debug!("staging directory: {}", staging_path.display());
+
+        if !self.run(&staging_path).await? {
+            return Ok(false);
+        }
+        if path.exists() {
+            uninstall(path)?;
+        }
+        utils::ensure_dir_exists("toolchains", &cfg.toolchains_dir)?;
+        utils::rename(
+            "toolchain",
+            &staging_path,
+            path,
+            cfg.process.permit_copy_rename(),
+        )?;
+        Ok(true)
+    }
+
     fn cfg(&self) -> &Cfg<'_> {
         match self {
             InstallMethod::Copy { cfg, .. } => cfg,
@@ -140,8 +212,11 @@ impl InstallMethod<'_, '_> {
 
     fn dest_path(&self) -> PathBuf {
         match self {
-            InstallMethod::Copy { cfg, dest, .. } => cfg.toolchain_path(&(*dest).into()),
-            InstallMethod::Link { cfg, dest, .. } => cfg.toolchain_path(&(*dest).into()),
+            // Not `toolchain_path`, which would follow link files to the
+            // directories they link
+            InstallMethod::Copy { cfg, dest, .. } | InstallMethod::Link { cfg, dest, .. } => {
+                cfg.toolchains_dir.join(dest.to_string())
+            }
             InstallMethod::Dist(DistOptions {
                 cfg,
                 toolchain: desc,
@@ -0,0 +1,3954 @@
+//! Public programmatic installation API.
+//!
+//! Exposes rustup's internal install machinery for use as a library dependency,
+//! bypassing the CLI arg-parsing layer. Callers should spawn a dedicated thread
+//! since the `*_blocking` functions create their own tokio runtime.
+//!
+//! Failures are reported as [`RustupError`], so callers can match on
+//! variants such as [`RustupError::ToolchainNotInstalled`] or
+//! [`RustupError::NetworkTimeout`]; errors without a dedicated variant are
+//! wrapped in [`RustupError::Other`].
+
+use std::collections::{HashMap, HashSet};
+use std::env::consts::EXE_SUFFIX;
+use std::fmt;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::Context;
+use chrono::{DateTime, NaiveDate, Utc};
+use clap::ValueEnum;
+use futures_util::stream::{self, StreamExt, TryStreamExt};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tracing::{Instrument, debug, info, info_span, warn};
+use url::Url;
+use xz2::{read::XzDecoder, write::XzEncoder};
+
+use crate::{
+    cli::{
+        completions, rustup_mode,
+        self_update::{self, InstallOpts, SelfUpdateMode},
+    },
+    config::{self, ActiveSource, Cfg},
+    dist::{
+        self, ComponentPolicy, DistOptions, PartialToolchainDesc, Profile, TargetTriple,
+        ToolchainDesc,
+        component::Components,
+        config::Config,
+        download::DownloadCfg,
+        manifest::{Component, ComponentStatus, CompressionKind, Manifest},
+        manifestation::DEFAULT_CONCURRENT_DOWNLOADS,
+        prefix::InstallPrefix,
+        requested_components, temp,
+    },
+    download::content_length,
+    errors::RustupError,
+    install::{InstallMethod, UpdateStatus},
+    is_proxyable_tools,
+    process::Process,
+    settings::Settings,
+    toolchain::{
+        CustomToolchainName, DistributableToolchain, InstallMetadata, LocalToolchainName,
+        ResolvableToolchainName, Toolchain, ToolchainName, ToolchainSpec,
+    },
+    utils::{self, ExitCode},
+    without_exe_suffix,
+};
+
+type Result<T> = std::result::Result<T, RustupError>;
+
+/// The channel manifest of a release, as returned by [`fetch_manifest`].
+///
+/// It lists every package of the release with the URL and checksum of its
+/// archive for each target.
+pub use crate::dist::manifest::Manifest as DistManifest;
+
+/// A stage of the installation, reported to progress callbacks.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum InstallProgress {
+    /// Bytes of a component archive have been received.
+    Downloading {
+        component: String,
+        bytes_received: u64,
+        total_bytes: Option<u64>,
+        /// The measured download rate, which stays within
+        /// [`DownloadConfig::bandwidth_limit`] if one is set.
+        bytes_per_second: u64,
+    },
+    /// A downloaded component archive is being unpacked.
+    Extracting { component: String },
+    /// The installation has completed successfully.
+    Finished,
+}
+
+/// A toolchain installed under RUSTUP_HOME.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InstalledToolchain {
+    /// The toolchain name, as shown by `rustup toolchain list`.
+    pub name: String,
+    /// Whether this is the configured default toolchain.
+    pub is_default: bool,
+    /// Whether this is the toolchain that would be used in the current directory.
+    pub is_active: bool,
+    /// The toolchain's directory on disk.
+    pub path: PathBuf,
+    /// The day the toolchain was installed, as `YYYY-MM-DD`, if rustup or
+    /// the file system records it.
+    pub installed_date: Option<String>,
+    /// The release channel for official toolchains, e.g. `nightly` or `1.80.0`.
+    pub channel: Option<String>,
+    /// The profile the toolchain was installed with, e.g. `minimal`, if
+    /// rustup recorded it.
+    pub profile: Option<String>,
+}
+
+impl InstalledToolchain {
+    pub(crate) fn new(
+        cfg: &Cfg<'_>,
+        name: &LocalToolchainName,
+        is_default: bool,
+        is_active: bool,
+    ) -> Self {
+        let path = cfg.toolchain_path(name);
+        let metadata = InstallMetadata::read(&path).ok().flatten();
+        let installed_date = match &metadata {
+            Some(metadata) => Some(metadata.install_date),
+            None => fs::symlink_metadata(&path)
+                .and_then(|meta| meta.created().or_else(|_| meta.modified()))
+                .ok()
+                .map(DateTime::<Utc>::from),
+        }
+        .map(|date| date.format("%Y-%m-%d").to_string());
+        let channel = match name {
+            LocalToolchainName::Named(ToolchainName::Official(desc)) => {
+                Some(desc.channel.to_string())
+            }
+            _ => None,
+        };
+        Self {
+            name: name.to_string(),
+            is_default,
+            is_active,
+            path,
+            installed_date,
+            channel,
+            profile: metadata.map(|m| m.profile.as_str().to_owned()),
+        }
+    }
+}
+
+/// Why a toolchain is active, as reported by [`active_toolchain_info`] and
+/// `rustup show active-toolchain --json`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ActiveToolchainInfo {
+    /// The full name of the active toolchain.
+    pub toolchain: String,
+    /// What selected the toolchain: `default`, `env`, `cli`, `path-override`
+    /// or `toolchain-file`.
+    pub reason: String,
+    /// The `rust-toolchain{.toml}` file that selected the toolchain, if any.
+    pub file: Option<PathBuf>,
+    /// The version `rustc --version` reports, e.g. `1.77.0-nightly`, if it
+    /// could be run.
+    pub rustc_version: Option<String>,
+}
+
+impl ActiveToolchainInfo {
+    pub(crate) fn new(toolchain: &Toolchain<'_>, source: &ActiveSource) -> Self {
+        let version = toolchain.rustc_version();
+        // Failures are described in parentheses, e.g. `(rustc does not exist)`
+        let rustc_version = match version.starts_with('(') {
+            true => None,
+            false => version
+                .strip_prefix("rustc ")
+                .unwrap_or(&version)
+                .split_whitespace()
+                .next()
+                .map(str::to_owned),
+        };
+        Self {
+            toolchain: toolchain.name().to_string(),
+            reason: source.to_string(),
+            file: match source {
+                ActiveSource::ToolchainFile(path) => Some(path.clone()),
+                _ => None,
+            },
+            rustc_version,
+        }
+    }
+}
+
+/// The outcome of [`update_toolchain`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum UpdateResult {
+    /// A newer release was installed.
+    Updated {
+        from_date: String,
+        to_date: String,
+        /// The number of bytes downloaded to perform the update.
+        bytes_downloaded: u64,
+    },
+    /// The toolchain already matches the latest release.
+    AlreadyCurrent { date: String },
+    /// The toolchain is not installed, so nothing was updated.
+    NotInstalled,
+}
+
+/// The outcome of [`check_for_toolchain_update`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ToolchainUpdateStatus {
+    /// The toolchain matches the latest release of its channel.
+    Current,
+    /// A newer release is available, e.g. from `1.80.0 (051478957 2024-07-21)`.
+    UpdateAvailable { from: String, to: String },
+    /// The toolchain is not installed, so there is nothing to compare.
+    NotInstalled,
+}
+
+/// The outcome of [`check_for_self_update`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SelfUpdateStatus {
+    /// The running rustup is the latest release.
+    UpToDate { version: String },
+    /// A newer rustup release is available.
+    UpdateAvailable { current: String, latest: String },
+}
+
+/// What installing a toolchain would entail, as computed by [`resolve`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResolvedToolchain {
+    /// The fully-qualified toolchain name, e.g. `stable-x86_64-unknown-linux-gnu`.
+    pub name: String,
+    /// The date of the release the toolchain resolves to.
+    pub date: String,
+    /// The version of the `rust` package in that release.
+    pub rust_version: Option<String>,
+    /// The component archives that would be downloaded.
+    pub downloads: Vec<ComponentDownload>,
+    /// The combined size of `downloads`, or `None` if any of the sizes is unknown.
+    pub total_download_size: Option<u64>,
+    /// The requested components that are unavailable in the release.
+    pub missing_components: Vec<String>,
+}
+
+/// A component archive listed in a [`ResolvedToolchain`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ComponentDownload {
+    pub component: String,
+    pub url: String,
+    /// The archive size reported by the dist server.
+    pub size: Option<u64>,
+}
+
+/// The outcome of [`verify_toolchain`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VerificationReport {
+    /// Whether no file is missing or corrupted.
+    pub ok: bool,
+    /// Installed files that no longer exist.
+    pub missing_files: Vec<PathBuf>,
+    /// Installed files whose contents differ from when they were installed.
+    pub corrupted_files: Vec<PathBuf>,
+}
+
+/// The outcome of [`diagnose`]: one entry per check, in the order they ran.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiagnosticReport {
+    pub checks: Vec<Diagnostic>,
+}
+
+impl DiagnosticReport {
+    /// Whether no check found an error; warnings are allowed.
+    pub fn is_ok(&self) -> bool {
+        self.checks
+            .iter()
+            .all(|c| c.status != DiagnosticStatus::Error)
+    }
+}
+
+/// The outcome of [`check_self_integrity`]: one entry per check, in the order they ran.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SelfCheckReport {
+    pub checks: Vec<Diagnostic>,
+}
+
+impl SelfCheckReport {
+    /// Whether no check found an error; warnings are allowed.
+    pub fn is_ok(&self) -> bool {
+        self.checks
+            .iter()
+            .all(|c| c.status != DiagnosticStatus::Error)
+    }
+}
+
+/// A check run by [`diagnose`] or [`check_self_integrity`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Diagnostic {
+    /// What was checked, e.g. `cargo-bin-on-path`.
+    pub check: String,
+    pub status: DiagnosticStatus,
+    /// What the check found.
+    pub message: String,
+    /// How to fix what the check found, unless its status is `Ok`.
+    pub fix: Option<String>,
+}
+
+impl Diagnostic {
+    pub(crate) fn ok(check: &str, message: impl Into<String>) -> Self {
+        Self {
+            check: check.to_owned(),
+            status: DiagnosticStatus::Ok,
+            message: message.into(),
+            fix: None,
+        }
+    }
+
+    pub(crate) fn warning(check: &str, message: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            status: DiagnosticStatus::Warning,
+            fix: Some(fix.into()),
+            ..Self::ok(check, message)
+        }
+    }
+
+    pub(crate) fn error(check: &str, message: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            status: DiagnosticStatus::Error,
+            ..Self::warning(check, message, fix)
+        }
+    }
+}
+
+/// How a [`Diagnostic`] turned out.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DiagnosticStatus {
+    Ok,
+    /// Rustup works, but possibly not as intended.
+    Warning,
+    /// Rustup, or some of the toolchains it manages, will not work.
+    Error,
+}
+
+/// The outcome of [`gc_toolchains`] and [`gc_old_nightlies`].
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GcReport {
+    /// The toolchains that were removed, or would be removed on a dry run.
+    pub removed: Vec<String>,
+    /// The combined size of the removed toolchains' directories.
+    pub freed_bytes: u64,
+}
+
+/// The outcome of [`update_toolchain_file`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ToolchainFileUpdateResult {
+    /// The `rust-toolchain.toml` that was read.
+    pub path: PathBuf,
+    /// The channel the file is pinned to, without its date, e.g. `nightly`.
+    pub channel: String,
+    /// The date the file was pinned to.
+    pub old_date: String,
+    /// The date of the newest release of the channel that has all the
+    /// components and targets the file asks for, or `None` if there is none
+    /// newer than `old_date`.
+    pub new_date: Option<String>,
+    /// Whether the file was rewritten with `new_date`; never on a dry run.
+    pub updated: bool,
+}
+
+/// The disk space used by rustup, as computed by [`disk_usage`].
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiskUsage {
+    /// The size of each installed toolchain, in the order of [`list_toolchains`].
+    pub toolchains: Vec<(String, u64)>,
+    /// The size of the downloaded component archives and cached dist files.
+    pub downloads_cache_bytes: u64,
+    /// The size of everything under RUSTUP_HOME.
+    pub total_bytes: u64,
+}
+
+/// A release channel, as taken by [`list_available_toolchains`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Channel {
+    Stable,
+    Beta,
+    Nightly,
+}
+
+impl From<Channel> for dist::Channel {
+    fn from(channel: Channel) -> Self {
+        match channel {
+            Channel::Stable => Self::Stable,
+            Channel::Beta => Self::Beta,
+            Channel::Nightly => Self::Nightly,
+        }
+    }
+}
+
+/// A release listed by [`list_available_toolchains`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ToolchainRelease {
+    /// The name to install the release by, e.g. `nightly-2024-01-15`.
+    pub name: String,
+    /// The date of the release.
+    pub date: NaiveDate,
+    /// The version of the `rust` package in the release, e.g.
+    /// `1.77.0-nightly (714b29a17 2024-01-14)`.
+    pub rust_version: String,
+    /// Whether a toolchain of the release is installed for the host.
+    pub installed: bool,
+}
+
+/// A target listed by [`list_available_targets`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TargetInfo {
+    /// The target triple, e.g. `wasm32-unknown-unknown`.
+    pub triple: String,
+    /// Whether the standard library for the target is installed.
+    pub installed: bool,
+    /// Whether the release provides the standard library for the target.
+    pub available: bool,
+}
+
+/// What [`add_targets`] did with one of the targets it was given.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TargetAddResult {
+    /// The standard library for the target was installed.
+    Added,
+    /// The standard library for the target was already installed.
+    AlreadyInstalled,
+    /// The toolchain's release has no standard library for the target.
+    NotAvailable,
+}
+
+impl fmt::Display for TargetAddResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Added => "added",
+            Self::AlreadyInstalled => "already installed",
+            Self::NotAvailable => "not available",
+        })
+    }
+}
+
+/// A component listed by [`list_available_components`] or [`list_components`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ComponentInfo {
+    /// The component name, e.g. `rust-src`.
+    pub name: String,
+    /// Whether the component is installed.
+    pub installed: bool,
+    /// Whether the release provides the component; nightly releases often
+    /// lack components that failed to build.
+    pub available: bool,
+    /// Whether this is the standard library of a target or another component.
+    pub kind: ComponentKind,
+}
+
+impl ComponentInfo {
+    /// Describes `status`, keeping the full name of the component, e.g.
+    /// `rust-std-wasm32-unknown-unknown`, as `rustup component list` prints it.
+    pub(crate) fn from_status(status: ComponentStatus) -> Self {
+        Self {
+            kind: ComponentKind::of(status.component.short_name_in_manifest()),
+            name: status.name,
+            installed: status.installed,
+            available: status.available,
+        }
+    }
+}
+
+/// What a [`ComponentInfo`] installs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ComponentKind {
+    /// A tool or library of the toolchain, e.g. `clippy` or `rust-src`.
+    Component,
+    /// The standard library of a target, as added by `rustup target add`.
+    Target,
+}
+
+impl ComponentKind {
+    /// The kind of the component named `name`, with or without its target.
+    pub(crate) fn of(name: &str) -> Self {
+        match name.strip_prefix("rust-std") {
+            Some("") => Self::Target,
+            Some(rest) if rest.starts_with('-') => Self::Target,
+            _ => Self::Component,
+        }
+    }
+}
+
+/// How [`export_toolchain`] packs a toolchain.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ArchiveFormat {
+    Tar,
+    TarXz,
+    TarZst,
+}
+
+/// The sidecar [`export_toolchain`] writes next to the archive, and
+/// [`import_toolchain`] reads back.
+#[derive(Debug, Serialize, Deserialize)]
+struct ExportManifest {
+    name: String,
+    host: String,
+    format: ArchiveFormat,
+    components: Vec<String>,
+}
+
+/// The shells [`generate_completions`] can write completions for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    PowerShell,
+    Elvish,
+}
+
+impl From<Shell> for clap_complete::Shell {
+    fn from(shell: Shell) -> Self {
+        match shell {
+            Shell::Bash => Self::Bash,
+            Shell::Zsh => Self::Zsh,
+            Shell::Fish => Self::Fish,
+            Shell::PowerShell => Self::PowerShell,
+            Shell::Elvish => Self::Elvish,
+        }
+    }
+}
+
+/// The kinds of argument [`completion_candidates`] lists the values of.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum CompletionKind {
+    /// The installed toolchains and the release channels.
+    Toolchain,
+    /// The targets the active toolchain has a standard library for.
+    Target,
+    /// The components of the active toolchain.
+    Component,
+    /// The installation profiles.
+    Profile,
+}
+
+/// Network behaviour for downloads performed by the installer.
+///
+/// When no configuration is supplied, the timeout is read from
+/// `RUSTUP_DOWNLOAD_TIMEOUT` and the retry count from `RUSTUP_MAX_RETRIES`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NetworkConfig {
+    /// How long to wait for a connection to be established.
+    pub connect_timeout: Duration,
+    /// How long to wait for more data on an established connection.
+    pub read_timeout: Duration,
+    /// How many times a failed component download is retried.
+    pub max_retries: u32,
+    /// How long to wait before retrying a failed download.
+    pub retry_backoff: Duration,
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        Self {
+            connect_timeout: Duration::from_secs(180),
+            read_timeout: Duration::from_secs(180),
+            max_retries: 3,
+            retry_backoff: Duration::ZERO,
+        }
+    }
+}
+
+/// How component archives are fetched by the installer.
+///
+/// Archives are downloaded and checksummed concurrently, while unpacking
+/// remains one component at a time.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DownloadConfig {
+    /// The number of archives downloaded at once; `0` is treated as `1`.
+    pub max_parallel_downloads: usize,
+    /// The most bytes per second to receive, or `None` to download as fast as
+    /// possible. The limit is split evenly between the parallel downloads.
+    pub bandwidth_limit: Option<u64>,
+    /// Whether to talk HTTP/2 to HTTPS dist servers, so that the parallel
+    /// downloads share a single connection. The server must support HTTP/2,
+    /// as the protocol is not negotiated. Only the reqwest backends honour it.
+    pub use_http2: bool,
+    /// The number of byte ranges a large archive is split into, to download
+    /// them concurrently when the server supports range requests; `0` and `1`
+    /// download every archive as a single stream. Only the reqwest backends
+    /// honour it.
+    pub download_parts: usize,
+    /// The format to download component archives in where the dist server
+    /// offers a choice; archives only offered in other formats are still
+    /// downloaded in those.
+    pub preferred_compression: CompressionFormat,
+    /// How long the download of a single component may take, or `None` for
+    /// no limit. A download that takes longer is retried like any other
+    /// failed download.
+    pub timeout: Option<Duration>,
+}
+
+impl DownloadConfig {
+    /// The share of [`Self::bandwidth_limit`] granted to a single download.
+    pub(crate) fn bandwidth_limit_per_download(&self) -> Option<u64> {
+        let parallel = self.max_parallel_downloads.max(1) as u64;
+        self.bandwidth_limit.map(|limit| (limit / parallel).max(1))
+    }
+}
+
+impl Default for DownloadConfig {
+    fn default() -> Self {
+        Self {
+            max_parallel_downloads: DEFAULT_CONCURRENT_DOWNLOADS,
+            bandwidth_limit: None,
+            use_http2: false,
+            download_parts: 4,
+            preferred_compression: CompressionFormat::default(),
+            timeout: None,
+        }
+    }
+}
+
+/// The compression of component archives, as preferred by
+/// [`DownloadConfig::preferred_compression`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CompressionFormat {
+    /// `.tar.zst`, which is smaller, and decompresses faster.
+    #[default]
+    Zst,
+    /// `.tar.xz`.
+    Xz,
+}
+
+impl From<CompressionFormat> for CompressionKind {
+    fn from(format: CompressionFormat) -> Self {
+        match format {
+            CompressionFormat::Zst => Self::ZStd,
+            CompressionFormat::Xz => Self::XZ,
+        }
+    }
+}
+
+/// The shared form of a progress callback, as threaded through [`Cfg`].
+pub(crate) type ProgressHandler = Arc<dyn Fn(InstallProgress) + Send + Sync>;
+
+/// Share a callback that is only `Send` by serializing the calls to it.
+fn progress_from_fn(f: impl Fn(InstallProgress) + Send + 'static) -> ProgressHandler {
+    let f = Mutex::new(f);
+    Arc::new(move |p| (f.lock().unwrap())(p))
+}
+
+/// Forward updates to `tx` without waiting, dropping those that do not fit
+/// in the channel's buffer.
+fn progress_from_channel(tx: mpsc::Sender<InstallProgress>) -> ProgressHandler {
+    Arc::new(move |p| {
+        let _ = tx.try_send(p);
+    })
+}
+
+/// The options of [`install_rust_with_config`], set builder-style.
+///
+/// The defaults match running `rustup-init` without arguments: prompting,
+/// modifying PATH and installing the default profile from the official
+/// dist server into `RUSTUP_HOME` and `CARGO_HOME`.
+///
+/// ```no_run
+/// use rustup::dist::Profile;
+/// use rustup::installer::{self, RustupConfig};
+///
+/// let config = RustupConfig::new()
+///     .no_prompt(true)
+///     .no_modify_path(true)
+///     .profile(Profile::Minimal);
+/// installer::install_rust_with_config(config)?;
+/// # Ok::<(), rustup::errors::RustupError>(())
+/// ```
+#[derive(Clone, Default)]
+pub struct RustupConfig {
+    no_prompt: bool,
+    no_modify_path: bool,
+    profile: Profile,
+    dist_server: Option<Url>,
+    network: Option<NetworkConfig>,
+    download: Option<DownloadConfig>,
+    offline: bool,
+    rustup_home: Option<PathBuf>,
+    cargo_home: Option<PathBuf>,
+    progress: Option<ProgressHandler>,
+    keep_max_nightly: Option<usize>,
+    allow_downgrade: bool,
+    components: Vec<String>,
+    targets: Vec<String>,
+    verify_after_install: bool,
+    component_policy: Option<ComponentPolicy>,
+}
+
+impl RustupConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Skips the interactive confirmation; set it for unattended installs.
+    pub fn no_prompt(mut self, no_prompt: bool) -> Self {
+        self.no_prompt = no_prompt;
+        self
+    }
+
+    /// Leaves the system PATH alone instead of adding `~/.cargo/bin` to it.
+    pub fn no_modify_path(mut self, no_modify_path: bool) -> Self {
+        self.no_modify_path = no_modify_path;
+        self
+    }
+
+    /// The components to install; a [`Profile::Custom`] list only applies to
+    /// this install.
+    pub fn profile(mut self, profile: Profile) -> Self {
+        self.profile = profile;
+        self
+    }
+
+    /// Components to install on top of the `profile`, e.g. `clippy` with
+    /// [`Profile::Minimal`], like `rustup-init --component`. Those that are
+    /// unavailable for the toolchain installed are skipped with a warning,
+    /// unless [`Self::component_policy`] makes them fail the installation.
+    pub fn components(mut self, components: &[&str]) -> Self {
+        self.components = components.iter().map(|&c| c.to_owned()).collect();
+        self
+    }
+
+    /// Targets to install the standard library for, like `rustup-init --target`.
+    pub fn targets(mut self, targets: &[&str]) -> Self {
+        self.targets = targets.iter().map(|&t| t.to_owned()).collect();
+        self
+    }
+
+    /// The release server to install from and to use for later updates, e.g.
+    /// `https://my-mirror.example.com`; `RUSTUP_DIST_SERVER` still takes precedence.
+    pub fn dist_server(mut self, dist_server: Url) -> Self {
+        self.dist_server = Some(dist_server);
+        self
+    }
+
+    /// Timeouts and retries for downloads, in place of the environment's.
+    pub fn network(mut self, network: NetworkConfig) -> Self {
+        self.network = Some(network);
+        self
+    }
+
+    /// Download concurrency, in place of `RUSTUP_CONCURRENT_DOWNLOADS`.
+    pub fn download(mut self, download: DownloadConfig) -> Self {
+        self.download = Some(download);
+        self
+    }
+
+    /// Only installs from files cached by earlier runs, failing with
+    /// [`RustupError::Offline`] when one is missing; see `RUSTUP_OFFLINE`.
+    pub fn offline(mut self, offline: bool) -> Self {
+        self.offline = offline;
+        self
+    }
+
+    /// Where to install rustup's data, in place of `RUSTUP_HOME`; the process
+    /// environment is left untouched.
+    pub fn rustup_home(mut self, rustup_home: PathBuf) -> Self {
+        self.rustup_home = Some(rustup_home);
+        self
+    }
+
+    /// Where to install the binaries, in place of `CARGO_HOME`; the process
+    /// environment is left untouched.
+    pub fn cargo_home(mut self, cargo_home: PathBuf) -> Self {
+        self.cargo_home = Some(cargo_home);
+        self
+    }
+
+    /// Called with each [`InstallProgress`] update; it may be invoked from
+    /// worker threads, one call at a time.
+    pub fn progress(mut self, progress: impl Fn(InstallProgress) + Send + 'static) -> Self {
+        self.progress = Some(progress_from_fn(progress));
+        self
+    }
+
+    /// Sends each [`InstallProgress`] update on `progress` without waiting;
+    /// updates that do not fit in the channel's buffer are dropped rather
+    /// than stalling the download.
+    pub fn progress_channel(mut self, progress: mpsc::Sender<InstallProgress>) -> Self {
+        self.progress = Some(progress_from_channel(progress));
+        self
+    }
+
+    /// When the default toolchain installed is a nightly, removes the older
+    /// nightlies beyond the `keep_max_nightly` most recent ones, like
+    /// `rustup toolchain install --keep-max-nightly`.
+    pub fn keep_max_nightly(mut self, keep_max_nightly: usize) -> Self {
+        self.keep_max_nightly = Some(keep_max_nightly);
+        self
+    }
+
+    /// Lets the update of an existing default toolchain go back to an older
+    /// release, when the latest one lacks some of the installed components,
+    /// like `rustup toolchain install --allow-downgrade`.
+    pub fn allow_downgrade(mut self, allow_downgrade: bool) -> Self {
+        self.allow_downgrade = allow_downgrade;
+        self
+    }
+
+    /// Rehashes the installed files against the packages they came from,
+    /// failing with [`RustupError::VerificationFailed`] on a mismatch, like
+    /// `rustup toolchain install --verify`. This is the default when `CI` is set.
+    pub fn verify_after_install(mut self, verify_after_install: bool) -> Self {
+        self.verify_after_install = verify_after_install;
+        self
+    }
+
+    /// Whether a requested component that is unavailable fails the install,
+    /// like `rustup toolchain install --component-policy`. Unlike that command,
+    /// unavailable components are skipped with a warning by default.
+    pub fn component_policy(mut self, component_policy: ComponentPolicy) -> Self {
+        self.component_policy = Some(component_policy);
+        self
+    }
+}
+
+impl fmt::Debug for RustupConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self {
+            no_prompt,
+            no_modify_path,
+            profile,
+            dist_server,
+            network,
+            download,
+            offline,
+            rustup_home,
+            cargo_home,
+            progress,
+            keep_max_nightly,
+            allow_downgrade,
+            components,
+            targets,
+            verify_after_install,
+            component_policy,
+        } = self;
+        f.debug_struct("RustupConfig")
+            .field("no_prompt", no_prompt)
+            .field("no_modify_path", no_modify_path)
+            .field("profile", profile)
+            .field("dist_server", dist_server)
+            .field("network", network)
+            .field("download", download)
+            .field("offline", offline)
+            .field("rustup_home", rustup_home)
+            .field("cargo_home", cargo_home)
+            .field("progress", &progress.is_some())
+            .field("keep_max_nightly", keep_max_nightly)
+            .field("allow_downgrade", allow_downgrade)
+            .field("components", components)
+            .field("targets", targets)
+            .field("verify_after_install", verify_after_install)
+            .field("component_policy", component_policy)
+            .finish()
+    }
+}
+
+/// Install Rust synchronously as configured by `config`.
+///
+/// Must not be called from within a tokio runtime.
+pub fn install_rust_with_config(config: RustupConfig) -> Result<()> {
+    let exit_code = block_on(install(config))?;
+    check_exit_code("install", exit_code)
+}
+
+/// Async version of [`install_rust_with_config`]. Requires an existing tokio
+/// runtime.
+pub async fn install_rust_with_config_async(config: RustupConfig) -> Result<ExitCode> {
+    install(config).await
+}
+
+/// Install Rust synchronously using rustup's standard installation flow.
+///
+/// Internally spins up a multi-thread tokio runtime. Call this from a
+/// dedicated `std::thread::spawn` to avoid conflicting with any existing
+/// async executor (e.g. GPUI).
+///
+/// - `no_prompt`: skip interactive confirmation (pass `true` for unattended installs)
+/// - `no_modify_path`: when `false`, rustup adds `~/.cargo/bin` to the system PATH
+///
+/// Use [`install_rust_with_config`] for the other options, such as a
+/// [`RustupConfig::progress`] callback.
+pub fn install_rust_blocking(no_prompt: bool, no_modify_path: bool) -> Result<()> {
+    install_rust_with_config(
+        RustupConfig::new()
+            .no_prompt(no_prompt)
+            .no_modify_path(no_modify_path),
+    )
+}
+
+/// Async version of the install flow. Requires an existing tokio runtime.
+///
+/// Use [`install_rust_with_config_async`] for the other options, such as a
+/// [`RustupConfig::progress_channel`] for progress updates.
+pub async fn install_rust(no_prompt: bool, no_modify_path: bool) -> Result<ExitCode> {
+    install_rust_with_config_async(
+        RustupConfig::new()
+            .no_prompt(no_prompt)
+            .no_modify_path(no_modify_path),
+    )
+    .await
+}
+
+async fn install(config: RustupConfig) -> Result<ExitCode> {
+    let RustupConfig {
+        no_prompt,
+        no_modify_path,
+        profile,
+        dist_server,
+        network,
+        download,
+        offline,
+        rustup_home,
+        cargo_home,
+        progress,
+        keep_max_nightly,
+        allow_downgrade,
+        components,
+        targets,
+        verify_after_install,
+        component_policy,
+    } = config;
+    let span = info_span!("install_rust", profile = %profile, no_modify_path);
+    let start = Instant::now();
+    let mut process = process();
+    if let Some(rustup_home) = rustup_home {
+        process = process.with_var("RUSTUP_HOME", rustup_home);
+    }
+    if let Some(cargo_home) = cargo_home {
+        process = process.with_var("CARGO_HOME", cargo_home);
+    }
+    let mut cfg = cfg(no_prompt, &process)?;
+    cfg.progress_handler = progress.clone();
+    let opts = InstallOpts {
+        default_host_triple: None,
+        default_toolchain: None,
+        profile,
+        no_modify_path,
+        no_update_toolchain: false,
+        components: &components.iter().map(String::as_str).collect::<Vec<_>>(),
+        targets: &targets.iter().map(String::as_str).collect::<Vec<_>>(),
+        dist_server,
+        network,
+        download,
+        offline,
+        allow_downgrade,
+        verify_after_install,
+        component_policy: component_policy.unwrap_or(ComponentPolicy::Lenient),
+    };
+    let exit_code = self_update::install(no_prompt, opts, &mut cfg)
+        .instrument(span)
+        .await?;
+    if let (Some(keep), true) = (keep_max_nightly, exit_code == ExitCode::SUCCESS)
+        && let Some(ToolchainName::Official(desc)) = cfg.get_default()?
+        && desc.channel == dist::Channel::Nightly
+    {
+        prune_nightlies_for(&cfg, &desc, keep)?;
+    }
+    debug!(
+        code = exit_code.0,
+        elapsed_ms = start.elapsed().as_millis() as u64,
+        "install finished"
+    );
+    if let (Some(progress), true) = (progress, exit_code == ExitCode::SUCCESS) {
+        progress(InstallProgress::Finished);
+    }
+    Ok(exit_code)
+}
+
+/// Builds a [`RustupHandle`], for working with one rustup installation
+/// directly rather than through the functions of this module.
+///
+/// The homes default to `RUSTUP_HOME` and `CARGO_HOME` of the process, which
+/// defaults to the one set with [`set_process`].
+///
+/// ```no_run
+/// use rustup::dist::Profile;
+/// use rustup::installer::CfgBuilder;
+///
+/// let rustup = CfgBuilder::new().rustup_home("/opt/rustup".into()).build()?;
+/// rustup.edit_settings(|settings| {
+///     settings.default_toolchain(Some("stable"))?;
+///     settings.profile(Profile::Minimal);
+///     Ok(())
+/// })?;
+/// # Ok::<(), rustup::errors::RustupError>(())
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct CfgBuilder {
+    process: Option<Process>,
+    rustup_home: Option<PathBuf>,
+    cargo_home: Option<PathBuf>,
+    current_dir: Option<PathBuf>,
+    no_prompt: bool,
+}
+
+impl CfgBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The process whose environment and output rustup uses, e.g. one made by
+    /// [`Process::library`].
+    pub fn process(mut self, process: Process) -> Self {
+        self.process = Some(process);
+        self
+    }
+
+    /// The rustup home, in place of `RUSTUP_HOME`.
+    pub fn rustup_home(mut self, rustup_home: PathBuf) -> Self {
+        self.rustup_home = Some(rustup_home);
+        self
+    }
+
+    /// The cargo home, in place of `CARGO_HOME`.
+    pub fn cargo_home(mut self, cargo_home: PathBuf) -> Self {
+        self.cargo_home = Some(cargo_home);
+        self
+    }
+
+    /// The directory overrides and toolchain files are looked up from, in
+    /// place of the current directory.
+    pub fn current_dir(mut self, current_dir: PathBuf) -> Self {
+        self.current_dir = Some(current_dir);
+        self
+    }
+
+    /// Skips the interactive prompts, and the progress bars.
+    pub fn no_prompt(mut self, no_prompt: bool) -> Self {
+        self.no_prompt = no_prompt;
+        self
+    }
+
+    /// Opens the installation, creating the rustup home if it is missing.
+    ///
+    /// Fails if its metadata is out of date, as rustup does until `rustup self
+    /// upgrade-data` is run.
+    pub fn build(self) -> Result<RustupHandle> {
+        let Self {
+            process: base,
+            rustup_home,
+            cargo_home,
+            current_dir,
+            no_prompt,
+        } = self;
+        let mut process = base.unwrap_or_else(process);
+        if let Some(rustup_home) = rustup_home {
+            process = process.with_var("RUSTUP_HOME", rustup_home);
+        }
+        if let Some(cargo_home) = cargo_home {
+            process = process.with_var("CARGO_HOME", cargo_home);
+        }
+        let current_dir = match current_dir {
+            Some(dir) => dir,
+            None => process.current_dir()?,
+        };
+        let handle = RustupHandle {
+            process,
+            current_dir,
+            quiet: no_prompt,
+        };
+        handle.cfg()?;
+        Ok(handle)
+    }
+}
+
+/// A rustup installation, as opened by [`CfgBuilder::build`].
+#[derive(Clone, Debug)]
+pub struct RustupHandle {
+    process: Process,
+    current_dir: PathBuf,
+    quiet: bool,
+}
+
+impl RustupHandle {
+    /// The process rustup runs as; pass it to [`set_process`] to have the
+    /// functions of this module work on this installation too.
+    pub fn process(&self) -> &Process {
+        &self.process
+    }
+
+    pub fn rustup_home(&self) -> Result<PathBuf> {
+        Ok(self.process.rustup_home()?)
+    }
+
+    pub fn cargo_home(&self) -> Result<PathBuf> {
+        Ok(self.process.cargo_home()?)
+    }
+
+    /// Changes any number of settings with `f`, writing `settings.toml` once
+    /// when it returns, and not at all if it fails.
+    ///
+    /// The home is locked meanwhile, so other rustup processes do not see the
+    /// changes half made.
+    pub fn edit_settings<T>(
+        &self,
+        f: impl FnOnce(&mut SettingsEditor<'_>) -> Result<T>,
+    ) -> Result<T> {
+        let cfg = self.cfg()?;
+        Ok(cfg.with_settings_mut(|settings| {
+            let mut editor = SettingsEditor {
+                settings,
+                process: &self.process,
+            };
+            Ok(f(&mut editor)?)
+        })?)
+    }
+
+    fn cfg(&self) -> Result<Cfg<'_>> {
+        Ok(Cfg::from_env(
+            self.current_dir.clone(),
+            self.quiet,
+            &self.process,
+        )?)
+    }
+}
+
+/// The settings of a rustup installation, as changed by
+/// [`RustupHandle::edit_settings`].
+///
+/// Each method sets what the `rustup` command of the same name does, but does
+/// not install anything.
+pub struct SettingsEditor<'a> {
+    settings: &'a mut Settings,
+    process: &'a Process,
+}
+
+impl SettingsEditor<'_> {
+    /// Like `rustup default`, with `None` unsetting the default toolchain.
+    ///
+    /// Names are resolved against the default host, as set at the time.
+    pub fn default_toolchain(&mut self, toolchain: Option<&str>) -> Result<()> {
+        self.settings.default_toolchain = match toolchain {
+            Some(name) => Some(
+                ResolvableToolchainName::try_from(name)
+                    .map_err(|_| RustupError::InvalidToolchainName(name.to_owned()))?
+                    .resolve(&self.default_host())?
+                    .to_string(),
+            ),
+            None => None,
+        };
+        Ok(())
+    }
+
+    /// Like `rustup set default-host`.
+    pub fn default_host_triple(&mut self, host_triple: &str) -> Result<()> {
+        // Fail early on triples that cannot name a toolchain
+        "stable"
+            .parse::<PartialToolchainDesc>()?
+            .resolve(&TargetTriple::new(host_triple))?;
+        self.settings.default_host_triple = Some(host_triple.to_owned());
+        Ok(())
+    }
+
+    /// Like `rustup set profile`.
+    pub fn profile(&mut self, profile: Profile) {
+        self.settings.profile = Some(profile);
+    }
+
+    /// Like `rustup set auto-self-update`.
+    pub fn auto_self_update(&mut self, mode: SelfUpdateMode) {
+        self.settings.auto_self_update = Some(mode);
+    }
+
+    /// Like `rustup set dist-server`, with `None` going back to the default.
+    pub fn dist_server(&mut self, server: Option<&Url>) {
+        self.settings.dist_server = server.map(|s| config::server_root(s.as_str()));
+    }
+
+    /// Like [`set_proxy`], with `None` unsetting the proxy.
+    pub fn proxy(&mut self, proxy: Option<&Url>) {
+        self.settings.proxy = proxy.map(Url::to_string);
+    }
+
+    /// Like [`set_mirrors`].
+    pub fn mirrors(&mut self, mirrors: &[Url]) {
+        self.settings.mirrors = mirrors
+            .iter()
+            .map(|m| config::server_root(m.as_str()))
+            .collect();
+    }
+
+    /// Like `rustup override set`, except that the toolchain need not be
+    /// installed.
+    pub fn set_override(&mut self, path: &Path, toolchain: &str) -> Result<()> {
+        let name = ResolvableToolchainName::try_from(toolchain)
+            .map_err(|_| RustupError::InvalidToolchainName(toolchain.to_owned()))?
+            .resolve(&self.default_host())?;
+        self.settings.add_override(path, name.to_string());
+        Ok(())
+    }
+
+    /// Like `rustup override unset`, returning whether there was an override.
+    pub fn remove_override(&mut self, path: &Path) -> bool {
+        self.settings.remove_override(path)
+    }
+
+    fn default_host(&self) -> TargetTriple {
+        match &self.settings.default_host_triple {
+            Some(host) => TargetTriple::new(host),
+            None => TargetTriple::from_host_or_build(self.process),
+        }
+    }
+}
+
+/// Uninstall rustup synchronously, as `rustup self uninstall` does.
+///
+/// Removes RUSTUP_HOME, the contents of CARGO_HOME and the rustup binary itself,
+/// and takes `~/.cargo/bin` back out of the system PATH if it was added there.
+///
+/// - `no_prompt`: skip interactive confirmation (pass `true` for unattended uninstalls)
+pub fn uninstall_rust_blocking(no_prompt: bool) -> Result<()> {
+    let exit_code = block_on(uninstall_rust(no_prompt))?;
+    check_exit_code("uninstall", exit_code)
+}
+
+/// Async version of the uninstall flow. Requires an existing tokio runtime.
+pub async fn uninstall_rust(no_prompt: bool) -> Result<ExitCode> {
+    let process = process();
+    Ok(self_update::uninstall(no_prompt, false, &process)?)
+}
+
+/// Check whether a newer release of rustup is available, synchronously.
+pub fn check_for_self_update_blocking() -> Result<SelfUpdateStatus> {
+    block_on(check_for_self_update())
+}
+
+/// Async version of [`check_for_self_update_blocking`]. Requires an existing tokio runtime.
+///
+/// Only the release metadata consulted by `rustup self update` is fetched;
+/// nothing is installed.
+pub async fn check_for_self_update() -> Result<SelfUpdateStatus> {
+    let process = process();
+    let cfg = cfg(true, &process)?;
+    let current = env!("CARGO_PKG_VERSION");
+    let latest = self_update::get_available_rustup_version(&DownloadCfg::new(&cfg)).await?;
+
+    let newer = match (
+        semver::Version::parse(current),
+        semver::Version::parse(&latest),
+    ) {
+        (Ok(current), Ok(latest)) => latest > current,
+        _ => latest != current,
+    };
+    Ok(match newer {
+        true => SelfUpdateStatus::UpdateAvailable {
+            current: current.to_owned(),
+            latest,
+        },
+        false => SelfUpdateStatus::UpToDate {
+            version: current.to_owned(),
+        },
+    })
+}
+
+/// Update rustup itself to the latest release, synchronously.
+///
+/// This is equivalent to `rustup self update`, and fails if self-update is
+/// disabled for this build of rustup.
+pub fn self_update_blocking() -> Result<()> {
+    block_on(self_update())
+}
+
+/// Async version of [`self_update_blocking`]. Requires an existing tokio runtime.
+pub async fn self_update() -> Result<()> {
+    let process = process();
+    let cfg = cfg(false, &process)?;
+    let exit_code = self_update::update(&cfg).await?;
+    check_exit_code("self update", exit_code)
+}
+
+/// Install the named toolchain, e.g. `nightly-2024-01-15`, synchronously.
+///
+/// Only the toolchain itself is installed: the default toolchain is left
+/// untouched and no self-install steps are performed. If the toolchain is
+/// already installed it is updated, as `rustup toolchain install` does, and
+/// any missing `components` and `targets` are added.
+///
+/// With `if_not_installed`, an installed toolchain is left as it is and the
+/// network is not used when it already has all of `components` and `targets`,
+/// as with `rustup toolchain install --if-not-installed`.
+pub fn install_toolchain_blocking(
+    name: &str,
+    profile: Profile,
+    components: &[&str],
+    targets: &[&str],
+    if_not_installed: bool,
+) -> Result<()> {
+    block_on(install_toolchain(
+        name,
+        profile,
+        components,
+        targets,
+        if_not_installed,
+    ))
+}
+
+/// Async version of [`install_toolchain_blocking`]. Requires an existing tokio runtime.
+pub async fn install_toolchain(
+    name: &str,
+    profile: Profile,
+    components: &[&str],
+    targets: &[&str],
+    if_not_installed: bool,
+) -> Result<()> {
+    let process = process();
+    let cfg = cfg(false, &process)?;
+    let desc = toolchain_desc(&cfg, name)?;
+    let span = info_span!("install_toolchain", toolchain = %desc, profile = %profile);
+    let start = Instant::now();
+    async {
+        if if_not_installed {
+            cfg.ensure_installed(
+                &desc,
+                components.iter().map(|&c| c.to_owned()).collect(),
+                targets.iter().map(|&t| t.to_owned()).collect(),
+                Some(profile),
+                false,
+                true,
+            )
+            .await?;
+            return Ok(());
+        }
+
+        let options = DistOptions::new(components, targets, &desc, profile, false, &cfg)?;
+        match DistributableToolchain::new(&cfg, desc.clone()) {
+            Ok(distributable) => {
+                InstallMethod::Dist(options.for_update(&distributable, false))
+                    .install()
+                    .await?;
+            }
+            Err(RustupError::ToolchainNotInstalled { .. }) => {
+                DistributableToolchain::install(options).await?;
+            }
+            Err(e) => return Err(e.into()),
+        }
+        Ok::<_, anyhow::Error>(())
+    }
+    .instrument(span)
+    .await?;
+    debug!(
+        toolchain = %desc,
+        elapsed_ms = start.elapsed().as_millis() as u64,
+        "toolchain installed"
+    );
+    Ok(())
+}
+
+/// Install the toolchain that the `rust-toolchain.toml` or `rust-toolchain`
+/// file at the root of a Cargo workspace asks for, with its components and
+/// targets, as `rustup toolchain install --workspace` does.
+///
+/// `workspace_root` may also be any directory inside the workspace: the root
+/// is the closest directory upwards whose `Cargo.toml` has a `[workspace]`
+/// table, or else that of the closest `Cargo.toml`. Must not be called from
+/// within a tokio runtime.
+pub fn install_for_workspace(workspace_root: &Path) -> Result<InstalledToolchain> {
+    let process = process();
+    let mut cfg = cfg(false, &process)?;
+    cfg.current_dir = cfg.current_dir.join(workspace_root);
+    let (_, desc) = block_on(async {
+        Ok(cfg
+            .install_workspace_toolchain(&cfg.current_dir, false)
+            .await?)
+    })?;
+    let name = LocalToolchainName::from(&desc);
+    let is_default = cfg.get_default()? == Some(ToolchainName::Official(desc));
+    let is_active = matches!(cfg.active_toolchain()?, Some((active, _)) if active == name);
+    Ok(InstalledToolchain::new(&cfg, &name, is_default, is_active))
+}
+
+/// List the installed toolchains, in the same order as `rustup toolchain list`.
+pub fn list_toolchains() -> Result<Vec<InstalledToolchain>> {
+    let process = process();
+    let cfg = cfg(true, &process)?;
+    let active = match cfg.active_toolchain()? {
+        Some((LocalToolchainName::Named(name), _)) => Some(name),
+        _ => None,
+    };
+    list_toolchains_for(&cfg, active.as_ref())
+}
+
+/// The installed toolchains, with `active` marked as the active one.
+pub(crate) fn list_toolchains_for(
+    cfg: &Cfg<'_>,
+    active: Option<&ToolchainName>,
+) -> Result<Vec<InstalledToolchain>> {
+    let default = cfg.get_default()?;
+    Ok(cfg
+        .list_toolchains()?
+        .into_iter()
+        .map(|name| {
+            let is_default = default.as_ref() == Some(&name);
+            let is_active = active == Some(&name);
+            InstalledToolchain::new(cfg, &name.into(), is_default, is_active)
+        })
+        .collect())
+}
+
+/// The installed toolchain that would be used when running in `cwd`.
+///
+/// Overrides, `rust-toolchain.toml` files and `RUSTUP_TOOLCHAIN` are taken into
+/// account. Returns `None` if no toolchain is selected, or if the selected
+/// toolchain is not installed.
+pub fn active_toolchain(cwd: &Path) -> Result<Option<InstalledToolchain>> {
+    let process = process();
+    let mut cfg = cfg(true, &process)?;
+    cfg.current_dir = cwd.to_owned();
+    let Some((name, _)) = cfg.active_toolchain()? else {
+        return Ok(None);
+    };
+    if !Toolchain::exists(&cfg, &name)? {
+        return Ok(None);
+    }
+    let is_default = match (&name, cfg.get_default()?) {
+        (LocalToolchainName::Named(name), Some(default)) => *name == default,
+        _ => false,
+    };
+    Ok(Some(InstalledToolchain::new(&cfg, &name, is_default, true)))
+}
+
+/// The toolchain that would be used when running in `cwd`, and what selected
+/// it.
+///
+/// Unlike [`active_toolchain`], this fails if no toolchain is selected or the
+/// selected one is not installed. Nothing is installed either way.
+pub fn active_toolchain_info(cwd: &Path) -> Result<ActiveToolchainInfo> {
+    let process = process();
+    let mut cfg = cfg(true, &process)?;
+    cfg.current_dir = cwd.to_owned();
+    let Some((name, source)) = cfg.active_toolchain()? else {
+        return Err(RustupError::ToolchainNotSelected("Rust".to_owned()));
+    };
+    let toolchain = Toolchain::with_source(&cfg, name, &source)?;
+    Ok(ActiveToolchainInfo::new(&toolchain, &source))
+}
+
+/// The first `rust-toolchain` or `rust-toolchain.toml` file found walking up
+/// from `start`, along with the toolchain it requests.
+///
+/// Each directory from `start` upwards is checked, and `rust-toolchain` wins
+/// when a directory holds both files. The walk ends at the filesystem root,
+/// or sooner as set with `rustup set toolchain-file-search-limit`: at the
+/// first directory containing `.git` (`git-root`), at the home directory when
+/// `start` is inside it (`home`), or at the edge of the filesystem `start` is
+/// on (`filesystem`). Directory overrides are not considered; see
+/// [`active_toolchain`] for the toolchain that is actually selected.
+pub fn find_toolchain_file(start: &Path) -> Result<Option<(PathBuf, ToolchainSpec)>> {
+    let process = process();
+    let cfg = cfg(true, &process)?;
+    let Some((path, file)) = cfg.find_toolchain_file(&cfg.current_dir.join(start))? else {
+        return Ok(None);
+    };
+    let spec = ToolchainSpec::from_override_file(&path, file)?;
+    Ok(Some((path, spec)))
+}
+
+/// The path of the proxied `binary`, e.g. `cargo`, that would run in `cwd`.
+///
+/// The toolchain is selected as the proxies select it, except that a missing
+/// toolchain is reported as an error rather than installed.
+pub fn which(binary: &str, cwd: &Path) -> Result<PathBuf> {
+    is_proxyable_tools(binary)?;
+    let binary = without_exe_suffix(binary);
+
+    let process = process();
+    let mut cfg = cfg(true, &process)?;
+    cfg.current_dir = cwd.to_owned();
+    let Some((name, _)) = cfg.active_toolchain()? else {
+        return Err(RustupError::ToolchainNotSelected(binary.to_owned()));
+    };
+    let toolchain = Toolchain::new(&cfg, name)?;
+
+    let binary_path = toolchain.binary_file(binary);
+    if !utils::is_file(&binary_path) {
+        return Err(RustupError::BinaryNotInstalled {
+            binary: binary.to_owned(),
+            toolchain: toolchain.name().to_string(),
+        });
+    }
+    Ok(binary_path)
+}
+
+/// The path of `tool`, e.g. `rust-analyzer`, in the toolchain active for `cwd`.
+///
+/// Unlike searching `PATH`, this finds the binary matching the toolchain the
+/// project is built with. `tool` must be one of the proxied tools, and `None`
+/// is returned if the active toolchain does not have it installed.
+pub fn find_tool_path(tool: &str, cwd: &Path) -> Result<Option<PathBuf>> {
+    let process = process();
+    let mut cfg = cfg(true, &process)?;
+    cfg.current_dir = cwd.to_owned();
+    find_tool_path_for(&cfg, tool)
+}
+
+pub(crate) fn find_tool_path_for(cfg: &Cfg<'_>, tool: &str) -> Result<Option<PathBuf>> {
+    is_proxyable_tools(tool)?;
+    let tool = without_exe_suffix(tool);
+
+    let Some((name, _)) = cfg.active_toolchain()? else {
+        return Err(RustupError::ToolchainNotSelected(tool.to_owned()));
+    };
+    let toolchain = Toolchain::new(cfg, name)?;
+
+    let tool_path = toolchain.binary_file(tool);
+    Ok(utils::is_file(&tool_path).then_some(tool_path))
+}
+
+/// Run `binary` from an installed toolchain and wait for it to exit.
+///
+/// The child inherits the environment prepared by the proxies, e.g.
+/// `RUSTUP_TOOLCHAIN` and `PATH`, with `env` applied on top. Its stdio is
+/// inherited from the calling process.
+pub fn run_with_toolchain(
+    toolchain: &str,
+    binary: &str,
+    args: &[&str],
+    env: &HashMap<String, String>,
+    cwd: &Path,
+) -> Result<ExitStatus> {
+    let process = process();
+    let cfg = cfg(true, &process)?;
+    let mut cmd = toolchain_command(&cfg, toolchain, binary, args, env, cwd)?;
+    Ok(cmd.status().with_context(|| RustupError::RunningCommand {
+        name: binary.into(),
+    })?)
+}
+
+/// Like [`run_with_toolchain`], but calls `on_stdout` and `on_stderr` with each
+/// line the child writes to the respective stream.
+pub fn run_with_toolchain_streaming(
+    toolchain: &str,
+    binary: &str,
+    args: &[&str],
+    env: &HashMap<String, String>,
+    cwd: &Path,
+    mut on_stdout: impl FnMut(&str) + Send,
+    mut on_stderr: impl FnMut(&str) + Send,
+) -> Result<ExitStatus> {
+    let process = process();
+    let cfg = cfg(true, &process)?;
+    let mut cmd = toolchain_command(&cfg, toolchain, binary, args, env, cwd)?;
+    let mut child = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| RustupError::RunningCommand {
+            name: binary.into(),
+        })?;
+
+    let stdout = child.stdout.take().expect("stdout was piped");
+    let stderr = child.stderr.take().expect("stderr was piped");
+    thread::scope(|s| {
+        let stderr = s.spawn(move || {
+            BufReader::new(stderr)
+                .lines()
+                .try_for_each(|line| line.map(|l| on_stderr(&l)))
+        });
+        BufReader::new(stdout)
+            .lines()
+            .try_for_each(|line| line.map(|l| on_stdout(&l)))?;
+        stderr.join().expect("stderr reader panicked")
+    })
+    .context("error reading the output of the child process")?;
+
+    Ok(child.wait()?)
+}
+
+/// Builds the command running `binary` from the installed `toolchain`.
+fn toolchain_command(
+    cfg: &Cfg<'_>,
+    toolchain: &str,
+    binary: &str,
+    args: &[&str],
+    env: &HashMap<String, String>,
+    cwd: &Path,
+) -> Result<Command> {
+    let name = installed_toolchain_name(cfg, toolchain)?;
+    let mut cmd = Toolchain::new(cfg, name.into())?.command(binary)?;
+    cmd.args(args).envs(env).current_dir(cwd);
+    Ok(cmd)
+}
+
+/// The environment variables `rustup run <toolchain>` sets for the tools it
+/// runs in `cwd`, e.g. `RUSTUP_TOOLCHAIN`, `CARGO_HOME` and the updated
+/// `PATH`, without spawning anything.
+///
+/// Variables whose values are not valid Unicode are converted lossily.
+pub fn env_vars(toolchain: &str, cwd: &Path) -> Result<HashMap<String, String>> {
+    let process = process();
+    let mut cfg = cfg(true, &process)?;
+    cfg.current_dir = cwd.to_owned();
+    let name = installed_toolchain_name(&cfg, toolchain)?;
+
+    let mut cmd = Command::new("rustc");
+    Toolchain::new(&cfg, name.into())?.set_env(&mut cmd);
+    Ok(cmd
+        .get_envs()
+        .filter_map(|(key, value)| {
+            let value = value?;
+            Some((
+                key.to_string_lossy().into_owned(),
+                value.to_string_lossy().into_owned(),
+            ))
+        })
+        .collect())
+}
+
+/// Register the toolchain in `path`, e.g. a build of Rust from source, as the
+/// custom toolchain `name`, as `rustup toolchain link` does.
+///
+/// The directory is linked into the toolchains directory rather than copied,
+/// so it must contain at least `bin/rustc`. Names of official channels, such
+/// as `stable`, are rejected with [`RustupError::InvalidToolchainName`]. Where
+/// directories cannot be linked, a `<name>.link` file holding the path is
+/// written instead, and followed wherever the toolchain is used.
+/// Must not be called from within a tokio runtime.
+pub fn link_toolchain(name: &str, path: &Path) -> Result<()> {
+    let process = process();
+    let cfg = cfg(true, &process)?;
+    let dest = CustomToolchainName::try_from(name)
+        .map_err(|_| RustupError::InvalidToolchainName(name.to_owned()))?;
+    if !path
+        .join("bin")
+        .join(format!("rustc{EXE_SUFFIX}"))
+        .is_file()
+    {
+        return Err(RustupError::BinaryNotInstalled {
+            binary: "rustc".to_owned(),
+            toolchain: name.to_owned(),
+        });
+    }
+
+    cfg.ensure_toolchains_dir()?;
+    let src = cfg.current_dir.join(path);
+    block_on(async {
+        InstallMethod::Link {
+            src: &src,
+            dest: &dest,
+            cfg: &cfg,
+        }
+        .install()
+        .await?;
+        Ok(())
+    })
+}
+
+/// The same as [`link_toolchain`].
+pub fn create_toolchain_from_path(name: &str, path: &Path) -> Result<()> {
+    link_toolchain(name, path)
+}
+
+/// Write the completion script for `rustup` in `shell` to `writer`, as
+/// `rustup completions` does.
+///
+/// The toolchains installed at the time are offered wherever a toolchain
+/// name is expected, so the script should be regenerated after installing
+/// or removing one.
+pub fn generate_completions(shell: Shell, writer: &mut dyn Write) -> Result<()> {
+    let process = process();
+    let cfg = cfg(true, &process)?;
+    let toolchains = cfg
+        .list_toolchains()?
+        .iter()
+        .map(|t| t.to_string())
+        .collect::<Vec<_>>();
+    clap_complete::generate(
+        clap_complete::Shell::from(shell),
+        &mut rustup_mode::completion_command(&toolchains),
+        "rustup",
+        writer,
+    );
+    Ok(())
+}
+
+/// The completion script for `shell` installed where `rustup completions
+/// --help` suggests, as `rustup self completions-location` prints.
+///
+/// Returns `None` if there is no script there. PowerShell and Elvish
+/// completions are sourced from the shell's profile, so there is never a
+/// file for them.
+pub fn completion_file_path(shell: Shell) -> Result<Option<PathBuf>> {
+    let process = process();
+    Ok(completions::installed_script(shell.into(), &process))
+}
+
+/// List the values an argument of `kind` can take, as the completion scripts
+/// written by `rustup completions --dynamic` do when completing it.
+///
+/// Only the local installation is read, so that this is fast enough to run on
+/// every completion: targets and components come from the installed manifest
+/// of the active toolchain, and there are none without one.
+pub fn completion_candidates(kind: CompletionKind) -> Result<Vec<String>> {
+    let process = process();
+    let cfg = cfg(true, &process)?;
+    Ok(completion_candidates_for(&cfg, kind)?)
+}
+
+pub(crate) fn completion_candidates_for(
+    cfg: &Cfg<'_>,
+    kind: CompletionKind,
+) -> anyhow::Result<Vec<String>> {
+    let mut candidates = Vec::new();
+    match kind {
+        CompletionKind::Toolchain => {
+            candidates.extend(cfg.list_toolchains()?.iter().map(|t| t.to_string()));
+            candidates.extend(["stable", "beta", "nightly"].map(String::from));
+        }
+        CompletionKind::Profile => {
+            candidates.extend(Profile::value_variants().iter().map(|p| p.to_string()));
+        }
+        CompletionKind::Target | CompletionKind::Component => {
+            let Some((LocalToolchainName::Named(ToolchainName::Official(desc)), _)) =
+                cfg.active_toolchain()?
+            else {
+                return Ok(candidates);
+            };
+            let Ok(distributable) = DistributableToolchain::new(cfg, desc) else {
+                return Ok(candidates);
+            };
+            for status in distributable.components()? {
+                if !status.available {
+                    continue;
+                }
+                let short_name = status.component.short_name_in_manifest();
+                match (kind, &status.component.target) {
+                    (CompletionKind::Target, Some(target)) if short_name == "rust-std" => {
+                        candidates.push(target.to_string())
+                    }
+                    (CompletionKind::Component, _) => candidates.push(short_name.clone()),
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    let mut seen = HashSet::new();
+    candidates.retain(|c| seen.insert(c.clone()));
+    Ok(candidates)
+}
+
+/// Pack an installed toolchain into the archive `dest`, for [`import_toolchain`]
+/// to install it on another machine.
+///
+/// A sidecar describing the toolchain is written to `dest` with `.toml`
+/// appended; it must be kept next to the archive.
+pub fn export_toolchain(name: &str, dest: &Path, format: ArchiveFormat) -> Result<()> {
+    let process = process();
+    let cfg = cfg(true, &process)?;
+    let name = installed_toolchain_name(&cfg, name)?;
+    let toolchain = Toolchain::new(&cfg, (&name).into())?;
+    // Linked toolchains are packed with their contents
+    let src =
+        fs::canonicalize(toolchain.path()).with_context(|| RustupError::ReadingDirectory {
+            name: "toolchain",
+            path: toolchain.path().to_owned(),
+        })?;
+    let host = match &name {
+        ToolchainName::Official(desc) => desc.target.clone(),
+        ToolchainName::Custom(_) => cfg.get_default_host_triple()?,
+    };
+
+    let writing = || RustupError::WritingFile {
+        name: "toolchain archive",
+        path: dest.to_owned(),
+    };
+    let file = fs::File::create(dest).with_context(writing)?;
+    match format {
+        ArchiveFormat::Tar => pack_toolchain(file, &src).map(drop),
+        ArchiveFormat::TarXz => pack_toolchain(XzEncoder::new(file, 6), &src)
+            .and_then(|w| w.finish())
+            .map(drop),
+        ArchiveFormat::TarZst => zstd::Encoder::new(file, 0)
+            .and_then(|w| pack_toolchain(w, &src))
+            .and_then(|w| w.finish())
+            .map(drop),
+    }
+    .with_context(writing)?;
+
+    let manifest = ExportManifest {
+        name: name.to_string(),
+        host: host.to_string(),
+        format,
+        components: toolchain
+            .installed_components()?
+            .iter()
+            .map(|c| c.name().to_owned())
+            .collect(),
+    };
+    let manifest = toml::to_string(&manifest).context("could not serialize toolchain manifest")?;
+    Ok(utils::write_file(
+        "toolchain manifest",
+        &export_manifest_path(dest),
+        &manifest,
+    )?)
+}
+
+/// Install a toolchain from an archive written by [`export_toolchain`].
+///
+/// The toolchain keeps the name it was exported with. Fails with
+/// [`RustupError::ToolchainAlreadyInstalled`] rather than replacing an
+/// installed toolchain of that name.
+pub fn import_toolchain(archive: &Path) -> Result<()> {
+    let process = process();
+    let cfg = cfg(true, &process)?;
+    let manifest_path = export_manifest_path(archive);
+    let manifest = utils::read_file("toolchain manifest", &manifest_path)?;
+    let manifest =
+        toml::from_str::<ExportManifest>(&manifest).with_context(|| RustupError::ParsingFile {
+            name: "toolchain manifest",
+            path: manifest_path.clone(),
+        })?;
+
+    let name = ToolchainName::try_from(&manifest.name)
+        .map_err(|_| RustupError::InvalidToolchainName(manifest.name.clone()))?;
+    let local_name = LocalToolchainName::from(&name);
+    if Toolchain::exists(&cfg, &local_name)? {
+        return Err(RustupError::ToolchainAlreadyInstalled(manifest.name));
+    }
+    let host = cfg.get_default_host_triple()?;
+    if manifest.host != *host {
+        warn!(
+            "toolchain '{name}' was exported for '{}', and may not run on '{host}'",
+            manifest.host
+        );
+    }
+
+    let tmp_cx = temp::Context::new(cfg.rustup_dir.join("tmp"), cfg.dist_root_server.as_str());
+    let staging_dir = tmp_cx.new_directory()?;
+    let staging_path = staging_dir.join("toolchain");
+    let file = fs::File::open(archive).with_context(|| RustupError::ReadingFile {
+        name: "toolchain archive",
+        path: archive.to_owned(),
+    })?;
+    match manifest.format {
+        ArchiveFormat::Tar => unpack_toolchain(file, &staging_path),
+        ArchiveFormat::TarXz => unpack_toolchain(XzDecoder::new(file), &staging_path),
+        ArchiveFormat::TarZst => {
+            zstd::Decoder::new(file).and_then(|r| unpack_toolchain(r, &staging_path))
+        }
+    }
+    .with_context(|| format!("could not extract '{}'", archive.display()))?;
+
+    // Make sure the archive holds what the sidecar promised
+    let components = Components::open(InstallPrefix::from(staging_path.clone()))?.list()?;
+    for expected in &manifest.components {
+        if !components.iter().any(|c| c.name() == expected) {
+            return Err(RustupError::CorruptComponent(expected.clone()));
+        }
+    }
+
+    cfg.ensure_toolchains_dir()?;
+    Ok(utils::rename(
+        "toolchain",
+        &staging_path,
+        &cfg.toolchain_path(&local_name),
+        process.permit_copy_rename(),
+    )?)
+}
+
+fn export_manifest_path(archive: &Path) -> PathBuf {
+    let mut path = archive.as_os_str().to_owned();
+    path.push(".toml");
+    PathBuf::from(path)
+}
+
+fn pack_toolchain<W: Write>(writer: W, src: &Path) -> io::Result<W> {
+    let mut builder = tar::Builder::new(writer);
+    builder.follow_symlinks(false);
+    builder.append_dir_all(".", src)?;
+    builder.into_inner()
+}
+
+fn unpack_toolchain(reader: impl Read, dest: &Path) -> io::Result<()> {
+    let mut archive = tar::Archive::new(reader);
+    archive.set_preserve_permissions(true);
+    archive.unpack(dest)
+}
+
+/// Remove an installed toolchain.
+///
+/// Fails with [`RustupError::ToolchainNotInstalled`] if there is no such
+/// toolchain, and with [`RustupError::ToolchainIsDefault`] if it is the
+/// default toolchain and `force` is `false`.
+pub fn remove_toolchain(name: &str, force: bool) -> Result<()> {
+    let process = process();
+    let cfg = cfg(true, &process)?;
+    let name = installed_toolchain_name(&cfg, name)?;
+    if !force && cfg.get_default()?.as_ref() == Some(&name) {
+        return Err(RustupError::ToolchainIsDefault(name));
+    }
+    Ok(Toolchain::ensure_removed(&cfg, name.into())?)
+}
+
+/// Remove every installed toolchain except those in `keep`, the default
+/// toolchain and those selected by an override, like
+/// `rustup toolchain remove --all-but-default`.
+///
+/// The overrides are the directory overrides set with `rustup override set`
+/// and any toolchain file that is active in the current directory. Returns
+/// the names of the removed toolchains; with `dry_run` those that would be
+/// removed are returned, but nothing is deleted.
+pub fn remove_all_toolchains_except(keep: &[&str], dry_run: bool) -> Result<Vec<String>> {
+    let process = process();
+    let cfg = cfg(true, &process)?;
+    remove_all_toolchains_except_for(&cfg, keep, dry_run)
+}
+
+pub(crate) fn remove_all_toolchains_except_for(
+    cfg: &Cfg<'_>,
+    keep: &[&str],
+    dry_run: bool,
+) -> Result<Vec<String>> {
+    let mut keep = toolchains_in_use(cfg, keep, &[])?;
+    if let Some((LocalToolchainName::Named(name), _)) = cfg.active_toolchain()? {
+        keep.push(name);
+    }
+    let others = cfg
+        .list_toolchains()?
+        .into_iter()
+        .filter(|name| !keep.contains(name))
+        .collect();
+    Ok(remove_toolchains(cfg, others, dry_run)?.removed)
+}
+
+/// Remove the installed toolchains that nothing refers to.
+///
+/// A toolchain is kept if it is listed in `retain`, is the default toolchain,
+/// is used by a directory override, or is the channel of a `rust-toolchain`
+/// or `rust-toolchain.toml` file under `search_paths`. The search skips hidden
+/// directories, `target` and `node_modules` directories, and directories more
+/// than eight levels down. With `dry_run` the report lists what would be
+/// removed, but nothing is deleted.
+pub fn gc_toolchains(retain: &[&str], search_paths: &[&Path], dry_run: bool) -> Result<GcReport> {
+    let process = process();
+    let cfg = cfg(true, &process)?;
+    let keep = toolchains_in_use(&cfg, retain, search_paths)?;
+    let unused = cfg
+        .list_toolchains()?
+        .into_iter()
+        .filter(|name| !keep.contains(name))
+        .collect();
+    remove_toolchains(&cfg, unused, dry_run)
+}
+
+/// Remove the dated nightly toolchains, such as `nightly-2024-01-01`, whose
+/// date is more than `older_than` ago, like `rustup toolchain gc`.
+///
+/// The toolchains [`gc_toolchains`] would keep without any `retain` are kept
+/// here too, however old they are. With `dry_run` the report lists what would
+/// be removed, but nothing is deleted.
+pub fn gc_old_nightlies(
+    older_than: Duration,
+    search_paths: &[&Path],
+    dry_run: bool,
+) -> Result<GcReport> {
+    let process = process();
+    let cfg = cfg(true, &process)?;
+    gc_old_nightlies_for(&cfg, older_than, search_paths, dry_run)
+}
+
+pub(crate) fn gc_old_nightlies_for(
+    cfg: &Cfg<'_>,
+    older_than: Duration,
+    search_paths: &[&Path],
+    dry_run: bool,
+) -> Result<GcReport> {
+    let cutoff = SystemTime::now()
+        .checked_sub(older_than)
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .and_then(|since_epoch| DateTime::from_timestamp(since_epoch.as_secs() as i64, 0))
+        .map_or(NaiveDate::MIN, |time| time.date_naive());
+    let keep = toolchains_in_use(cfg, &[], search_paths)?;
+    let old = cfg
+        .list_toolchains()?
+        .into_iter()
+        .filter(|name| {
+            let ToolchainName::Official(desc) = name else {
+                return false;
+            };
+            let date = desc
+                .date
+                .as_deref()
+                .and_then(|date| NaiveDate::parse_from_str(date, "%Y-%m-%d").ok());
+            desc.channel == dist::Channel::Nightly && date.is_some_and(|date| date < cutoff)
+        })
+        .filter(|name| !keep.contains(name))
+        .collect();
+    remove_toolchains(cfg, old, dry_run)
+}
+
+/// Removes the nightly toolchains for the target of `installed` beyond the
+/// `keep` most recent ones, by date, where an undated `nightly` is dated by its
+/// installed release.
+///
+/// `installed` is always kept, as are the toolchains [`gc_toolchains`] would
+/// keep without any `retain` and the toolchain active in the current directory.
+pub(crate) fn prune_nightlies_for(
+    cfg: &Cfg<'_>,
+    installed: &ToolchainDesc,
+    keep: usize,
+) -> Result<GcReport> {
+    let mut in_use = toolchains_in_use(cfg, &[], &[])?;
+    if let Some((LocalToolchainName::Named(name), _)) = cfg.active_toolchain()? {
+        in_use.push(name);
+    }
+
+    let mut nightlies = Vec::new();
+    for desc in cfg
+        .list_toolchains()?
+        .into_iter()
+        .filter_map(|name| match name {
+            ToolchainName::Official(desc) => Some(desc),
+            ToolchainName::Custom(_) => None,
+        })
+    {
+        if desc.channel != dist::Channel::Nightly || desc.target != installed.target {
+            continue;
+        }
+        let date = match &desc.date {
+            Some(date) => Some(date.clone()),
+            None => DistributableToolchain::new(cfg, desc.clone())
+                .and_then(|toolchain| Ok(toolchain.get_manifest()?.date))
+                .ok(),
+        };
+        let date = date.and_then(|date| NaiveDate::parse_from_str(&date, "%Y-%m-%d").ok());
+        nightlies.push((date, desc));
+    }
+    // Newest first, with undated toolchains that cannot be read last; among
+    // toolchains of the same date, `installed` comes first
+    nightlies.sort_by_key(|(date, desc)| std::cmp::Reverse((*date, desc == installed)));
+
+    let old = nightlies
+        .into_iter()
+        .skip(keep)
+        .map(|(_, desc)| ToolchainName::Official(desc))
+        .filter(|name| {
+            !in_use.contains(name) && *name != ToolchainName::Official(installed.clone())
+        })
+        .collect();
+    remove_toolchains(cfg, old, false)
+}
+
+/// The toolchains that `retain` lists, that are the default or are used by an
+/// override, or that are named by a toolchain file under `search_paths`.
+fn toolchains_in_use(
+    cfg: &Cfg<'_>,
+    retain: &[&str],
+    search_paths: &[&Path],
+) -> Result<Vec<ToolchainName>> {
+    let host = cfg.get_default_host_triple()?;
+    let resolve = |name: &str| -> Result<ToolchainName> {
+        Ok(ResolvableToolchainName::try_from(name)
+            .map_err(|_| RustupError::InvalidToolchainName(name.to_owned()))?
+            .resolve(&host)?)
+    };
+
+    let mut keep = retain
+        .iter()
+        .map(|name| resolve(name))
+        .collect::<Result<Vec<_>>>()?;
+    keep.extend(cfg.get_default()?);
+    let overrides = cfg
+        .settings_file
+        .with(|s| Ok(s.overrides.values().cloned().collect::<Vec<_>>()))?;
+    for name in overrides {
+        match resolve(&name) {
+            Ok(name) => keep.push(name),
+            Err(e) => warn!("ignoring override for toolchain '{name}': {e}"),
+        }
+    }
+    let mut toolchain_files = Vec::new();
+    for path in search_paths {
+        find_toolchain_files(path, TOOLCHAIN_FILE_SEARCH_DEPTH, &mut toolchain_files);
+    }
+    for file in toolchain_files {
+        let channel = match crate::toolchain::parse_toolchain_file(&file) {
+            Ok(spec) => spec.channel,
+            Err(e) => {
+                warn!("ignoring toolchain file '{}': {e:#}", file.display());
+                continue;
+            }
+        };
+        if let Some(channel) = channel {
+            match resolve(&channel) {
+                Ok(name) => keep.push(name),
+                Err(e) => warn!("ignoring toolchain file '{}': {e}", file.display()),
+            }
+        }
+    }
+    Ok(keep)
+}
+
+/// Removes `toolchains`, or only measures them with `dry_run`.
+fn remove_toolchains(
+    cfg: &Cfg<'_>,
+    toolchains: Vec<ToolchainName>,
+    dry_run: bool,
+) -> Result<GcReport> {
+    let _lock = if dry_run {
+        None
+    } else {
+        Some(cfg.lock_home()?)
+    };
+    let mut report = GcReport::default();
+    for name in toolchains {
+        let path = cfg.toolchains_dir.join(name.to_string());
+        report.freed_bytes +=
+            dir_size(&path, &Mutex::default()).with_context(|| RustupError::ReadingDirectory {
+                name: "toolchain",
+                path: path.clone(),
+            })?;
+        if !dry_run {
+            Toolchain::ensure_removed(cfg, (&name).into())?;
+        }
+        report.removed.push(name.to_string());
+    }
+    Ok(report)
+}
+
+/// How many levels of subdirectories [`gc_toolchains`] searches for toolchain
+/// files.
+const TOOLCHAIN_FILE_SEARCH_DEPTH: usize = 8;
+
+/// Collects the toolchain files in `dir` and its subdirectories, up to
+/// `depth` levels down. Hidden directories, build output and dependency
+/// directories are skipped, symlinks are not followed, and unreadable
+/// directories are ignored.
+fn find_toolchain_files(dir: &Path, depth: usize, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        // Unlike `fs::metadata`, this is the type of a symlink itself.
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        let name = entry.file_name();
+        let name = name.to_str();
+        if file_type.is_dir() {
+            let skipped = match name {
+                Some(name) => name.starts_with('.') || matches!(name, "target" | "node_modules"),
+                None => false,
+            };
+            if depth > 0 && !skipped {
+                find_toolchain_files(&entry.path(), depth - 1, files);
+            }
+        } else if file_type.is_file()
+            && matches!(name, Some("rust-toolchain" | "rust-toolchain.toml"))
+        {
+            files.push(entry.path());
+        }
+    }
+}
+
+/// The directory rustup keeps its toolchains and settings in, as
+/// `rustup show home` prints it.
+pub fn rustup_home() -> Result<PathBuf> {
+    Ok(process().rustup_home()?)
+}
+
+/// The directory cargo and the rustup proxies are installed in, as
+/// `rustup show cargo-home` prints it.
+pub fn cargo_home() -> Result<PathBuf> {
+    Ok(process().cargo_home()?)
+}
+
+/// Measure the disk space used by RUSTUP_HOME.
+///
+/// Hard-linked files are only counted once, towards whichever toolchain is
+/// measured first. Hard links are not detected on Windows.
+pub fn disk_usage() -> Result<DiskUsage> {
+    let process = process();
+    let cfg = cfg(true, &process)?;
+    let seen = Mutex::default();
+    let measure = |path: &Path| -> Result<u64> {
+        Ok(
+            dir_size(path, &seen).with_context(|| RustupError::ReadingDirectory {
+                name: "rustup home",
+                path: path.to_owned(),
+            })?,
+        )
+    };
+
+    let toolchains = cfg
+        .list_toolchains()?
+        .into_iter()
+        .map(|name| {
+            let path = cfg.toolchains_dir.join(name.to_string());
+            (name.to_string(), path)
+        })
+        .collect::<Vec<_>>()
+        .into_par_iter()
+        .map(|(name, path)| Ok((name, measure(&path)?)))
+        .collect::<Result<Vec<_>>>()?;
+    let cache_dir = DownloadCfg::new(&cfg).cache_dir;
+    let downloads_cache_bytes = measure(&cfg.download_dir)? + measure(&cache_dir)?;
+
+    // Everything else, e.g. the settings and update hashes
+    let mut total_bytes =
+        downloads_cache_bytes + toolchains.iter().map(|(_, size)| size).sum::<u64>();
+    let counted = [&cfg.toolchains_dir, &cfg.download_dir, &cache_dir];
+    for entry in fs::read_dir(&cfg.rustup_dir).with_context(|| RustupError::ReadingDirectory {
+        name: "rustup home",
+        path: cfg.rustup_dir.clone(),
+    })? {
+        let path = entry?.path();
+        if !counted.contains(&&path) {
+            total_bytes += measure(&path)?;
+        }
+    }
+
+    Ok(DiskUsage {
+        toolchains,
+        downloads_cache_bytes,
+        total_bytes,
+    })
+}
+
+/// The combined size of the files in `path`, walking directories in parallel
+/// without following symlinks. Files whose identity is already in `seen` are
+/// skipped, so that hard links are counted once; files that disappear during
+/// the walk are ignored.
+fn dir_size(path: &Path, seen: &Mutex<HashSet<(u64, u64)>>) -> io::Result<u64> {
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e),
+    };
+    if !metadata.is_dir() {
+        return Ok(match file_id(&metadata) {
+            Some(id) if !seen.lock().unwrap().insert(id) => 0,
+            _ => metadata.len(),
+        });
+    }
+    let entries = match fs::read_dir(path) {
+        Ok(entries) => entries
+            .map(|entry| Ok(entry?.path()))
+            .collect::<io::Result<Vec<_>>>()?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e),
+    };
+    entries.par_iter().map(|path| dir_size(path, seen)).sum()
+}
+
+/// Identifies files with more than one hard link.
+#[cfg(unix)]
+fn file_id(metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    (metadata.nlink() > 1).then(|| (metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_id(_: &fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+/// The profile an installed toolchain was installed with, e.g. [`Profile::Minimal`].
+///
+/// Toolchains installed without a record of their profile, such as custom
+/// toolchains or those installed by older versions of rustup, report the
+/// default profile set with `rustup set profile` instead.
+pub fn active_profile(toolchain: &str) -> Result<Profile> {
+    let process = process();
+    let cfg = cfg(true, &process)?;
+    let name = installed_toolchain_name(&cfg, toolchain)?;
+    Ok(toolchain_profile(&Toolchain::new(&cfg, name.into())?)?)
+}
+
+/// The profile `toolchain` was installed with, or else the default profile.
+pub(crate) fn toolchain_profile(toolchain: &Toolchain<'_>) -> anyhow::Result<Profile> {
+    match toolchain.install_metadata()? {
+        Some(metadata) => Ok(metadata.profile),
+        None => toolchain.cfg.get_profile(),
+    }
+}
+
+/// Check the files of an installed toolchain against its component manifests.
+///
+/// Files are hashed and compared with the hashes recorded at install time;
+/// components installed by older versions of rustup are only checked for
+/// missing files. No network access is needed.
+pub fn verify_toolchain(name: &str) -> Result<VerificationReport> {
+    let process = process();
+    let cfg = cfg(true, &process)?;
+    let name = installed_toolchain_name(&cfg, name)?;
+    let toolchain = Toolchain::new(&cfg, name.into())?;
+
+    let mut missing_files = Vec::new();
+    let mut corrupted_files = Vec::new();
+    for component in toolchain.installed_components()? {
+        let (missing, corrupted) = component.verify()?;
+        missing_files.extend(missing);
+        corrupted_files.extend(corrupted);
+    }
+
+    Ok(VerificationReport {
+        ok: missing_files.is_empty() && corrupted_files.is_empty(),
+        missing_files,
+        corrupted_files,
+    })
+}
+
+/// Check the installation for common problems, like `rustup self diagnose`.
+///
+/// The checks look for rustup and `$CARGO_HOME/bin` on `PATH`, another Rust
+/// installation on `PATH`, installed toolchains with missing or corrupted
+/// files, an unwritable `RUSTUP_HOME` and an unreachable dist server. A failed
+/// check is reported in the [`DiagnosticReport`] rather than as an error.
+///
+/// Must not be called from within a tokio runtime.
+pub fn diagnose() -> Result<DiagnosticReport> {
+    let process = process();
+    let cfg = cfg(true, &process)?;
+    block_on(async { Ok(self_update::diagnose(&cfg).await) })
+}
+
+/// Check that rustup itself is intact, as `rustup self check` does: that its
+/// binary is the release build of this version, that the proxies in
+/// `CARGO_HOME/bin` run it, that `rustup self update` can replace it, and
+/// that this rustup supports the metadata of the installed toolchains.
+///
+/// Fetches the hash of the release from `RUSTUP_UPDATE_ROOT`. As with
+/// [`diagnose`], the outcome of each check is reported in the
+/// [`SelfCheckReport`] rather than as an error.
+pub fn check_self_integrity() -> Result<SelfCheckReport> {
+    let process = process();
+    let cfg = cfg(true, &process)?;
+    block_on(async { Ok(self_update::check_integrity(&cfg).await) })
+}
+
+/// Make an installed toolchain the default one, as `rustup default` does.
+///
+/// Fails with [`RustupError::ToolchainNotInstalled`] rather than recording a
+/// default that does not exist; unlike `rustup default`, nothing is installed.
+pub fn set_default_toolchain(name: &str) -> Result<()> {
+    let process = process();
+    let cfg = cfg(true, &process)?;
+    let name = installed_toolchain_name(&cfg, name)?;
+    Ok(cfg.set_default(Some(&name))?)
+}
+
+/// Use an installed toolchain whenever running in `path` or its subdirectories,
+/// as `rustup override set` does.
+///
+/// Fails with [`RustupError::ToolchainNotInstalled`] if there is no such
+/// toolchain; unlike `rustup override set`, nothing is installed.
+pub fn set_override(path: &Path, toolchain: &str) -> Result<()> {
+    let process = process();
+    let cfg = cfg(true, &process)?;
+    let name = installed_toolchain_name(&cfg, toolchain)?;
+    Ok(cfg.make_override(path, &name)?)
+}
+
+/// Remove the override for `path`, if there is one.
+pub fn unset_override(path: &Path) -> Result<()> {
+    let process = process();
+    let cfg = cfg(true, &process)?;
+    cfg.with_settings_mut(|s| {
+        s.remove_override(path);
+        Ok(())
+    })?;
+    Ok(())
+}
+
+/// Pin `dir` to an installed toolchain by writing its channel to the
+/// `rust-toolchain.toml` in `dir`, as `rustup toolchain pin` does.
+///
+/// With `include_components`, the installed components beyond the minimal
+/// profile and the installed targets other than the host are written too.
+/// Other keys of an existing `rust-toolchain.toml` are kept, and the directory
+/// override for `dir`, if any, is removed so that the file takes effect.
+pub fn pin_toolchain(dir: &Path, toolchain: &str, include_components: bool) -> Result<()> {
+    let process = process();
+    let cfg = cfg(true, &process)?;
+    pin_toolchain_for(&cfg, dir, toolchain, include_components)?;
+    Ok(())
+}
+
+/// Does the work of [`pin_toolchain`], returning the path written.
+pub(crate) fn pin_toolchain_for(
+    cfg: &Cfg<'_>,
+    dir: &Path,
+    toolchain: &str,
+    include_components: bool,
+) -> Result<PathBuf> {
+    let name = installed_toolchain_name(cfg, toolchain)?;
+    let mut section = toml::Table::new();
+    match &name {
+        ToolchainName::Official(desc) => {
+            // Toolchain files may not name a target, which is left to each machine
+            let channel = match &desc.date {
+                Some(date) => format!("{}-{date}", desc.channel),
+                None => desc.channel.to_string(),
+            };
+            section.insert("channel".to_owned(), channel.into());
+
+            if include_components {
+                let distributable = DistributableToolchain::new(cfg, desc.clone())?;
+                let manifest = distributable.get_manifest()?;
+                let minimal = manifest.get_profile_components(Profile::Minimal, &desc.target)?;
+                let (mut components, mut targets) = (Vec::new(), Vec::new());
+                for status in distributable.components()? {
+                    let component = status.component;
+                    if !status.installed || minimal.contains(&component) {
+                        continue;
+                    }
+                    let short_name = manifest.short_name(&component);
+                    match &component.target {
+                        Some(target) if short_name == "rust-std" => {
+                            targets.push(target.to_string())
+                        }
+                        Some(target) if *target != desc.target => {}
+                        _ => components.push(short_name.to_owned()),
+                    }
+                }
+                section.insert("components".to_owned(), components.into());
+                section.insert("targets".to_owned(), targets.into());
+            }
+        }
+        ToolchainName::Custom(custom) => {
+            section.insert("channel".to_owned(), custom.to_string().into());
+        }
+    }
+
+    let path = dir.join("rust-toolchain.toml");
+    write_toolchain_section(&path, section)?;
+    if cfg.with_settings_mut(|s| Ok(s.remove_override(dir)))? {
+        info!("override toolchain for '{}' removed", dir.display());
+    }
+    Ok(path)
+}
+
+/// Sets the keys of `section` in the `[toolchain]` table of the toolchain file
+/// at `path`, keeping its other settings, or creates the file.
+fn write_toolchain_section(path: &Path, section: toml::Table) -> anyhow::Result<()> {
+    let mut file = if utils::is_file(path) {
+        let contents = utils::read_file("toolchain file", path)?;
+        toml::from_str::<toml::Table>(&contents).with_context(|| RustupError::ParsingFile {
+            name: "toolchain file",
+            path: path.to_owned(),
+        })?
+    } else {
+        toml::Table::new()
+    };
+    let existing = file
+        .entry("toolchain")
+        .or_insert_with(|| toml::Table::new().into());
+    let existing = existing.as_table_mut().ok_or_else(|| {
+        anyhow::anyhow!(
+            "'{}' has a `toolchain` key that is not a table",
+            path.display()
+        )
+    })?;
+    // A `path` would take precedence over the channel
+    existing.remove("path");
+    existing.extend(section);
+
+    let contents = toml::to_string(&file).context("could not serialize the toolchain file")?;
+    utils::write_file("toolchain file", path, &contents)
+}
+
+/// Move the dated channel pinned by the `rust-toolchain.toml` in `dir` to the
+/// newest release of that channel, as `rustup toolchain update-file` does.
+///
+/// Nightlies that lack any of the components or targets the file asks for,
+/// including those of its profile, are skipped in favor of older ones. With
+/// `dry_run` the file is left as it is, and the result only reports the date
+/// it would be updated to. Nothing is installed.
+pub fn update_toolchain_file(dir: &Path, dry_run: bool) -> Result<ToolchainFileUpdateResult> {
+    let process = process();
+    let cfg = cfg(true, &process)?;
+    block_on(async { Ok(update_toolchain_file_for(&cfg, dir, dry_run).await?) })
+}
+
+pub(crate) async fn update_toolchain_file_for(
+    cfg: &Cfg<'_>,
+    dir: &Path,
+    dry_run: bool,
+) -> anyhow::Result<ToolchainFileUpdateResult> {
+    let path = dir.join("rust-toolchain.toml");
+    let spec = crate::toolchain::parse_toolchain_file(&path)?;
+    let pinned = spec
+        .channel
+        .as_deref()
+        .ok_or_else(|| anyhow::anyhow!("'{}' does not name a channel", path.display()))?
+        .parse::<PartialToolchainDesc>()?;
+    let old_date = pinned.date.clone().ok_or_else(|| {
+        anyhow::anyhow!(
+            "the channel in '{}' is not pinned to a date",
+            path.display()
+        )
+    })?;
+    let old = NaiveDate::parse_from_str(&old_date, "%Y-%m-%d")
+        .with_context(|| format!("invalid date '{old_date}' in '{}'", path.display()))?;
+
+    let mut desc = PartialToolchainDesc {
+        date: None,
+        ..pinned
+    }
+    .resolve(&cfg.get_default_host_triple()?)?;
+    let channel = desc.channel.to_string();
+    let profile = match spec.profile {
+        Some(profile) => profile,
+        None => cfg.get_profile()?,
+    };
+    let components = spec.components.iter().map(|c| &**c).collect::<Vec<_>>();
+    let targets = spec.targets.iter().map(|t| &**t).collect::<Vec<_>>();
+
+    // Walk back from the newest release to the pinned one, skipping the
+    // nightlies that are missing or lack a requested component
+    let dl_cfg = DownloadCfg::new(cfg);
+    let new_date = loop {
+        let current = match dl_cfg.dl_v2_manifest(None, &desc, cfg).await? {
+            Some((manifest, _)) => {
+                let current = NaiveDate::parse_from_str(&manifest.date, "%Y-%m-%d")
+                    .with_context(|| format!("invalid date '{}' in manifest", manifest.date))?;
+                if current <= old {
+                    break None;
+                }
+                let unavailable = requested_components(
+                    &manifest,
+                    &desc,
+                    Some(profile.clone()),
+                    &components,
+                    &targets,
+                )?
+                .iter()
+                .filter(|c| {
+                    !manifest
+                        .get_package(c.short_name_in_manifest())
+                        .and_then(|p| p.get_target(c.target.as_ref()))
+                        .is_ok_and(|t| t.available())
+                })
+                .map(|c| manifest.name(c))
+                .collect::<Vec<_>>();
+                if unavailable.is_empty() {
+                    break Some(manifest.date);
+                }
+                info!(
+                    "skipping {channel}-{} with missing components: {}",
+                    manifest.date,
+                    unavailable.join(", ")
+                );
+                current
+            }
+            None => match &desc.date {
+                Some(date) => NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                    .with_context(|| format!("invalid date '{date}'"))?,
+                None => return Err(RustupError::ToolchainNotInstallable(desc.to_string()).into()),
+            },
+        };
+
+        // Only nightlies are released often enough to look for an older one
+        let previous = current.pred_opt().unwrap_or(current);
+        if desc.channel != dist::Channel::Nightly || previous <= old {
+            break None;
+        }
+        desc.date = Some(previous.format("%Y-%m-%d").to_string());
+    };
+
+    let updated = match &new_date {
+        Some(date) if !dry_run => {
+            let channel = format!("{channel}-{date}");
+            write_toolchain_section(
+                &path,
+                toml::Table::from_iter([("channel".into(), channel.into())]),
+            )?;
+            true
+        }
+        _ => false,
+    };
+    Ok(ToolchainFileUpdateResult {
+        path,
+        channel,
+        old_date,
+        new_date,
+        updated,
+    })
+}
+
+/// Download through the proxy at `proxy_url` by setting `proxy` in
+/// `settings.toml`, for both HTTP and HTTPS unless `https_proxy` is also set.
+///
+/// As with the other proxy settings, the proxy environment variables, such
+/// as `HTTPS_PROXY`, take precedence.
+pub fn set_proxy(proxy_url: &str) -> Result<()> {
+    let proxy =
+        Url::parse(proxy_url).with_context(|| format!("invalid proxy URL: '{proxy_url}'"))?;
+    let process = process();
+    let cfg = cfg(true, &process)?;
+    cfg.with_settings_mut(|s| {
+        s.proxy = Some(proxy.to_string());
+        Ok(())
+    })?;
+    Ok(())
+}
+
+/// Download from the release server mirrors at `urls`, in that order, by
+/// setting `mirrors` in `settings.toml`, replacing any mirrors set before.
+///
+/// Each file is downloaded from the next mirror when it fails to download
+/// from one, and finally from the release server, as `rustup mirror add`
+/// sets up. An empty `urls` removes all the mirrors.
+pub fn set_mirrors(urls: &[Url]) -> Result<()> {
+    let process = process();
+    let mut cfg = cfg(true, &process)?;
+    cfg.set_mirrors(urls)?;
+    Ok(())
+}
+
+/// Remove the overrides for directories that no longer exist, like
+/// `rustup override clean`, returning those directories. With `dry_run`,
+/// nothing is removed.
+pub fn clean_stale_overrides(dry_run: bool) -> Result<Vec<PathBuf>> {
+    let process = process();
+    let cfg = cfg(true, &process)?;
+    clean_stale_overrides_for(&cfg, dry_run)
+}
+
+pub(crate) fn clean_stale_overrides_for(cfg: &Cfg<'_>, dry_run: bool) -> Result<Vec<PathBuf>> {
+    if dry_run {
+        return Ok(cfg.settings_file.with(|s| Ok(s.stale_overrides()))?);
+    }
+    Ok(cfg.with_settings_mut(|s| {
+        let stale = s.stale_overrides();
+        for dir in &stale {
+            s.remove_override(dir);
+        }
+        Ok(stale)
+    })?)
+}
+
+/// A directory override, as listed by [`list_overrides`] and
+/// `rustup override list --json`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OverrideInfo {
+    /// The directory the override applies to.
+    pub dir: PathBuf,
+    /// The toolchain name the override selects.
+    pub toolchain: String,
+    /// Whether the directory still exists; stale overrides can be removed
+    /// with `rustup override unset --nonexistent`.
+    pub exists: bool,
+}
+
+/// List the directory overrides, including those for directories that no
+/// longer exist.
+pub fn list_overrides() -> Result<Vec<OverrideInfo>> {
+    let process = process();
+    let cfg = cfg(true, &process)?;
+    list_overrides_for(&cfg)
+}
+
+pub(crate) fn list_overrides_for(cfg: &Cfg<'_>) -> Result<Vec<OverrideInfo>> {
+    Ok(cfg.settings_file.with(|s| {
+        Ok(s.overrides
+            .iter()
+            .map(|(dir, toolchain)| OverrideInfo {
+                dir: PathBuf::from(dir),
+                toolchain: toolchain.clone(),
+                exists: Path::new(dir).is_dir(),
+            })
+            .collect())
+    })?)
+}
+
+/// Update an installed toolchain to the latest release of its channel, synchronously.
+pub fn update_toolchain_blocking(name: &str) -> Result<UpdateResult> {
+    block_on(update_toolchain(name))
+}
+
+/// Async version of [`update_toolchain_blocking`]. Requires an existing tokio runtime.
+///
+/// The components of the toolchain are only downloaded if the dist server
+/// advertises a release different from the installed one.
+pub async fn update_toolchain(name: &str) -> Result<UpdateResult> {
+    let process = process();
+    let mut cfg = cfg(true, &process)?;
+    let received = track_downloads(&mut cfg);
+    let desc = toolchain_desc(&cfg, name)?;
+    update_desc(&cfg, desc, false, &received).await
+}
+
+/// Update every installed toolchain, then rustup itself, synchronously.
+pub fn update_all_blocking(config: UpdateConfig) -> Result<Vec<(String, UpdateResult)>> {
+    block_on(update_all(config))
+}
+
+/// Async version of [`update_all_blocking`]. Requires an existing tokio runtime.
+///
+/// Like `rustup update`, this returns the outcome for each installed official
+/// toolchain; custom toolchains are left alone. Rustup updates itself
+/// afterwards unless [`UpdateConfig::no_self_update`] is set, self-updates
+/// are disabled with `rustup set auto-self-update disable`, or
+/// `RUSTUP_NO_SELF_UPDATE` is set.
+pub async fn update_all(config: UpdateConfig) -> Result<Vec<(String, UpdateResult)>> {
+    let process = process();
+    let mut cfg = cfg(true, &process)?;
+    let received = track_downloads(&mut cfg);
+    let cfg = &cfg;
+
+    let mut results = Vec::new();
+    for name in cfg.list_toolchains()? {
+        let ToolchainName::Official(desc) = &name else {
+            continue;
+        };
+        let result = update_desc(cfg, desc.clone(), config.force, &received).await?;
+        results.push((name.to_string(), result));
+    }
+
+    let exit_code = SelfUpdateMode::from_cfg(cfg)?
+        .update(!config.no_self_update, &DownloadCfg::new(cfg))
+        .await?;
+    check_exit_code("self update", exit_code)?;
+    Ok(results)
+}
+
+/// Keeps the latest byte count reported for each component downloaded with `cfg`.
+fn track_downloads(cfg: &mut Cfg<'_>) -> Arc<Mutex<HashMap<String, u64>>> {
+    let received = Arc::new(Mutex::new(HashMap::new()));
+    cfg.progress_handler = Some({
+        let received = received.clone();
+        Arc::new(move |p| {
+            if let InstallProgress::Downloading {
+                component,
+                bytes_received,
+                ..
+            } = p
+            {
+                received.lock().unwrap().insert(component, bytes_received);
+            }
+        })
+    });
+    received
+}
+
+async fn update_desc(
+    cfg: &Cfg<'_>,
+    desc: ToolchainDesc,
+    force: bool,
+    received: &Mutex<HashMap<String, u64>>,
+) -> Result<UpdateResult> {
+    let distributable = match DistributableToolchain::new(cfg, desc.clone()) {
+        Ok(d) => d,
+        Err(RustupError::ToolchainNotInstalled { .. }) => return Ok(UpdateResult::NotInstalled),
+        Err(e) => return Err(e),
+    };
+
+    received.lock().unwrap().clear();
+    let from_date = distributable.get_manifest()?.date;
+    let options = DistOptions::new(&[], &[], &desc, cfg.get_profile()?, force, cfg)?;
+    let status = InstallMethod::Dist(options.for_update(&distributable, false))
+        .install()
+        .await?;
+
+    Ok(match status {
+        UpdateStatus::Unchanged => UpdateResult::AlreadyCurrent { date: from_date },
+        UpdateStatus::Installed | UpdateStatus::Updated(_) => UpdateResult::Updated {
+            from_date,
+            to_date: distributable.get_manifest()?.date,
+            bytes_downloaded: received.lock().unwrap().values().sum(),
+        },
+    })
+}
+
+/// Check whether a newer release of an installed toolchain is available,
+/// without downloading more than its channel manifest.
+///
+/// Must not be called from within a tokio runtime.
+pub fn check_for_toolchain_update(toolchain: &str) -> Result<ToolchainUpdateStatus> {
+    let process = process();
+    let cfg = cfg(true, &process)?;
+    let distributable = match DistributableToolchain::new(&cfg, toolchain_desc(&cfg, toolchain)?) {
+        Ok(d) => d,
+        Err(RustupError::ToolchainNotInstalled { .. }) => {
+            return Ok(ToolchainUpdateStatus::NotInstalled);
+        }
+        Err(e) => return Err(e),
+    };
+    block_on(async { Ok(toolchain_update_status(&distributable).await?) })
+}
+
+/// Compares the installed release of `distributable` with the latest one.
+pub(crate) async fn toolchain_update_status(
+    distributable: &DistributableToolchain<'_>,
+) -> anyhow::Result<ToolchainUpdateStatus> {
+    // The dist version is only known when the channel manifest has changed
+    Ok(match distributable.show_dist_version().await? {
+        Some(to) => ToolchainUpdateStatus::UpdateAvailable {
+            from: distributable
+                .show_version()?
+                .unwrap_or_else(|| "(unknown version)".to_owned()),
+            to,
+        },
+        None => ToolchainUpdateStatus::Current,
+    })
+}
+
+/// Options for [`update_all`].
+#[derive(Clone, Debug, Default)]
+pub struct UpdateConfig {
+    no_self_update: bool,
+    force: bool,
+}
+
+impl UpdateConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Leaves the rustup binary alone, like `rustup update --no-self-update`;
+    /// for managed environments that update rustup by other means. To make
+    /// this stick for every update, set `auto_self_update = "disable"` in
+    /// `settings.toml`.
+    pub fn no_self_update(mut self, no_self_update: bool) -> Self {
+        self.no_self_update = no_self_update;
+        self
+    }
+
+    /// Updates toolchains even if some of their components are missing from
+    /// the new release, like `rustup update --force`.
+    pub fn force(mut self, force: bool) -> Self {
+        self.force = force;
+        self
+    }
+}
+
+/// Work out what installing the named toolchain would download, synchronously.
+pub fn resolve_blocking(
+    name: &str,
+    profile: Profile,
+    components: &[&str],
+    targets: &[&str],
+) -> Result<ResolvedToolchain> {
+    block_on(resolve(name, profile, components, targets))
+}
+
+/// Async version of [`resolve_blocking`]. Requires an existing tokio runtime.
+///
+/// Only the channel manifest is fetched, and nothing is installed; no
+/// files are written outside of rustup's temporary directory.
+pub async fn resolve(
+    name: &str,
+    profile: Profile,
+    components: &[&str],
+    targets: &[&str],
+) -> Result<ResolvedToolchain> {
+    let process = process();
+    let cfg = cfg(true, &process)?;
+    let desc = toolchain_desc(&cfg, name)?;
+    Ok(resolve_for(&cfg, &desc, profile, components, targets, &[]).await?)
+}
+
+/// Resolves the downloads of the requested components that are not in
+/// `installed`, as for [`resolve`].
+async fn resolve_for(
+    cfg: &Cfg<'_>,
+    desc: &ToolchainDesc,
+    profile: Profile,
+    components: &[&str],
+    targets: &[&str],
+    installed: &[Component],
+) -> anyhow::Result<ResolvedToolchain> {
+    let manifest = fetch_manifest_for(cfg, desc).await?;
+    let dl_cfg = DownloadCfg::new(cfg);
+    let mut downloads = Vec::new();
+    let mut missing_components = Vec::new();
+    for component in requested_components(&manifest, desc, Some(profile), components, targets)? {
+        if installed.contains(&component) {
+            continue;
+        }
+        let name = manifest.name(&component);
+        let Ok(Some(bin)) = manifest.binary(&component, dl_cfg.preferred_compression()) else {
+            missing_components.push(name);
+            continue;
+        };
+        let url = dl_cfg.url(&bin.url)?;
+        downloads.push(ComponentDownload {
+            component: name,
+            size: match cfg.offline {
+                true => None,
+                false => {
+                    content_length(&url, dl_cfg.network, dl_cfg.download_config, cfg.process)
+                        .await?
+                }
+            },
+            url: url.to_string(),
+        });
+    }
+
+    Ok(ResolvedToolchain {
+        name: desc.to_string(),
+        rust_version: manifest.get_rust_version().ok().map(str::to_owned),
+        date: manifest.date,
+        total_download_size: downloads.iter().map(|d| d.size).sum(),
+        downloads,
+        missing_components,
+    })
+}
+
+/// Add `component`, e.g. `rust-src`, to an installed toolchain, synchronously.
+pub fn add_component_blocking(toolchain: &str, component: &str, allow_missing: bool) -> Result<()> {
+    block_on(add_component(toolchain, component, allow_missing))
+}
+
+/// Async version of [`add_component_blocking`]. Requires an existing tokio runtime.
+///
+/// Fails with [`RustupError::UnknownComponent`] if the toolchain's release
+/// does not provide the component, unless `allow_missing` is set, in which
+/// case a warning is logged and nothing is installed.
+pub async fn add_component(toolchain: &str, component: &str, allow_missing: bool) -> Result<()> {
+    let process = process();
+    let cfg = cfg(true, &process)?;
+    let distributable = DistributableToolchain::new(&cfg, toolchain_desc(&cfg, toolchain)?)?;
+    let target = distributable.desc().target.clone();
+    let component = Component::try_new(component, &distributable, Some(&target))?;
+    match allow_missing {
+        true => distributable.add_component_if_available(component).await?,
+        false => distributable.add_component(component).await?,
+    }
+    Ok(())
+}
+
+/// Add `component` to an installed toolchain for each of `targets`, or for
+/// each target installed for it when `targets` is `None`, synchronously.
+pub fn add_component_to_targets_blocking(
+    toolchain: &str,
+    component: &str,
+    targets: Option<&[&str]>,
+) -> Result<()> {
+    block_on(add_component_to_targets(toolchain, component, targets))
+}
+
+/// Async version of [`add_component_to_targets_blocking`]. Requires an existing
+/// tokio runtime.
+///
+/// The component is added for all the targets in a single update of the
+/// toolchain, which fails as a whole if the release lacks it for any of them.
+pub async fn add_component_to_targets(
+    toolchain: &str,
+    component: &str,
+    targets: Option<&[&str]>,
+) -> Result<()> {
+    let process = process();
+    let cfg = cfg(true, &process)?;
+    let distributable = DistributableToolchain::new(&cfg, toolchain_desc(&cfg, toolchain)?)?;
+    let targets = targets.map(|t| t.iter().map(|&t| TargetTriple::new(t)).collect::<Vec<_>>());
+    let components = distributable.component_for_targets(component, targets.as_deref())?;
+    Ok(distributable.add_components(components).await?)
+}
+
+/// Remove `component` from an installed toolchain, synchronously.
+pub fn remove_component_blocking(toolchain: &str, component: &str) -> Result<()> {
+    block_on(remove_component(toolchain, component))
+}
+
+/// Async version of [`remove_component_blocking`]. Requires an existing tokio runtime.
+///
+/// A warning is logged if another installed component relies on the removed one.
+pub async fn remove_component(toolchain: &str, component: &str) -> Result<()> {
+    let process = process();
+    let cfg = cfg(true, &process)?;
+    let distributable = DistributableToolchain::new(&cfg, toolchain_desc(&cfg, toolchain)?)?;
+    let target = distributable.desc().target.clone();
+    let component = Component::try_new(component, &distributable, Some(&target))?;
+
+    let removed = component.short_name_in_manifest();
+    for installed in distributable.components()?.iter().filter(|c| c.installed) {
+        let dependent = installed.component.short_name_in_manifest();
+        if COMPONENT_DEPENDENCIES
+            .iter()
+            .any(|&(c, dep)| c == dependent && dep == removed)
+        {
+            warn!("component '{dependent}' relies on '{removed}', which is being removed");
+        }
+    }
+
+    Ok(distributable.remove_component(component).await?)
+}
+
+/// Add the standard library for `target` to an installed toolchain, synchronously.
+pub fn add_target_blocking(toolchain: &str, target: &str) -> Result<()> {
+    block_on(add_target(toolchain, target))
+}
+
+/// Async version of [`add_target_blocking`]. Requires an existing tokio runtime.
+///
+/// Fails with [`RustupError::UnknownTarget`] if the toolchain's release has no
+/// standard library for `target`.
+pub async fn add_target(toolchain: &str, target: &str) -> Result<()> {
+    let process = process();
+    let cfg = cfg(true, &process)?;
+    let distributable = DistributableToolchain::new(&cfg, toolchain_desc(&cfg, toolchain)?)?;
+
+    let available = distributable
+        .components()?
+        .into_iter()
+        .filter(|c| c.component.short_name_in_manifest() == "rust-std" && c.available)
+        .filter_map(|c| c.component.target)
+        .collect::<Vec<_>>();
+    if !available.iter().any(|t| **t == *target) {
+        return Err(RustupError::UnknownTarget {
+            desc: distributable.desc().clone(),
+            target: TargetTriple::new(target),
+            suggestion: closest_target(&available, target),
+        });
+    }
+
+    let component = Component::new(
+        "rust-std".to_owned(),
+        Some(TargetTriple::new(target)),
+        false,
+    );
+    Ok(distributable.add_component(component).await?)
+}
+
+/// Add the standard libraries for all of `targets` to an installed toolchain,
+/// synchronously.
+pub fn add_targets_blocking(toolchain: &str, targets: &[&str]) -> Result<Vec<TargetAddResult>> {
+    block_on(add_targets(toolchain, targets))
+}
+
+/// Async version of [`add_targets_blocking`]. Requires an existing tokio runtime.
+///
+/// Unlike calling [`add_target`] for each target, the manifest is read once
+/// and the missing standard libraries are downloaded together. Targets the
+/// release has no standard library for are reported as
+/// [`TargetAddResult::NotAvailable`] rather than failing the others. The
+/// results are in the order of `targets`.
+pub async fn add_targets(toolchain: &str, targets: &[&str]) -> Result<Vec<TargetAddResult>> {
+    let process = process();
+    let cfg = cfg(true, &process)?;
+    let distributable = DistributableToolchain::new(&cfg, toolchain_desc(&cfg, toolchain)?)?;
+    Ok(add_targets_to(&distributable, targets).await?)
+}
+
+/// Add the targets listed in the file at `path` to an installed toolchain,
+/// like `rustup target add --bulk`, returning each target with its result.
+///
+/// The file lists one target triple per line; blank lines and lines starting
+/// with `#` are ignored. Must not be called from within a tokio runtime.
+pub fn add_targets_from_file(
+    toolchain: &str,
+    path: &Path,
+) -> Result<Vec<(String, TargetAddResult)>> {
+    let targets = read_target_list(path)?;
+    let names = targets.iter().map(String::as_str).collect::<Vec<_>>();
+    let results = add_targets_blocking(toolchain, &names)?;
+    Ok(targets.into_iter().zip(results).collect())
+}
+
+/// Add the standard library for every target the toolchain's release provides,
+/// except those in `exclude`, synchronously. Returns the targets that were added.
+///
+/// Like `rustup target add --all`, the missing standard libraries are
+/// downloaded together in one update; `progress` is called with each
+/// [`InstallProgress`] update and may be invoked from worker threads.
+pub fn add_all_targets_blocking(
+    toolchain: &str,
+    exclude: &[&str],
+    progress: Option<Box<dyn Fn(InstallProgress) + Send>>,
+) -> Result<Vec<String>> {
+    let progress = progress.map(progress_from_fn);
+    block_on(add_all_targets_with(toolchain, exclude, progress))
+}
+
+/// Async version of [`add_all_targets_blocking`]. Requires an existing tokio runtime.
+///
+/// Progress updates are sent on `progress` without waiting; updates that do
+/// not fit in the channel's buffer are dropped rather than stalling the
+/// download.
+pub async fn add_all_targets(
+    toolchain: &str,
+    exclude: &[&str],
+    progress: Option<mpsc::Sender<InstallProgress>>,
+) -> Result<Vec<String>> {
+    let progress = progress.map(progress_from_channel);
+    add_all_targets_with(toolchain, exclude, progress).await
+}
+
+async fn add_all_targets_with(
+    toolchain: &str,
+    exclude: &[&str],
+    progress: Option<ProgressHandler>,
+) -> Result<Vec<String>> {
+    let process = process();
+    let mut cfg = cfg(true, &process)?;
+    cfg.progress_handler = progress;
+    let distributable = DistributableToolchain::new(&cfg, toolchain_desc(&cfg, toolchain)?)?;
+    let targets = missing_targets(&distributable, exclude)?;
+    let names = targets.iter().map(String::as_str).collect::<Vec<_>>();
+    add_targets_to(&distributable, &names).await?;
+    Ok(targets)
+}
+
+/// The available targets of `distributable` whose standard library is not
+/// installed, leaving out those in `exclude`.
+pub(crate) fn missing_targets(
+    distributable: &DistributableToolchain<'_>,
+    exclude: &[&str],
+) -> anyhow::Result<Vec<String>> {
+    Ok(distributable
+        .components()?
+        .into_iter()
+        .filter(|c| c.component.short_name_in_manifest() == "rust-std")
+        .filter(|c| c.available && !c.installed)
+        .filter_map(|c| c.component.target)
+        .filter(|t| !exclude.contains(&&**t))
+        .map(|t| t.to_string())
+        .collect())
+}
+
+/// Reads a list of target triples, one per line, as taken by
+/// `rustup target add --bulk`.
+pub(crate) fn read_target_list(path: &Path) -> anyhow::Result<Vec<String>> {
+    let contents = utils::read_file("target list", path)?;
+    Ok(contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_owned)
+        .collect())
+}
+
+/// Adds the available ones of `targets` to `distributable` in one update.
+pub(crate) async fn add_targets_to(
+    distributable: &DistributableToolchain<'_>,
+    targets: &[&str],
+) -> anyhow::Result<Vec<TargetAddResult>> {
+    let std_libs = distributable
+        .components()?
+        .into_iter()
+        .filter(|c| c.component.short_name_in_manifest() == "rust-std")
+        .filter_map(|c| Some((c.component.target?, c.installed, c.available)))
+        .collect::<Vec<_>>();
+
+    let mut components = Vec::new();
+    let mut results = Vec::with_capacity(targets.len());
+    for &target in targets {
+        let result = match std_libs.iter().find(|(t, ..)| **t == *target) {
+            Some((_, true, _)) => TargetAddResult::AlreadyInstalled,
+            Some((triple, false, true)) => {
+                let component = Component::new("rust-std".to_owned(), Some(triple.clone()), false);
+                if !components.contains(&component) {
+                    components.push(component);
+                }
+                TargetAddResult::Added
+            }
+            _ => TargetAddResult::NotAvailable,
+        };
+        results.push(result);
+    }
+
+    if !components.is_empty() {
+        distributable.add_components(components).await?;
+    }
+    Ok(results)
+}
+
+/// Remove the standard library for `target` from an installed toolchain, synchronously.
+pub fn remove_target_blocking(toolchain: &str, target: &str) -> Result<()> {
+    block_on(remove_target(toolchain, target))
+}
+
+/// Async version of [`remove_target_blocking`]. Requires an existing tokio runtime.
+///
+/// The toolchain's own host target cannot be removed.
+pub async fn remove_target(toolchain: &str, target: &str) -> Result<()> {
+    let process = process();
+    let cfg = cfg(true, &process)?;
+    let distributable = DistributableToolchain::new(&cfg, toolchain_desc(&cfg, toolchain)?)?;
+    let target = TargetTriple::new(target);
+    if target == distributable.desc().target {
+        return Err(RustupError::TargetIsHost {
+            desc: distributable.desc().clone(),
+            target,
+        });
+    }
+
+    let component = Component::new("rust-std".to_owned(), Some(target), false);
+    Ok(distributable.remove_component(component).await?)
+}
+
+/// List the targets whose standard library the named toolchain's release lists.
+///
+/// The manifest of the installed toolchain is used if there is one; otherwise
+/// the channel manifest is fetched and no target is reported as installed.
+/// Must not be called from within a tokio runtime.
+pub fn list_available_targets(toolchain: &str) -> Result<Vec<TargetInfo>> {
+    let process = process();
+    let cfg = cfg(true, &process)?;
+    let (_, components) = component_statuses(&cfg, toolchain)?;
+    Ok(components
+        .into_iter()
+        .filter(|c| c.component.short_name_in_manifest() == "rust-std")
+        .filter_map(|c| {
+            Some(TargetInfo {
+                triple: c.component.target?.to_string(),
+                installed: c.installed,
+                available: c.available,
+            })
+        })
+        .collect())
+}
+
+/// Fetch the channel manifest of the release `toolchain` resolves to, as
+/// `rustup toolchain install --print-manifest` does, without installing
+/// anything.
+///
+/// The manifest is always downloaded, even when the toolchain is installed.
+pub fn fetch_manifest(toolchain: &str) -> Result<DistManifest> {
+    let process = process();
+    let cfg = cfg(true, &process)?;
+    let desc = toolchain_desc(&cfg, toolchain)?;
+    block_on(async { Ok(fetch_manifest_for(&cfg, &desc).await?) })
+}
+
+pub(crate) async fn fetch_manifest_for(
+    cfg: &Cfg<'_>,
+    desc: &ToolchainDesc,
+) -> anyhow::Result<DistManifest> {
+    let fetched = DownloadCfg::new(cfg)
+        .dl_v2_manifest(None, desc, cfg)
+        .await?;
+    let (manifest, _) =
+        fetched.ok_or_else(|| RustupError::ToolchainNotInstallable(desc.to_string()))?;
+    Ok(manifest)
+}
+
+/// The number of bytes installing the named toolchain with `profile` and the
+/// extra `components` and `targets` would download, as printed by
+/// `rustup toolchain install --print-download-size`.
+///
+/// Only the channel manifest is downloaded. Components that are already
+/// installed are left out, and the size of each archive is asked of the
+/// server that hosts it; `None` is returned if a server does not report the
+/// size, or if `RUSTUP_OFFLINE` is set. Must not be called from within a
+/// tokio runtime.
+pub fn estimate_download_size(
+    toolchain: &str,
+    profile: Profile,
+    components: &[&str],
+    targets: &[&str],
+) -> Result<Option<u64>> {
+    let process = process();
+    let cfg = cfg(true, &process)?;
+    let desc = toolchain_desc(&cfg, toolchain)?;
+    block_on(async {
+        let resolved =
+            estimate_download_size_for(&cfg, &desc, profile, components, targets).await?;
+        Ok(resolved.total_download_size)
+    })
+}
+
+/// Resolves what installing `desc` would download on top of the components
+/// that are already installed.
+pub(crate) async fn estimate_download_size_for(
+    cfg: &Cfg<'_>,
+    desc: &ToolchainDesc,
+    profile: Profile,
+    components: &[&str],
+    targets: &[&str],
+) -> anyhow::Result<ResolvedToolchain> {
+    let installed = match DistributableToolchain::new(cfg, desc.clone()) {
+        Ok(d) => {
+            d.get_manifestation()?
+                .read_config()?
+                .unwrap_or_default()
+                .components
+        }
+        Err(RustupError::ToolchainNotInstalled { .. }) => Vec::new(),
+        Err(e) => return Err(e.into()),
+    };
+    resolve_for(cfg, desc, profile, components, targets, &installed).await
+}
+
+/// List the components of the named toolchain's release for its host target.
+///
+/// Components that are missing from the release are listed as unavailable.
+/// As with [`list_available_targets`], the channel manifest is only fetched if
+/// the toolchain is not installed.
+pub fn list_available_components(toolchain: &str) -> Result<Vec<ComponentInfo>> {
+    let process = process();
+    let cfg = cfg(true, &process)?;
+    let desc = toolchain_desc(&cfg, toolchain)?;
+    let (manifest, components) = component_statuses(&cfg, toolchain)?;
+    Ok(components
+        .into_iter()
+        .filter(|c| {
+            c.component
+                .target
+                .as_ref()
+                .is_none_or(|t| *t == desc.target)
+        })
+        .map(|c| ComponentInfo {
+            name: manifest.short_name(&c.component).to_owned(),
+            kind: ComponentKind::of(c.component.short_name_in_manifest()),
+            installed: c.installed,
+            available: c.available,
+        })
+        .collect())
+}
+
+/// Every component of `toolchain` for every target, like `rustup component
+/// list --json`.
+///
+/// Components are listed by their full names, e.g. `cargo-x86_64-unknown-linux-gnu`,
+/// and the standard library of each target is listed with the kind
+/// [`ComponentKind::Target`]. Unlike [`list_available_components`], components
+/// the release lacks are included with `available` unset.
+pub fn list_components(toolchain: &str) -> Result<Vec<ComponentInfo>> {
+    let process = process();
+    let cfg = cfg(true, &process)?;
+    let (_, components) = component_statuses(&cfg, toolchain)?;
+    Ok(components
+        .into_iter()
+        .map(ComponentInfo::from_status)
+        .collect())
+}
+
+/// Replace `binary_name` in `CARGO_HOME/bin` with a rustup proxy, so that it
+/// runs in the environment of the active toolchain, like `rustup proxy add`.
+///
+/// Only binaries installed by `cargo install` are replaced; registering one
+/// that is already proxied does nothing.
+pub fn register_proxy(binary_name: &str) -> Result<()> {
+    let process = process();
+    let cfg = cfg(true, &process)?;
+    Ok(self_update::register_proxy(&cfg, binary_name, false)?)
+}
+
+/// Add `binary` to the `dup_tools` of `settings.toml`, so that rustup installs
+/// a proxy for it in `CARGO_HOME/bin` like it does for `rustfmt`.
+///
+/// As with the built-in ones, a `binary` that is already installed there and
+/// is not a proxy is left alone, with a warning.
+pub fn add_dup_tool(binary: &str) -> Result<()> {
+    let process = process();
+    let cfg = cfg(true, &process)?;
+    Ok(self_update::add_dup_tool(&cfg, binary)?)
+}
+
+/// Remove `binary` from the `dup_tools` of `settings.toml`, along with its
+/// proxy in `CARGO_HOME/bin`.
+///
+/// The tools rustup proxies by default cannot be removed.
+pub fn remove_dup_tool(binary: &str) -> Result<()> {
+    let process = process();
+    let cfg = cfg(true, &process)?;
+    Ok(self_update::remove_dup_tool(&cfg, binary)?)
+}
+
+/// Register rustup as the system Rust provider, like `rustup self install-hook`.
+///
+/// `CARGO_HOME/bin` is put at the front of the system `PATH`: with an entry in
+/// `/etc/paths.d` on macOS, a script in `/etc/profile.d` on other Unix systems
+/// and the user `PATH` in the registry on Windows. Writing to `/etc` usually
+/// requires root.
+pub fn install_path_hook() -> Result<()> {
+    Ok(self_update::install_path_hook(&process())?)
+}
+
+/// Undo the changes made by [`install_path_hook`], like
+/// `rustup self uninstall-hook`.
+pub fn uninstall_path_hook() -> Result<()> {
+    Ok(self_update::uninstall_path_hook(&process())?)
+}
+
+/// List the releases of `channel`, newest first, going back to `since` and
+/// listing at most `limit` of them.
+///
+/// The releases are found in the index of the dist server, `manifests.txt`,
+/// so that only the manifests of the listed releases are fetched.
+/// Must not be called from within a tokio runtime.
+pub fn list_available_toolchains(
+    channel: Channel,
+    since: Option<NaiveDate>,
+    limit: usize,
+) -> Result<Vec<ToolchainRelease>> {
+    let process = process();
+    let cfg = cfg(true, &process)?;
+    let host = cfg.get_default_host_triple()?;
+    let channel = dist::Channel::from(channel);
+    let dl_cfg = DownloadCfg::new(&cfg);
+
+    let index_url = format!("{}/manifests.txt", cfg.dist_root_server);
+    let index = block_on(async { Ok(dl_cfg.download_cached(&index_url, ".txt").await?) })?;
+    let index = utils::read_file("release index", &index)?;
+    // Lines look like `static.rust-lang.org/dist/2024-01-15/channel-rust-nightly.toml`
+    let manifest_name = format!("channel-rust-{channel}.toml");
+    let mut dates = index
+        .lines()
+        .filter_map(|line| {
+            let (dir, name) = line.trim().rsplit_once('/')?;
+            let (_, date) = dir.rsplit_once('/')?;
+            let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?;
+            (name == manifest_name).then_some(date)
+        })
+        .filter(|date| since.is_none_or(|since| *date >= since))
+        .collect::<Vec<_>>();
+    dates.sort_unstable_by(|a, b| b.cmp(a));
+    dates.dedup();
+    dates.truncate(limit);
+
+    // The dates of the releases installed for the host, dated or not
+    let mut installed = HashSet::new();
+    for name in cfg.list_toolchains()? {
+        let ToolchainName::Official(desc) = name else {
+            continue;
+        };
+        if desc.channel != channel || desc.target != host {
+            continue;
+        }
+        let date = match &desc.date {
+            Some(date) => date.clone(),
+            None => {
+                DistributableToolchain::new(&cfg, desc)?
+                    .get_manifest()?
+                    .date
+            }
+        };
+        installed.insert(date);
+    }
+
+    let fetch = |date: NaiveDate| {
+        let desc = ToolchainDesc {
+            channel: channel.clone(),
+            date: Some(date.to_string()),
+            target: host.clone(),
+        };
+        let (dl_cfg, cfg, installed) = (&dl_cfg, &cfg, &installed);
+        async move {
+            let fetched = dl_cfg.dl_v2_manifest(None, &desc, cfg).await?;
+            let (manifest, _) =
+                fetched.ok_or_else(|| RustupError::ToolchainNotInstallable(desc.to_string()))?;
+            let rust_version = manifest.get_package("rust")?.version.clone();
+            let day = date.to_string();
+            Ok(ToolchainRelease {
+                name: format!("{}-{day}", desc.channel),
+                installed: installed.contains(&day),
+                date,
+                rust_version,
+            })
+        }
+    };
+    block_on(async {
+        stream::iter(dates)
+            .map(fetch)
+            .buffered(MANIFEST_FETCHES)
+            .try_collect()
+            .await
+    })
+}
+
+/// How many release manifests [`list_available_toolchains`] fetches at once.
+const MANIFEST_FETCHES: usize = 4;
+
+/// The status of every component in the named toolchain's release, and the
+/// manifest of that release.
+fn component_statuses(cfg: &Cfg<'_>, name: &str) -> Result<(Manifest, Vec<ComponentStatus>)> {
+    let desc = toolchain_desc(cfg, name)?;
+    match DistributableToolchain::new(cfg, desc.clone()) {
+        Ok(distributable) => Ok((distributable.get_manifest()?, distributable.components()?)),
+        Err(RustupError::ToolchainNotInstalled { .. }) => {
+            let fetched = block_on(async {
+                Ok(DownloadCfg::new(cfg)
+                    .dl_v2_manifest(None, &desc, cfg)
+                    .await?)
+            })?;
+            let not_installable = || RustupError::ToolchainNotInstallable(desc.to_string());
+            let (manifest, _) = fetched.ok_or_else(not_installable)?;
+            // `query_components` expects the release to support the host
+            manifest
+                .get_package("rust")?
+                .get_target(Some(&desc.target))
+                .map_err(|_| not_installable())?;
+            let components = manifest.query_components(&desc, &Config::default())?;
+            Ok((manifest, components))
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Components which need another component to work, as `(dependent, dependency)` pairs.
+static COMPONENT_DEPENDENCIES: &[(&str, &str)] = &[
+    ("clippy-preview", "rustc"),
+    ("miri-preview", "rust-src"),
+    ("rust-analyzer-preview", "rust-src"),
+    ("rustc-dev", "rustc"),
+];
+
+/// The target in `available` closest to the unknown `target`, if any is close enough.
+fn closest_target(available: &[TargetTriple], target: &str) -> Option<String> {
+    // Keep in line with the component suggestions of `DistributableToolchain`.
+    const MAX_DISTANCE: usize = 3;
+
+    available
+        .iter()
+        .map(|t| (strsim::damerau_levenshtein(t, target), t))
+        .filter(|(distance, _)| *distance <= MAX_DISTANCE)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, t)| t.to_string())
+}
+
+/// Resolves `name` into the description of an official toolchain.
+fn toolchain_desc(cfg: &Cfg<'_>, name: &str) -> Result<ToolchainDesc> {
+    Ok(name
+        .parse::<PartialToolchainDesc>()?
+        .resolve(&cfg.get_default_host_triple()?)?)
+}
+
+/// Resolves `name` against the default host, requiring the toolchain to be installed.
+fn installed_toolchain_name(cfg: &Cfg<'_>, name: &str) -> Result<ToolchainName> {
+    let name = ResolvableToolchainName::try_from(name)
+        .map_err(|_| RustupError::InvalidToolchainName(name.to_owned()))?
+        .resolve(&cfg.get_default_host_triple()?)?;
+    if !Toolchain::exists(cfg, &(&name).into())? {
+        return Err(RustupError::ToolchainNotInstalled {
+            name,
+            is_active: false,
+        });
+    }
+    Ok(name)
+}
+
+/// Makes the functions of this module run as `process`, e.g. one made by
+/// [`Process::library`] to capture rustup's diagnostics.
+///
+/// By default they run as [`Process::os`].
+pub fn set_process(process: Process) {
+    *PROCESS.lock().unwrap() = Some(process);
+}
+
+static PROCESS: Mutex<Option<Process>> = Mutex::new(None);
+
+/// The process set by [`set_process`], or else the running program.
+fn process() -> Process {
+    match &*PROCESS.lock().unwrap() {
+        Some(process) => process.clone(),
+        None => Process::os(),
+    }
+}
+
+/// Builds the configuration for `process`, rooted at the current directory.
+fn cfg(quiet: bool, process: &Process) -> Result<Cfg<'_>> {
+    let current_dir = process.current_dir().unwrap_or_else(|_| PathBuf::from("."));
+    Ok(Cfg::from_env(current_dir, quiet, process)?)
+}
+
+/// Runs `fut` to completion on a fresh multi-thread tokio runtime.
+fn block_on<T>(fut: impl Future<Output = Result<T>>) -> Result<T> {
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()?
+        .block_on(fut)
+}
+
+fn check_exit_code(operation: &'static str, exit_code: ExitCode) -> Result<()> {
+    if exit_code == ExitCode::SUCCESS {
+        Ok(())
+    } else {
+        Err(RustupError::OperationFailed {
+            operation,
+            code: exit_code.0,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::{MutexGuard, PoisonError};
+
+    use super::*;
+    use crate::for_host;
+    use crate::process::TestProcess;
+    use crate::test::{
+        CROSS_ARCH1, CliTestContext, Scenario, output_release_file, this_host_triple,
+    };
+
+    /// Serializes the tests, as they share the process set with [`set_process`].
+    static SERIAL: Mutex<()> = Mutex::new(());
+
+    /// A rustup home served by the mock dist server of a [`Scenario`], that the
+    /// functions of this module run against while it is alive.
+    struct TestHome {
+        cx: CliTestContext,
+        vars: HashMap<String, String>,
+        _tp: TestProcess,
+        _serial: MutexGuard<'static, ()>,
+    }
+
+    impl TestHome {
+        fn new(scenario: Scenario) -> Self {
+            let serial = SERIAL.lock().unwrap_or_else(PoisonError::into_inner);
+            let cx = block_on(async { Ok(CliTestContext::new(scenario).await) }).unwrap();
+            let mut vars = HashMap::new();
+            cx.config.env(&mut vars);
+            let tp = Self::set_process(&cx, &vars);
+            Self {
+                cx,
+                vars,
+                _tp: tp,
+                _serial: serial,
+            }
+        }
+
+        fn set_process(cx: &CliTestContext, vars: &HashMap<String, String>) -> TestProcess {
+            let tp = TestProcess::new(cx.config.current_dir(), &["rustup"], vars.clone(), "");
+            set_process(tp.process.clone());
+            tp
+        }
+
+        /// Sets the environment variable `key` for the functions run afterwards.
+        fn set_var(&mut self, key: &str, value: &str) {
+            self.vars.insert(key.to_owned(), value.to_owned());
+            self._tp = Self::set_process(&self.cx, &self.vars);
+        }
+
+        /// Serves the releases of `scenario` to the functions run afterwards.
+        fn set_scenario(&mut self, scenario: Scenario) {
+            let mut vars = HashMap::new();
+            self.cx.with_dist_dir(scenario).config.env(&mut vars);
+            self.set_var("RUSTUP_DIST_SERVER", &vars["RUSTUP_DIST_SERVER"]);
+        }
+
+        fn toolchain_dir(&self, name: &str) -> PathBuf {
+            self.cx.config.rustupdir.join("toolchains").join(name)
+        }
+    }
+
+    #[test]
+    fn progress_handlers_adapt_send_callbacks_and_channels() {
+        // A `Cell` makes the callback `Send` but not `Sync`.
+        let (tx, rx) = std::sync::mpsc::channel();
+        let calls = std::cell::Cell::new(0);
+        let handler = progress_from_fn(move |p| {
+            calls.set(calls.get() + 1);
+            tx.send((calls.get(), p)).unwrap();
+        });
+        handler(InstallProgress::Finished);
+        handler(InstallProgress::Finished);
+        let received = rx.try_iter().map(|(n, _)| n).collect::<Vec<_>>();
+        assert_eq!(received, [1, 2]);
+
+        // Updates beyond the channel's buffer are dropped without blocking.
+        let (tx, mut rx) = mpsc::channel(1);
+        let handler = progress_from_channel(tx);
+        handler(InstallProgress::Extracting {
+            component: "rustc".to_owned(),
+        });
+        handler(InstallProgress::Finished);
+        assert!(matches!(
+            rx.try_recv(),
+            Ok(InstallProgress::Extracting { component }) if component == "rustc"
+        ));
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn install_toolchain_installs_the_toolchain_only() {
+        let mut home = TestHome::new(Scenario::SimpleV2);
+        install_toolchain_blocking("nightly", Profile::Minimal, &["rust-src"], &[], false).unwrap();
+
+        let toolchain = home.toolchain_dir(for_host!("nightly-{0}"));
+        assert!(toolchain.join(format!("bin/rustc{EXE_SUFFIX}")).is_file());
+        assert!(toolchain.join("lib/rustlib/src").is_dir());
+        assert!(!toolchain.join(format!("bin/cargo{EXE_SUFFIX}")).exists());
+        // The default toolchain is left alone.
+        let process = process();
+        assert_eq!(cfg(true, &process).unwrap().get_default().unwrap(), None);
+
+        // With `if_not_installed`, nothing is fetched for an installed toolchain.
+        home.set_var("RUSTUP_DIST_SERVER", "file:///no-such-dist-server");
+        block_on(install_toolchain(
+            "nightly",
+            Profile::Minimal,
+            &["rust-src"],
+            &[],
+            true,
+        ))
+        .unwrap();
+    }
+
+    #[test]
+    fn list_toolchains_and_active_toolchain_follow_the_default() {
+        let home = TestHome::new(Scenario::SimpleV2);
+        assert!(list_toolchains().unwrap().is_empty());
+        let cwd = home.cx.config.current_dir();
+        assert!(active_toolchain(&cwd).unwrap().is_none());
+
+        for name in ["nightly", "beta"] {
+            install_toolchain_blocking(name, Profile::Minimal, &[], &[], false).unwrap();
+        }
+        set_default_toolchain("nightly").unwrap();
+        let toolchains = list_toolchains().unwrap();
+        let names = toolchains
+            .iter()
+            .map(|t| t.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [for_host!("beta-{0}").as_str(), for_host!("nightly-{0}")]
+        );
+        assert!(!toolchains[0].is_default && !toolchains[0].is_active);
+        assert!(toolchains[1].is_default && toolchains[1].is_active);
+        assert_eq!(
+            toolchains[1].path,
+            home.toolchain_dir(for_host!("nightly-{0}"))
+        );
+
+        // A toolchain file selects the toolchain in its directory only.
+        let project = cwd.join("project");
+        fs::create_dir(&project).unwrap();
+        fs::write(project.join("rust-toolchain"), "beta").unwrap();
+        let active = active_toolchain(&project).unwrap().unwrap();
+        assert_eq!(active.name, for_host!("beta-{0}").as_str());
+        assert!(active.is_active && !active.is_default);
+        let active = active_toolchain(&cwd).unwrap().unwrap();
+        assert_eq!(active.name, for_host!("nightly-{0}").as_str());
+
+        // A toolchain that is selected but not installed is not active.
+        fs::write(project.join("rust-toolchain"), "stable").unwrap();
+        assert!(active_toolchain(&project).unwrap().is_none());
+    }
+
+    #[test]
+    fn update_toolchain_reports_the_outcome() {
+        let mut home = TestHome::new(Scenario::ArchivesV2_2015_01_01);
+        install_toolchain_blocking("nightly", Profile::Minimal, &[], &[], false).unwrap();
+        assert_eq!(
+            update_toolchain_blocking("nightly").unwrap(),
+            UpdateResult::AlreadyCurrent {
+                date: "2015-01-01".to_owned()
+            }
+        );
+        assert_eq!(
+            update_toolchain_blocking("beta").unwrap(),
+            UpdateResult::NotInstalled
+        );
+
+        home.set_scenario(Scenario::SimpleV2);
+        let UpdateResult::Updated {
+            from_date,
+            to_date,
+            bytes_downloaded,
+        } = block_on(update_toolchain("nightly")).unwrap()
+        else {
+            panic!("nightly was not updated");
+        };
+        assert_eq!(
+            (from_date.as_str(), to_date.as_str()),
+            ("2015-01-01", "2015-01-02")
+        );
+        assert!(bytes_downloaded > 0);
+    }
+
+    #[test]
+    fn components_can_be_added_and_removed() {
+        let home = TestHome::new(Scenario::SimpleV2);
+        install_toolchain_blocking("nightly", Profile::Minimal, &[], &[], false).unwrap();
+        let src = home
+            .toolchain_dir(for_host!("nightly-{0}"))
+            .join("lib/rustlib/src");
+
+        add_component_blocking("nightly", "rust-src", false).unwrap();
+        assert!(src.is_dir());
+        let err = add_component_blocking("nightly", "no-such-component", false).unwrap_err();
+        assert!(
+            matches!(err, RustupError::UnknownComponent { .. }),
+            "{err:?}"
+        );
+        block_on(add_component("nightly", "no-such-component", true)).unwrap();
+
+        block_on(remove_component("nightly", "rust-src")).unwrap();
+        assert!(!src.exists());
+    }
+
+    #[test]
+    fn targets_can_be_added_and_removed() {
+        let home = TestHome::new(Scenario::SimpleV2);
+        install_toolchain_blocking("nightly", Profile::Minimal, &[], &[], false).unwrap();
+        let std = home
+            .toolchain_dir(for_host!("nightly-{0}"))
+            .join("lib/rustlib")
+            .join(CROSS_ARCH1);
+
+        add_target_blocking("nightly", CROSS_ARCH1).unwrap();
+        assert!(std.is_dir());
+        let err = block_on(add_target("nightly", "no-such-target")).unwrap_err();
+        assert!(matches!(err, RustupError::UnknownTarget { .. }), "{err:?}");
+
+        let err = remove_target_blocking("nightly", &this_host_triple()).unwrap_err();
+        assert!(matches!(err, RustupError::TargetIsHost { .. }), "{err:?}");
+        block_on(remove_target("nightly", CROSS_ARCH1)).unwrap();
+        assert!(!std.exists());
+    }
+
+    #[test]
+    fn set_default_toolchain_requires_an_installed_toolchain() {
+        let _home = TestHome::new(Scenario::SimpleV2);
+        let err = set_default_toolchain("nightly").unwrap_err();
+        assert!(
+            matches!(err, RustupError::ToolchainNotInstalled { .. }),
+            "{err:?}"
+        );
+
+        install_toolchain_blocking("nightly", Profile::Minimal, &[], &[], false).unwrap();
+        set_default_toolchain("nightly").unwrap();
+        let process = process();
+        assert_eq!(
+            cfg(true, &process).unwrap().get_default().unwrap(),
+            Some(ToolchainName::Official(
+                for_host!("nightly-{0}").parse::<ToolchainDesc>().unwrap()
+            ))
+        );
+    }
+
+    #[test]
+    fn overrides_can_be_set_listed_and_unset() {
+        let home = TestHome::new(Scenario::SimpleV2);
+        let project = home.cx.config.current_dir().join("project");
+        fs::create_dir(&project).unwrap();
+        let err = set_override(&project, "nightly").unwrap_err();
+        assert!(
+            matches!(err, RustupError::ToolchainNotInstalled { .. }),
+            "{err:?}"
+        );
+
+        install_toolchain_blocking("nightly", Profile::Minimal, &[], &[], false).unwrap();
+        set_override(&project, "nightly").unwrap();
+        let overrides = list_overrides().unwrap();
+        assert_eq!(overrides.len(), 1);
+        assert_eq!(overrides[0].dir, utils::canonicalize_path(&project));
+        assert_eq!(overrides[0].toolchain, for_host!("nightly-{0}").as_str());
+        assert!(overrides[0].exists);
+        let active = active_toolchain(&project.join("src")).unwrap().unwrap();
+        assert_eq!(active.name, for_host!("nightly-{0}").as_str());
+
+        unset_override(&project).unwrap();
+        assert!(list_overrides().unwrap().is_empty());
+        assert!(active_toolchain(&project).unwrap().is_none());
+    }
+
+    #[test]
+    fn which_finds_the_binary_of_the_active_toolchain() {
+        let home = TestHome::new(Scenario::SimpleV2);
+        let cwd = home.cx.config.current_dir();
+        install_toolchain_blocking("nightly", Profile::Minimal, &[], &[], false).unwrap();
+        let err = which("rustc", &cwd).unwrap_err();
+        assert!(
+            matches!(err, RustupError::ToolchainNotSelected(_)),
+            "{err:?}"
+        );
+
+        set_default_toolchain("nightly").unwrap();
+        let bin = home.toolchain_dir(for_host!("nightly-{0}")).join("bin");
+        assert_eq!(
+            which("rustc", &cwd).unwrap(),
+            bin.join(format!("rustc{EXE_SUFFIX}"))
+        );
+        assert_eq!(
+            which(&format!("rustc{EXE_SUFFIX}"), &cwd).unwrap(),
+            bin.join(format!("rustc{EXE_SUFFIX}"))
+        );
+        // The minimal profile has no cargo.
+        let err = which("cargo", &cwd).unwrap_err();
+        assert!(
+            matches!(err, RustupError::BinaryNotInstalled { .. }),
+            "{err:?}"
+        );
+        assert!(which("not-a-proxy", &cwd).is_err());
+    }
+
+    #[test]
+    fn run_with_toolchain_runs_the_toolchain_binary() {
+        let home = TestHome::new(Scenario::SimpleV2);
+        let cwd = home.cx.config.current_dir();
+        install_toolchain_blocking("nightly", Profile::Minimal, &[], &[], false).unwrap();
+
+        let env = HashMap::new();
+        // With no arguments to echo, the inherited stdio stays quiet.
+        let status = run_with_toolchain("nightly", "rustc", &["--echo-args"], &env, &cwd).unwrap();
+        assert!(status.success());
+
+        let (mut stdout, mut stderr) = (Vec::new(), Vec::new());
+        let env = HashMap::from([("CARGO".to_owned(), "from-env".to_owned())]);
+        let status = run_with_toolchain_streaming(
+            "nightly",
+            "rustc",
+            &["--echo-cargo-env"],
+            &env,
+            &cwd,
+            |line| stdout.push(line.to_owned()),
+            |line| stderr.push(line.to_owned()),
+        )
+        .unwrap();
+        assert!(status.success());
+        assert!(stdout.is_empty());
+        assert_eq!(stderr, ["from-env"]);
+
+        let err = run_with_toolchain("beta", "rustc", &[], &env, &cwd).unwrap_err();
+        assert!(
+            matches!(err, RustupError::ToolchainNotInstalled { .. }),
+            "{err:?}"
+        );
+    }
+
+    #[test]
+    fn self_update_checks_the_latest_release() {
+        let mut home = TestHome::new(Scenario::SimpleV2);
+        let update_root = tempfile::tempdir().unwrap();
+        let root_url = format!("file://{}", update_root.path().display());
+        home.set_var("RUSTUP_UPDATE_ROOT", &root_url);
+        let current = env!("CARGO_PKG_VERSION");
+
+        output_release_file(update_root.path(), "1", "999.0.0");
+        assert_eq!(
+            check_for_self_update_blocking().unwrap(),
+            SelfUpdateStatus::UpdateAvailable {
+                current: current.to_owned(),
+                latest: "999.0.0".to_owned()
+            }
+        );
+
+        output_release_file(update_root.path(), "1", current);
+        assert_eq!(
+            block_on(check_for_self_update()).unwrap(),
+            SelfUpdateStatus::UpToDate {
+                version: current.to_owned()
+            }
+        );
+
+        // Only an installed rustup can update itself.
+        let bin = home.cx.config.cargodir.join("bin");
+        assert!(self_update_blocking().is_err());
+        // With nothing newer to install, the proxies are linked again.
+        fs::create_dir_all(&bin).unwrap();
+        fs::write(bin.join(format!("rustup{EXE_SUFFIX}")), "").unwrap();
+        block_on(self_update()).unwrap();
+        assert!(bin.join(format!("cargo{EXE_SUFFIX}")).exists());
+    }
+}
@@ -10,6 +10,10 @@
 ))]
 #![recursion_limit = "1024"]
 
+use std::collections::HashSet;
+use std::env::consts::EXE_SUFFIX;
+use std::sync::{LazyLock, RwLock};
+
 use anyhow::{Result, anyhow};
 use errors::RustupError;
 use itertools::{Itertools, chain};
@@ -36,9 +40,18 @@ pub static TOOLS: &[&str] = &[
 // installation.
 pub static DUP_TOOLS: &[&str] = &["rust-analyzer", "rustfmt", "cargo-fmt"];
 
-// If the given name is one of the tools we proxy.
+// The union of `TOOLS` and `DUP_TOOLS`, for checking membership in either.
+static PROXYABLE_TOOLS: LazyLock<HashSet<&str>> =
+    LazyLock::new(|| chain!(TOOLS, DUP_TOOLS).copied().collect());
+
+// If the given name is one of the tools we proxy, with or without the
+// platform's executable suffix, e.g. `rustc.exe` on Windows.
 pub fn is_proxyable_tools(tool: &str) -> Result<()> {
-    if chain!(TOOLS, DUP_TOOLS).contains(&tool) {
+    check_proxyable_tool(tool, EXE_SUFFIX)
+}
+
+fn check_proxyable_tool(tool: &str, exe_suffix: &str) -> Result<()> {
+    if is_proxyable_tool_fast(tool.strip_suffix(exe_suffix).unwrap_or(tool)) {
         Ok(())
     } else {
         Err(anyhow!(
@@ -50,10 +63,44 @@ pub fn is_proxyable_tools(tool: &str) -> Result<()> {
     }
 }
 
-fn component_for_bin(binary: &str) -> Option<&'static str> {
-    use std::env::consts::EXE_SUFFIX;
+/// `binary` without the platform's executable suffix, e.g. `rustc` for `rustc.exe`.
+pub(crate) fn without_exe_suffix(binary: &str) -> &str {
+    binary.strip_suffix(EXE_SUFFIX).unwrap_or(binary)
+}
+
+/// Whether `tool` is one of the tools we proxy, like [`is_proxyable_tools`]
+/// but without building an error message for the tools that are not.
+pub fn is_proxyable_tool_fast(tool: &str) -> bool {
+    PROXYABLE_TOOLS.contains(tool)
+}
+
+// Binary-to-component mappings added by `register_component_for_bins`.
+static EXTRA_COMPONENTS_FOR_BINS: RwLock<Vec<(&str, &str)>> = RwLock::new(Vec::new());
 
-    let binary_without_suffix = binary.strip_suffix(EXE_SUFFIX).unwrap_or(binary);
+/// Register additional `(binary, component)` pairs for [`component_for_bin`].
+///
+/// Registered pairs are consulted, most recent first, before rustup's own mapping,
+/// so tools shipping their own binaries can also override a built-in entry.
+pub fn register_component_for_bins(mappings: &[(&'static str, &'static str)]) {
+    EXTRA_COMPONENTS_FOR_BINS
+        .write()
+        .unwrap()
+        .extend_from_slice(mappings);
+}
+
+/// The name of the component providing `binary`, with or without the executable suffix.
+pub fn component_for_bin(binary: &str) -> Option<&'static str> {
+    let binary_without_suffix = without_exe_suffix(binary);
+
+    let registered = EXTRA_COMPONENTS_FOR_BINS
+        .read()
+        .unwrap()
+        .iter()
+        .rev()
+        .find_map(|&(bin, component)| (bin == binary_without_suffix).then_some(component));
+    if registered.is_some() {
+        return registered;
+    }
 
     match binary_without_suffix {
         "rustc" | "rustdoc" => Some("rustc"),
@@ -81,25 +128,34 @@ pub mod env_var;
 pub mod errors;
 mod fallback_settings;
 mod install;
+pub mod installer;
 pub mod process;
 mod settings;
 #[cfg(feature = "test")]
 pub mod test;
-mod toolchain;
+pub mod toolchain;
 pub mod utils;
 
 #[cfg(test)]
 mod tests {
-    use crate::{DUP_TOOLS, TOOLS, is_proxyable_tools};
+    use std::env::consts::EXE_SUFFIX;
+
+    use crate::{
+        DUP_TOOLS, TOOLS, check_proxyable_tool, component_for_bin, is_proxyable_tool_fast,
+        is_proxyable_tools, register_component_for_bins,
+    };
 
     #[test]
     fn test_is_proxyable_tools() {
         for tool in TOOLS {
             assert!(is_proxyable_tools(tool).is_ok());
+            assert!(is_proxyable_tool_fast(tool));
         }
         for tool in DUP_TOOLS {
             assert!(is_proxyable_tools(tool).is_ok());
+            assert!(is_proxyable_tool_fast(tool));
         }
+        assert!(!is_proxyable_tool_fast("unknown-tool"));
         let message = "unknown proxy name: 'unknown-tool'; valid proxy names are 'rustc', \
         'rustdoc', 'cargo', 'rust-lldb', 'rust-gdb', 'rust-gdbgui', 'rls', \
         'cargo-clippy', 'clippy-driver', 'cargo-miri', 'rust-analyzer', 'rustfmt', 'cargo-fmt'";
@@ -108,64 +164,42 @@ mod tests {
             message
         );
     }
-}
 
-/// Public programmatic installation API.
-///
-/// Exposes rustup's internal install machinery for use as a library dependency,
-/// bypassing the CLI arg-parsing layer. Callers should spawn a dedicated thread
-/// since `install_rust_blocking` creates its own tokio runtime.
-pub mod installer {
-    use std::path::PathBuf;
-
-    use anyhow::{Result, anyhow};
-
-    use crate::{
-        cli::self_update::{self, InstallOpts},
-        config::Cfg,
-        dist::Profile,
-        process::Process,
-        utils::ExitCode,
-    };
-
-    /// Install Rust synchronously using rustup's standard installation flow.
-    ///
-    /// Internally spins up a multi-thread tokio runtime. Call this from a
-    /// dedicated `std::thread::spawn` to avoid conflicting with any existing
-    /// async executor (e.g. GPUI).
-    ///
-    /// - `no_prompt`: skip interactive confirmation (pass `true` for unattended installs)
-    /// - `no_modify_path`: when `false`, rustup adds `~/.cargo/bin` to the system PATH
-    pub fn install_rust_blocking(no_prompt: bool, no_modify_path: bool) -> Result<()> {
-        let rt = tokio::runtime::Builder::new_multi_thread()
-            .enable_all()
-            .build()?;
-        let exit_code = rt.block_on(install_rust(no_prompt, no_modify_path))?;
-        if exit_code == ExitCode::SUCCESS {
-            Ok(())
-        } else {
-            Err(anyhow!("rustup install exited with code {}", exit_code.0))
+    #[test]
+    fn proxyable_tools_with_exe_suffix() {
+        for tool in TOOLS.iter().chain(DUP_TOOLS) {
+            assert!(is_proxyable_tools(&format!("{tool}{EXE_SUFFIX}")).is_ok());
+            assert!(check_proxyable_tool(&format!("{tool}.exe"), ".exe").is_ok());
         }
+        assert!(check_proxyable_tool("rustc.exe", "").is_err());
+        let message = "unknown proxy name: 'unknown-tool.exe'; valid proxy names are 'rustc', \
+        'rustdoc', 'cargo', 'rust-lldb', 'rust-gdb', 'rust-gdbgui', 'rls', \
+        'cargo-clippy', 'clippy-driver', 'cargo-miri', 'rust-analyzer', 'rustfmt', 'cargo-fmt'";
+        assert_eq!(
+            check_proxyable_tool("unknown-tool.exe", ".exe")
+                .unwrap_err()
+                .to_string(),
+            message
+        );
     }
 
-    /// Async version of the install flow. Requires an existing tokio runtime.
-    pub async fn install_rust(
-        no_prompt: bool,
-        no_modify_path: bool,
-    ) -> Result<ExitCode> {
-        let process = Process::os();
-        let current_dir =
-            std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
-        let mut cfg = Cfg::from_env(current_dir, no_prompt, &process)?;
-        let opts = InstallOpts {
-            default_host_triple: None,
-            default_toolchain: None,
-            profile: Profile::Default,
-            no_modify_path,
-            no_update_toolchain: false,
-            components: &[],
-            targets: &[],
-        };
-        self_update::install(no_prompt, opts, &mut cfg).await
+    #[test]
+    fn registered_components_for_bins() {
+        assert_eq!(component_for_bin("cargo-objdump"), None);
+        register_component_for_bins(&[("cargo-objdump", "llvm-tools"), ("rls", "rls-preview")]);
+        assert_eq!(component_for_bin("cargo-objdump"), Some("llvm-tools"));
+        assert_eq!(component_for_bin("rls"), Some("rls-preview"));
+        assert_eq!(component_for_bin("cargo"), Some("cargo"));
+        assert_eq!(
+            component_for_bin(&format!("cargo-objdump{EXE_SUFFIX}")),
+            Some("llvm-tools")
+        );
+
+        // The most recent registration wins.
+        register_component_for_bins(&[("cargo-objdump", "llvm-tools-preview")]);
+        assert_eq!(
+            component_for_bin("cargo-objdump"),
+            Some("llvm-tools-preview")
+        );
     }
 }
@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::ffi::OsString;
 use std::fmt::Debug;
 use std::io;
@@ -5,14 +6,13 @@ use std::io::IsTerminal;
 use std::num::NonZero;
 use std::path::PathBuf;
 use std::str::FromStr;
+use std::{env, thread};
 #[cfg(feature = "test")]
 use std::{
-    collections::HashMap,
     io::Cursor,
     path::Path,
     sync::{Arc, Mutex},
 };
-use std::{env, thread};
 
 use anstream::ColorChoice;
 use anyhow::{Context, Result, bail};
@@ -28,6 +28,7 @@ use tracing_subscriber::{EnvFilter, Registry, reload::Handle};
 use crate::cli::log;
 
 mod file_source;
+use file_source::SharedWriter;
 mod terminal_source;
 pub use terminal_source::ColorableTerminal;
 
@@ -44,6 +45,64 @@ impl Process {
         Self::OsProcess(OsProcess::new())
     }
 
+    /// The running program, for embedding rustup as a library.
+    ///
+    /// Diagnostics that would go to stderr are written to `sink` instead,
+    /// without color codes, and no progress bars are drawn. The embedding
+    /// program's own arguments, such as `-v`, are not taken as rustup's.
+    pub fn library(sink: impl io::Write + Send + 'static) -> Self {
+        Self::OsProcess(OsProcess {
+            stderr_is_a_tty: false,
+            stdout_is_a_tty: false,
+            vars: HashMap::new(),
+            sink: Some(SharedWriter::new(sink)),
+        })
+    }
+
+    /// The running program, with the environment variables in `overrides`
+    /// in place of its own.
+    ///
+    /// Other variables are still read from the real environment, and an empty
+    /// value hides one that is set there. The environment of the running
+    /// program is left alone, so this can point the functions of
+    /// [`installer`](crate::installer) at another `RUSTUP_HOME`,
+    /// `RUSTUP_DIST_SERVER` or `RUSTUP_TOOLCHAIN` without affecting other
+    /// threads.
+    pub fn with_env(overrides: HashMap<String, String>) -> Self {
+        Self::OsProcess(OsProcess {
+            vars: overrides
+                .into_iter()
+                .map(|(key, value)| (key, value.into()))
+                .collect(),
+            ..OsProcess::new()
+        })
+    }
+
+    /// Whether this process was made by [`Self::library`].
+    pub(crate) fn is_library(&self) -> bool {
+        match self {
+            Process::OsProcess(p) => p.sink.is_some(),
+            #[cfg(feature = "test")]
+            Process::TestProcess(_) => false,
+        }
+    }
+
+    /// Returns this process with the environment variable `key` set to `value`,
+    /// without touching the environment of the running program.
+    pub(crate) fn with_var(mut self, key: &str, value: impl Into<OsString>) -> Self {
+        match &mut self {
+            Process::OsProcess(p) => {
+                p.vars.insert(key.to_owned(), value.into());
+            }
+            #[cfg(feature = "test")]
+            Process::TestProcess(p) => {
+                let value = value.into().to_string_lossy().into_owned();
+                p.vars.insert(key.to_owned(), value);
+            }
+        }
+        self
+    }
+
     pub fn name(&self) -> Option<String> {
         let arg0 = match self.var("RUSTUP_FORCE_ARG0") {
             Ok(v) => Some(v),
@@ -106,7 +165,13 @@ impl Process {
 
     pub fn var(&self, key: &str) -> Result<String, env::VarError> {
         let value = match self {
-            Process::OsProcess(_) => env::var(key)?,
+            Process::OsProcess(p) => match p.vars.get(key) {
+                Some(val) => val
+                    .clone()
+                    .into_string()
+                    .map_err(env::VarError::NotUnicode)?,
+                None => env::var(key)?,
+            },
             #[cfg(feature = "test")]
             Process::TestProcess(p) => match p.vars.get(key) {
                 Some(val) => val.to_owned(),
@@ -127,16 +192,15 @@ impl Process {
 
     #[cfg(target_os = "linux")]
     pub fn permit_copy_rename(&self) -> bool {
-        match self {
-            Process::OsProcess(_) => env::var_os("RUSTUP_PERMIT_COPY_RENAME").is_some(),
-            #[cfg(feature = "test")]
-            Process::TestProcess(p) => p.vars.contains_key("RUSTUP_PERMIT_COPY_RENAME"),
-        }
+        self.var_os("RUSTUP_PERMIT_COPY_RENAME").is_some()
     }
 
     pub(crate) fn var_os(&self, key: &str) -> Option<OsString> {
         let value = match self {
-            Process::OsProcess(_) => env::var_os(key)?,
+            Process::OsProcess(p) => match p.vars.get(key) {
+                Some(val) => val.clone(),
+                None => env::var_os(key)?,
+            },
             #[cfg(feature = "test")]
             Process::TestProcess(p) => p.vars.get(key).map(OsString::from)?,
         };
@@ -183,6 +247,9 @@ impl Process {
 
     pub(crate) fn stderr(&self) -> ColorableTerminal {
         match self {
+            Process::OsProcess(OsProcess {
+                sink: Some(sink), ..
+            }) => ColorableTerminal::sink(sink.clone(), self),
             Process::OsProcess(_) => ColorableTerminal::stderr(self),
             #[cfg(feature = "test")]
             Process::TestProcess(p) => {
@@ -201,6 +268,7 @@ impl Process {
 
     pub fn progress_draw_target(&self) -> ProgressDrawTarget {
         match self {
+            Process::OsProcess(_) if self.is_library() => return ProgressDrawTarget::hidden(),
             Process::OsProcess(_) => (),
             #[cfg(feature = "test")]
             Process::TestProcess(_) => return ProgressDrawTarget::hidden(),
@@ -221,6 +289,9 @@ impl Process {
     }
 
     fn color_choice(&self, is_a_tty: bool) -> ColorChoice {
+        if self.is_library() {
+            return ColorChoice::Never;
+        }
         match self.var("RUSTUP_TERM_COLOR") {
             Ok(s) if s.eq_ignore_ascii_case("always") => ColorChoice::Always,
             Ok(s) if s.eq_ignore_ascii_case("never") => ColorChoice::Never,
@@ -263,6 +334,10 @@ impl home::env::Env for Process {
 pub struct OsProcess {
     pub(self) stderr_is_a_tty: bool,
     pub(self) stdout_is_a_tty: bool,
+    /// Variables that take precedence over the real environment.
+    pub(self) vars: HashMap<String, OsString>,
+    /// Where diagnostics go in place of stderr, see [`Process::library`].
+    pub(self) sink: Option<SharedWriter>,
 }
 
 impl OsProcess {
@@ -270,6 +345,8 @@ impl OsProcess {
         OsProcess {
             stderr_is_a_tty: io::stderr().is_terminal(),
             stdout_is_a_tty: io::stdout().is_terminal(),
+            vars: HashMap::new(),
+            sink: None,
         }
     }
 }
@@ -395,4 +472,68 @@ mod tests {
         // non-tty + `auto` does not enable the colors.
         assert_color_choice("aUTo", false, ColorChoice::Never);
     }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn permit_copy_rename_follows_env_overrides() {
+        let permit = |value: &str| {
+            let vars = HashMap::from([("RUSTUP_PERMIT_COPY_RENAME".to_owned(), value.to_owned())]);
+            Process::with_env(vars).permit_copy_rename()
+        };
+        assert!(permit("1"));
+        // An empty override hides the variable of the real environment.
+        assert!(!permit(""));
+    }
+
+    #[test]
+    fn library_process_writes_plain_diagnostics_to_its_sink() {
+        use std::io::Write;
+
+        #[derive(Clone, Default)]
+        struct Buf(Arc<Mutex<Vec<u8>>>);
+        impl Write for Buf {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.0.lock().unwrap().write(buf)
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let buf = Buf::default();
+        let process = Process::library(buf.clone()).with_var("RUSTUP_TERM_COLOR", "always");
+        assert_eq!(process.color_choice(true), ColorChoice::Never);
+        write!(process.stderr().lock(), "\x1b[1mwarning:\x1b[0m offline").unwrap();
+        assert_eq!(&*buf.0.lock().unwrap(), b"warning: offline");
+    }
+
+    #[test]
+    fn os_process_with_env_falls_back_to_the_environment() {
+        let process = Process::with_env(HashMap::from([
+            ("RUSTUP_TOOLCHAIN".to_owned(), "nightly".to_owned()),
+            ("PATH".to_owned(), String::new()),
+        ]));
+        assert_eq!(process.var("RUSTUP_TOOLCHAIN").unwrap(), "nightly");
+        assert_eq!(process.var("PATH"), Err(env::VarError::NotPresent));
+        assert_eq!(
+            process.var_os("CARGO_PKG_NAME"),
+            env::var_os("CARGO_PKG_NAME")
+        );
+    }
+
+    #[test]
+    fn overridden_homes_are_used_by_os_process() {
+        let process = Process::os()
+            .with_var("RUSTUP_HOME", "/sandbox/rustup")
+            .with_var("CARGO_HOME", "/sandbox/cargo");
+        assert_eq!(
+            process.rustup_home().unwrap(),
+            PathBuf::from("/sandbox/rustup")
+        );
+        assert_eq!(
+            process.cargo_home().unwrap(),
+            PathBuf::from("/sandbox/cargo")
+        );
+        assert_eq!(process.var("RUSTUP_HOME").unwrap(), "/sandbox/rustup");
+    }
 }
@@ -1,4 +1,6 @@
-use std::io::{self, BufRead, Read};
+use std::fmt;
+use std::io::{self, BufRead, Read, Write};
+use std::sync::{Arc, Mutex};
 
 /// Stand-in for std::io::Stdin
 pub trait Stdin {
@@ -18,6 +20,36 @@ impl Stdin for io::Stdin {
     }
 }
 
+// ----------------- writers supplied by embedders -----------------
+
+/// A writer shared between the clones of a process, e.g. the sink of
+/// [`Process::library`](super::Process::library).
+#[derive(Clone)]
+pub(crate) struct SharedWriter(Arc<Mutex<Box<dyn Write + Send>>>);
+
+impl SharedWriter {
+    pub(crate) fn new(writer: impl Write + Send + 'static) -> Self {
+        Self(Arc::new(Mutex::new(Box::new(writer))))
+    }
+}
+
+impl Write for SharedWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // The writer can be locked even if a thread panicked while writing to it
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).flush()
+    }
+}
+
+impl fmt::Debug for SharedWriter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SharedWriter { .. }")
+    }
+}
+
 #[cfg(feature = "test")]
 pub(crate) use self::test_support::*;
 
@@ -3,14 +3,13 @@ use std::{
     num::NonZero,
 };
 
-#[cfg(feature = "test")]
-use anstream::StripStream;
-use anstream::{AutoStream, ColorChoice};
+use anstream::{AutoStream, ColorChoice, StripStream};
 use console::Term;
 use indicatif::TermLike;
 use tracing_subscriber::fmt::MakeWriter;
 
 use super::Process;
+use super::file_source::SharedWriter;
 #[cfg(feature = "test")]
 use super::file_source::TestWriter;
 
@@ -43,6 +42,11 @@ impl ColorableTerminal {
         Self::new(StreamSelector::Stderr, is_a_tty, process)
     }
 
+    /// A terminal writing plain text to `writer`, which is never a tty.
+    pub(super) fn sink(writer: SharedWriter, process: &Process) -> Self {
+        Self::new(StreamSelector::Sink(writer), false, process)
+    }
+
     #[cfg(feature = "test")]
     pub(super) fn test(writer: TestWriter, process: &Process) -> Self {
         Self::new(StreamSelector::TestWriter(writer), false, process)
@@ -58,6 +62,7 @@ impl ColorableTerminal {
         let inner = match stream {
             StreamSelector::Stdout => TerminalInner::Stdout(AutoStream::new(io::stdout(), choice)),
             StreamSelector::Stderr => TerminalInner::Stderr(AutoStream::new(io::stderr(), choice)),
+            StreamSelector::Sink(w) => TerminalInner::Sink(w),
             #[cfg(feature = "test")]
             StreamSelector::TestWriter(w) if choice == ColorChoice::Always => {
                 TerminalInner::StyledTestWriter(w)
@@ -87,6 +92,9 @@ impl ColorableTerminal {
                 s.as_inner().lock(),
                 self.color_choice,
             )),
+            TerminalInner::Sink(w) => {
+                ColorableTerminalLocked::Sink(StripStream::new(Box::new(w.clone())))
+            }
             #[cfg(feature = "test")]
             TerminalInner::StyledTestWriter(w) => {
                 ColorableTerminalLocked::StyledTestWriter(Box::new(w.clone()))
@@ -196,6 +204,7 @@ impl std::fmt::Debug for ColorableTerminal {
 pub enum ColorableTerminalLocked {
     Stdout(AutoStream<io::StdoutLock<'static>>),
     Stderr(AutoStream<io::StderrLock<'static>>),
+    Sink(StripStream<Box<dyn Write>>),
     #[cfg(feature = "test")]
     StyledTestWriter(Box<dyn Write>),
     #[cfg(feature = "test")]
@@ -207,6 +216,7 @@ impl ColorableTerminalLocked {
         match self {
             Self::Stdout(s) => s,
             Self::Stderr(s) => s,
+            Self::Sink(w) => w,
             #[cfg(feature = "test")]
             Self::StyledTestWriter(w) => w,
             #[cfg(feature = "test")]
@@ -241,6 +251,7 @@ impl Write for ColorableTerminalLocked {
 enum TerminalInner {
     Stdout(AutoStream<io::Stdout>),
     Stderr(AutoStream<io::Stderr>),
+    Sink(SharedWriter),
     #[cfg(feature = "test")]
     StyledTestWriter(TestWriter),
     #[cfg(feature = "test")]
@@ -251,6 +262,7 @@ enum TerminalInner {
 pub(super) enum StreamSelector {
     Stdout,
     Stderr,
+    Sink(SharedWriter),
     #[cfg(feature = "test")]
     TestWriter(TestWriter),
 }
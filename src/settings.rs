@@ -5,12 +5,18 @@ use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
 use anyhow::{Context, Result};
+use clap::{ValueEnum, builder::PossibleValue};
+use serde::de::IgnoredAny;
 use serde::{Deserialize, Serialize};
+use thiserror::Error as ThisError;
 use tracing::info;
+use url::Url;
 
 use crate::cli::self_update::SelfUpdateMode;
+use crate::dist::TargetTriple;
 use crate::dist::{AutoInstallMode, Profile};
 use crate::errors::RustupError;
+use crate::toolchain::{ResolvableToolchainName, ToolchainName};
 use crate::utils;
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -61,6 +67,11 @@ impl SettingsFile {
         Ok(())
     }
 
+    /// Forgets the cached settings, so that they are read again from disk.
+    pub(crate) fn invalidate(&self) {
+        *self.cache.borrow_mut() = None;
+    }
+
     pub(crate) fn with<T, F: FnOnce(&Settings) -> Result<T>>(&self, f: F) -> Result<T> {
         self.read_settings()?;
 
@@ -95,6 +106,34 @@ pub struct Settings {
     pub auto_self_update: Option<SelfUpdateMode>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub auto_install: Option<AutoInstallMode>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dist_server: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub toolchain_file_search_limit: Option<ToolchainFileSearchLimit>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dup_tools: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub https_proxy: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub no_proxy: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mirrors: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mirror_fallback: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mirror_selection: Option<MirrorSelection>,
+    /// How long a mirror chosen by [`MirrorSelection::Latency`] is kept, in seconds.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mirror_selection_ttl: Option<u64>,
+    /// Whether `RUSTUP_TOOLCHAIN` is also read from `.env` files.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub env_file: bool,
+    /// The keys in `settings.toml` that rustup does not know about, and
+    /// which are dropped when the settings are written back.
+    #[serde(skip)]
+    pub(crate) unknown_keys: Vec<String>,
 }
 
 impl Settings {
@@ -125,8 +164,95 @@ impl Settings {
         self.overrides.get(&key).cloned()
     }
 
+    /// The directories with an override that are no longer directories.
+    pub(crate) fn stale_overrides(&self) -> Vec<PathBuf> {
+        self.overrides
+            .keys()
+            .map(PathBuf::from)
+            .filter(|path| !path.is_dir())
+            .collect()
+    }
+
     pub(crate) fn parse(data: &str) -> Result<Self> {
-        toml::from_str(data).context("error parsing settings")
+        #[derive(Deserialize)]
+        struct WithUnknownKeys {
+            #[serde(flatten)]
+            settings: Settings,
+            #[serde(flatten)]
+            unknown: BTreeMap<String, IgnoredAny>,
+        }
+
+        let parsed: WithUnknownKeys = toml::from_str(data).context("error parsing settings")?;
+        Ok(Self {
+            unknown_keys: parsed.unknown.into_keys().collect(),
+            ..parsed.settings
+        })
+    }
+
+    /// Checks the settings for values that cannot work, such as a default
+    /// toolchain that is not among the `installed` ones, or overrides for
+    /// directories that no longer exist.
+    ///
+    /// Problems that rustup can carry on past are returned as warnings.
+    pub(crate) fn validate(
+        &self,
+        installed: &[ToolchainName],
+        host: &TargetTriple,
+    ) -> Result<Vec<SettingsWarning>, SettingsError> {
+        if self.version != MetadataVersion::default() {
+            return Err(SettingsError::OutdatedVersion(self.version));
+        }
+
+        if let Some(name) = &self.default_toolchain {
+            let resolved = ResolvableToolchainName::try_from(name.as_str())
+                .ok()
+                .and_then(|n| n.resolve(host).ok());
+            if !resolved.is_some_and(|n| installed.contains(&n)) {
+                return Err(SettingsError::DefaultToolchainNotInstalled(name.clone()));
+            }
+        }
+
+        let mut dirs = BTreeMap::new();
+        for key in self.overrides.keys() {
+            let path = Path::new(key);
+            if !path.exists() {
+                return Err(SettingsError::MissingOverridePath(key.clone()));
+            }
+            if let Some(other) = dirs.insert(utils::canonicalize_path(path), key) {
+                return Err(SettingsError::DuplicateOverride(other.clone(), key.clone()));
+            }
+        }
+
+        for (setting, url) in [("proxy", &self.proxy), ("https_proxy", &self.https_proxy)] {
+            if let Some(url) = url
+                && Url::parse(url).is_err()
+            {
+                return Err(SettingsError::InvalidProxy {
+                    setting,
+                    url: url.clone(),
+                });
+            }
+        }
+
+        if let Some(mirror) = self
+            .mirrors
+            .iter()
+            .find(|m| Url::parse(m).map_or(true, |u| !matches!(u.scheme(), "https" | "file")))
+        {
+            return Err(SettingsError::InsecureMirror(mirror.clone()));
+        }
+
+        let mut warnings = Vec::new();
+        if self.pgp_keys.is_some() {
+            warnings.push(SettingsWarning::DeprecatedField("pgp_keys"));
+        }
+        warnings.extend(
+            self.unknown_keys
+                .iter()
+                .cloned()
+                .map(SettingsWarning::UnknownKey),
+        );
+        Ok(warnings)
     }
 
     fn stringify(&self) -> Result<String> {
@@ -134,6 +260,164 @@ impl Settings {
     }
 }
 
+/// A problem found by [`Settings::validate`] that leaves the settings unusable.
+#[derive(Debug, Eq, PartialEq, ThisError)]
+pub enum SettingsError {
+    #[error("metadata version {0} is out of date; run `rustup self upgrade-data`")]
+    OutdatedVersion(MetadataVersion),
+    #[error("the default toolchain '{0}' is not installed")]
+    DefaultToolchainNotInstalled(String),
+    #[error("the override for '{0}' is for a directory that does not exist")]
+    MissingOverridePath(String),
+    #[error("the overrides for '{0}' and '{1}' are for the same directory")]
+    DuplicateOverride(String, String),
+    #[error("`{setting}` is not a valid URL: '{url}'")]
+    InvalidProxy { setting: &'static str, url: String },
+    #[error("the mirror '{0}' is not an HTTPS URL")]
+    InsecureMirror(String),
+}
+
+/// Something in the settings that rustup ignores.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum SettingsWarning {
+    /// A setting that is no longer used.
+    DeprecatedField(&'static str),
+    /// A key that rustup does not know about.
+    UnknownKey(String),
+}
+
+impl fmt::Display for SettingsWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DeprecatedField(name) => write!(f, "`{name}` is no longer used"),
+            Self::UnknownKey(key) => write!(f, "unknown setting `{key}` is ignored"),
+        }
+    }
+}
+
+/// How far up the directory tree rustup looks for directory overrides and
+/// `rust-toolchain{.toml}` files.
+///
+/// The walk always ends at the filesystem root; each limit can only end it
+/// sooner.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ToolchainFileSearchLimit {
+    /// Walk up to the filesystem root.
+    #[default]
+    Root,
+    /// Do not leave the filesystem the walk started on (Unix only).
+    Filesystem,
+    /// Do not walk above the home directory when starting inside it.
+    Home,
+    /// Stop at the first directory containing `.git`.
+    GitRoot,
+}
+
+impl ToolchainFileSearchLimit {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            Self::Root => "root",
+            Self::Filesystem => "filesystem",
+            Self::Home => "home",
+            Self::GitRoot => "git-root",
+        }
+    }
+
+    /// The directories from `start` up to where this limit ends the walk,
+    /// innermost first.
+    pub(crate) fn search_dirs<'a>(&self, start: &'a Path, home: Option<&Path>) -> Vec<&'a Path> {
+        let mut dirs = Vec::new();
+        for dir in start.ancestors() {
+            if let Some(&last) = dirs.last()
+                && !self.may_leave(last, dir, home)
+            {
+                break;
+            }
+            dirs.push(dir);
+        }
+        dirs
+    }
+
+    /// Whether the walk may continue from `dir` to its `parent`.
+    fn may_leave(&self, dir: &Path, parent: &Path, home: Option<&Path>) -> bool {
+        match self {
+            Self::Root => true,
+            Self::Filesystem => same_filesystem(dir, parent),
+            Self::Home => home.is_none_or(|home| dir != home),
+            Self::GitRoot => !dir.join(".git").exists(),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn same_filesystem(a: &Path, b: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    match (a.metadata(), b.metadata()) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev(),
+        _ => true,
+    }
+}
+
+#[cfg(not(unix))]
+fn same_filesystem(_: &Path, _: &Path) -> bool {
+    true
+}
+
+impl ValueEnum for ToolchainFileSearchLimit {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[Self::Root, Self::Filesystem, Self::Home, Self::GitRoot]
+    }
+
+    fn to_possible_value(&self) -> Option<PossibleValue> {
+        Some(PossibleValue::new(self.as_str()))
+    }
+}
+
+impl fmt::Display for ToolchainFileSearchLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Which mirror of the release server is downloaded from first.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MirrorSelection {
+    /// The first mirror added.
+    #[default]
+    Ordered,
+    /// The mirror that responds the fastest, which is measured again once
+    /// `mirror_selection_ttl` has passed.
+    Latency,
+}
+
+impl MirrorSelection {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            Self::Ordered => "ordered",
+            Self::Latency => "latency",
+        }
+    }
+}
+
+impl ValueEnum for MirrorSelection {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[Self::Ordered, Self::Latency]
+    }
+
+    fn to_possible_value(&self) -> Option<PossibleValue> {
+        Some(PossibleValue::new(self.as_str()))
+    }
+}
+
+impl fmt::Display for MirrorSelection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
 #[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
 pub enum MetadataVersion {
     #[serde(rename = "2")]
@@ -218,6 +502,113 @@ mod tests {
         assert_eq!(settings.profile, Some(Profile::Default));
     }
 
+    #[test]
+    fn search_limits_end_the_walk() {
+        let root = tempfile::tempdir().unwrap();
+        let home = root.path().join("home");
+        let repo = home.join("repo");
+        let start = repo.join("src");
+        std::fs::create_dir_all(&start).unwrap();
+        std::fs::create_dir(repo.join(".git")).unwrap();
+
+        let dirs = ToolchainFileSearchLimit::GitRoot.search_dirs(&start, Some(&home));
+        assert_eq!(dirs, [start.as_path(), repo.as_path()]);
+
+        let dirs = ToolchainFileSearchLimit::Home.search_dirs(&start, Some(&home));
+        assert_eq!(dirs, [start.as_path(), repo.as_path(), home.as_path()]);
+
+        let dirs = ToolchainFileSearchLimit::Root.search_dirs(&start, Some(&home));
+        assert_eq!(dirs, start.ancestors().collect::<Vec<_>>());
+
+        // Outside the home directory, `home` does not end the walk early.
+        let dirs = ToolchainFileSearchLimit::Home.search_dirs(root.path(), Some(&home));
+        assert_eq!(dirs, root.path().ancestors().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn dup_tools_round_trip() {
+        let settings = Settings {
+            dup_tools: vec!["taplo".to_owned()],
+            ..Default::default()
+        };
+        let toml = settings.stringify().unwrap();
+        assert!(toml.contains(r#"dup_tools = ["taplo"]"#), "{toml}");
+        assert_eq!(Settings::parse(&toml).unwrap(), settings);
+    }
+
+    #[test]
+    fn validate_reports_unusable_settings() {
+        let host = TargetTriple::new("x86_64-unknown-linux-gnu");
+        let stable = ToolchainName::try_from("stable-x86_64-unknown-linux-gnu").unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path().display().to_string();
+
+        let settings = Settings::parse(
+            r#"version = "12"
+default_toolchain = "stable"
+pgp_keys = "keys.asc"
+colour = "blue"
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            settings.validate(std::slice::from_ref(&stable), &host),
+            Ok(vec![
+                SettingsWarning::DeprecatedField("pgp_keys"),
+                SettingsWarning::UnknownKey("colour".to_owned()),
+            ])
+        );
+        assert_eq!(
+            settings.validate(&[], &host),
+            Err(SettingsError::DefaultToolchainNotInstalled(
+                "stable".to_owned()
+            ))
+        );
+
+        let settings = Settings {
+            overrides: [
+                (dir.clone(), "stable".to_owned()),
+                (format!("{dir}/."), "nightly".to_owned()),
+            ]
+            .into(),
+            ..Default::default()
+        };
+        assert_eq!(
+            settings.validate(&[], &host),
+            Err(SettingsError::DuplicateOverride(
+                dir.clone(),
+                format!("{dir}/.")
+            ))
+        );
+
+        let settings = Settings {
+            overrides: [(format!("{dir}/gone"), "stable".to_owned())].into(),
+            ..Default::default()
+        };
+        assert_eq!(
+            settings.validate(&[], &host),
+            Err(SettingsError::MissingOverridePath(format!("{dir}/gone")))
+        );
+
+        let settings = Settings {
+            mirrors: vec!["http://mirror.example.com".to_owned()],
+            ..Default::default()
+        };
+        assert_eq!(
+            settings.validate(&[], &host),
+            Err(SettingsError::InsecureMirror(
+                "http://mirror.example.com".to_owned()
+            ))
+        );
+
+        // Mirrors on the local filesystem cannot be tampered with in transit.
+        let settings = Settings {
+            mirrors: vec!["file:///srv/rust-mirror".to_owned()],
+            ..Default::default()
+        };
+        assert_eq!(settings.validate(&[], &host), Ok(Vec::new()));
+    }
+
     const BASIC: &str = r#"version = "12"
 default_toolchain = "stable-aarch64-apple-darwin"
 profile = "default"
@@ -453,11 +453,13 @@ impl Config {
         }
 
         let tp = process::TestProcess::new(&*self.workdir.borrow(), &arg_strings, vars, "");
-        let process_res = rustup_mode::main(
+        // Boxed so that the futures of the tests, which run many commands,
+        // do not hold the whole state of `main` and overflow the stack.
+        let process_res = Box::pin(rustup_mode::main(
             tp.process.current_dir().unwrap(),
             &tp.process,
             tp.console_filter.clone(),
-        )
+        ))
         .await;
         // convert Err's into an ec
         let ec = match process_res {
@@ -786,6 +786,7 @@ impl MockDistServer {
                 let mut tpkg = TargetedPackage {
                     bins: Vec::new(),
                     components: Vec::new(),
+                    delta: None,
                 };
 
                 let package_file_name = if target.target != "*" {
@@ -872,9 +873,10 @@ impl MockDistServer {
         ];
 
         for (profile, values) in profiles {
-            manifest
-                .profiles
-                .insert(*profile, values.iter().map(|&v| v.to_owned()).collect());
+            manifest.profiles.insert(
+                profile.clone(),
+                values.iter().map(|&v| v.to_owned()).collect(),
+            );
         }
 
         let manifest_name = format!("dist/channel-rust-{}", channel.name);
@@ -21,9 +21,9 @@ use wait_timeout::ChildExt;
 
 use crate::{
     RustupError,
-    config::{ActiveSource, Cfg, InstalledPath},
+    config::{ActiveSource, Cfg, InstalledPath, OverrideFile, ParseMode, ToolchainSection},
     dist::{
-        DistOptions, PartialToolchainDesc, TargetTriple,
+        DistOptions, PartialToolchainDesc, Profile, TargetTriple,
         component::{Component, Components},
         prefix::InstallPrefix,
     },
@@ -34,6 +34,9 @@ use crate::{
 mod distributable;
 pub(crate) use distributable::DistributableToolchain;
 
+mod metadata;
+pub(crate) use metadata::InstallMetadata;
+
 mod names;
 pub(crate) use names::{
     CustomToolchainName, LocalToolchainName, MaybeOfficialToolchainName,
@@ -98,6 +101,10 @@ impl<'a> Toolchain<'a> {
                 "the toolchain file at '{}' specifies an uninstalled toolchain",
                 utils::canonicalize_path(path).display(),
             ),
+            ActiveSource::EnvFile(path) => format!(
+                "the RUSTUP_TOOLCHAIN in '{}' specifies an uninstalled toolchain",
+                utils::canonicalize_path(path).display(),
+            ),
             ActiveSource::Default => {
                 "the default toolchain does not describe an installed toolchain".to_string()
             }
@@ -180,6 +187,14 @@ impl<'a> Toolchain<'a> {
         cmd.env("RUSTUP_HOME", &self.cfg.rustup_dir);
     }
 
+    /// A command running `path`, a binary proxied with `rustup proxy add`, in
+    /// the environment of this toolchain.
+    pub(crate) fn proxied_command(&self, path: &Path) -> Command {
+        let mut cmd = Command::new(path);
+        self.set_env(&mut cmd);
+        cmd
+    }
+
     /// Apply the appropriate LD path for a command being run from a toolchain.
     fn set_ldpath(&self, cmd: &mut Command) {
         #[cfg_attr(not(target_os = "macos"), allow(unused_mut))]
@@ -532,11 +547,21 @@ impl<'a> Toolchain<'a> {
     ///
     ///
     pub fn ensure_removed(cfg: &Cfg<'_>, name: LocalToolchainName) -> anyhow::Result<()> {
+        let _lock = cfg.lock_home()?;
         let path = cfg.toolchain_path(&name);
         let name = match name {
             LocalToolchainName::Named(t) => t,
             LocalToolchainName::Path(_) => bail!("Cannot remove a path based toolchain"),
         };
+        // Only the link file goes, never the directory it links
+        if let ToolchainName::Custom(custom) = &name
+            && path != cfg.toolchains_dir.join(custom.to_string())
+        {
+            info!("uninstalling toolchain {name}");
+            utils::remove_file("toolchain link", &cfg.toolchain_link_file(custom))?;
+            info!("toolchain {name} uninstalled");
+            return Ok(());
+        }
         let fs_modified = match Self::exists(cfg, &(&name).into())? {
             true => {
                 info!("uninstalling toolchain {name}");
@@ -587,6 +612,26 @@ impl<'a> Toolchain<'a> {
         Components::open(prefix)?.list()
     }
 
+    /// Get the record of how the toolchain was installed, if it has one
+    pub(crate) fn install_metadata(&self) -> anyhow::Result<Option<InstallMetadata>> {
+        InstallMetadata::read(&self.path)
+    }
+
+    /// Update the components added since install in the record of how the
+    /// toolchain was installed, if it has one
+    pub(crate) fn sync_install_metadata(&self) -> anyhow::Result<()> {
+        let Some(mut metadata) = self.install_metadata()? else {
+            return Ok(());
+        };
+        let installed = self
+            .installed_components()?
+            .iter()
+            .map(|c| c.name().to_owned())
+            .collect::<Vec<_>>();
+        metadata.sync_components(&installed);
+        metadata.write(&self.path)
+    }
+
     /// Get the list of installed targets for any toolchain
     pub fn installed_targets(&self) -> anyhow::Result<Vec<TargetTriple>> {
         Ok(self
@@ -600,3 +645,136 @@ impl<'a> Toolchain<'a> {
             .collect())
     }
 }
+
+/// Links the toolchain directory `dest` in `RUSTUP_HOME/toolchains` to `src`.
+///
+/// On Windows a directory junction is created, as those do not need elevated
+/// privileges or Developer Mode the way symbolic links do. Junctions must hold
+/// absolute paths, which is fine for linked toolchains as `src` is always
+/// absolute. Where a junction cannot be created, e.g. for a network share, a
+/// symbolic link is tried instead.
+pub(crate) fn link_dir(src: &Path, dest: &Path) -> anyhow::Result<()> {
+    #[cfg(windows)]
+    match utils::raw::symlink_junction(src, dest) {
+        Ok(()) => return Ok(()),
+        Err(e) => tracing::debug!(
+            "could not create a junction from '{}' to '{}': {e}",
+            src.display(),
+            dest.display()
+        ),
+    }
+    utils::symlink_dir(src, dest)
+}
+
+/// The toolchain requested by a `rust-toolchain` or `rust-toolchain.toml` file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ToolchainSpec {
+    /// The requested channel, e.g. `nightly-2024-01-01`.
+    ///
+    /// `None` if the file names a `path` toolchain instead, or only lists
+    /// components and targets to add to the default toolchain.
+    pub channel: Option<String>,
+    /// The absolute path of a toolchain that is not managed by rustup.
+    pub path: Option<PathBuf>,
+    pub components: Vec<String>,
+    pub targets: Vec<String>,
+    pub profile: Option<Profile>,
+}
+
+/// Parse a toolchain file the way rustup does when looking for overrides.
+///
+/// A file with a `.toml` extension must be in the TOML format; any other file
+/// may also hold just the channel name, as the legacy `rust-toolchain` did.
+/// Whether the named toolchain is installed is not checked.
+pub fn parse_toolchain_file(path: &Path) -> anyhow::Result<ToolchainSpec> {
+    let parse_mode = match path.extension() {
+        Some(ext) if ext == "toml" => ParseMode::OnlyToml,
+        _ => ParseMode::Both,
+    };
+    let contents = utils::read_file("toolchain file", path)?;
+    let file = Cfg::parse_override_file(contents, parse_mode).with_context(|| {
+        RustupError::ParsingFile {
+            name: "override",
+            path: path.to_owned(),
+        }
+    })?;
+    ToolchainSpec::from_override_file(path, file)
+}
+
+impl ToolchainSpec {
+    /// Validates the parsed toolchain file at `path`.
+    pub(crate) fn from_override_file(path: &Path, file: OverrideFile) -> anyhow::Result<Self> {
+        file.check_min_rustup_version(path)?;
+
+        let ToolchainSection {
+            channel,
+            path: toolchain_path,
+            components,
+            targets,
+            profile,
+            min_rustup_version: _,
+        } = file.toolchain;
+        match (&channel, &toolchain_path) {
+            (Some(channel), Some(toolchain_path)) => bail!(
+                "cannot specify both channel ({}) and path ({}) simultaneously",
+                channel,
+                toolchain_path.display()
+            ),
+            (Some(channel), None) => {
+                ResolvableToolchainName::try_from(channel).map_err(|_| {
+                    anyhow!(
+                        "invalid toolchain name detected in override file '{}'",
+                        path.display()
+                    )
+                })?;
+            }
+            (None, Some(toolchain_path)) => {
+                if targets.is_some() || components.is_some() || profile.is_some() {
+                    bail!(
+                        "toolchain options are ignored for path toolchain ({})",
+                        toolchain_path.display()
+                    )
+                }
+                PathBasedToolchainName::try_from(toolchain_path as &Path)?;
+            }
+            (None, None) => {}
+        }
+
+        Ok(ToolchainSpec {
+            channel,
+            path: toolchain_path,
+            components: components.unwrap_or_default(),
+            targets: targets.unwrap_or_default(),
+            profile: profile.as_deref().map(Profile::from_str).transpose()?,
+        })
+    }
+}
+
+#[cfg(all(test, windows))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn linked_toolchains_are_junctions() {
+        let tmp = tempfile::tempdir().unwrap();
+        let src = tmp.path().join("build").join("stage2");
+        let dest = tmp.path().join("toolchains").join("custom");
+        fs::create_dir_all(src.join("bin")).unwrap();
+        fs::create_dir_all(dest.parent().unwrap()).unwrap();
+        fs::write(src.join("bin").join("rustc.exe"), "").unwrap();
+
+        link_dir(&src, &dest).unwrap();
+
+        // Junctions are reparse points that `read_link` resolves, just like
+        // symbolic links, but creating them needs no privileges.
+        let target = fs::read_link(&dest).unwrap();
+        assert!(is_same_file(&target, &src).unwrap());
+        assert!(utils::is_file(dest.join("bin").join("rustc.exe")));
+        assert!(utils::is_directory(&dest));
+
+        // Removing the link leaves the linked toolchain alone.
+        utils::remove_dir("toolchain", &dest).unwrap();
+        assert!(!dest.exists());
+        assert!(utils::is_file(src.join("bin").join("rustc.exe")));
+    }
+}
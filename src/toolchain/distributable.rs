@@ -6,12 +6,13 @@ use std::{convert::Infallible, env::consts::EXE_SUFFIX, ffi::OsStr, process::Com
 use anyhow::Context;
 use anyhow::anyhow;
 use platforms::Platform;
+use tracing::warn;
 
 use crate::{
     RustupError, component_for_bin,
     config::{ActiveSource, Cfg},
     dist::{
-        DistOptions, PartialToolchainDesc, ToolchainDesc,
+        DistOptions, PartialToolchainDesc, TargetTriple, ToolchainDesc,
         config::Config,
         download::DownloadCfg,
         manifest::{Component, ComponentStatus, Manifest},
@@ -60,10 +61,102 @@ impl<'a> DistributableToolchain<'a> {
         &self.desc
     }
 
-    pub(crate) async fn add_component(&self, mut component: Component) -> anyhow::Result<()> {
-        // TODO: take multiple components?
+    pub(crate) async fn add_component(&self, component: Component) -> anyhow::Result<()> {
+        self.add_components(vec![component]).await
+    }
+
+    /// Adds `component` like [`Self::add_component`], but only warns when the
+    /// release of the toolchain lacks it, or has it but not for download.
+    pub(crate) async fn add_component_if_available(
+        &self,
+        component: Component,
+    ) -> anyhow::Result<()> {
+        let description = self.get_manifest()?.description(&component);
+        match self.add_component(component).await {
+            Err(e)
+                if matches!(
+                    e.downcast_ref::<RustupError>(),
+                    Some(
+                        RustupError::UnknownComponent { .. }
+                            | RustupError::RequestedComponentsUnavailable { .. }
+                    )
+                ) =>
+            {
+                warn!(
+                    "skipping component {description}, which is unavailable for '{}'",
+                    self.desc
+                );
+                Ok(())
+            }
+            res => res,
+        }
+    }
+
+    /// Adds all of `components` in a single update of the toolchain, so that
+    /// the manifest is only read once and they are downloaded together.
+    pub(crate) async fn add_components(&self, components: Vec<Component>) -> anyhow::Result<()> {
+        let _lock = self.toolchain.cfg.lock_home_async().await?;
         let manifestation = self.get_manifestation()?;
         let manifest = self.get_manifest()?;
+        let components = components
+            .into_iter()
+            .map(|component| self.validate_component(component, &manifestation, &manifest))
+            .collect::<anyhow::Result<_>>()?;
+
+        let changes = Changes {
+            explicit_add_components: components,
+            remove_components: vec![],
+        };
+
+        let download_cfg = DownloadCfg::new(self.toolchain.cfg);
+        manifestation
+            .update(
+                manifest,
+                changes,
+                false,
+                &download_cfg,
+                self.desc.manifest_name(),
+                false,
+            )
+            .await?;
+
+        self.toolchain.sync_install_metadata()
+    }
+
+    /// The component `name` for each of `targets`, or for each installed
+    /// target when `targets` is `None`. A component that is the same for all
+    /// targets, like `rust-src`, is only returned once.
+    pub(crate) fn component_for_targets(
+        &self,
+        name: &str,
+        targets: Option<&[TargetTriple]>,
+    ) -> anyhow::Result<Vec<Component>> {
+        let installed;
+        let targets = match targets {
+            Some(targets) => targets,
+            None => {
+                installed = self.toolchain.installed_targets()?;
+                &installed
+            }
+        };
+        let mut components = Vec::new();
+        for target in targets {
+            let component = Component::try_new(name, self, Some(target))?;
+            if !components.contains(&component) {
+                components.push(component);
+            }
+        }
+        Ok(components)
+    }
+
+    /// Checks that the release of the toolchain has `component`, returning it
+    /// under the name the manifest uses for it.
+    fn validate_component(
+        &self,
+        mut component: Component,
+        manifestation: &Manifestation,
+        manifest: &Manifest,
+    ) -> anyhow::Result<Component> {
         // Rename the component if necessary.
         if let Some(c) = manifest.rename_component(&component) {
             component = c;
@@ -86,7 +179,7 @@ impl<'a> DistributableToolchain<'a> {
             } else {
                 let config = manifestation.read_config()?.unwrap_or_default();
                 let suggestion =
-                    self.get_component_suggestion(&component, &config, &manifest, false);
+                    self.get_component_suggestion(&component, &config, manifest, false);
                 let desc = self.desc.clone();
                 // Check if the target is supported.
                 if !targ_pkg
@@ -113,25 +206,7 @@ impl<'a> DistributableToolchain<'a> {
                 .into());
             }
         }
-
-        let changes = Changes {
-            explicit_add_components: vec![component],
-            remove_components: vec![],
-        };
-
-        let download_cfg = DownloadCfg::new(self.toolchain.cfg);
-        manifestation
-            .update(
-                manifest,
-                changes,
-                false,
-                &download_cfg,
-                self.desc.manifest_name(),
-                false,
-            )
-            .await?;
-
-        Ok(())
+        Ok(component)
     }
 
     pub(crate) fn components(&self) -> anyhow::Result<Vec<ComponentStatus>> {
@@ -387,6 +462,7 @@ impl<'a> DistributableToolchain<'a> {
 
     pub(crate) async fn remove_component(&self, mut component: Component) -> anyhow::Result<()> {
         // TODO: take multiple components?
+        let _lock = self.toolchain.cfg.lock_home_async().await?;
         let manifestation = self.get_manifestation()?;
         let config = manifestation.read_config()?.unwrap_or_default();
         let manifest = self.get_manifest()?;
@@ -442,7 +518,7 @@ impl<'a> DistributableToolchain<'a> {
             )
             .await?;
 
-        Ok(())
+        self.toolchain.sync_install_metadata()
     }
 
     pub async fn show_dist_version(&self) -> anyhow::Result<Option<String>> {
@@ -0,0 +1,84 @@
+//! The record of how a toolchain was installed, kept in its directory.
+
+use std::path::Path;
+use std::time::SystemTime;
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{RustupError, dist::Profile, utils};
+
+/// The file in the toolchain directory holding its [`InstallMetadata`].
+pub(crate) const METADATA_FILE: &str = "rustup-metadata.toml";
+
+/// What rustup knew about a toolchain when installing it from dist.
+///
+/// Toolchains installed by older versions of rustup, and custom toolchains,
+/// have no such record.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub(crate) struct InstallMetadata {
+    /// The profile the toolchain was first installed with.
+    pub(crate) profile: Profile,
+    pub(crate) install_date: DateTime<Utc>,
+    /// The dist server the toolchain was installed from.
+    pub(crate) dist_server: String,
+    /// The version of rustup that installed the toolchain.
+    pub(crate) installer_version: String,
+    /// The components installed with the toolchain, e.g. `rust-std-x86_64-unknown-linux-gnu`.
+    pub(crate) components_at_install: Vec<String>,
+    /// The components added since, and still installed.
+    #[serde(default)]
+    pub(crate) components_added: Vec<String>,
+}
+
+impl InstallMetadata {
+    /// The record of a toolchain installed just now.
+    pub(crate) fn new(profile: Profile, dist_server: &str, components: Vec<String>) -> Self {
+        Self {
+            profile,
+            install_date: DateTime::from(SystemTime::now()),
+            dist_server: dist_server.to_owned(),
+            installer_version: env!("CARGO_PKG_VERSION").to_owned(),
+            components_at_install: components,
+            components_added: Vec::new(),
+        }
+    }
+
+    /// Reads the record from `toolchain_dir`, if it has one.
+    pub(crate) fn read(toolchain_dir: &Path) -> anyhow::Result<Option<Self>> {
+        let path = toolchain_dir.join(METADATA_FILE);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        let contents = utils::read_file("install metadata", &path)?;
+        let metadata = toml::from_str(&contents).with_context(|| RustupError::ParsingFile {
+            name: "install metadata",
+            path: path.clone(),
+        })?;
+        Ok(Some(metadata))
+    }
+
+    /// Writes the record into `toolchain_dir`.
+    pub(crate) fn write(&self, toolchain_dir: &Path) -> anyhow::Result<()> {
+        let contents = toml::to_string(self).context("could not serialize install metadata")?;
+        utils::write_file(
+            "install metadata",
+            &toolchain_dir.join(METADATA_FILE),
+            &contents,
+        )
+    }
+
+    /// Brings `components_added` in line with the `installed` components,
+    /// after components were added or removed.
+    pub(crate) fn sync_components(&mut self, installed: &[String]) {
+        self.components_added.retain(|c| installed.contains(c));
+        for component in installed {
+            if !self.components_at_install.contains(component)
+                && !self.components_added.contains(component)
+            {
+                self.components_added.push(component.clone());
+            }
+        }
+    }
+}
@@ -139,6 +139,14 @@ pub fn symlink_dir(src: &Path, dest: &Path) -> io::Result<()> {
     symlink_dir_inner(src, dest)
 }
 
+/// Creates a directory junction at `junction` pointing to `target`, replacing
+/// an existing empty directory or link there.
+#[cfg(windows)]
+pub fn symlink_junction(target: &Path, junction: &Path) -> io::Result<()> {
+    let _ = remove_dir(junction);
+    symlink_junction_inner(target, junction)
+}
+
 // Creating a directory junction on windows involves dealing with reparse
 // points and the DeviceIoControl function, and this code is a skeleton of
 // what can be found here:
@@ -214,6 +214,52 @@ nightly-[HOST_TRIPLE] - update available: 1.2.0 (hash-nightly-1) -> 1.3.0 (hash-
 "#]]);
 }
 
+#[tokio::test]
+async fn update_notify_only() {
+    let mut cx = CliTestContext::new(Scenario::None).await;
+
+    {
+        let cx = cx.with_dist_dir(Scenario::ArchivesV2_2015_01_01);
+        cx.config
+            .expect(["rustup", "toolchain", "add", "stable", "beta"])
+            .await
+            .is_ok();
+    }
+
+    let cx = cx.with_dist_dir(Scenario::SimpleV2);
+    cx.config
+        .expect(["rustup", "update", "--notify-only"])
+        .await
+        .with_stdout(snapbox::str![[r#"
+stable-[HOST_TRIPLE] - update available: 1.0.0 (hash-stable-1.0.0) -> 1.1.0 (hash-stable-1.1.0)
+
+"#]])
+        .with_stderr(snapbox::str![[r#"
+info: run `rustup update` to install the new releases
+
+"#]])
+        .is_ok();
+    cx.config
+        .expect(["rustup", "update", "--notify-only", "beta", "nightly"])
+        .await
+        .with_stdout(snapbox::str![[r#"
+beta-[HOST_TRIPLE] - update available: 1.1.0 (hash-beta-1.1.0) -> 1.2.0 (hash-beta-1.2.0)
+nightly-[HOST_TRIPLE] - not installed
+
+"#]])
+        .is_ok();
+
+    // Nothing was downloaded
+    cx.config
+        .expect(["rustup", "run", "stable", "rustc", "--version"])
+        .await
+        .with_stdout(snapbox::str![[r#"
+1.0.0 (hash-stable-1.0.0)
+
+"#]])
+        .is_ok();
+}
+
 #[tokio::test]
 async fn check_updates_self() {
     let test_version = "2.0.0";
@@ -464,6 +510,7 @@ async fn remove_override_with_path_deleted() {
             .is_ok()
             .with_stdout(snapbox::str![[""]])
             .with_stderr(snapbox::str![[r#"
+warn: invalid settings: the override for '[PATH]' is for a directory that does not exist
 info: override toolchain for '[PATH]' removed
 
 "#]]);
@@ -498,6 +545,7 @@ async fn remove_override_nonexistent() {
             .is_ok()
             .with_stdout(snapbox::str![[""]])
             .with_stderr(snapbox::str![[r#"
+warn: invalid settings: the override for '[PATH]' is for a directory that does not exist
 info: override toolchain for '[PATH]' removed
 
 "#]]);
@@ -566,9 +614,109 @@ async fn list_overrides_with_nonexistent() {
 
 "#]])
         .with_stderr(snapbox::str![[r#"
+warn: invalid settings: the override for '[..]' is for a directory that does not exist
 info: you may remove overrides for non-existent directories with
 `rustup override unset --nonexistent`
 
+"#]]);
+    cx.config
+        .expect(["rustup", "override", "list", "--json"])
+        .await
+        .extend_redactions([("[DIR]", serde_json::to_string(&nonexistent_path).unwrap())])
+        .is_ok()
+        .with_stdout(snapbox::str![[r#"
+[{"dir":[DIR],"toolchain":"nightly-[HOST_TRIPLE]","exists":false}]
+
+"#]])
+        .with_stderr(snapbox::str![[r#"
+warn: invalid settings: the override for '[..]' is for a directory that does not exist
+
+"#]]);
+}
+
+#[tokio::test]
+#[cfg_attr(target_os = "windows", ignore)] // FIXME #1103
+async fn override_clean_removes_nonexistent() {
+    let mut cx = CliTestContext::new(Scenario::SimpleV2).await;
+    let path = {
+        let dir = tempfile::Builder::new()
+            .prefix("rustup-test")
+            .tempdir()
+            .unwrap();
+        let path = std::fs::canonicalize(dir.path()).unwrap();
+        let cx = cx.change_dir(&path);
+        cx.config
+            .expect(["rustup", "override", "add", "nightly"])
+            .await
+            .is_ok();
+        path
+    };
+    let _ = raw::remove_dir(&path);
+    assert!(!path.exists());
+
+    cx.config
+        .expect(["rustup", "override", "clean", "--dry-run"])
+        .await
+        .extend_redactions([("[PATH]", &path)])
+        .is_ok()
+        .with_stderr(snapbox::str![[r#"
+warn: invalid settings: the override for '[PATH]' is for a directory that does not exist
+info: would remove the override for '[PATH]'
+
+"#]]);
+    cx.config
+        .expect(["rustup", "override", "clean"])
+        .await
+        .extend_redactions([("[PATH]", &path)])
+        .is_ok()
+        .with_stderr(snapbox::str![[r#"
+warn: invalid settings: the override for '[PATH]' is for a directory that does not exist
+info: override toolchain for '[PATH]' removed
+
+"#]]);
+    cx.config
+        .expect(["rustup", "override", "clean"])
+        .await
+        .is_ok()
+        .with_stderr(snapbox::str![[r#"
+info: no overrides for nonexistent directories
+
+"#]]);
+}
+
+#[tokio::test]
+#[cfg_attr(target_os = "windows", ignore)] // FIXME #1103
+async fn stale_override_is_removed_when_reached() {
+    let mut cx = CliTestContext::new(Scenario::SimpleV2).await;
+    cx.config
+        .expect(["rustup", "default", "nightly"])
+        .await
+        .is_ok();
+    let dir = tempfile::Builder::new()
+        .prefix("rustup-test")
+        .tempdir()
+        .unwrap();
+    let path = std::fs::canonicalize(dir.path()).unwrap();
+    let cx = cx.change_dir(&path);
+    cx.config
+        .expect(["rustup", "override", "add", "beta"])
+        .await
+        .is_ok();
+    raw::remove_dir(&path).unwrap();
+
+    cx.config
+        .expect(["rustup", "show", "active-toolchain"])
+        .await
+        .extend_redactions([("[PATH]", &path)])
+        .is_ok()
+        .with_stdout(snapbox::str![[r#"
+nightly-[HOST_TRIPLE] (default)
+
+"#]])
+        .with_stderr(snapbox::str![[r#"
+warn: invalid settings: the override for '[PATH]' is for a directory that does not exist
+warn: removing the override for '[PATH]', which no longer exists
+
 "#]]);
 }
 
@@ -742,6 +890,33 @@ async fn list_installed_targets() {
         .with_stderr(snapbox::str![[""]]);
 }
 
+#[tokio::test]
+async fn list_not_installed_targets() {
+    let cx = CliTestContext::new(Scenario::SimpleV2).await;
+    cx.config
+        .expect(["rustup", "default", "nightly"])
+        .await
+        .is_ok();
+    cx.config
+        .expect(["rustup", "target", "add", CROSS_ARCH1])
+        .await
+        .is_ok();
+    cx.config
+        .expect(["rustup", "target", "list", "--not-installed"])
+        .await
+        .extend_redactions([("[CROSS_ARCH_II]", CROSS_ARCH2)])
+        .is_ok()
+        .with_stdout(snapbox::str![[r#"
+[CROSS_ARCH_II]
+
+"#]])
+        .with_stderr(snapbox::str![[""]]);
+    cx.config
+        .expect(["rustup", "target", "list", "--installed", "--not-installed"])
+        .await
+        .is_err();
+}
+
 #[tokio::test]
 async fn cross_install_indicates_target() {
     let cx = CliTestContext::new(Scenario::SimpleV2).await;
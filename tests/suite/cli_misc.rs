@@ -57,6 +57,28 @@ async fn no_colors_in_piped_error_output() {
         .without_stderr("\u{1b}");
 }
 
+#[tokio::test]
+async fn json_flag_prints_log_lines_as_json() {
+    let cx = CliTestContext::new(Scenario::SimpleV2).await;
+    cx.config
+        .expect(["rustup", "--json", "default", "nightly"])
+        .await
+        .with_stderr(snapbox::str![[r#"
+...
+{"level":"info","message":"default toolchain set to nightly-[HOST_TRIPLE]"}
+...
+"#]])
+        .is_ok();
+    cx.config
+        .expect(["rustup", "--json", "default", "foo"])
+        .await
+        .with_stderr(snapbox::str![[r#"
+{"level":"error","message":"[..]"}
+
+"#]])
+        .is_err();
+}
+
 #[tokio::test]
 async fn rustc_with_bad_rustup_toolchain_env_var() {
     let cx = CliTestContext::new(Scenario::SimpleV2).await;
@@ -1302,6 +1324,101 @@ error: cargo does not currently support completions for[..]
         .is_err();
 }
 
+#[tokio::test]
+async fn completion_dynamic() {
+    let cx = CliTestContext::new(Scenario::SimpleV2).await;
+    cx.config
+        .expect(["rustup", "default", "nightly"])
+        .await
+        .is_ok();
+    cx.config
+        .expect(["rustup", "completions", "bash", "--dynamic"])
+        .await
+        .with_stdout(snapbox::str![[r#"
+_rustup() {
+...
+    complete -F _rustup_dynamic -o nosort -o bashdefault -o default rustup
+...
+"#]])
+        .is_ok();
+    cx.config
+        .expect(["rustup", "completions", "bash", "--candidates", "toolchain"])
+        .await
+        .with_stdout(snapbox::str![[r#"
+nightly-[HOST_TRIPLE]
+stable
+beta
+nightly
+
+"#]])
+        .is_ok();
+    cx.config
+        .expect(["rustup", "completions", "bash", "--candidates", "target"])
+        .await
+        .with_stdout(snapbox::str![[r#"
+...
+[CROSS_ARCH_I]
+...
+"#]])
+        .is_ok();
+    cx.config
+        .expect(["rustup", "completions", "powershell", "--dynamic"])
+        .await
+        .with_stderr(snapbox::str![[r#"
+error: rustup does not currently support dynamic completions for powershell
+
+"#]])
+        .is_err();
+}
+
+#[tokio::test]
+async fn completions_location() {
+    let cx = CliTestContext::new(Scenario::None).await;
+    let data_home = cx.config.homedir.join("data");
+    let data_home = data_home.to_str().unwrap();
+    let env = [("XDG_DATA_HOME", data_home)];
+    cx.config
+        .expect_with_env(["rustup", "self", "completions-location"], env)
+        .await
+        .with_stdout(snapbox::str![[""]])
+        .with_stderr(snapbox::str![[r#"
+info: no completion scripts are installed; see `rustup completions --help`
+
+"#]])
+        .is_ok();
+
+    let zsh = cx.config.homedir.join(".zfunc").join("_rustup");
+    let bash = cx
+        .config
+        .homedir
+        .join("data/bash-completion/completions/rustup");
+    for path in [&zsh, &bash] {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, "").unwrap();
+    }
+    cx.config
+        .expect_with_env(["rustup", "self", "completions-location"], env)
+        .await
+        .extend_redactions([("[HOME]", cx.config.homedir.clone())])
+        .with_stdout(snapbox::str![[r#"
+bash: [HOME]/data/bash-completion/completions/rustup
+zsh: [HOME]/.zfunc/_rustup
+
+"#]])
+        .is_ok();
+    cx.config
+        .expect_with_env(
+            ["rustup", "self", "completions-location", "--shell", "fish"],
+            env,
+        )
+        .await
+        .with_stderr(snapbox::str![[r#"
+error: no completion script is installed for fish
+
+"#]])
+        .is_err();
+}
+
 #[tokio::test]
 async fn add_remove_component() {
     let cx = CliTestContext::new(Scenario::SimpleV2).await;
@@ -722,6 +722,21 @@ async fn show_home() {
         .is_ok();
 }
 
+#[tokio::test]
+async fn show_cargo_home() {
+    let cx = CliTestContext::new(Scenario::None).await;
+    cx.config
+        .expect(["rustup", "show", "cargo-home"])
+        .await
+        .extend_redactions([("[CARGO_DIR]", &cx.config.cargodir)])
+        .with_stdout(snapbox::str![[r#"
+[CARGO_DIR]
+
+"#]])
+        .with_stderr(snapbox::str![[""]])
+        .is_ok();
+}
+
 #[tokio::test]
 async fn show_toolchain_none() {
     let cx = CliTestContext::new(Scenario::None).await;
@@ -1348,6 +1363,52 @@ installed targets:
 "#]]);
 }
 
+#[tokio::test]
+async fn show_toolchain_env_file() {
+    let cx = CliTestContext::new(Scenario::SimpleV2).await;
+    cx.config
+        .expect(["rustup", "default", "nightly"])
+        .await
+        .is_ok();
+    let env_file = cx.config.current_dir().join(".env");
+    raw::write_file(&env_file, "export RUSTUP_TOOLCHAIN=beta # pinned\n").unwrap();
+
+    // `.env` files are only read once enabled
+    cx.config
+        .expect(["rustup", "show", "active-toolchain"])
+        .await
+        .with_stdout(snapbox::str![[r#"
+nightly-[HOST_TRIPLE] (default)
+
+"#]])
+        .is_ok();
+
+    let settings_file = cx.config.rustupdir.join("settings.toml");
+    let settings = fs::read_to_string(&settings_file).unwrap();
+    raw::write_file(&settings_file, &format!("env_file = true\n{settings}")).unwrap();
+    cx.config
+        .expect(["rustup", "show", "active-toolchain"])
+        .await
+        .with_stdout(snapbox::str![[r#"
+beta-[HOST_TRIPLE] (overridden by RUSTUP_TOOLCHAIN in '[..].env')
+
+"#]])
+        .is_ok();
+
+    // The process environment still comes first
+    cx.config
+        .expect_with_env(
+            ["rustup", "show", "active-toolchain"],
+            [("RUSTUP_TOOLCHAIN", "nightly")],
+        )
+        .await
+        .with_stdout(snapbox::str![[r#"
+nightly-[HOST_TRIPLE] (overridden by environment variable RUSTUP_TOOLCHAIN)
+
+"#]])
+        .is_ok();
+}
+
 #[tokio::test]
 async fn show_toolchain_env_not_installed() {
     let cx = CliTestContext::new(Scenario::SimpleV2).await;
@@ -1463,6 +1524,39 @@ path: [RUSTUP_DIR]/toolchains/nightly-[HOST_TRIPLE]
         .is_ok();
 }
 
+#[tokio::test]
+async fn show_active_toolchain_as_json() {
+    let cx = CliTestContext::new(Scenario::SimpleV2).await;
+    cx.config
+        .expect(["rustup", "default", "stable"])
+        .await
+        .is_ok();
+    cx.config
+        .expect(["rustup", "show", "active-toolchain", "--json"])
+        .await
+        .with_stdout(snapbox::str![[r#"
+{"toolchain":"stable-[HOST_TRIPLE]","reason":"default","file":null,"rustc_version":"1.1.0"}
+
+"#]])
+        .with_stderr(snapbox::str![[""]])
+        .is_ok();
+
+    cx.config
+        .expect(["rustup", "toolchain", "install", "nightly"])
+        .await
+        .is_ok();
+    let toolchain_file = cx.config.current_dir().join("rust-toolchain.toml");
+    raw::write_file(&toolchain_file, "[toolchain]\nchannel = 'nightly'").unwrap();
+    cx.config
+        .expect(["rustup", "show", "active-toolchain", "--json"])
+        .await
+        .with_stdout(snapbox::str![[r#"
+{"toolchain":"nightly-[HOST_TRIPLE]","reason":"toolchain-file","file":"[..]rust-toolchain.toml","rustc_version":"1.3.0"}
+
+"#]])
+        .is_ok();
+}
+
 #[tokio::test]
 async fn show_active_toolchain_with_override() {
     let cx = CliTestContext::new(Scenario::SimpleV2).await;
@@ -1575,6 +1669,110 @@ default
         .is_ok();
 }
 
+#[tokio::test]
+async fn show_profile_of_active_toolchain() {
+    let cx = CliTestContext::new(Scenario::SimpleV2).await;
+    cx.config
+        .expect(["rustup", "set", "profile", "minimal"])
+        .await
+        .is_ok();
+    cx.config
+        .expect(["rustup", "default", "nightly"])
+        .await
+        .is_ok();
+    cx.config
+        .expect(["rustup", "set", "profile", "complete"])
+        .await
+        .is_ok();
+    cx.config
+        .expect(["rustup", "show", "profile"])
+        .await
+        .with_stdout(snapbox::str![[r#"
+minimal
+
+"#]])
+        .is_ok();
+
+    // Updates keep the profile of the first install.
+    cx.config
+        .expect(["rustup", "update", "nightly"])
+        .await
+        .is_ok();
+    cx.config
+        .expect(["rustup", "show", "profile"])
+        .await
+        .with_stdout(snapbox::str![[r#"
+minimal
+
+"#]])
+        .is_ok();
+
+    // Without a record of the profile, the default profile is shown.
+    let toolchain_dir = cx
+        .config
+        .rustupdir
+        .join(format!("toolchains/nightly-{}", this_host_triple()));
+    fs::remove_file(toolchain_dir.join("rustup-metadata.toml")).unwrap();
+    cx.config
+        .expect(["rustup", "show", "profile"])
+        .await
+        .with_stdout(snapbox::str![[r#"
+complete
+
+"#]])
+        .is_ok();
+}
+
+#[tokio::test]
+async fn show_rust_analyzer_path() {
+    let cx = CliTestContext::new(Scenario::SimpleV2).await;
+    cx.config
+        .expect(["rustup", "toolchain", "install", "nightly", "stable"])
+        .await
+        .is_ok();
+    cx.config
+        .expect(["rustup", "default", "nightly"])
+        .await
+        .is_ok();
+    cx.config
+        .expect(["rustup", "show", "rust-analyzer-path"])
+        .await
+        .with_stderr(snapbox::str![[r#"
+error: 'rust-analyzer' is not installed for the active toolchain.
+help: run `rustup component add rust-analyzer` to install it
+
+"#]])
+        .is_err();
+
+    let toolchains = cx.config.rustupdir.join("toolchains");
+    for name in ["nightly", "stable"] {
+        let bindir = toolchains
+            .join(format!("{name}-{}", this_host_triple()))
+            .join("bin");
+        raw::write_file(&bindir.join(format!("rust-analyzer{EXE_SUFFIX}")), "").unwrap();
+    }
+    cx.config
+        .expect(["rustup", "show", "rust-analyzer-path"])
+        .await
+        .with_stdout(snapbox::str![[r#"
+[..]/toolchains/nightly-[HOST_TRIPLE]/bin/rust-analyzer[EXE]
+
+"#]])
+        .is_ok();
+
+    let project = cx.config.current_dir().join("project");
+    fs::create_dir_all(&project).unwrap();
+    raw::write_file(&project.join("rust-toolchain"), "stable").unwrap();
+    cx.config
+        .expect(["rustup", "show", "rust-analyzer-path", "--dir", "project"])
+        .await
+        .with_stdout(snapbox::str![[r#"
+[..]/toolchains/stable-[HOST_TRIPLE]/bin/rust-analyzer[EXE]
+
+"#]])
+        .is_ok();
+}
+
 // #846
 #[tokio::test]
 async fn set_default_host() {
@@ -1761,6 +1959,73 @@ async fn toolchain_install_no_change_with_no_update() {
         .is_ok();
 }
 
+#[tokio::test]
+async fn toolchain_install_if_not_installed() {
+    let mut cx = CliTestContext::new(Scenario::None).await;
+
+    {
+        let cx = cx.with_dist_dir(Scenario::SimpleV2);
+        cx.config
+            .expect(["rustup", "toolchain", "add", "stable"])
+            .await
+            .is_ok();
+    }
+
+    // Without a dist server, this only succeeds if nothing is fetched.
+    cx.config
+        .expect([
+            "rustup",
+            "toolchain",
+            "install",
+            "--if-not-installed",
+            "stable",
+            "-c",
+            "cargo",
+        ])
+        .await
+        .with_stdout(snapbox::str![[""]])
+        .with_stderr(snapbox::str![[r#"
+info: toolchain 'stable-[HOST_TRIPLE]' is already installed
+
+"#]])
+        .is_ok();
+    cx.config
+        .expect([
+            "rustup",
+            "toolchain",
+            "install",
+            "--if-not-installed",
+            "stable",
+            "-c",
+            "rls",
+        ])
+        .await
+        .is_err();
+
+    let cx = cx.with_dist_dir(Scenario::SimpleV2);
+    cx.config
+        .expect([
+            "rustup",
+            "toolchain",
+            "install",
+            "--if-not-installed",
+            "stable",
+            "-c",
+            "rls",
+        ])
+        .await
+        .is_ok();
+    cx.config
+        .expect(["rustup", "+stable", "component", "list", "--installed"])
+        .await
+        .with_stdout(snapbox::str![[r#"
+...
+rls-[HOST_TRIPLE]
+...
+"#]])
+        .is_ok();
+}
+
 #[tokio::test]
 async fn toolchain_update_is_like_update() {
     let cx = CliTestContext::new(Scenario::SimpleV2).await;
@@ -2390,6 +2655,81 @@ async fn file_override_path() {
         .is_ok();
 }
 
+#[tokio::test]
+async fn toolchain_install_workspace() {
+    let mut cx = CliTestContext::new(Scenario::SimpleV2).await;
+    let root = cx.config.current_dir();
+    raw::write_file(
+        &root.join("Cargo.toml"),
+        "[workspace]\nmembers = [\"member\"]\n",
+    )
+    .unwrap();
+    raw::write_file(
+        &root.join("rust-toolchain.toml"),
+        "[toolchain]\nchannel = \"nightly\"\ncomponents = [\"rust-src\"]\n",
+    )
+    .unwrap();
+    // The toolchain file of a member of the workspace is not the one installed
+    let member = root.join("member");
+    fs::create_dir(&member).unwrap();
+    raw::write_file(&member.join("Cargo.toml"), "[package]\nname = \"member\"\n").unwrap();
+    raw::write_file(
+        &member.join("rust-toolchain.toml"),
+        "[toolchain]\nchannel = \"beta\"\n",
+    )
+    .unwrap();
+
+    let cx = cx.change_dir(&member);
+    cx.config
+        .expect(["rustup", "toolchain", "install", "--workspace"])
+        .await
+        .with_stdout(snapbox::str![[r#"
+
+  nightly-[HOST_TRIPLE] installed - 1.3.0 (hash-nightly-2)
+
+
+"#]])
+        .is_ok();
+    cx.config
+        .expect([
+            "rustup",
+            "component",
+            "list",
+            "--installed",
+            "--toolchain",
+            "nightly",
+        ])
+        .await
+        .with_stdout(snapbox::str![[r#"
+...
+rust-src
+...
+"#]])
+        .is_ok();
+    cx.config
+        .expect(["rustup", "toolchain", "list"])
+        .await
+        .with_stdout(snapbox::str![[r#"
+nightly-[HOST_TRIPLE]
+
+"#]])
+        .is_ok();
+}
+
+#[tokio::test]
+async fn toolchain_install_workspace_without_toolchain_file() {
+    let cx = CliTestContext::new(Scenario::SimpleV2).await;
+    raw::write_file(&cx.config.current_dir().join("Cargo.toml"), "[workspace]\n").unwrap();
+    cx.config
+        .expect(["rustup", "toolchain", "install", "--workspace"])
+        .await
+        .with_stderr(snapbox::str![[r#"
+error: no `rust-toolchain.toml` or `rust-toolchain` file at the workspace root '[..]'
+
+"#]])
+        .is_err();
+}
+
 #[tokio::test]
 async fn proxy_override_path() {
     let cx = CliTestContext::new(Scenario::SimpleV2).await;
@@ -2605,6 +2945,44 @@ async fn file_override_subdir() {
         .is_ok();
 }
 
+#[tokio::test]
+async fn file_override_search_stops_at_git_root() {
+    let mut cx = CliTestContext::new(Scenario::SimpleV2).await;
+    cx.config
+        .expect(["rustup", "default", "stable"])
+        .await
+        .is_ok();
+    cx.config
+        .expect(["rustup", "toolchain", "install", "nightly"])
+        .await
+        .is_ok();
+    cx.config
+        .expect(["rustup", "set", "toolchain-file-search-limit", "git-root"])
+        .await
+        .with_stderr(snapbox::str![[r#"
+info: setting toolchain file search limit to git-root
+
+"#]])
+        .is_ok();
+
+    let cwd = cx.config.current_dir();
+    raw::write_file(&cwd.join("rust-toolchain"), "nightly").unwrap();
+
+    let repo = cwd.join("repo");
+    let subdir = repo.join("subdir");
+    fs::create_dir_all(&subdir).unwrap();
+    fs::create_dir(repo.join(".git")).unwrap();
+    let cx = cx.change_dir(&subdir);
+    cx.config
+        .expect(["rustc", "--version"])
+        .await
+        .with_stdout(snapbox::str![[r#"
+1.1.0 (hash-stable-1.1.0)
+
+"#]])
+        .is_ok();
+}
+
 #[tokio::test]
 async fn file_override_with_archive() {
     let mut cx = CliTestContext::new(Scenario::None).await;
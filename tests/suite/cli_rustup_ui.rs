@@ -213,6 +213,19 @@ fn rustup_man_cmd_help_flag() {
     test_help("rustup_man_cmd_help_flag", &["man", "--help"]);
 }
 
+#[test]
+fn rustup_mirror_cmd_help_flag() {
+    test_help("rustup_mirror_cmd_help_flag", &["mirror", "--help"]);
+}
+
+#[test]
+fn rustup_mirror_cmd_add_cmd_help_flag() {
+    test_help(
+        "rustup_mirror_cmd_add_cmd_help_flag",
+        &["mirror", "add", "--help"],
+    );
+}
+
 #[test]
 fn rustup_override_cmd_help_flag() {
     test_help("rustup_override_cmd_help_flag", &["override", "--help"]);
@@ -226,6 +239,14 @@ fn rustup_override_cmd_add_cmd_help_flag() {
     );
 }
 
+#[test]
+fn rustup_override_cmd_clean_cmd_help_flag() {
+    test_help(
+        "rustup_override_cmd_clean_cmd_help_flag",
+        &["override", "clean", "--help"],
+    );
+}
+
 #[test]
 fn rustup_override_cmd_list_cmd_help_flag() {
     test_help(
@@ -258,6 +279,27 @@ fn rustup_override_cmd_unset_cmd_help_flag() {
     );
 }
 
+#[test]
+fn rustup_proxy_cmd_help_flag() {
+    test_help("rustup_proxy_cmd_help_flag", &["proxy", "--help"]);
+}
+
+#[test]
+fn rustup_proxy_cmd_add_cmd_help_flag() {
+    test_help(
+        "rustup_proxy_cmd_add_cmd_help_flag",
+        &["proxy", "add", "--help"],
+    );
+}
+
+#[test]
+fn rustup_proxy_cmd_remove_cmd_help_flag() {
+    test_help(
+        "rustup_proxy_cmd_remove_cmd_help_flag",
+        &["proxy", "remove", "--help"],
+    );
+}
+
 #[test]
 fn rustup_run_cmd_help_flag() {
     test_help("rustup_run_cmd_help_flag", &["run", "--help"]);
@@ -268,6 +310,38 @@ fn rustup_self_cmd_help_flag() {
     test_help("rustup_self_cmd_help_flag", &["self", "--help"]);
 }
 
+#[test]
+fn rustup_self_cmd_completions_location_cmd_help_flag() {
+    test_help(
+        "rustup_self_cmd_completions_location_cmd_help_flag",
+        &["self", "completions-location", "--help"],
+    );
+}
+
+#[test]
+fn rustup_self_cmd_check_cmd_help_flag() {
+    test_help(
+        "rustup_self_cmd_check_cmd_help_flag",
+        &["self", "check", "--help"],
+    );
+}
+
+#[test]
+fn rustup_self_cmd_diagnose_cmd_help_flag() {
+    test_help(
+        "rustup_self_cmd_diagnose_cmd_help_flag",
+        &["self", "diagnose", "--help"],
+    );
+}
+
+#[test]
+fn rustup_self_cmd_install_hook_cmd_help_flag() {
+    test_help(
+        "rustup_self_cmd_install_hook_cmd_help_flag",
+        &["self", "install-hook", "--help"],
+    );
+}
+
 #[test]
 fn rustup_self_cmd_uninstall_cmd_help_flag() {
     test_help(
@@ -276,6 +350,14 @@ fn rustup_self_cmd_uninstall_cmd_help_flag() {
     );
 }
 
+#[test]
+fn rustup_self_cmd_uninstall_hook_cmd_help_flag() {
+    test_help(
+        "rustup_self_cmd_uninstall_hook_cmd_help_flag",
+        &["self", "uninstall-hook", "--help"],
+    );
+}
+
 #[test]
 fn rustup_self_cmd_update_cmd_help_flag() {
     test_help(
@@ -313,6 +395,22 @@ fn rustup_set_cmd_auto_self_update_cmd_help_flag() {
     );
 }
 
+#[test]
+fn rustup_set_cmd_toolchain_file_search_limit_cmd_help_flag() {
+    test_help(
+        "rustup_set_cmd_toolchain_file_search_limit_cmd_help_flag",
+        &["set", "toolchain-file-search-limit", "--help"],
+    );
+}
+
+#[test]
+fn rustup_set_cmd_mirror_selection_cmd_help_flag() {
+    test_help(
+        "rustup_set_cmd_mirror_selection_cmd_help_flag",
+        &["set", "mirror-selection", "--help"],
+    );
+}
+
 #[test]
 fn rustup_set_cmd_default_host_cmd_help_flag() {
     test_help(
@@ -370,6 +468,14 @@ fn rustup_show_cmd_home_cmd_help_flag() {
     );
 }
 
+#[test]
+fn rustup_show_cmd_cargo_home_cmd_help_flag() {
+    test_help(
+        "rustup_show_cmd_cargo_home_cmd_help_flag",
+        &["show", "cargo-home", "--help"],
+    );
+}
+
 #[test]
 fn rustup_show_cmd_profile_cmd_help_flag() {
     test_help(
@@ -378,6 +484,14 @@ fn rustup_show_cmd_profile_cmd_help_flag() {
     );
 }
 
+#[test]
+fn rustup_show_cmd_rust_analyzer_path_cmd_help_flag() {
+    test_help(
+        "rustup_show_cmd_rust_analyzer_path_cmd_help_flag",
+        &["show", "rust-analyzer-path", "--help"],
+    );
+}
+
 #[test]
 fn rustup_target_cmd_help_flag() {
     test_help("rustup_target_cmd_help_flag", &["target", "--help"]);
@@ -412,6 +526,14 @@ fn rustup_toolchain_cmd_help_flag() {
     test_help("rustup_toolchain_cmd_help_flag", &["toolchain", "--help"]);
 }
 
+#[test]
+fn rustup_toolchain_cmd_gc_cmd_help_flag() {
+    test_help(
+        "rustup_toolchain_cmd_gc_cmd_help_flag",
+        &["toolchain", "gc", "--help"],
+    );
+}
+
 #[test]
 fn rustup_toolchain_cmd_install_cmd_help_flag() {
     test_help(
@@ -428,6 +550,22 @@ fn rustup_toolchain_cmd_link_cmd_help_flag() {
     );
 }
 
+#[test]
+fn rustup_toolchain_cmd_pin_cmd_help_flag() {
+    test_help(
+        "rustup_toolchain_cmd_pin_cmd_help_flag",
+        &["toolchain", "pin", "--help"],
+    );
+}
+
+#[test]
+fn rustup_toolchain_cmd_update_file_cmd_help_flag() {
+    test_help(
+        "rustup_toolchain_cmd_update_file_cmd_help_flag",
+        &["toolchain", "update-file", "--help"],
+    );
+}
+
 #[tokio::test]
 async fn rustup_toolchain_list() {
     let name = "rustup_toolchain_list";
@@ -283,6 +283,130 @@ error: rustup is not installed at '[..]'
         .is_err();
 }
 
+#[tokio::test]
+#[cfg(unix)]
+async fn proxy_add_runs_cargo_installed_binaries_through_rustup() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let cx = setup_installed().await;
+    let sccache = cx.config.cargodir.join("bin/sccache");
+    let script = "#!/bin/sh\necho \"toolchain: $RUSTUP_TOOLCHAIN\"\n";
+    fs::write(&sccache, script).unwrap();
+    fs::set_permissions(&sccache, fs::Permissions::from_mode(0o755)).unwrap();
+
+    cx.config
+        .expect(["rustup", "proxy", "add", "sccache"])
+        .await
+        .with_stderr(snapbox::str![[r#"
+error: '[..]/bin/sccache' was not installed by `cargo install`; use `--force` to proxy it anyway
+
+"#]])
+        .is_err();
+
+    let crates = "[v1]\n\"sccache 0.7.4 (registry+https://github.com/rust-lang/crates.io-index)\" = [\"sccache\"]\n";
+    fs::write(cx.config.cargodir.join(".crates.toml"), crates).unwrap();
+    cx.config
+        .expect(["rustup", "proxy", "add", "sccache"])
+        .await
+        .is_ok();
+    assert!(fs::symlink_metadata(&sccache).unwrap().is_symlink());
+
+    let mut cmd = Command::new(&sccache);
+    cx.config.env(&mut cmd);
+    let out = cmd.output().unwrap();
+    assert!(out.status.success());
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        format!("toolchain: stable-{}\n", this_host_triple())
+    );
+
+    cx.config
+        .expect(["rustup", "proxy", "remove", "sccache"])
+        .await
+        .is_ok();
+    assert_eq!(fs::read_to_string(&sccache).unwrap(), script);
+}
+
+#[tokio::test]
+async fn self_diagnose_reports_broken_toolchains() {
+    let cx = setup_installed().await;
+    cx.config
+        .expect(["rustup", "self", "diagnose"])
+        .await
+        .with_stdout(snapbox::str![[r#"
+ok: rustup is on PATH at '[..]'
+warning: '[..]' is not on PATH, so the rustup proxies for cargo and rustc will not be found
+  help: restart your shell, or run `. "$CARGO_HOME/env"` in it
+ok: no other installation of Rust is on PATH
+ok: 1 installed toolchain(s) are intact
+ok: '[..]' is writable
+ok: the dist server '[..]' is reachable
+
+"#]])
+        .is_ok();
+
+    let toolchain = format!("stable-{}", this_host_triple());
+    let rustc = cx
+        .config
+        .rustupdir
+        .join("toolchains")
+        .join(&toolchain)
+        .join(format!("bin/rustc{EXE_SUFFIX}"));
+    fs::remove_file(rustc).unwrap();
+    cx.config
+        .expect(["rustup", "self", "diagnose"])
+        .await
+        .with_stdout(snapbox::str![[r#"
+...
+error: files of these toolchains are missing or corrupted: stable-[HOST_TRIPLE]
+  help: reinstall them, e.g. `rustup toolchain uninstall stable-[HOST_TRIPLE] && rustup toolchain install stable-[HOST_TRIPLE] --profile default`
+...
+"#]])
+        .is_err();
+}
+
+#[tokio::test]
+async fn self_check_reports_broken_install() {
+    let mut cx = setup_installed().await;
+    let rustup = cx.config.cargodir.join(format!("bin/rustup{EXE_SUFFIX}"));
+    let update_root = tempfile::tempdir().unwrap();
+    let release_dir = update_root.path().join(format!(
+        "archive/{}/{}",
+        env!("CARGO_PKG_VERSION"),
+        this_host_triple()
+    ));
+    fs::create_dir_all(&release_dir).unwrap();
+    let hash_file = release_dir.join(format!("rustup-init{EXE_SUFFIX}.sha256"));
+    fs::write(&hash_file, format!("{}  rustup-init\n", calc_hash(&rustup))).unwrap();
+    cx.config.rustup_update_root = Some(format!("file://{}", update_root.path().display()));
+
+    cx.config
+        .expect(["rustup", "self", "check"])
+        .await
+        .with_stdout(snapbox::str![[r#"
+ok: '[..]' is the release build of rustup [..]
+ok: the proxies in '[..]' run rustup
+ok: '[..]' can be replaced by `rustup self update`
+ok: the metadata of 1 installed toolchain(s) is supported
+
+"#]])
+        .is_ok();
+
+    fs::write(&hash_file, "0123456789abcdef  rustup-init\n").unwrap();
+    fs::remove_file(cx.config.cargodir.join(format!("bin/rustc{EXE_SUFFIX}"))).unwrap();
+    cx.config
+        .expect(["rustup", "self", "check"])
+        .await
+        .with_stdout(snapbox::str![[r#"
+error: '[..]' differs from the release build of rustup [..]
+  help: reinstall rustup with rustup-init, unless it was built or patched on purpose
+error: these proxies are missing from '[..]' or do not run rustup: rustc
+  help: run `rustup self update` to link them again
+...
+"#]])
+        .is_err();
+}
+
 // The other tests here just run rustup from a temp directory. This
 // does the uninstall by actually invoking the installed binary in
 // order to test that it can successfully delete itself.
@@ -621,6 +745,66 @@ async fn rustup_self_updates_trivial() {
     assert_ne!(before_hash, after_hash);
 }
 
+#[tokio::test]
+async fn rustup_update_honors_rustup_no_self_update() {
+    let cx = SelfUpdateTestContext::new(TEST_VERSION).await;
+    cx.config
+        .expect(["rustup", "set", "auto-self-update", "enable"])
+        .await
+        .is_ok();
+    cx.config
+        .expect(["rustup-init", "-y", "--no-modify-path"])
+        .await
+        .is_ok();
+
+    let bin = cx.config.cargodir.join(format!("bin/rustup{EXE_SUFFIX}"));
+    let before_hash = calc_hash(&bin);
+
+    cx.config
+        .expect_with_env(["rustup", "update"], [("RUSTUP_NO_SELF_UPDATE", "1")])
+        .await
+        .is_ok();
+
+    let after_hash = calc_hash(&bin);
+
+    assert_eq!(before_hash, after_hash);
+}
+
+#[tokio::test]
+async fn rustup_update_honors_auto_self_update_disable_in_settings_file() {
+    let cx = SelfUpdateTestContext::new(TEST_VERSION).await;
+    cx.config
+        .expect(["rustup", "set", "auto-self-update", "enable"])
+        .await
+        .is_ok();
+    cx.config
+        .expect(["rustup-init", "-y", "--no-modify-path"])
+        .await
+        .is_ok();
+
+    // Managed installs may write the setting without running `rustup set`.
+    let settings_file = cx.config.rustupdir.join("settings.toml");
+    let settings = fs::read_to_string(&settings_file).unwrap();
+    let settings = settings.replace(
+        r#"auto_self_update = "enable""#,
+        r#"auto_self_update = "disable""#,
+    );
+    raw::write_file(&settings_file, &settings).unwrap();
+
+    let bin = cx.config.cargodir.join(format!("bin/rustup{EXE_SUFFIX}"));
+    let before_hash = calc_hash(&bin);
+
+    cx.config.expect(["rustup", "update"]).await.is_ok();
+    cx.config
+        .expect(["rustup", "update", "stable"])
+        .await
+        .is_ok();
+
+    let after_hash = calc_hash(&bin);
+
+    assert_eq!(before_hash, after_hash);
+}
+
 #[tokio::test]
 async fn rustup_self_updates_with_specified_toolchain() {
     let cx = SelfUpdateTestContext::new(TEST_VERSION).await;
@@ -1078,6 +1262,73 @@ warn: tool `rustfmt` is already installed, remove it from `[..]`, then run `rust
     assert!(utils::file_size(&rustfmt_path).unwrap() > 0);
 }
 
+#[tokio::test]
+async fn update_installs_dup_tools_from_settings() {
+    let cx = SelfUpdateTestContext::new(TEST_VERSION).await;
+    cx.config
+        .expect(["rustup-init", "-y", "--no-modify-path"])
+        .await
+        .is_ok();
+    let version = env!("CARGO_PKG_VERSION");
+    output_release_file(cx.path(), "1", version);
+
+    let settings_path = cx.config.rustupdir.join("settings.toml");
+    let settings = fs::read_to_string(&settings_path).unwrap();
+    raw::write_file(
+        &settings_path,
+        &format!("dup_tools = [\"taplo\", \"cargo-deny\"]\n{settings}"),
+    )
+    .unwrap();
+    let deny_path = cx
+        .config
+        .cargodir
+        .join(format!("bin/cargo-deny{EXE_SUFFIX}"));
+    raw::write_file(&deny_path, "").unwrap();
+
+    cx.config
+        .expect(["rustup", "self", "update"])
+        .await
+        .with_stderr(snapbox::str![[r#"
+info: checking for self-update (current version: [CURRENT_VERSION])
+warn: tool `cargo-deny` is already installed, remove it from `[..]`, then run `rustup update` to have rustup manage this tool.
+
+"#]])
+        .is_ok();
+    let taplo_path = cx.config.cargodir.join(format!("bin/taplo{EXE_SUFFIX}"));
+    assert!(utils::file_size(&taplo_path).unwrap() > 0);
+    assert_eq!(utils::file_size(&deny_path).unwrap(), 0);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn install_hook_and_uninstall_hook() {
+    let cx = SelfUpdateTestContext::new(TEST_VERSION).await;
+    cx.config
+        .expect(["rustup-init", "-y", "--no-modify-path"])
+        .await
+        .is_ok();
+
+    let hook_path = cx.config.homedir.join("rustup-path-hook");
+    let hook_env = [("RUSTUP_OVERRIDE_PATH_HOOK", hook_path.to_str().unwrap())];
+    cx.config
+        .expect_with_env(["rustup", "self", "install-hook"], hook_env)
+        .await
+        .is_ok();
+    let hook = fs::read_to_string(&hook_path).unwrap();
+    let expected = if cfg!(target_os = "macos") {
+        format!("{}\n", cx.config.cargodir.join("bin").display())
+    } else {
+        format!(". \"{}\"\n", cx.config.cargodir.join("env").display())
+    };
+    assert_eq!(hook, expected);
+
+    cx.config
+        .expect_with_env(["rustup", "self", "uninstall-hook"], hook_env)
+        .await
+        .is_ok();
+    assert!(!hook_path.exists());
+}
+
 #[tokio::test]
 async fn update_installs_clippy_cargo_and() {
     let cx = SelfUpdateTestContext::new(TEST_VERSION).await;
@@ -83,6 +83,532 @@ async fn install_toolchain_from_channel() {
         .is_ok();
 }
 
+#[tokio::test]
+async fn toolchain_install_verify() {
+    let cx = CliTestContext::new(Scenario::SimpleV2).await;
+    cx.config
+        .expect(["rustup", "toolchain", "install", "nightly", "--verify"])
+        .await
+        .is_ok();
+    cx.config
+        .expect(["rustup", "run", "nightly", "rustc", "--version"])
+        .await
+        .with_stdout(snapbox::str![[r#"
+1.3.0 (hash-nightly-2)
+
+"#]])
+        .is_ok();
+}
+
+#[tokio::test]
+async fn install_falls_back_from_failing_mirror() {
+    let cx = CliTestContext::new(Scenario::SimpleV2).await;
+    let mirror = format!(
+        "file://{}",
+        cx.config.homedir.join("no-such-mirror").display()
+    );
+    cx.config
+        .expect(["rustup", "mirror", "add", &mirror])
+        .await
+        .is_ok();
+    cx.config
+        .expect(["rustup", "mirror", "list"])
+        .await
+        .with_stdout(snapbox::str![[r#"
+file://[..]/no-such-mirror
+
+"#]])
+        .is_ok();
+    cx.config
+        .expect(["rustup", "toolchain", "install", "nightly"])
+        .await
+        .with_stderr(snapbox::str![[r#"
+...
+warn: could not download file://[..]/no-such-mirror/dist/channel-rust-nightly.toml[..], trying the next mirror: [..]
+...
+"#]])
+        .is_ok();
+
+    cx.config
+        .expect(["rustup", "mirror", "add", "--no-fallback", &mirror])
+        .await
+        .is_ok();
+    cx.config
+        .expect(["rustup", "toolchain", "install", "beta"])
+        .await
+        .is_err();
+
+    cx.config
+        .expect(["rustup", "mirror", "remove", &mirror])
+        .await
+        .is_ok();
+    cx.config
+        .expect(["rustup", "mirror", "list"])
+        .await
+        .with_stdout(snapbox::str![""])
+        .is_ok();
+    cx.config
+        .expect(["rustup", "toolchain", "install", "beta"])
+        .await
+        .is_ok();
+}
+
+#[tokio::test]
+async fn install_from_fastest_mirror() {
+    let cx = CliTestContext::new(Scenario::SimpleV2).await;
+    let broken = format!(
+        "file://{}",
+        cx.config.homedir.join("no-such-mirror").display()
+    );
+    let working = format!("file://{}", cx.config.distdir.as_ref().unwrap().display());
+    for mirror in [&broken, &working] {
+        cx.config
+            .expect(["rustup", "mirror", "add", "--no-fallback", mirror])
+            .await
+            .is_ok();
+    }
+    cx.config
+        .expect(["rustup", "set", "mirror-selection", "latency"])
+        .await
+        .is_ok();
+    cx.config
+        .expect(["rustup", "toolchain", "install", "nightly"])
+        .await
+        .with_stderr(snapbox::str![[r#"
+info: syncing channel updates for nightly-[HOST_TRIPLE]
+info: downloading from the fastest mirror, file://[..]
+...
+"#]])
+        .is_ok();
+    assert!(cx.config.rustupdir.join("mirror-selection.toml").exists());
+}
+
+#[tokio::test]
+async fn toolchain_install_keep_max_nightly() {
+    let cx = CliTestContext::new(Scenario::ArchivesV2).await;
+    cx.config
+        .expect(["rustup", "default", "nightly-2015-01-01"])
+        .await
+        .is_ok();
+    cx.config
+        .expect([
+            "rustup",
+            "toolchain",
+            "install",
+            "nightly-2015-01-02",
+            "beta-2015-01-01",
+        ])
+        .await
+        .is_ok();
+    cx.config
+        .expect([
+            "rustup",
+            "toolchain",
+            "install",
+            "nightly",
+            "--keep-max-nightly",
+            "1",
+        ])
+        .await
+        .is_ok();
+    cx.config
+        .expect(["rustup", "toolchain", "list"])
+        .await
+        .with_stdout(snapbox::str![[r#"
+beta-2015-01-01-[HOST_TRIPLE]
+nightly-[HOST_TRIPLE]
+nightly-2015-01-01-[HOST_TRIPLE] (active, default)
+
+"#]])
+        .is_ok();
+
+    cx.config
+        .expect(["rustup", "toolchain", "install", "--keep-max-nightly", "1"])
+        .await
+        .with_stderr(snapbox::str![[r#"
+error: the following required arguments were not provided:
+  <TOOLCHAIN>...
+
+...
+"#]])
+        .is_err();
+}
+
+#[tokio::test]
+async fn toolchain_gc_removes_old_nightlies() {
+    let cx = CliTestContext::new(Scenario::ArchivesV2).await;
+    cx.config
+        .expect(["rustup", "default", "nightly-2015-01-01"])
+        .await
+        .is_ok();
+    cx.config
+        .expect([
+            "rustup",
+            "toolchain",
+            "install",
+            "nightly-2015-01-02",
+            "nightly",
+            "beta-2015-01-01",
+        ])
+        .await
+        .is_ok();
+    cx.config
+        .expect(["rustup", "default", "nightly"])
+        .await
+        .is_ok();
+    let project = cx.config.current_dir().join("project");
+    fs::create_dir(&project).unwrap();
+    fs::write(
+        project.join("rust-toolchain.toml"),
+        "[toolchain]\nchannel = \"nightly-2015-01-02\"\n",
+    )
+    .unwrap();
+
+    cx.config
+        .expect([
+            "rustup",
+            "toolchain",
+            "gc",
+            "--older-than",
+            "30",
+            "--dry-run",
+        ])
+        .await
+        .with_stderr(snapbox::str![[r#"
+info: would uninstall toolchain 'nightly-2015-01-01-[HOST_TRIPLE]'
+info: would uninstall toolchain 'nightly-2015-01-02-[HOST_TRIPLE]'
+info: [..] would be freed
+
+"#]])
+        .is_ok();
+    cx.config
+        .expect([
+            "rustup",
+            "toolchain",
+            "gc",
+            "--older-than",
+            "30",
+            "--search-path",
+            "project",
+        ])
+        .await
+        .is_ok();
+    cx.config
+        .expect(["rustup", "toolchain", "list"])
+        .await
+        .with_stdout(snapbox::str![[r#"
+beta-2015-01-01-[HOST_TRIPLE]
+nightly-[HOST_TRIPLE] (active, default)
+nightly-2015-01-02-[HOST_TRIPLE]
+
+"#]])
+        .is_ok();
+}
+
+#[tokio::test]
+async fn toolchain_gc_skips_hidden_and_build_directories() {
+    let cx = CliTestContext::new(Scenario::ArchivesV2).await;
+    cx.config
+        .expect(["rustup", "toolchain", "install", "nightly-2015-01-01"])
+        .await
+        .is_ok();
+    cx.config
+        .expect(["rustup", "default", "nightly"])
+        .await
+        .is_ok();
+    let project = cx.config.current_dir().join("project");
+    for dir in [".git", "target/debug", "node_modules/pkg"] {
+        let dir = project.join(dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("rust-toolchain.toml"),
+            "[toolchain]\nchannel = \"nightly-2015-01-01\"\n",
+        )
+        .unwrap();
+    }
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(&project, project.join("loop")).unwrap();
+
+    cx.config
+        .expect([
+            "rustup",
+            "toolchain",
+            "gc",
+            "--older-than",
+            "30",
+            "--search-path",
+            "project",
+            "--dry-run",
+        ])
+        .await
+        .with_stderr(snapbox::str![[r#"
+info: would uninstall toolchain 'nightly-2015-01-01-[HOST_TRIPLE]'
+info: [..] would be freed
+
+"#]])
+        .is_ok();
+}
+
+#[tokio::test]
+async fn toolchain_update_file_moves_to_newest_nightly() {
+    let cx = CliTestContext::new(Scenario::ArchivesV2).await;
+    let toolchain_file = cx.config.current_dir().join("rust-toolchain.toml");
+    fs::write(
+        &toolchain_file,
+        "[toolchain]\nchannel = \"nightly-2015-01-01\"\nprofile = \"minimal\"\n",
+    )
+    .unwrap();
+
+    cx.config
+        .expect(["rustup", "toolchain", "update-file", "--dry-run"])
+        .await
+        .with_stderr(snapbox::str![[r#"
+info: would update '[..]rust-toolchain.toml' from nightly-2015-01-01 to nightly-2015-01-02
+
+"#]])
+        .is_ok();
+    cx.config
+        .expect(["rustup", "toolchain", "update-file", "--install"])
+        .await
+        .with_stderr(snapbox::str![[r#"
+info: updated '[..]rust-toolchain.toml' from nightly-2015-01-01 to nightly-2015-01-02
+...
+"#]])
+        .is_ok();
+    assert_eq!(
+        fs::read_to_string(&toolchain_file).unwrap(),
+        "[toolchain]\nchannel = \"nightly-2015-01-02\"\nprofile = \"minimal\"\n",
+    );
+    cx.config
+        .expect(["rustup", "toolchain", "list"])
+        .await
+        .with_stdout(snapbox::str![[r#"
+nightly-2015-01-02-[HOST_TRIPLE] (active)
+
+"#]])
+        .is_ok();
+    cx.config
+        .expect(["rustup", "toolchain", "update-file"])
+        .await
+        .with_stderr(snapbox::str![[r#"
+info: '[..]rust-toolchain.toml' is up to date with nightly-2015-01-02
+
+"#]])
+        .is_ok();
+}
+
+#[tokio::test]
+async fn toolchain_install_retries_failed_downloads() {
+    let cx = CliTestContext::new(Scenario::SimpleV2).await;
+    let dist_dir = cx.config.distdir.as_ref().unwrap().join("dist/2015-01-02");
+    for ext in ["tar.gz", "tar.xz", "tar.zst"] {
+        let archive = dist_dir.join(format!("rustc-nightly-{}.{ext}", this_host_triple()));
+        fs::remove_file(archive).unwrap();
+    }
+    cx.config
+        .expect(["rustup", "toolchain", "install", "nightly", "--retry", "1"])
+        .await
+        .with_stderr(snapbox::str![[r#"
+...
+info: retrying the installation of 'nightly-[HOST_TRIPLE]' in 1s (attempt 1 of 1)
+...
+error: component download failed for rustc-[HOST_TRIPLE]: [..]
+
+"#]])
+        .is_err();
+    cx.config
+        .expect(["rustup", "toolchain", "list"])
+        .await
+        .with_stdout(snapbox::str![[r#"
+no installed toolchains
+
+"#]])
+        .is_ok();
+}
+
+#[tokio::test]
+async fn toolchain_install_print_manifest() {
+    let cx = CliTestContext::new(Scenario::SimpleV2).await;
+    cx.config
+        .expect([
+            "rustup",
+            "toolchain",
+            "install",
+            "nightly",
+            "--print-manifest",
+        ])
+        .await
+        .with_stdout(snapbox::str![[r#"
+manifest-version = "2"
+date = "2015-01-02"
+...
+[pkg.rust.target.[HOST_TRIPLE]]
+available = true
+url = "[..]/dist/2015-01-02/rust-nightly-[HOST_TRIPLE].tar.gz"
+hash = "[..]"
+...
+"#]])
+        .is_ok();
+    cx.config
+        .expect([
+            "rustup",
+            "toolchain",
+            "install",
+            "nightly",
+            "--print-manifest",
+            "--json",
+        ])
+        .await
+        .with_stdout(snapbox::str![[r#"
+{
+  "manifest-version": "2",
+  "date": "2015-01-02",
+...
+"#]])
+        .is_ok();
+    cx.config
+        .expect(["rustup", "toolchain", "list"])
+        .await
+        .with_stdout(snapbox::str![[r#"
+no installed toolchains
+
+"#]])
+        .is_ok();
+}
+
+#[tokio::test]
+async fn toolchain_install_print_download_size() {
+    let cx = CliTestContext::new(Scenario::SimpleV2).await;
+    cx.config
+        .expect([
+            "rustup",
+            "toolchain",
+            "install",
+            "nightly",
+            "--print-download-size",
+        ])
+        .await
+        .with_stderr(snapbox::str![[r#"
+info: [..] to download for 'nightly-[HOST_TRIPLE]'
+
+"#]])
+        .without_stderr("info: 0 B to download")
+        .is_ok();
+    cx.config
+        .expect(["rustup", "toolchain", "list"])
+        .await
+        .with_stdout(snapbox::str![[r#"
+no installed toolchains
+
+"#]])
+        .is_ok();
+
+    // Installed components are not downloaded again.
+    cx.config
+        .expect(["rustup", "toolchain", "install", "nightly"])
+        .await
+        .is_ok();
+    cx.config
+        .expect([
+            "rustup",
+            "toolchain",
+            "install",
+            "nightly",
+            "--print-download-size",
+        ])
+        .await
+        .with_stdout(snapbox::str![[r#"
+0
+
+"#]])
+        .with_stderr(snapbox::str![[r#"
+info: 0 B to download for 'nightly-[HOST_TRIPLE]'
+
+"#]])
+        .is_ok();
+    cx.config
+        .expect([
+            "rustup",
+            "toolchain",
+            "install",
+            "nightly",
+            "-c",
+            "rls",
+            "--print-download-size",
+        ])
+        .await
+        .without_stderr("info: 0 B to download")
+        .is_ok();
+    cx.config
+        .expect(["rustup", "component", "list", "--installed"])
+        .await
+        .without_stdout("rls")
+        .is_ok();
+}
+
+#[tokio::test]
+async fn toolchain_pin_writes_installed_components() {
+    let cx = CliTestContext::new(Scenario::SimpleV2).await;
+    cx.config
+        .expect([
+            "rustup",
+            "toolchain",
+            "install",
+            "nightly",
+            "--profile",
+            "minimal",
+            "--component",
+            "rls",
+            "--target",
+            CROSS_ARCH1,
+        ])
+        .await
+        .is_ok();
+    let project = cx.config.current_dir().join("project");
+    fs::create_dir(&project).unwrap();
+    fs::write(
+        project.join("rust-toolchain.toml"),
+        "[toolchain]\nchannel = \"stable\"\nprofile = \"minimal\"\n",
+    )
+    .unwrap();
+    cx.config
+        .expect([
+            "rustup",
+            "override",
+            "set",
+            "nightly",
+            "--path",
+            project.to_str().unwrap(),
+        ])
+        .await
+        .is_ok();
+
+    cx.config
+        .expect(["rustup", "toolchain", "pin", "nightly", "--path", "project"])
+        .await
+        .with_stderr(snapbox::str![[r#"
+info: override toolchain for '[..]project' removed
+info: pinned '[..]rust-toolchain.toml' to toolchain 'nightly'
+
+"#]])
+        .is_ok();
+    assert_eq!(
+        fs::read_to_string(project.join("rust-toolchain.toml")).unwrap(),
+        format!(
+            "[toolchain]\nchannel = \"nightly\"\ncomponents = [\"rls\"]\n\
+             profile = \"minimal\"\ntargets = [\"{CROSS_ARCH1}\"]\n"
+        ),
+    );
+    cx.config
+        .expect(["rustup", "override", "list"])
+        .await
+        .with_stdout(snapbox::str![[r#"
+no overrides
+
+"#]])
+        .is_ok();
+}
+
 #[tokio::test]
 async fn install_toolchain_from_archive() {
     let cx = CliTestContext::new(Scenario::ArchivesV2).await;
@@ -260,6 +786,84 @@ nightly-[HOST_TRIPLE] (active, default)
 beta-2015-01-01-[HOST_TRIPLE] [..]/toolchains/beta-2015-01-01-[HOST_TRIPLE]
 nightly-[HOST_TRIPLE] (active, default) [..]/toolchains/nightly-[HOST_TRIPLE]
 
+"#]])
+        .is_ok();
+    cx.config
+        .expect(["rustup", "toolchain", "list", "--json"])
+        .await
+        .with_stdout(snapbox::str![[r#"
+[{"name":"beta-2015-01-01-[HOST_TRIPLE]","is_default":false,"is_active":false,"path":"[..]/toolchains/beta-2015-01-01-[HOST_TRIPLE]","installed_date":"[..]-[..]-[..]","channel":"beta","profile":"default"},{"name":"nightly-[HOST_TRIPLE]","is_default":true,"is_active":true,"path":"[..]/toolchains/nightly-[HOST_TRIPLE]","installed_date":"[..]-[..]-[..]","channel":"nightly","profile":"default"}]
+
+"#]])
+        .is_ok();
+}
+
+#[tokio::test]
+async fn install_metadata_tracks_added_components() {
+    let cx = CliTestContext::new(Scenario::SimpleV2).await;
+    cx.config
+        .expect([
+            "rustup",
+            "toolchain",
+            "install",
+            "nightly",
+            "--profile",
+            "minimal",
+        ])
+        .await
+        .is_ok();
+    cx.config
+        .expect(["rustup", "component", "add", "rust-src"])
+        .await
+        .is_ok();
+    cx.config
+        .expect(["rustup", "+nightly", "target", "add", CROSS_ARCH1])
+        .await
+        .is_ok();
+    cx.config
+        .expect(["rustup", "+nightly", "target", "remove", CROSS_ARCH1])
+        .await
+        .is_ok();
+
+    let path = cx.config.rustupdir.join(format!(
+        "toolchains/nightly-{}/rustup-metadata.toml",
+        this_host_triple()
+    ));
+    let metadata = fs::read_to_string(path)
+        .unwrap()
+        .parse::<toml::Table>()
+        .unwrap();
+    assert_eq!(metadata["profile"].as_str(), Some("minimal"));
+    assert_eq!(
+        metadata["installer_version"].as_str(),
+        Some(env!("CARGO_PKG_VERSION"))
+    );
+    assert!(metadata["install_date"].as_str().is_some());
+    assert!(metadata["dist_server"].as_str().is_some());
+    let components = |key: &str| {
+        metadata[key]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|c| c.as_str().unwrap().to_owned())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        components("components_at_install"),
+        [format!("rustc-{}", this_host_triple())]
+    );
+    assert_eq!(components("components_added"), ["rust-src"]);
+}
+
+#[tokio::test]
+async fn list_toolchains_with_none_as_json() {
+    let cx = CliTestContext::new(Scenario::SimpleV2).await;
+    cx.config
+        .expect(["rustup", "toolchain", "list", "--json"])
+        .await
+        .with_stdout(snapbox::str![[r#"
+[]
+
 "#]])
         .is_ok();
 }
@@ -359,6 +963,85 @@ nightly-[HOST_TRIPLE] (default)
         .is_ok();
 }
 
+#[tokio::test]
+async fn remove_toolchain_all_but_default() {
+    let cx = CliTestContext::new(Scenario::SimpleV2).await;
+    for toolchain in ["stable", "beta", "nightly"] {
+        cx.config
+            .expect(["rustup", "toolchain", "install", toolchain])
+            .await
+            .is_ok();
+    }
+    cx.config
+        .expect(["rustup", "default", "stable"])
+        .await
+        .is_ok();
+    let project = cx.config.current_dir().join("project");
+    fs::create_dir(&project).unwrap();
+    cx.config
+        .expect([
+            "rustup",
+            "override",
+            "set",
+            "beta",
+            "--path",
+            project.to_str().unwrap(),
+        ])
+        .await
+        .is_ok();
+
+    cx.config
+        .expect([
+            "rustup",
+            "toolchain",
+            "remove",
+            "--all-but-default",
+            "--dry-run",
+        ])
+        .await
+        .with_stderr(snapbox::str![[r#"
+info: would remove toolchain 'nightly-[HOST_TRIPLE]'
+
+"#]])
+        .is_ok();
+    cx.config
+        .expect(["rustup", "toolchain", "remove", "--all-but-default"])
+        .await
+        .with_stderr(snapbox::str![[r#"
+info: uninstalling toolchain nightly-[HOST_TRIPLE]
+info: toolchain nightly-[HOST_TRIPLE] uninstalled
+
+"#]])
+        .is_ok();
+    cx.config
+        .expect(["rustup", "toolchain", "list"])
+        .await
+        .with_stdout(snapbox::str![[r#"
+stable-[HOST_TRIPLE] (active, default)
+beta-[HOST_TRIPLE]
+
+"#]])
+        .is_ok();
+    cx.config
+        .expect(["rustup", "toolchain", "remove", "--all-but-default"])
+        .await
+        .with_stderr(snapbox::str![[r#"
+info: no toolchains to remove
+
+"#]])
+        .is_ok();
+    cx.config
+        .expect([
+            "rustup",
+            "toolchain",
+            "remove",
+            "--all-but-default",
+            "stable",
+        ])
+        .await
+        .is_err();
+}
+
 // Issue #2873
 #[tokio::test]
 async fn remove_toolchain_ignore_trailing_slash() {
@@ -1175,11 +1858,45 @@ async fn upgrade_v2_to_v1() {
     )
     .unwrap();
     cx.config
-        .expect(["rustup", "update", "nightly"])
+        .expect(["rustup", "update", "nightly"])
+        .await
+        .with_stderr(snapbox::str![[r#"
+...
+error: the server unexpectedly provided an obsolete version of the distribution manifest
+...
+"#]])
+        .is_err();
+}
+
+#[tokio::test]
+async fn unsigned_manifests_fail_signature_verification() {
+    let cx = CliTestContext::new(Scenario::SimpleV2).await;
+    cx.config
+        .expect_with_env(
+            ["rustup", "toolchain", "install", "nightly"],
+            [("RUSTUP_VERIFY_SIGNATURES", "1")],
+        )
+        .await
+        .with_stderr(snapbox::str![[r#"
+...
+error: RUSTUP_VERIFY_SIGNATURES is set, but RUSTUP_SIGNATURE_KEYRING does not name a keyring to verify signatures against
+...
+"#]])
+        .is_err();
+
+    let keyring = cx.config.rustupdir.join("keyring.gpg");
+    cx.config
+        .expect_with_env(
+            ["rustup", "toolchain", "install", "nightly"],
+            [
+                ("RUSTUP_VERIFY_SIGNATURES", "1"),
+                ("RUSTUP_SIGNATURE_KEYRING", keyring.to_str().unwrap()),
+            ],
+        )
         .await
         .with_stderr(snapbox::str![[r#"
 ...
-error: the server unexpectedly provided an obsolete version of the distribution manifest
+error: signature verification failed for '[..]/channel-rust-nightly.toml': could not download the signature: [..]
 ...
 "#]])
         .is_err();
@@ -1361,6 +2078,27 @@ async fn add_target1() {
     assert!(cx.config.rustupdir.has(path));
 }
 
+#[tokio::test]
+async fn list_components_as_json() {
+    let cx = CliTestContext::new(Scenario::SimpleV2).await;
+    cx.config
+        .expect(["rustup", "default", "nightly"])
+        .await
+        .is_ok();
+    cx.config
+        .expect(["rustup", "target", "add", CROSS_ARCH1])
+        .await
+        .is_ok();
+    cx.config
+        .expect(["rustup", "component", "list", "--installed", "--json"])
+        .await
+        .with_stdout(snapbox::str![[r#"
+[{"name":"cargo-[HOST_TRIPLE]","installed":true,"available":true,"kind":"component"},{"name":"rust-docs-[HOST_TRIPLE]","installed":true,"available":true,"kind":"component"},{"name":"rust-std-[HOST_TRIPLE]","installed":true,"available":true,"kind":"target"},{"name":"rust-std-[CROSS_ARCH_I]","installed":true,"available":true,"kind":"target"},{"name":"rustc-[HOST_TRIPLE]","installed":true,"available":true,"kind":"component"}]
+
+"#]])
+        .is_ok();
+}
+
 #[tokio::test]
 async fn add_target2() {
     let cx = CliTestContext::new(Scenario::SimpleV2).await;
@@ -1380,6 +2118,43 @@ async fn add_target2() {
     assert!(cx.config.rustupdir.has(path));
 }
 
+#[tokio::test]
+async fn add_targets_in_bulk() {
+    let cx = CliTestContext::new(Scenario::SimpleV2).await;
+    cx.config
+        .expect(["rustup", "default", "nightly"])
+        .await
+        .is_ok();
+    let list = cx.config.current_dir().join("targets.txt");
+    let host = this_host_triple();
+    fs::write(
+        &list,
+        format!("# targets for the board\n{CROSS_ARCH1}\n\n{host}\nbogus-target\n"),
+    )
+    .unwrap();
+    cx.config
+        .expect([
+            "rustup",
+            "target",
+            "add",
+            CROSS_ARCH2,
+            "--bulk",
+            list.to_str().unwrap(),
+        ])
+        .await
+        .with_stderr(snapbox::str![[r#"
+...
+info: target '[HOST_TRIPLE]' is already installed
+warn: target 'bogus-target' is not available for nightly-[HOST_TRIPLE]
+
+"#]])
+        .is_err();
+    for target in [CROSS_ARCH1, CROSS_ARCH2] {
+        let path = format!("toolchains/nightly-{host}/lib/rustlib/{target}/lib/libstd.rlib");
+        assert!(cx.config.rustupdir.has(path));
+    }
+}
+
 #[tokio::test]
 async fn add_all_targets() {
     let cx = CliTestContext::new(Scenario::SimpleV2).await;
@@ -1405,6 +2180,40 @@ async fn add_all_targets() {
     assert!(cx.config.rustupdir.has(path));
 }
 
+#[tokio::test]
+async fn add_all_targets_flag_with_exclude() {
+    let cx = CliTestContext::new(Scenario::SimpleV2).await;
+    cx.config
+        .expect(["rustup", "default", "nightly"])
+        .await
+        .is_ok();
+    cx.config
+        .expect(["rustup", "target", "add", "--all", "--exclude", CROSS_ARCH2])
+        .await
+        .is_ok();
+    cx.config
+        .expect(["rustup", "target", "list", "--installed"])
+        .await
+        .is_ok()
+        .with_stdout(snapbox::str![[r#"
+[HOST_TRIPLE]
+[CROSS_ARCH_I]
+
+"#]]);
+    cx.config
+        .expect(["rustup", "target", "add", "--all", "--exclude", CROSS_ARCH2])
+        .await
+        .with_stderr(snapbox::str![[r#"
+info: all available targets are already installed
+
+"#]])
+        .is_ok();
+    cx.config
+        .expect(["rustup", "target", "add", "--all", CROSS_ARCH2])
+        .await
+        .is_err();
+}
+
 #[tokio::test]
 async fn add_all_targets_fail() {
     let cx = CliTestContext::new(Scenario::SimpleV2).await;
@@ -1985,6 +2794,98 @@ note: sometimes not all components are available in any given nightly
         .without_stderr("If you don't need the component, you can remove it with:");
 }
 
+#[tokio::test]
+async fn add_missing_component_allowed() {
+    let cx = CliTestContext::new(Scenario::SimpleV2).await;
+    make_component_unavailable(&cx.config, "rls-preview", this_host_triple());
+    cx.config
+        .expect(["rustup", "toolchain", "add", "nightly"])
+        .await
+        .is_ok();
+    cx.config
+        .expect([
+            "rustup",
+            "component",
+            "add",
+            "--allow-missing",
+            "rls-preview",
+            "miri",
+            "rust-src",
+        ])
+        .await
+        .with_stderr(snapbox::str![[r#"
+warn: skipping component 'rls' for target '[HOST_TRIPLE]', which is unavailable for 'nightly-[HOST_TRIPLE]'
+warn: skipping component 'miri' for target '[HOST_TRIPLE]', which is unavailable for 'nightly-[HOST_TRIPLE]'
+info: downloading component rust-src
+...
+"#]])
+        .is_ok();
+    cx.config
+        .expect(["rustup", "component", "list", "--installed"])
+        .await
+        .is_ok()
+        .with_stdout(snapbox::str![[r#"
+cargo-[HOST_TRIPLE]
+rust-docs-[HOST_TRIPLE]
+rust-src
+rust-std-[HOST_TRIPLE]
+rustc-[HOST_TRIPLE]
+
+"#]]);
+}
+
+#[tokio::test]
+async fn add_component_all_targets() {
+    let cx = CliTestContext::new(Scenario::SimpleV2).await;
+    cx.config
+        .expect(["rustup", "default", "nightly"])
+        .await
+        .is_ok();
+    cx.config
+        .expect(["rustup", "target", "add", CROSS_ARCH1])
+        .await
+        .is_ok();
+    cx.config
+        .expect(["rustup", "component", "add", "--all-targets", "rls"])
+        .await
+        .with_stderr(snapbox::str![[r#"
+error: toolchain 'nightly-[HOST_TRIPLE]' does not contain component 'rls' for target '[CROSS_ARCH_I]'
+...
+"#]])
+        .is_err();
+    cx.config
+        .expect([
+            "rustup",
+            "component",
+            "add",
+            "--all-targets",
+            "--allow-missing",
+            "rls",
+            "rust-src",
+        ])
+        .await
+        .with_stderr(snapbox::str![[r#"
+...
+warn: skipping component 'rls' for target '[CROSS_ARCH_I]', which is unavailable for 'nightly-[HOST_TRIPLE]'
+...
+"#]])
+        .is_ok();
+    cx.config
+        .expect(["rustup", "component", "list", "--installed"])
+        .await
+        .is_ok()
+        .with_stdout(snapbox::str![[r#"
+cargo-[HOST_TRIPLE]
+rls-[HOST_TRIPLE]
+rust-docs-[HOST_TRIPLE]
+rust-src
+rust-std-[HOST_TRIPLE]
+rust-std-[CROSS_ARCH_I]
+rustc-[HOST_TRIPLE]
+
+"#]]);
+}
+
 #[tokio::test]
 async fn add_toolchain_with_missing_component() {
     let cx = CliTestContext::new(Scenario::SimpleV2).await;
@@ -2458,6 +3359,65 @@ rust-docs-[HOST_TRIPLE] (installed)
         .is_ok();
 }
 
+#[tokio::test]
+async fn toolchain_install_component_policy() {
+    let cx = CliTestContext::new(Scenario::UnavailableRls).await;
+    cx.config.set_current_dist_date("2015-01-01");
+
+    cx.config
+        .expect(["rustup", "set", "profile", "complete"])
+        .await
+        .is_ok();
+    cx.config
+        .expect([
+            "rustup",
+            "toolchain",
+            "install",
+            "--component-policy",
+            "strict",
+            "nightly",
+        ])
+        .await
+        .with_stderr(snapbox::str![[r#"
+...
+error: component 'rls' for target '[HOST_TRIPLE]' is unavailable for download for channel 'nightly'
+...
+"#]])
+        .is_err();
+    cx.config
+        .expect([
+            "rustup",
+            "toolchain",
+            "install",
+            "--component-policy",
+            "lenient",
+            "nightly",
+        ])
+        .await
+        .with_stderr(snapbox::str![[r#"
+...
+warn: skipping unavailable component rls
+...
+"#]])
+        .is_ok();
+    cx.config
+        .expect([
+            "rustup",
+            "toolchain",
+            "install",
+            "--component-policy",
+            "lenient",
+            "--force",
+            "nightly",
+        ])
+        .await
+        .with_stderr(snapbox::str![[r#"
+error: the argument '--component-policy <POLICY>' cannot be used with '--force'
+...
+"#]])
+        .is_err();
+}
+
 #[tokio::test]
 async fn run_with_install_flag_against_unavailable_component() {
     let cx = CliTestContext::new(Scenario::SimpleV2).await;
@@ -2559,6 +3519,60 @@ error: component 'rls' for target '[HOST_TRIPLE]' is unavailable for download fo
     cx.config.expect_component_executable("rls").await;
 }
 
+#[tokio::test]
+async fn install_minimal_profile_with_component_for_date() {
+    let cx = CliTestContext::new(Scenario::UnavailableRls).await;
+    cx.config
+        .expect([
+            "rustup",
+            "toolchain",
+            "install",
+            "nightly-2015-01-01",
+            "--profile",
+            "minimal",
+            "--component",
+            "rls",
+        ])
+        .await
+        .with_stderr(snapbox::str![[r#"
+...
+error: component 'rls' for target '[HOST_TRIPLE]' is unavailable for download for channel 'nightly-2015-01-01'
+...
+"#]])
+        .is_err();
+    cx.config
+        .expect(["rustup", "toolchain", "list"])
+        .await
+        .with_stdout(snapbox::str![[r#"
+no installed toolchains
+
+"#]])
+        .is_ok();
+
+    cx.config
+        .expect([
+            "rustup",
+            "toolchain",
+            "install",
+            "nightly-2015-01-02",
+            "--profile",
+            "minimal",
+            "--component",
+            "rls",
+        ])
+        .await
+        .is_ok();
+    cx.config
+        .expect(["rustup", "component", "list", "--installed"])
+        .await
+        .with_stdout(snapbox::str![[r#"
+rls-[HOST_TRIPLE]
+rustc-[HOST_TRIPLE]
+
+"#]])
+        .is_ok();
+}
+
 #[tokio::test]
 async fn regression_2601() {
     // We're checking that we don't regress per #2601
@@ -190,6 +190,42 @@ fn uninstall() {
     assert!(components.find("mycomponent2").unwrap().is_none());
 }
 
+#[test]
+fn verify_reports_missing_and_corrupted_files() {
+    let mock = MockInstallerBuilder {
+        components: vec![MockComponentBuilder {
+            name: "mycomponent".to_string(),
+            files: vec![
+                MockFile::new("bin/foo", b"foo"),
+                MockFile::new("lib/bar", b"bar"),
+                MockFile::new_dir("doc/stuff", &[("doc1", b"", false), ("doc2", b"", false)]),
+            ],
+        }],
+    };
+
+    let cx = DistContext::new(Some(mock)).unwrap();
+    let (tx, components, pkg) = cx.start().unwrap();
+    let tx = pkg.install(&components, "mycomponent", None, tx).unwrap();
+    tx.commit();
+
+    let component = components.find("mycomponent").unwrap().unwrap();
+    let (missing, corrupted) = component.verify().unwrap();
+    assert!(missing.is_empty());
+    assert!(corrupted.is_empty());
+
+    let inst_dir = cx.inst_dir.path();
+    utils::remove_file("test", &inst_dir.join("bin/foo")).unwrap();
+    utils::remove_file("test", &inst_dir.join("doc/stuff/doc2")).unwrap();
+    utils::write_file("test", &inst_dir.join("lib/bar"), "baz").unwrap();
+
+    let (missing, corrupted) = component.verify().unwrap();
+    assert_eq!(
+        missing,
+        [inst_dir.join("bin/foo"), inst_dir.join("doc/stuff/doc2")]
+    );
+    assert_eq!(corrupted, [inst_dir.join("lib/bar")]);
+}
+
 // If any single file can't be uninstalled, it is not a fatal error
 // and the subsequent files will still be removed.
 #[test]